@@ -0,0 +1,129 @@
+use std::io::Cursor;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use image::{AnimationDecoder, Frame, ImageFormat, GenericImage, RgbaImage};
+use image::gif::GifDecoder;
+use image::png::PngDecoder;
+
+use crate::backend::{Renderer, TextureData};
+use crate::texture::{TextureManager, TextureError, Sprite};
+
+/// Delays up to this long are played at `DEFAULT_FRAME_DELAY`, as browsers do, since many GIFs store 0 or 10 ms
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(10);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationFrame {
+    pub sprite: Sprite,
+    pub delay: Duration
+}
+
+/// Frames of an animated image packed into a grid on a single texture, played in a loop from the
+/// moment it was loaded or last restarted.
+#[derive(Debug, Clone)]
+pub struct AnimatedTexture {
+    pub texture: String,
+    pub frame_size: (u32, u32),
+    frames: Vec<AnimationFrame>,
+    duration: Duration,
+    started: Instant
+}
+
+impl AnimatedTexture {
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    /// Length of one loop of the animation.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn restart(&mut self) {
+        self.started = Instant::now();
+    }
+
+    /// Frame shown after `elapsed` time, wrapping around at the end of the animation.
+    pub fn frame_at(&self, elapsed: Duration) -> &AnimationFrame {
+        if self.duration == Duration::from_secs(0) {
+            return &self.frames[0];
+        }
+        let mut time = Duration::from_nanos((elapsed.as_nanos() % self.duration.as_nanos()) as u64);
+        for frame in &self.frames {
+            if time < frame.delay {
+                return frame;
+            }
+            time -= frame.delay;
+        }
+        &self.frames[self.frames.len() - 1]
+    }
+
+    /// Time since the animation started or was restarted.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn current_frame(&self) -> &AnimationFrame {
+        self.frame_at(self.started.elapsed())
+    }
+}
+
+impl TextureManager {
+    /// Loads a GIF or APNG animation. Other images load as an animation of a single frame.
+    pub fn load_animated<N, P>(&mut self, name: N, path: P) -> Result<AnimatedTexture, TextureError>
+        where N: Into<String>, P: AsRef<Path> {
+
+        let bytes = std::fs::read(path)?;
+        self.load_animated_bytes(name, &bytes)
+    }
+
+    pub fn load_animated_bytes<N>(&mut self, name: N, bytes: &[u8]) -> Result<AnimatedTexture, TextureError>
+        where N: Into<String> {
+
+        let mut frames = match image::guess_format(bytes)? {
+            ImageFormat::Gif => GifDecoder::new(Cursor::new(bytes))?.into_frames().collect_frames()?,
+            ImageFormat::Png => {
+                let decoder = PngDecoder::new(Cursor::new(bytes))?;
+                if decoder.is_apng() {
+                    decoder.apng().into_frames().collect_frames()?
+                } else {
+                    vec![Frame::new(image::load_from_memory(bytes)?.to_rgba())]
+                }
+            },
+            _ => vec![Frame::new(image::load_from_memory(bytes)?.to_rgba())]
+        };
+        if frames.is_empty() {
+            frames.push(Frame::new(image::load_from_memory(bytes)?.to_rgba()));
+        }
+
+        let name = name.into();
+        let (fw, fh) = frames[0].buffer().dimensions();
+        let columns = (frames.len() as f32).sqrt().ceil() as u32;
+        let rows = (frames.len() as u32 + columns - 1) / columns;
+        let size = (fw * columns, fh * rows);
+        let mut pixels = RgbaImage::new(size.0, size.1);
+        let mut animation_frames = Vec::with_capacity(frames.len());
+        for (i, frame) in frames.into_iter().enumerate() {
+            let (x, y) = (i as u32 % columns * fw, i as u32 / columns * fh);
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            pixels.copy_from(frame.buffer(), x, y).expect("Animation frames differ in size");
+            let delay = Duration::from_micros(numer as u64 * 1000 / denom.max(1) as u64);
+            animation_frames.push(AnimationFrame {
+                sprite: Sprite::from_pixels(name.clone(), [x, y, fw, fh], size),
+                delay: if delay <= MIN_FRAME_DELAY { DEFAULT_FRAME_DELAY } else { delay }
+            });
+        }
+
+        let texture = self.display.create_texture(TextureData::rgba(pixels.into_raw(), size))?;
+        self.textures.insert(name.clone(), Rc::new(Box::new(texture)));
+        Ok(AnimatedTexture {
+            texture: name,
+            frame_size: (fw, fh),
+            duration: animation_frames.iter().map(|frame| frame.delay).sum(),
+            frames: animation_frames,
+            started: Instant::now()
+        })
+    }
+}
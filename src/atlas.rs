@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+use image::{DynamicImage, RgbaImage, GenericImage};
+
+use crate::backend::{Renderer, TextureData};
+use crate::texture::{TextureManager, TextureError, Sprite};
+
+/// Collects small images and packs them into one texture, so drawing any of them binds the same texture.
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+    padding: u32,
+    max_width: u32
+}
+
+/// Packed atlas texture with the regions of the images it was built from.
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    pub texture: String,
+    pub size: (u32, u32),
+    sprites: HashMap<String, Sprite>
+}
+
+impl Atlas {
+    pub fn sprite<N>(&self, name: N) -> Option<&Sprite> where N: AsRef<str> {
+        self.sprites.get(name.as_ref())
+    }
+
+    pub fn sprites(&self) -> &HashMap<String, Sprite> {
+        &self.sprites
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> AtlasBuilder {
+        AtlasBuilder {
+            images: Vec::new(),
+            padding: 1,
+            max_width: 2048
+        }
+    }
+
+    /// Transparent pixels kept around every image so filtering does not bleed into its neighbours.
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn max_width(mut self, max_width: u32) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    pub fn image<N>(mut self, name: N, image: DynamicImage) -> Self where N: Into<String> {
+        self.images.push((name.into(), image.to_rgba()));
+        self
+    }
+
+    pub fn file<N, P>(self, name: N, path: P) -> Result<Self, TextureError> where N: Into<String>, P: AsRef<Path> {
+        let bytes = std::fs::read(path)?;
+        Ok(self.image(name, image::load_from_memory(&bytes)?))
+    }
+
+    /// Packs the images into shelves, tallest first, and registers the atlas under `texture`.
+    pub fn build<T>(self, textures: &mut TextureManager, texture: T) -> Result<Atlas, TextureError> where T: Into<String> {
+        let texture = texture.into();
+        let padding = self.padding;
+        let mut order = (0 .. self.images.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.images[i].1.height()));
+
+        let area = self.images.iter()
+            .map(|(_, image)| ((image.width() + padding * 2) * (image.height() + padding * 2)) as f64)
+            .sum::<f64>();
+        let widest = self.images.iter().map(|(_, image)| image.width() + padding * 2).max().unwrap_or(1);
+        // roughly square, but never narrower than the widest image
+        let width = (area.sqrt().ceil() as u32).next_power_of_two().min(self.max_width).max(widest);
+
+        let mut placed = vec![(0, 0); self.images.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for &i in &order {
+            let image = &self.images[i].1;
+            let (w, h) = (image.width() + padding * 2, image.height() + padding * 2);
+            if x + w > width {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            placed[i] = (x + padding, y + padding);
+            x += w;
+            shelf_height = shelf_height.max(h);
+        }
+        let height = (y + shelf_height).max(1);
+
+        let mut pixels = RgbaImage::new(width, height);
+        let mut sprites = HashMap::new();
+        for (i, (name, image)) in self.images.into_iter().enumerate() {
+            let (px, py) = placed[i];
+            pixels.copy_from(&image, px, py).expect("Atlas image out of bounds");
+            let region = [px, py, image.width(), image.height()];
+            sprites.insert(name, Sprite::from_pixels(texture.clone(), region, (width, height)));
+        }
+
+        let atlas = textures.display.create_texture(TextureData::rgba(pixels.into_raw(), (width, height)))?;
+        textures.textures.insert(texture.clone(), Rc::new(Box::new(atlas)));
+        Ok(Atlas { texture, size: (width, height), sprites })
+    }
+}
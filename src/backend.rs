@@ -0,0 +1,141 @@
+//! Graphics API seam of the crate.
+//!
+//! Texture uploads of `TextureManager` and the loaders built on it, render targets, canvas
+//! layers, post processing buffers, glyph atlas pages, the programs of `ShaderManager` and those
+//! of the text pipeline are created through `Renderer`, which glium implements on `Display`.
+//!
+//! Still to be moved behind it: vertex and index buffers, streaming and compressed textures,
+//! and the draw calls of `Canvas` and `FontManager`, which go through glium's `Surface`.
+use glium::Display;
+use glium::texture::{Texture2d, SrgbTexture2d, RawImage2d, MipmapsOption, DepthStencilFormat,
+                     UncompressedFloatFormat};
+use glium::framebuffer::{SimpleFrameBuffer, DepthStencilRenderBuffer};
+use glium::program::{self, Program};
+use glium::Surface;
+
+use crate::error::Result;
+
+/// Layout of the bytes of `TextureData`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgba8,
+    Rgb8,
+    /// Single channel, as used by glyph coverage
+    R8
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgba8 => 4,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::R8 => 1
+        }
+    }
+}
+
+/// Tightly packed pixels, rows going from top to bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureData {
+    pub pixels: Vec<u8>,
+    pub size: (u32, u32),
+    pub format: PixelFormat
+}
+
+impl TextureData {
+    /// Panics if `pixels` does not hold exactly `size` pixels of `format`.
+    pub fn new(pixels: Vec<u8>, size: (u32, u32), format: PixelFormat) -> TextureData {
+        assert_eq!(pixels.len(), size.0 as usize * size.1 as usize * format.bytes_per_pixel(),
+                   "Pixel buffer does not match the size");
+        TextureData { pixels, size, format }
+    }
+
+    pub fn rgba(pixels: Vec<u8>, size: (u32, u32)) -> TextureData {
+        TextureData::new(pixels, size, PixelFormat::Rgba8)
+    }
+}
+
+/// Creates the GPU resources of the UI layer.
+pub trait Renderer {
+    /// sRGB color texture sampled by quads
+    type Texture;
+    /// Linear texture of offscreen layers and glyph atlases
+    type LinearTexture;
+    type DepthStencil;
+    type Program;
+
+    fn create_texture(&self, data: TextureData) -> Result<Self::Texture>;
+
+    /// Transparent texture with a depth and stencil buffer, to be drawn into.
+    fn create_render_target(&self, width: u32, height: u32) -> Result<(Self::Texture, Self::DepthStencil)>;
+
+    /// Transparent texture to be drawn into, with half float color when `hdr` is set.
+    fn create_layer(&self, width: u32, height: u32, hdr: bool) -> Result<Self::LinearTexture>;
+
+    /// Zeroed single channel texture.
+    fn create_coverage_texture(&self, width: u32, height: u32) -> Result<Self::LinearTexture>;
+
+    fn compile_program(&self, vertex: &str, fragment: &str, geometry: Option<&str>) -> Result<Self::Program>;
+
+    fn supports_geometry_shaders(&self) -> bool;
+
+    /// Physical size of the window surface.
+    fn framebuffer_dimensions(&self) -> (u32, u32);
+}
+
+impl Renderer for Display {
+    type Texture = SrgbTexture2d;
+    type LinearTexture = Texture2d;
+    type DepthStencil = DepthStencilRenderBuffer;
+    type Program = Program;
+
+    fn create_texture(&self, data: TextureData) -> Result<SrgbTexture2d> {
+        Ok(SrgbTexture2d::new(self, raw_image(data))?)
+    }
+
+    fn create_render_target(&self, width: u32, height: u32) -> Result<(SrgbTexture2d, DepthStencilRenderBuffer)> {
+        let texture = SrgbTexture2d::empty_with_mipmaps(self, MipmapsOption::NoMipmap, width, height)?;
+        let depth = DepthStencilRenderBuffer::new(self, DepthStencilFormat::I24I8, width, height)?;
+        SimpleFrameBuffer::new(self, &texture)?.clear_color(0.0, 0.0, 0.0, 0.0);
+        Ok((texture, depth))
+    }
+
+    fn create_layer(&self, width: u32, height: u32, hdr: bool) -> Result<Texture2d> {
+        let texture = if hdr {
+            Texture2d::empty_with_format(self, UncompressedFloatFormat::F16F16F16F16, MipmapsOption::NoMipmap, width, height)?
+        } else {
+            Texture2d::empty_with_mipmaps(self, MipmapsOption::NoMipmap, width, height)?
+        };
+        SimpleFrameBuffer::new(self, &texture)?.clear_color(0.0, 0.0, 0.0, 0.0);
+        Ok(texture)
+    }
+
+    fn create_coverage_texture(&self, width: u32, height: u32) -> Result<Texture2d> {
+        Ok(Texture2d::empty_with_format(self, UncompressedFloatFormat::U8, MipmapsOption::NoMipmap, width, height)?)
+    }
+
+    fn compile_program(&self, vertex: &str, fragment: &str, geometry: Option<&str>) -> Result<Program> {
+        Ok(Program::from_source(self, vertex, fragment, geometry)?)
+    }
+
+    fn supports_geometry_shaders(&self) -> bool {
+        program::is_geometry_shader_supported(self)
+    }
+
+    fn framebuffer_dimensions(&self) -> (u32, u32) {
+        self.get_framebuffer_dimensions()
+    }
+}
+
+fn raw_image(data: TextureData) -> RawImage2d<'static, u8> {
+    match data.format {
+        PixelFormat::Rgba8 => RawImage2d::from_raw_rgba(data.pixels, data.size),
+        PixelFormat::Rgb8 => RawImage2d::from_raw_rgb(data.pixels, data.size),
+        PixelFormat::R8 => RawImage2d {
+            data: data.pixels.into(),
+            width: data.size.0,
+            height: data.size.1,
+            format: glium::texture::ClientFormat::U8
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use image::RgbaImage;
+
+#[derive(Debug)]
+pub enum ClipboardError {
+    /// The system clipboard could not be opened
+    Unavailable(String),
+    /// The clipboard holds nothing of the requested kind
+    Empty,
+    /// Reading or writing the clipboard failed
+    Access(String)
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipboardError::Unavailable(e) => write!(f, "Clipboard is unavailable: {}", e),
+            ClipboardError::Empty => write!(f, "Clipboard holds no content of the requested kind"),
+            ClipboardError::Access(e) => write!(f, "Clipboard access failed: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for ClipboardError {}
+
+impl From<arboard::Error> for ClipboardError {
+    fn from(error: arboard::Error) -> Self {
+        match error {
+            arboard::Error::ContentNotAvailable => ClipboardError::Empty,
+            e => ClipboardError::Access(e.to_string())
+        }
+    }
+}
+
+pub type ClipboardResult<T> = std::result::Result<T, ClipboardError>;
+
+/// Storage behind a `Clipboard`, implemented by the system clipboard and `MemoryClipboard`.
+pub trait ClipboardBackend {
+    fn get_text(&mut self) -> ClipboardResult<String>;
+    fn set_text(&mut self, text: String) -> ClipboardResult<()>;
+    fn get_image(&mut self) -> ClipboardResult<RgbaImage>;
+    fn set_image(&mut self, image: &RgbaImage) -> ClipboardResult<()>;
+}
+
+/// Opened on first use, as that may be slow and not every canvas touches the clipboard.
+#[derive(Default)]
+struct SystemClipboard {
+    inner: Option<arboard::Clipboard>
+}
+
+impl SystemClipboard {
+    fn inner(&mut self) -> ClipboardResult<&mut arboard::Clipboard> {
+        if self.inner.is_none() {
+            let clipboard = arboard::Clipboard::new()
+                .map_err(|e| ClipboardError::Unavailable(e.to_string()))?;
+            self.inner = Some(clipboard);
+        }
+        Ok(self.inner.as_mut().unwrap())
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn get_text(&mut self) -> ClipboardResult<String> {
+        Ok(self.inner()?.get_text()?)
+    }
+
+    fn set_text(&mut self, text: String) -> ClipboardResult<()> {
+        Ok(self.inner()?.set_text(text)?)
+    }
+
+    fn get_image(&mut self) -> ClipboardResult<RgbaImage> {
+        let image = self.inner()?.get_image()?;
+        RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())
+            .ok_or_else(|| ClipboardError::Access("Clipboard image has an invalid size".to_owned()))
+    }
+
+    fn set_image(&mut self, image: &RgbaImage) -> ClipboardResult<()> {
+        let (width, height) = image.dimensions();
+        let image = arboard::ImageData {
+            width: width as usize,
+            height: height as usize,
+            bytes: Cow::Borrowed(image.as_raw())
+        };
+        Ok(self.inner()?.set_image(image)?)
+    }
+}
+
+/// Clipboard kept in memory, for headless rendering and tests.
+#[derive(Default)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+    image: Option<RgbaImage>
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get_text(&mut self) -> ClipboardResult<String> {
+        self.text.clone().ok_or(ClipboardError::Empty)
+    }
+
+    fn set_text(&mut self, text: String) -> ClipboardResult<()> {
+        self.text = Some(text);
+        Ok(())
+    }
+
+    fn get_image(&mut self) -> ClipboardResult<RgbaImage> {
+        self.image.clone().ok_or(ClipboardError::Empty)
+    }
+
+    fn set_image(&mut self, image: &RgbaImage) -> ClipboardResult<()> {
+        self.image = Some(image.clone());
+        Ok(())
+    }
+}
+
+/// Shared handle to a clipboard, clones access the same storage.
+#[derive(Clone)]
+pub struct Clipboard {
+    backend: Rc<RefCell<Box<dyn ClipboardBackend>>>
+}
+
+impl Clipboard {
+    pub fn system() -> Clipboard {
+        Clipboard::with_backend(SystemClipboard::default())
+    }
+
+    pub fn memory() -> Clipboard {
+        Clipboard::with_backend(MemoryClipboard::default())
+    }
+
+    pub fn with_backend<B>(backend: B) -> Clipboard where B: ClipboardBackend + 'static {
+        Clipboard { backend: Rc::new(RefCell::new(Box::new(backend))) }
+    }
+
+    pub fn get_text(&self) -> ClipboardResult<String> {
+        self.backend.borrow_mut().get_text()
+    }
+
+    pub fn set_text<T>(&self, text: T) -> ClipboardResult<()> where T: Into<String> {
+        self.backend.borrow_mut().set_text(text.into())
+    }
+
+    pub fn get_image(&self) -> ClipboardResult<RgbaImage> {
+        self.backend.borrow_mut().get_image()
+    }
+
+    pub fn set_image(&self, image: &RgbaImage) -> ClipboardResult<()> {
+        self.backend.borrow_mut().set_image(image)
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Clipboard::system()
+    }
+}
+
+impl fmt::Debug for Clipboard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Clipboard")
+    }
+}
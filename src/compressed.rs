@@ -0,0 +1,116 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use glium::texture::{CompressedSrgbTexture2d, CompressedSrgbFormat, CompressedMipmapsOption};
+
+use crate::texture::{TextureManager, TextureError};
+
+const KTX2_IDENTIFIER: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+
+/// Base level of a block compressed image read from a container file.
+struct CompressedImage<'a> {
+    width: u32,
+    height: u32,
+    format: CompressedSrgbFormat,
+    data: &'a [u8]
+}
+
+impl TextureManager {
+    /// Loads a BC1, BC2, BC3 or BC7 texture from a DDS or KTX2 file without decoding it. Only the base
+    /// level is uploaded, mipmaps stored in the file are ignored.
+    pub fn load_compressed<N, P>(&mut self, name: N, path: P) -> Result<Rc<Box<CompressedSrgbTexture2d>>, TextureError>
+        where N: Into<String>, P: AsRef<Path> {
+
+        let bytes = std::fs::read(path)?;
+        self.load_compressed_bytes(name, &bytes)
+    }
+
+    pub fn load_compressed_bytes<N>(&mut self, name: N, bytes: &[u8]) -> Result<Rc<Box<CompressedSrgbTexture2d>>, TextureError>
+        where N: Into<String> {
+
+        let image = if bytes.starts_with(b"DDS ") {
+            parse_dds(bytes)?
+        } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+            parse_ktx2(bytes)?
+        } else {
+            return Err(unsupported("not a DDS or KTX2 file"));
+        };
+        let texture = CompressedSrgbTexture2d::with_compressed_data(
+            &self.display, image.data, image.width, image.height, image.format, CompressedMipmapsOption::NoMipmap
+        )?;
+        let texture = Rc::new(Box::new(texture));
+        let name = name.into();
+        self.textures.remove(&name);
+        self.set_compressed_size(&name, image.data.len());
+        self.compressed.insert(name, texture.clone());
+        self.trim();
+        Ok(texture)
+    }
+}
+
+fn unsupported(reason: &str) -> TextureError {
+    TextureError::UnsupportedCompression(reason.to_owned())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, TextureError> {
+    bytes.get(offset .. offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| unsupported("truncated header"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, TextureError> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+/// Slice of the base level, checked against the size its dimensions and format require.
+fn base_level(bytes: &[u8], offset: usize, width: u32, height: u32, format: CompressedSrgbFormat) -> Result<CompressedImage, TextureError> {
+    let block_size = match format {
+        CompressedSrgbFormat::S3tcDxt1NoAlpha | CompressedSrgbFormat::S3tcDxt1Alpha => 8,
+        _ => 16
+    };
+    let length = ((width.max(1) + 3) / 4) as usize * ((height.max(1) + 3) / 4) as usize * block_size;
+    let data = bytes.get(offset .. offset + length).ok_or_else(|| unsupported("truncated image data"))?;
+    Ok(CompressedImage { width, height, format, data })
+}
+
+fn parse_dds(bytes: &[u8]) -> Result<CompressedImage, TextureError> {
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let four_cc = bytes.get(84 .. 88).ok_or_else(|| unsupported("truncated header"))?;
+    let (format, offset) = match four_cc {
+        b"DXT1" => (CompressedSrgbFormat::S3tcDxt1Alpha, 128),
+        b"DXT3" => (CompressedSrgbFormat::S3tcDxt3Alpha, 128),
+        b"DXT5" => (CompressedSrgbFormat::S3tcDxt5Alpha, 128),
+        b"DX10" => {
+            let format = match read_u32(bytes, 128)? {
+                71 | 72 => CompressedSrgbFormat::S3tcDxt1Alpha,
+                74 | 75 => CompressedSrgbFormat::S3tcDxt3Alpha,
+                77 | 78 => CompressedSrgbFormat::S3tcDxt5Alpha,
+                98 | 99 => CompressedSrgbFormat::Bptc,
+                other => return Err(unsupported(&format!("DXGI format {}", other)))
+            };
+            (format, 148)
+        },
+        other => return Err(unsupported(&format!("DDS pixel format {:?}", String::from_utf8_lossy(other))))
+    };
+    base_level(bytes, offset, width, height, format)
+}
+
+fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage, TextureError> {
+    let format = match read_u32(bytes, 12)? {
+        131 | 132 => CompressedSrgbFormat::S3tcDxt1NoAlpha,
+        133 | 134 => CompressedSrgbFormat::S3tcDxt1Alpha,
+        135 | 136 => CompressedSrgbFormat::S3tcDxt3Alpha,
+        137 | 138 => CompressedSrgbFormat::S3tcDxt5Alpha,
+        145 | 146 => CompressedSrgbFormat::Bptc,
+        other => return Err(unsupported(&format!("Vulkan format {}", other)))
+    };
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    if read_u32(bytes, 44)? != 0 {
+        return Err(unsupported("supercompressed KTX2"));
+    }
+    // the level index starts with the base level
+    let offset = read_u64(bytes, 80)? as usize;
+    base_level(bytes, offset, width, height, format)
+}
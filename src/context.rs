@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Instant;
+use glium::{Display, Frame};
+use glium::framebuffer::DefaultFramebuffer;
+use winit::event::{Event, WindowEvent};
+use winit::window::CursorIcon;
+
+use crate::clipboard::Clipboard;
+use crate::error::Result;
+use crate::font::FontManager;
+use crate::render::{Canvas, GeometryBuffers, ColorSpace};
+use crate::shader::{ShaderManager, ShaderError};
+use crate::texture::{TextureManager, TextureLoaded};
+use crate::ui::{Widgets, WidgetEvent};
+
+/// Entry point for applications that own the display and event loop, such as games drawing the UI
+/// over their scene. Events are forwarded with `handle_event` and widgets drawn with `draw`,
+/// without `Window::show`.
+pub struct UiContext {
+    display: Display,
+    shaders: Rc<RefCell<ShaderManager>>,
+    fonts: Rc<RefCell<FontManager>>,
+    textures: Rc<RefCell<TextureManager>>,
+    buffers: Rc<RefCell<GeometryBuffers>>,
+    widgets: Widgets<DefaultFramebuffer>,
+    clipboard: Clipboard,
+    color_space: ColorSpace,
+    /// Logical position of the mouse
+    mouse: (f32, f32),
+    created: Instant
+}
+
+impl UiContext {
+    pub fn new(display: &Display) -> std::result::Result<UiContext, ShaderError> {
+        let scale_factor = display.gl_window().window().scale_factor() as f32;
+        let fonts = Rc::new(RefCell::new(FontManager::new(display)));
+        let textures = Rc::new(RefCell::new(TextureManager::new(display)));
+        fonts.borrow_mut().set_scale_factor(scale_factor);
+        textures.borrow_mut().set_scale_factor(scale_factor);
+        let clipboard = Clipboard::system();
+        let mut widgets = Widgets::new();
+        widgets.set_clipboard(clipboard.clone());
+        Ok(UiContext {
+            display: display.clone(),
+            shaders: Rc::new(RefCell::new(ShaderManager::try_new(display)?)),
+            fonts,
+            textures,
+            buffers: Rc::new(RefCell::new(GeometryBuffers::new())),
+            widgets,
+            clipboard,
+            color_space: ColorSpace::default(),
+            mouse: (0.0, 0.0),
+            created: Instant::now()
+        })
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn shaders(&self) -> Rc<RefCell<ShaderManager>> {
+        self.shaders.clone()
+    }
+
+    pub fn fonts(&self) -> Rc<RefCell<FontManager>> {
+        self.fonts.clone()
+    }
+
+    pub fn textures(&self) -> Rc<RefCell<TextureManager>> {
+        self.textures.clone()
+    }
+
+    pub fn clipboard(&self) -> Clipboard {
+        self.clipboard.clone()
+    }
+
+    pub fn widgets(&self) -> &Widgets<DefaultFramebuffer> {
+        &self.widgets
+    }
+
+    pub fn widgets_mut(&mut self) -> &mut Widgets<DefaultFramebuffer> {
+        &mut self.widgets
+    }
+
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    pub fn mouse(&self) -> (f32, f32) {
+        self.mouse
+    }
+
+    /// Whether the mouse is over a widget, so the host should not act on mouse input itself.
+    pub fn wants_mouse(&self) -> bool {
+        self.widgets.is_mouse_over(self.mouse)
+    }
+
+    /// Whether a widget has keyboard focus, so the host should not act on keys itself.
+    pub fn wants_keyboard(&self) -> bool {
+        self.widgets.has_focus()
+    }
+
+    /// Cursor the widgets ask for at the mouse, left to the host to apply.
+    pub fn cursor(&self) -> CursorIcon {
+        self.widgets.get_cursor(self.mouse)
+    }
+
+    /// Passes an event of the host event loop to the widgets. Events of other windows have to be filtered out by the host.
+    pub fn handle_event<T>(&mut self, event: &Event<T>) -> Vec<WidgetEvent> {
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return vec![]
+        };
+        let display = &self.display;
+        let factor = display.gl_window().window().scale_factor();
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let (x, y): (f64, f64) = position.to_logical::<f64>(factor).into();
+                self.mouse = (x as f32, y as f32);
+                self.widgets.on_mouse_move(display, self.mouse)
+            }
+            WindowEvent::MouseInput { button, state, .. } =>
+                self.widgets.on_mouse_button(display, *button, *state, self.mouse),
+            WindowEvent::MouseWheel { delta, .. } =>
+                self.widgets.on_mouse_wheel(display, *delta),
+            WindowEvent::ReceivedCharacter(ch) =>
+                self.widgets.on_keyboard_char(display, *ch),
+            WindowEvent::KeyboardInput { input, .. } =>
+                self.widgets.on_keyboard_key(display, *input),
+            WindowEvent::ModifiersChanged(modifiers) =>
+                self.widgets.on_modifiers_changed(display, *modifiers),
+            WindowEvent::Focused(focused) =>
+                self.widgets.on_window_focused(display, *focused),
+            WindowEvent::Resized(size) => {
+                let (w, h): (f64, f64) = size.to_logical::<f64>(factor).into();
+                self.widgets.on_resized(display, (w as f32, h as f32))
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.fonts.borrow_mut().set_scale_factor(*scale_factor as f32);
+                self.textures.borrow_mut().set_scale_factor(*scale_factor as f32);
+                self.widgets.on_scale_factor_changed(display, *scale_factor);
+                vec![]
+            }
+            _ => vec![]
+        }
+    }
+
+    /// Uploads textures loaded with `TextureManager::load_async`, call it once per frame.
+    pub fn poll_textures(&mut self) -> Vec<TextureLoaded> {
+        self.textures.borrow_mut().poll_async()
+    }
+
+    /// Draws the widgets over the frame of the host, which must belong to the same display.
+    pub fn draw(&mut self, frame: &mut Frame) -> Result<()> {
+        self.draw_with(frame, |_| Ok(()))
+    }
+
+    /// Draws the widgets, then `overlay` with the same canvas, e.g. for tooltips the host draws itself.
+    pub fn draw_with<F>(&mut self, _frame: &mut Frame, overlay: F) -> Result<()>
+        where F: FnOnce(&mut Canvas<DefaultFramebuffer>) -> Result<()> {
+
+        // the frame only guarantees a back buffer is being drawn, the canvas draws into it directly
+        self.widgets.update(self.mouse, 0.0);
+        let mut canvas = Canvas::with_buffers(
+            self.display.clone(), self.shaders.clone(), self.fonts.clone(), self.textures.clone(),
+            self.buffers.clone(), DefaultFramebuffer::back_left(&self.display)
+        );
+        canvas.set_color_space(self.color_space);
+        canvas.set_time(self.created.elapsed().as_secs_f32());
+        canvas.set_clipboard(self.clipboard.clone());
+        let result = self.widgets.draw(&mut canvas, 0.0).and_then(|_| overlay(&mut canvas));
+        // flushed even after a failed draw, nothing may stay queued
+        result.and(canvas.into_inner().map(|_| ()))
+    }
+}
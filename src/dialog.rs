@@ -0,0 +1,164 @@
+use std::path::PathBuf;
+use std::thread;
+
+use crate::window::WindowProxy;
+
+/// Options of a native open or save file dialog.
+#[derive(Debug, Clone, Default)]
+pub struct FileDialog {
+    pub title: Option<String>,
+    pub directory: Option<PathBuf>,
+    /// Suggested file name of a save dialog
+    pub file_name: Option<String>,
+    /// Filter names with the file extensions they accept, without the leading dot
+    pub filters: Vec<(String, Vec<String>)>,
+    /// Lets an open dialog pick more than one file
+    pub multiple: bool
+}
+
+impl FileDialog {
+    pub fn new() -> FileDialog {
+        FileDialog::default()
+    }
+
+    pub fn title<T>(mut self, title: T) -> FileDialog where T: Into<String> {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn directory<P>(mut self, directory: P) -> FileDialog where P: Into<PathBuf> {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    pub fn file_name<T>(mut self, file_name: T) -> FileDialog where T: Into<String> {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    pub fn filter<T>(mut self, name: T, extensions: &[&str]) -> FileDialog where T: Into<String> {
+        self.filters.push((name.into(), extensions.iter().map(|e| e.to_string()).collect()));
+        self
+    }
+
+    pub fn multiple(mut self, multiple: bool) -> FileDialog {
+        self.multiple = multiple;
+        self
+    }
+
+    fn native(&self) -> rfd::FileDialog {
+        let mut dialog = rfd::FileDialog::new();
+        if let Some(title) = &self.title {
+            dialog = dialog.set_title(title);
+        }
+        if let Some(directory) = &self.directory {
+            dialog = dialog.set_directory(directory);
+        }
+        if let Some(file_name) = &self.file_name {
+            dialog = dialog.set_file_name(file_name);
+        }
+        for (name, extensions) in &self.filters {
+            let extensions: Vec<&str> = extensions.iter().map(|e| e.as_str()).collect();
+            dialog = dialog.add_filter(name, &extensions);
+        }
+        dialog
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageLevel {
+    Info, Warning, Error
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageButtons {
+    Ok, OkCancel, YesNo
+}
+
+/// Native message box, its answer is `true` for Ok and Yes.
+#[derive(Debug, Clone)]
+pub struct MessageBox {
+    pub title: String,
+    pub description: String,
+    pub level: MessageLevel,
+    pub buttons: MessageButtons
+}
+
+impl MessageBox {
+    pub fn new<T, D>(title: T, description: D) -> MessageBox where T: Into<String>, D: Into<String> {
+        MessageBox {
+            title: title.into(),
+            description: description.into(),
+            level: MessageLevel::Info,
+            buttons: MessageButtons::Ok
+        }
+    }
+
+    pub fn level(mut self, level: MessageLevel) -> MessageBox {
+        self.level = level;
+        self
+    }
+
+    pub fn buttons(mut self, buttons: MessageButtons) -> MessageBox {
+        self.buttons = buttons;
+        self
+    }
+
+    fn native(&self) -> rfd::MessageDialog {
+        let level = match self.level {
+            MessageLevel::Info => rfd::MessageLevel::Info,
+            MessageLevel::Warning => rfd::MessageLevel::Warning,
+            MessageLevel::Error => rfd::MessageLevel::Error
+        };
+        let buttons = match self.buttons {
+            MessageButtons::Ok => rfd::MessageButtons::Ok,
+            MessageButtons::OkCancel => rfd::MessageButtons::OkCancel,
+            MessageButtons::YesNo => rfd::MessageButtons::YesNo
+        };
+        rfd::MessageDialog::new()
+            .set_title(&self.title)
+            .set_description(&self.description)
+            .set_level(level)
+            .set_buttons(buttons)
+    }
+}
+
+/// Runs a blocking native dialog on its own thread and sends the event made of its result back through the proxy.
+fn spawn_dialog<T, R, D, F>(proxy: &WindowProxy<T>, dialog: D, event: F)
+    where T: Send + 'static, D: FnOnce() -> R + Send + 'static, F: FnOnce(R) -> T + Send + 'static {
+
+    let proxy = proxy.clone();
+    thread::spawn(move || {
+        let result = dialog();
+        // the window may be gone by the time the dialog closes
+        let _ = proxy.send_event(event(result));
+    });
+}
+
+/// Shows an open file dialog without blocking, the picked files are empty when it was cancelled.
+pub fn open_file_dialog<T, F>(proxy: &WindowProxy<T>, options: FileDialog, event: F)
+    where T: Send + 'static, F: FnOnce(Vec<PathBuf>) -> T + Send + 'static {
+
+    spawn_dialog(proxy, move || {
+        let dialog = options.native();
+        if options.multiple {
+            dialog.pick_files().unwrap_or_default()
+        } else {
+            dialog.pick_file().into_iter().collect()
+        }
+    }, event);
+}
+
+/// Shows a save file dialog without blocking, the path is `None` when it was cancelled.
+pub fn save_file_dialog<T, F>(proxy: &WindowProxy<T>, options: FileDialog, event: F)
+    where T: Send + 'static, F: FnOnce(Option<PathBuf>) -> T + Send + 'static {
+
+    spawn_dialog(proxy, move || options.native().save_file(), event);
+}
+
+/// Shows a message box without blocking.
+pub fn message_box<T, F>(proxy: &WindowProxy<T>, message: MessageBox, event: F)
+    where T: Send + 'static, F: FnOnce(bool) -> T + Send + 'static {
+
+    spawn_dialog(proxy, move || message.native().show(), event);
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+
+use glium::{DrawError, SwapBuffersError, ReadError};
+use glium::framebuffer::{ValidationError, RenderBufferCreationError};
+use glium::program::ProgramCreationError;
+use glium::texture::TextureCreationError;
+
+#[derive(Debug)]
+pub enum Error {
+    Draw(DrawError),
+    VertexBuffer(glium::vertex::BufferCreationError),
+    IndexBuffer(glium::index::BufferCreationError),
+    Texture(TextureCreationError),
+    RenderBuffer(RenderBufferCreationError),
+    Program(ProgramCreationError),
+    Framebuffer(ValidationError),
+    SwapBuffers(SwapBuffersError),
+    Read(ReadError),
+    /// Dynamic buffer could not be sliced to the uploaded length
+    BufferSlice,
+    /// More vertices than u16 indices address were drawn with `IndexFormat::U16`
+    IndexOverflow(usize),
+    /// No shader program is registered under the name
+    MissingProgram(String),
+    /// No render target was created under the name with `TextureManager::create_render_target`
+    MissingRenderTarget(String),
+    /// `DrawBuffer::start_drawing` was called again before a `reset`
+    AlreadyDrawing,
+    /// A `DrawBuffer` was filled or drawn before `start_drawing`
+    NotDrawing,
+    /// Vertices do not have the attributes the `DrawBuffer` was started with
+    VertexFormat(&'static str),
+    /// An index refers past the vertices it was added with
+    VertexIndex { index: u32, vertices: u32 }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Draw(e) => write!(f, "Drawing failed: {}", e),
+            Error::VertexBuffer(e) => write!(f, "Vertex buffer creation failed: {}", e),
+            Error::IndexBuffer(e) => write!(f, "Index buffer creation failed: {}", e),
+            Error::Texture(e) => write!(f, "Texture creation failed: {}", e),
+            Error::RenderBuffer(e) => write!(f, "Render buffer creation failed: {}", e),
+            Error::Program(e) => write!(f, "Program creation failed: {}", e),
+            Error::Framebuffer(e) => write!(f, "Framebuffer creation failed: {}", e),
+            Error::SwapBuffers(e) => write!(f, "Swapping buffers failed: {}", e),
+            Error::Read(e) => write!(f, "Reading pixels failed: {}", e),
+            Error::BufferSlice => write!(f, "Buffer slicing failed"),
+            Error::IndexOverflow(vertices) => write!(f, "{} vertices can not be addressed with u16 indices", vertices),
+            Error::MissingProgram(name) => write!(f, "Missing `{}` shader", name),
+            Error::MissingRenderTarget(name) => write!(f, "Missing render target: {}", name),
+            Error::AlreadyDrawing => write!(f, "Already drawing"),
+            Error::NotDrawing => write!(f, "Not drawing"),
+            Error::VertexFormat(reason) => write!(f, "Illegal vertex: {}", reason),
+            Error::VertexIndex { index, vertices } => write!(f, "Illegal buffer index {} while amount of vertices is {}", index, vertices)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<DrawError> for Error {
+    fn from(error: DrawError) -> Self {
+        Error::Draw(error)
+    }
+}
+
+impl From<glium::vertex::BufferCreationError> for Error {
+    fn from(error: glium::vertex::BufferCreationError) -> Self {
+        Error::VertexBuffer(error)
+    }
+}
+
+impl From<glium::index::BufferCreationError> for Error {
+    fn from(error: glium::index::BufferCreationError) -> Self {
+        Error::IndexBuffer(error)
+    }
+}
+
+impl From<TextureCreationError> for Error {
+    fn from(error: TextureCreationError) -> Self {
+        Error::Texture(error)
+    }
+}
+
+impl From<RenderBufferCreationError> for Error {
+    fn from(error: RenderBufferCreationError) -> Self {
+        Error::RenderBuffer(error)
+    }
+}
+
+impl From<ProgramCreationError> for Error {
+    fn from(error: ProgramCreationError) -> Self {
+        Error::Program(error)
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(error: ValidationError) -> Self {
+        Error::Framebuffer(error)
+    }
+}
+
+impl From<ReadError> for Error {
+    fn from(error: ReadError) -> Self {
+        Error::Read(error)
+    }
+}
+
+impl From<SwapBuffersError> for Error {
+    fn from(error: SwapBuffersError) -> Self {
+        Error::SwapBuffers(error)
+    }
+}
@@ -1,159 +1,51 @@
 /*!
-`FontTexture` will rasterize exact characters it's told to.  It can be passed
-anything that implements `IntoIterator<char>` trait.  It is possible to use
-chained ranges with it like this:
-`(0 .. 0x7f+1).chain(0x400 .. 0x4ff+1).filter_map(std::char::from_u32)`
-
-This will rasterize font with characters from "Basic Latin" and "Cyrillic"
-sets.  Here is a complete list of ranges with description.
-
-*As of now, Rust doesn't provide a way to create inclusive ranges yet so thou
-shall add +1 to the outer bound.*
-
-| Start | End   | Description                             |
-|-------+-------+-----------------------------------------|
-|  0000 | 007F  | Basic Latin                             |
-|  0080 | 00FF  | C1 Controls and Latin-1 Supplement      |
-|  0100 | 017F  | Latin Extended-A                        |
-|  0180 | 024F  | Latin Extended-B                        |
-|  0250 | 02AF  | IPA Extensions                          |
-|  02B0 | 02FF  | Spacing Modifier Letters                |
-|  0300 | 036F  | Combining Diacritical Marks             |
-|  0370 | 03FF  | Greek/Coptic                            |
-|  0400 | 04FF  | Cyrillic                                |
-|  0500 | 052F  | Cyrillic Supplement                     |
-|  0530 | 058F  | Armenian                                |
-|  0590 | 05FF  | Hebrew                                  |
-|  0600 | 06FF  | Arabic                                  |
-|  0700 | 074F  | Syriac                                  |
-|  0780 | 07BF  | Thaana                                  |
-|  0900 | 097F  | Devanagari                              |
-|  0980 | 09FF  | Bengali/Assamese                        |
-|  0A00 | 0A7F  | Gurmukhi                                |
-|  0A80 | 0AFF  | Gujarati                                |
-|  0B00 | 0B7F  | Oriya                                   |
-|  0B80 | 0BFF  | Tamil                                   |
-|  0C00 | 0C7F  | Telugu                                  |
-|  0C80 | 0CFF  | Kannada                                 |
-|  0D00 | 0DFF  | Malayalam                               |
-|  0D80 | 0DFF  | Sinhala                                 |
-|  0E00 | 0E7F  | Thai                                    |
-|  0E80 | 0EFF  | Lao                                     |
-|  0F00 | 0FFF  | Tibetan                                 |
-|  1000 | 109F  | Myanmar                                 |
-|  10A0 | 10FF  | Georgian                                |
-|  1100 | 11FF  | Hangul Jamo                             |
-|  1200 | 137F  | Ethiopic                                |
-|  13A0 | 13FF  | Cherokee                                |
-|  1400 | 167F  | Unified Canadian Aboriginal Syllabics   |
-|  1680 | 169F  | Ogham                                   |
-|  16A0 | 16FF  | Runic                                   |
-|  1700 | 171F  | Tagalog                                 |
-|  1720 | 173F  | Hanunoo                                 |
-|  1740 | 175F  | Buhid                                   |
-|  1760 | 177F  | Tagbanwa                                |
-|  1780 | 17FF  | Khmer                                   |
-|  1800 | 18AF  | Mongolian                               |
-|  1900 | 194F  | Limbu                                   |
-|  1950 | 197F  | Tai Le                                  |
-|  19E0 | 19FF  | Khmer Symbols                           |
-|  1D00 | 1D7F  | Phonetic Extensions                     |
-|  1E00 | 1EFF  | Latin Extended Additional               |
-|  1F00 | 1FFF  | Greek Extended                          |
-|  2000 | 206F  | General Punctuation                     |
-|  2070 | 209F  | Superscripts and Subscripts             |
-|  20A0 | 20CF  | Currency Symbols                        |
-|  20D0 | 20FF  | Combining Diacritical Marks for Symbols |
-|  2100 | 214F  | Letterlike Symbols                      |
-|  2150 | 218F  | Number Forms                            |
-|  2190 | 21FF  | Arrows                                  |
-|  2200 | 22FF  | Mathematical Operators                  |
-|  2300 | 23FF  | Miscellaneous Technical                 |
-|  2400 | 243F  | Control Pictures                        |
-|  2440 | 245F  | Optical Character Recognition           |
-|  2460 | 24FF  | Enclosed Alphanumerics                  |
-|  2500 | 257F  | Box Drawing                             |
-|  2580 | 259F  | Block Elements                          |
-|  25A0 | 25FF  | Geometric Shapes                        |
-|  2600 | 26FF  | Miscellaneous Symbols                   |
-|  2700 | 27BF  | Dingbats                                |
-|  27C0 | 27EF  | Miscellaneous Mathematical Symbols-A    |
-|  27F0 | 27FF  | Supplemental Arrows-A                   |
-|  2800 | 28FF  | Braille Patterns                        |
-|  2900 | 297F  | Supplemental Arrows-B                   |
-|  2980 | 29FF  | Miscellaneous Mathematical Symbols-B    |
-|  2A00 | 2AFF  | Supplemental Mathematical Operators     |
-|  2B00 | 2BFF  | Miscellaneous Symbols and Arrows        |
-|  2E80 | 2EFF  | CJK Radicals Supplement                 |
-|  2F00 | 2FDF  | Kangxi Radicals                         |
-|  2FF0 | 2FFF  | Ideographic Description Characters      |
-|  3000 | 303F  | CJK Symbols and Punctuation             |
-|  3040 | 309F  | Hiragana                                |
-|  30A0 | 30FF  | Katakana                                |
-|  3100 | 312F  | Bopomofo                                |
-|  3130 | 318F  | Hangul Compatibility Jamo               |
-|  3190 | 319F  | Kanbun (Kunten)                         |
-|  31A0 | 31BF  | Bopomofo Extended                       |
-|  31F0 | 31FF  | Katakana Phonetic Extensions            |
-|  3200 | 32FF  | Enclosed CJK Letters and Months         |
-|  3300 | 33FF  | CJK Compatibility                       |
-|  3400 | 4DBF  | CJK Unified Ideographs Extension A      |
-|  4DC0 | 4DFF  | Yijing Hexagram Symbols                 |
-|  4E00 | 9FAF  | CJK Unified Ideographs                  |
-|  A000 | A48F  | Yi Syllables                            |
-|  A490 | A4CF  | Yi Radicals                             |
-|  AC00 | D7AF  | Hangul Syllables                        |
-|  D800 | DBFF  | High Surrogate Area                     |
-|  DC00 | DFFF  | Low Surrogate Area                      |
-|  E000 | F8FF  | Private Use Area                        |
-|  F900 | FAFF  | CJK Compatibility Ideographs            |
-|  FB00 | FB4F  | Alphabetic Presentation Forms           |
-|  FB50 | FDFF  | Arabic Presentation Forms-A             |
-|  FE00 | FE0F  | Variation Selectors                     |
-|  FE20 | FE2F  | Combining Half Marks                    |
-|  FE30 | FE4F  | CJK Compatibility Forms                 |
-|  FE50 | FE6F  | Small Form Variants                     |
-|  FE70 | FEFF  | Arabic Presentation Forms-B             |
-|  FF00 | FFEF  | Halfwidth and Fullwidth Forms           |
-|  FFF0 | FFFF  | Specials                                |
-| 10000 | 1007F | Linear B Syllabary                      |
-| 10080 | 100FF | Linear B Ideograms                      |
-| 10100 | 1013F | Aegean Numbers                          |
-| 10300 | 1032F | Old Italic                              |
-| 10330 | 1034F | Gothic                                  |
-| 10380 | 1039F | Ugaritic                                |
-| 10400 | 1044F | Deseret                                 |
-| 10450 | 1047F | Shavian                                 |
-| 10480 | 104AF | Osmanya                                 |
-| 10800 | 1083F | Cypriot Syllabary                       |
-| 1D000 | 1D0FF | Byzantine Musical Symbols               |
-| 1D100 | 1D1FF | Musical Symbols                         |
-| 1D300 | 1D35F | Tai Xuan Jing Symbols                   |
-| 1D400 | 1D7FF | Mathematical Alphanumeric Symbols       |
-| 20000 | 2A6DF | CJK Unified Ideographs Extension B      |
-| 2F800 | 2FA1F | CJK Compatibility Ideographs Supplement |
-| E0000 | E007F | Tags                                    |
-| E0100 | E01EF | Variation Selectors Supplement          |
+Text rendering.
 
+Fonts are registered with `FontManager` per family and style. Glyphs are rasterized with rusttype
+the first time a face draws them at a given size and kept in a `GlyphCache` atlas shared by all
+faces, so every character covered by a font can be drawn and a string costs a single draw call.
+
+The manager lives on the thread of its display like every GL object. Its `FontLibrary` handle
+can be sent to worker threads to lay out text and rasterize glyphs ahead of drawing them.
 */
 
-use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ops::Range;
+use std::cmp::Ordering;
 use std::default::Default;
+use std::fs::File;
 use std::io::Read;
-use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::rc::Rc;
 
-use rusttype::{Rect, Point};
+use rusttype::{Scale, GlyphId, point};
+use unicode_bidi::BidiInfo;
+
+use glium::{DrawParameters, Surface, VertexBuffer, Rect as GLRect, Display};
+use glium::index::{NoIndices, PrimitiveType};
 
-use glium::{DrawParameters, Surface, backend::{Context, Facade}, Rect as GLRect, Display};
+use cgmath::Matrix4;
 
-use cgmath::{Matrix4, Vector3};
+use crate::glyph_cache::{GlyphCache, GlyphKey, GlyphBitmap, CachedGlyph, GlyphCacheStats};
+use crate::backend::Renderer;
+use crate::render::GpuTimer;
 
 pub const DEFAULT_FONT: &'static [u8] = include_bytes!("resources/fonts/default.ttf");
 pub const DEFAULT_FONT_SIZE: u32 = 40;
+/// Name the built-in font is registered under
+pub const DEFAULT_FAMILY: &'static str = "default";
 pub const BOLD_FACTOR: f32 = 100.0 / 3.0;
-pub const ITALIC_FACTOR: f32 = 1333.3;
+/// Horizontal offset per unit of height of faked italics
+pub const ITALIC_SHEAR: f32 = 0.2;
+
+/// Text is drawn at this fraction of `FontParameters::size` logical pixels, glyphs are rasterized
+/// at that size times the scale factor so they map to physical pixels.
+const RASTER_SCALE: f32 = 0.5;
+/// Size distance field glyphs are rasterized at, whatever size they are drawn at
+pub const SDF_SIZE: u32 = 64;
+/// Distance in raster pixels covered by the field on each side of a glyph outline
+pub const SDF_SPREAD: u32 = 6;
 
 #[derive(Clone)]
 pub struct FontParameters {
@@ -165,8 +57,229 @@ pub struct FontParameters {
     pub strikeout: bool,
     pub underline: bool,
     pub scissor: Option<GLRect>,
+    /// Only draws where the stencil buffer holds this value
+    pub stencil: Option<i32>,
     pub align_horizontal: TextAlignHorizontal,
-    pub align_vertical: TextAlignVertical
+    pub align_vertical: TextAlignVertical,
+    /// Family registered with `FontManager::load_font`, unknown names use the default font.
+    /// `bold` and `italic` pick the matching face of the family, or are faked when it has none
+    pub family: Option<String>,
+    /// Draws glyphs from signed distance fields shared by all sizes, which stay sharp when
+    /// scaled up but lose fine detail of small text
+    pub sdf: bool,
+    /// Line breaking of `FontManager::layout_text`
+    pub wrap: TextWrap,
+    pub shadow: Option<TextShadow>,
+    pub outline: Option<TextOutline>,
+    /// Logical pixels added after every character
+    pub letter_spacing: f32,
+    /// Multiplier of the distance between lines
+    pub line_height: f32,
+    /// Distance between tab stops in spaces
+    pub tab_width: u32,
+    /// Only supported by `draw_string`
+    pub orientation: TextOrientation
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextOrientation {
+    Horizontal,
+    /// Characters stay upright and are stacked from top to bottom, as on the side of a chart
+    Vertical
+}
+
+/// Copy of the text drawn behind it, offset and blur are in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextShadow {
+    pub offset: [f32; 2],
+    pub blur: f32,
+    pub color: [f32; 4]
+}
+
+/// Outline drawn around the glyphs, it can grow by at most `SDF_SPREAD` raster pixels with `sdf`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextOutline {
+    pub width: f32,
+    pub color: [f32; 4]
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextWrap {
+    /// Breaks words too long for a line with a hyphen instead of between any two characters
+    pub hyphenate: bool,
+    /// Ends the last line with an ellipsis when the text has more lines than `max_lines`
+    pub ellipsis: bool,
+    pub max_lines: Option<usize>
+}
+
+/// Box of a character placed by `FontManager::layout_text`, in logical units from the start of its line.
+/// Characters shaped into a single glyph, such as ligatures, split its advance evenly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutGlyph {
+    /// Index of the character in the laid out text
+    pub index: usize,
+    pub ch: char,
+    pub x: f32,
+    pub advance: f32,
+    /// Hyphens and ellipses added by the layout which are not part of the text
+    pub inserted: bool,
+    /// Whether the glyph is part of right to left text, drawn after the glyph that follows it
+    pub rtl: bool
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine {
+    /// Range of character indices shown on the line, without the line break
+    pub start: usize,
+    pub end: usize,
+    /// Offset of the line from the layout origin applied by the horizontal alignment
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub baseline: f32,
+    /// Character boxes in display order, for carets, hit testing and selections
+    pub glyphs: Vec<LayoutGlyph>,
+    /// Glyphs of the face in display order, which is what gets drawn
+    pub shaped: Vec<ShapedGlyph>
+}
+
+/// Glyph of the face chosen by shaping a line, several characters can share one and a character can take several.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph {
+    /// Index of the glyph in the face
+    pub id: u32,
+    /// Pen position in logical units from the start of the line
+    pub x: f32,
+    /// Shift above the baseline, as marks get
+    pub y: f32
+}
+
+/// Vertical metrics of a face at some size in logical pixels, see `FontManager::metrics`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    /// Height of the tallest glyphs above the baseline
+    pub ascent: f32,
+    /// Depth of the lowest glyphs, negative below the baseline
+    pub descent: f32,
+    pub line_gap: f32,
+    /// Distance between the baselines of wrapped lines, including `FontParameters::line_height`
+    pub line_height: f32
+}
+
+/// Text broken into lines, see `FontManager::layout_text`.
+#[derive(Clone)]
+pub struct TextLayout {
+    pub lines: Vec<TextLine>,
+    pub width: f32,
+    pub height: f32,
+    pub line_height: f32,
+    /// Whether lines were dropped because of `TextWrap::max_lines`
+    pub truncated: bool,
+    params: FontParameters
+}
+
+impl TextLayout {
+    pub fn params(&self) -> &FontParameters {
+        &self.params
+    }
+
+    /// Index of the character boundary closest to a point relative to the layout origin.
+    /// Points outside the layout snap to the nearest line and to its start or end.
+    pub fn hit_test(&self, x: f32, y: f32) -> usize {
+        if self.lines.is_empty() {
+            return 0;
+        }
+        let row = (y / self.line_height).floor().max(0.0) as usize;
+        let row = row.min(self.lines.len() - 1);
+        let line = &self.lines[row];
+        let x = x - line.x;
+        for glyph in line.glyphs.iter().filter(|glyph| !glyph.inserted) {
+            if x < glyph.x + glyph.advance / 2.0 {
+                // the leading half of a right to left glyph is its right half
+                return if glyph.rtl { glyph.index + 1 } else { glyph.index };
+            }
+            if glyph.rtl && x < glyph.x + glyph.advance {
+                return glyph.index;
+            }
+        }
+        // a line broken at whitespace puts the caret before the space, its end is the start of the next line
+        let wrapped = self.lines.get(row + 1).map_or(false, |next| next.start == line.end);
+        match line.glyphs.iter().rev().find(|glyph| !glyph.inserted) {
+            Some(glyph) if wrapped && glyph.ch.is_whitespace() => glyph.index,
+            _ => line.end
+        }
+    }
+
+    /// Top left corner and height of the caret placed before the character at `index`,
+    /// relative to the layout origin. Indices past the text put the caret at its end.
+    pub fn caret_position(&self, index: usize) -> (f32, f32, f32) {
+        let row = self.line_of(index);
+        let line = match self.lines.get(row) {
+            Some(line) => line,
+            None => return (0.0, 0.0, self.line_height)
+        };
+        let edge = |glyph: &LayoutGlyph, before: bool| if glyph.rtl == before { glyph.x + glyph.advance } else { glyph.x };
+        let x = line.glyphs.iter()
+            .filter(|glyph| !glyph.inserted)
+            .find(|glyph| glyph.index == index)
+            .map(|glyph| edge(glyph, true))
+            .or_else(|| line.glyphs.iter()
+                .filter(|glyph| !glyph.inserted)
+                .max_by_key(|glyph| glyph.index)
+                .map(|glyph| edge(glyph, false)))
+            .unwrap_or(0.0);
+        (line.x + x, line.y, self.line_height)
+    }
+
+    /// Boundary next to the caret at `index` on screen, to the right when `right` is set. Inside right
+    /// to left text that is an earlier character. The caret stays put at either end of its line.
+    pub fn move_caret(&self, index: usize, right: bool) -> usize {
+        let row = self.line_of(index);
+        let line = match self.lines.get(row) {
+            Some(line) => line,
+            None => return index
+        };
+        let (x, _, _) = self.caret_position(index);
+        (line.start ..= line.end)
+            .filter(|&other| other != index && self.line_of(other) == row)
+            .map(|other| (other, self.caret_position(other).0 - x))
+            .filter(|&(_, distance)| if right { distance > 0.0 } else { distance < 0.0 })
+            .min_by(|a, b| a.1.abs().partial_cmp(&b.1.abs()).unwrap_or(Ordering::Equal))
+            .map_or(index, |(other, _)| other)
+    }
+
+    /// Rectangles covering the characters of a range relative to the layout origin, one per line
+    /// and more where right to left runs split the range visually.
+    pub fn selection_rects(&self, range: Range<usize>) -> Vec<[f32; 4]> {
+        let mut rects = Vec::new();
+        for line in self.lines.iter().filter(|line| line.start < range.end && line.end >= range.start) {
+            let mut run: Option<(f32, f32)> = None;
+            for glyph in &line.glyphs {
+                let selected = !glyph.inserted && range.contains(&glyph.index);
+                // glyphs are in display order, so neighbouring selected glyphs form one rectangle
+                match (selected, run) {
+                    (true, Some((left, _))) => run = Some((left, glyph.x + glyph.advance)),
+                    (true, None) => run = Some((glyph.x, glyph.x + glyph.advance)),
+                    (false, Some((left, right))) => {
+                        rects.push([line.x + left, line.y, right - left, self.line_height]);
+                        run = None;
+                    },
+                    (false, None) => {}
+                }
+            }
+            if let Some((left, right)) = run {
+                rects.push([line.x + left, line.y, right - left, self.line_height]);
+            }
+        }
+        rects
+    }
+
+    /// Line showing the character at `index`, a boundary shared by two wrapped lines belongs to the later one.
+    pub fn line_of(&self, index: usize) -> usize {
+        self.lines.iter()
+            .rposition(|line| line.start <= index)
+            .unwrap_or(0)
+    }
 }
 
 impl Default for FontParameters {
@@ -175,661 +288,1625 @@ impl Default for FontParameters {
             size: DEFAULT_FONT_SIZE, width_limit: ::std::usize::MAX,
             color: [0.0, 0.0, 0.0, 1.0], bold: false, italic: false, underline: false, strikeout: false,
             scissor: None,
+            stencil: None,
             align_horizontal: TextAlignHorizontal::Center,
-            align_vertical: TextAlignVertical::Top
+            align_vertical: TextAlignVertical::Top,
+            family: None,
+            sdf: false,
+            wrap: TextWrap::default(),
+            shadow: None,
+            outline: None,
+            letter_spacing: 0.0,
+            line_height: 1.0,
+            tab_width: 4,
+            orientation: TextOrientation::Horizontal
         }
     }
 }
 
+/// Family name with the bold and italic flags of one of its faces.
+pub type FaceKey = (String, bool, bool);
+
 pub struct FontManager {
     display: Display,
     system: TextSystem,
-    textures: HashMap<u32, Rc<FontTexture>>
+    library: FontLibrary,
+    cache: GlyphCache,
+    vertices: Option<VertexBuffer<VertexFormat>>,
+    gpu_timer: Option<Rc<GpuTimer>>
 }
 
-impl FontManager {
-    pub fn new(display: &Display) -> FontManager {
-        let chars = Self::supported_chars();
-        let mut textures = HashMap::new();
-        textures.insert(DEFAULT_FONT_SIZE, Rc::new(
-            FontTexture::new(display, DEFAULT_FONT, DEFAULT_FONT_SIZE, chars)
-                .expect("Default font texture allocation failed")
-        ));
-        FontManager {
-            display: display.clone(),
-            system: TextSystem::new(display),
-            textures
-        }
+/// Faces and scale factor of a `FontManager`, shared with other threads to lay out text and
+/// rasterize glyphs away from the UI thread. The atlas and everything else on the GPU stays with
+/// the manager on the thread of its display.
+#[derive(Clone)]
+pub struct FontLibrary {
+    state: Arc<RwLock<LibraryState>>
+}
+
+struct LibraryState {
+    fonts: HashMap<FaceKey, Arc<rusttype::Font<'static>>>,
+    /// Font file and face index of every face, read by the shaper
+    data: HashMap<FaceKey, (Arc<[u8]>, u32)>,
+    scale_factor: f32
+}
+
+/// Glyphs rasterized by `FontLibrary::rasterize`, which `FontManager::insert_glyphs` adds to the atlas.
+pub struct RasterizedGlyphs {
+    glyphs: Vec<(GlyphKey, GlyphBitmap)>
+}
+
+impl RasterizedGlyphs {
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+}
+
+impl FontLibrary {
+    /// Library holding only the default face, as a `FontManager` starts with.
+    pub fn new() -> FontLibrary {
+        let data: Arc<[u8]> = DEFAULT_FONT.into();
+        let default = rusttype::FontCollection::from_bytes(data.clone())
+            .and_then(|collection| collection.into_font())
+            .expect("Default font is invalid");
+        let key = (DEFAULT_FAMILY.to_owned(), false, false);
+        let mut fonts = HashMap::new();
+        fonts.insert(key.clone(), Arc::new(default));
+        let mut faces = HashMap::new();
+        faces.insert(key, (data, 0));
+        FontLibrary { state: Arc::new(RwLock::new(LibraryState { fonts, data: faces, scale_factor: 1.0 })) }
+    }
+
+    fn state(&self) -> RwLockReadGuard<'_, LibraryState> {
+        self.state.read().expect("Font library lock poisoned")
     }
 
-    pub fn supported_chars() -> impl Iterator<Item=char> {
-        vec!['•' as u32].into_iter()
-            .chain(0 .. 0x7f+1)
-            .chain(0x370 .. 0x3FF+1)
-            .chain(0x400 .. 0x4ff+1)
-            .filter_map(std::char::from_u32)
+    fn state_mut(&self) -> RwLockWriteGuard<'_, LibraryState> {
+        self.state.write().expect("Font library lock poisoned")
     }
 
-    fn get_or_load_texture(&mut self, size: u32, chars: impl Iterator<Item=char>) -> Rc<FontTexture> {
-        if !self.textures.contains_key(&size) {
-            self.textures.insert(size, Rc::new(
-                FontTexture::new(&self.display, DEFAULT_FONT, size, chars)
-                    .expect("Font texture allocation failed")
-            ));
+    fn font(&self, key: &FaceKey) -> Arc<rusttype::Font<'static>> {
+        self.state().fonts[key].clone()
+    }
+
+    /// Whether a face was replaced. `data` is the font file `font` was read from, `index` its face in there.
+    fn insert(&self, key: FaceKey, font: rusttype::Font<'static>, data: Arc<[u8]>, index: u32) -> bool {
+        let mut state = self.state_mut();
+        state.data.insert(key.clone(), (data, index));
+        state.fonts.insert(key, Arc::new(font)).is_some()
+    }
+
+    pub fn has_font<N>(&self, name: N) -> bool where N: AsRef<str> {
+        let name = name.as_ref();
+        self.state().fonts.keys().any(|(family, _, _)| family == name)
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.state().scale_factor
+    }
+
+    /// Whether the factor changed.
+    fn set_scale_factor(&self, scale_factor: f32) -> bool {
+        let mut state = self.state_mut();
+        if scale_factor > 0.0 && scale_factor != state.scale_factor {
+            state.scale_factor = scale_factor;
+            true
+        } else {
+            false
         }
-        self.textures.get(&size).cloned().unwrap()
     }
 
-    pub fn draw_string<S, T>(&mut self, target: &mut S, text: T, x: f32, y: f32, mut viewport: Matrix4<f32>,
-                             params: &FontParameters)
-        where S: Surface, T: AsRef<str> {
+    /// Picks the closest registered face, preferring to keep the weight over the slant.
+    fn resolve_face(&self, params: &FontParameters) -> ResolvedFace {
+        let state = self.state();
+        let (bold, italic) = (params.bold, params.italic);
+        let families = params.family.as_deref().into_iter().chain(Some(DEFAULT_FAMILY));
+        for family in families {
+            let styles = [(bold, italic), (bold, false), (false, italic), (false, false)];
+            for &(b, i) in styles.iter() {
+                let key = (family.to_owned(), b, i);
+                if state.fonts.contains_key(&key) {
+                    return ResolvedFace { key, faux_bold: bold && !b, faux_italic: italic && !i };
+                }
+            }
+        }
+        unreachable!("Default font face is missing")
+    }
 
-        let color = &params.color;
-        let text = text.as_ref();
+    /// Pixel size the glyphs drawn with `params` are rasterized at, and whether they are distance fields.
+    fn raster_size(&self, params: &FontParameters) -> (u32, bool) {
+        if params.sdf {
+            (SDF_SIZE, true)
+        } else {
+            ((params.size as f32 * RASTER_SCALE * self.scale_factor()).round().max(1.0) as u32, false)
+        }
+    }
+
+    /// Logical units per raster pixel of the glyphs drawn with `params`.
+    fn glyph_unit(&self, params: &FontParameters) -> f32 {
+        let (size, _) = self.raster_size(params);
+        RASTER_SCALE * params.size as f32 / size as f32
+    }
 
-        if params.italic {
-            //mat.x.y = 0.0;
-            viewport.y.x = params.size as f32 / ITALIC_FACTOR;
+    pub fn metrics(&self, params: &FontParameters) -> FontMetrics {
+        let face = self.resolve_face(params);
+        let unit = self.glyph_unit(params);
+        let metrics = self.font(&face.key).v_metrics(Scale::uniform(self.raster_size(params).0 as f32));
+        FontMetrics {
+            ascent: metrics.ascent * unit,
+            descent: metrics.descent * unit,
+            line_gap: metrics.line_gap * unit,
+            line_height: (metrics.ascent - metrics.descent + metrics.line_gap) * unit * params.line_height
         }
+    }
 
-        let mut lines = Vec::new();
-        let mut chars = text.chars().collect::<Vec<char>>();
-        let parts = chars.len() / params.width_limit + 1;
+    /// Same as `FontManager::layout_text`, callable from any thread.
+    pub fn layout_text<T>(&self, text: T, max_width: f32, params: &FontParameters) -> TextLayout where T: AsRef<str> {
+        let chars = text.as_ref().chars().collect::<Vec<_>>();
+        let face = self.resolve_face(params);
+        let font = self.font(&face.key);
+        let unit = self.glyph_unit(params);
+        let scale = Scale::uniform(self.raster_size(params).0 as f32);
+        let metrics = font.v_metrics(scale);
+        let natural_height = (metrics.ascent - metrics.descent + metrics.line_gap) * unit;
+        let line_height = natural_height * params.line_height;
+        let bold = if face.faux_bold { params.size as f32 / BOLD_FACTOR } else { 0.0 };
+        let measure = |previous: Option<char>, ch: char| {
+            let (kerning, advance) = FontManager::advance(&font, scale, previous, ch);
+            (kerning * unit, advance * unit)
+        };
+        let tab_stop = tab_stop(&font, scale, params) * unit;
+        let levels = bidi_levels(&chars);
+        // shaped advances already hold the kerning, lines are broken on them and shaped again once broken
+        let advances = self.shaped_advances(&face.key, &font, scale, &chars, levels.as_deref());
 
-        for i in 0..parts {
-            let mut line = String::new();
-            for j in 0..(chars.len().min(params.width_limit)) {
-                line.push(chars.remove(0));
+        let mut lines = Vec::new();
+        let mut glyphs: Vec<LayoutGlyph> = Vec::new();
+        let mut pen = 0.0;
+        // number of glyphs the line can be cut after
+        let mut last_break = None;
+        let mut line_start = 0;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let ch = chars[i];
+            if ch == '\n' {
+                lines.push(FontManager::finish_line(glyphs, line_start, i, bold));
+                glyphs = Vec::new();
+                pen = 0.0;
+                last_break = None;
+                line_start = i + 1;
+                i += 1;
+                continue;
+            }
+            let advance = if ch == '\t' { tab_stop - pen % tab_stop } else { advances[i] * unit } + params.letter_spacing;
+            if pen + advance + bold > max_width && !ch.is_whitespace() && !glyphs.is_empty() {
+                let cut = match last_break {
+                    Some(count) => count,
+                    None if params.wrap.hyphenate => {
+                        let (_, hyphen) = measure(None, '-');
+                        let mut count = glyphs.len();
+                        while count > 1 && glyphs[count - 1].x + glyphs[count - 1].advance + hyphen + bold > max_width {
+                            count -= 1;
+                        }
+                        glyphs.truncate(count);
+                        let last = glyphs[count - 1];
+                        glyphs.push(LayoutGlyph {
+                            index: last.index + 1, ch: '-', x: last.x + last.advance, advance: hyphen, inserted: true, rtl: false
+                        });
+                        count
+                    },
+                    None => glyphs.len()
+                };
+                let next = glyphs[cut - 1].index + 1;
+                if glyphs.len() > cut && !glyphs[cut].inserted {
+                    glyphs.truncate(cut);
+                }
+                lines.push(FontManager::finish_line(glyphs, line_start, next, bold));
+                glyphs = Vec::new();
+                pen = 0.0;
+                last_break = None;
+                line_start = next;
+                i = next;
+                continue;
+            }
+            glyphs.push(LayoutGlyph { index: i, ch, x: pen, advance, inserted: false, rtl: false });
+            pen += advance;
+            if ch.is_whitespace() || ch == '-' {
+                last_break = Some(glyphs.len());
+            }
+            i += 1;
+        }
+        lines.push(FontManager::finish_line(glyphs, line_start, chars.len(), bold));
+
+        let mut truncated = false;
+        if let Some(max_lines) = params.wrap.max_lines {
+            if lines.len() > max_lines.max(1) {
+                lines.truncate(max_lines.max(1));
+                truncated = true;
+                if params.wrap.ellipsis {
+                    let ellipsis = if font.glyph('…').id().0 != 0 { '…' } else { '.' };
+                    let count = if ellipsis == '…' { 1 } else { 3 };
+                    let (_, advance) = measure(None, ellipsis);
+                    let line = lines.last_mut().unwrap();
+                    while line.glyphs.last().map_or(false, |g| {
+                        g.ch.is_whitespace() || g.inserted || g.x + g.advance + advance * count as f32 + bold > max_width
+                    }) {
+                        line.glyphs.pop();
+                    }
+                    let mut x = line.glyphs.last().map_or(0.0, |g| g.x + g.advance);
+                    line.end = line.glyphs.last().map_or(line.start, |g| g.index + 1);
+                    for _ in 0 .. count {
+                        line.glyphs.push(LayoutGlyph { index: line.end, ch: ellipsis, x, advance, inserted: true, rtl: false });
+                        x += advance;
+                    }
+                    line.width = x + bold;
+                }
             }
-            lines.push(line);
         }
 
-        let texture = self.get_or_load_texture(params.size, Self::supported_chars());
+        for line in lines.iter_mut() {
+            self.shape_line(line, levels.as_deref(), &face.key, &font, scale, unit, params.letter_spacing, bold);
+        }
 
-        for (i, text) in lines.into_iter().enumerate() {
-            let (w, h) = self.get_string_bounds(&text, params);
-            let x = match params.align_horizontal {
-                TextAlignHorizontal::Left => x,
-                TextAlignHorizontal::Right => x - w,
-                TextAlignHorizontal::Center => x - w / 2.0
+        let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+        let area = if max_width.is_finite() { max_width } else { width };
+        for (i, line) in lines.iter_mut().enumerate() {
+            line.x = match params.align_horizontal {
+                TextAlignHorizontal::Left => 0.0,
+                TextAlignHorizontal::Right => area - line.width,
+                TextAlignHorizontal::Center => (area - line.width) / 2.0
             };
-            let y = match params.align_vertical {
-                TextAlignVertical::Top => y,
-                TextAlignVertical::Bottom => y - h,
-                TextAlignVertical::Center => y - h / 2.0
-            };
-            let mat = viewport
-                * Matrix4::from_translation(Vector3::new(x, y + params.size as f32 / 2.0 * (i as f32 + 0.777777775), 0.0))
-                * Matrix4::from_scale(params.size as f32 / 2.0);
+            line.y = i as f32 * line_height;
+            // extra line height is split evenly above and below the line
+            line.baseline = line.y + metrics.ascent * unit + (line_height - natural_height) / 2.0;
+        }
+
+        TextLayout {
+            height: lines.len() as f32 * line_height,
+            lines,
+            width,
+            line_height,
+            truncated,
+            params: params.clone()
+        }
+    }
 
-            let text = TextDisplay::new(&self.system, &*texture, text.as_ref());
+    /// Shapes text going in a single direction with the face of `key`. Glyphs come in display order
+    /// with their advances and offsets in raster pixels of `scale`.
+    fn shape(&self, key: &FaceKey, font: &rusttype::Font, scale: Scale, text: &[char], rtl: bool) -> Vec<RunGlyph> {
+        let (data, index) = self.state().data[key].clone();
+        let face = match rustybuzz::Face::from_slice(&data, index) {
+            Some(face) => face,
+            // rusttype could read the face, so at least its characters can be drawn one by one
+            None => return nominal_glyphs(font, scale, text, rtl)
+        };
+        let string = text.iter().collect::<String>();
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(&string);
+        buffer.set_direction(if rtl { rustybuzz::Direction::RightToLeft } else { rustybuzz::Direction::LeftToRight });
+        buffer.guess_segment_properties();
+        let output = rustybuzz::shape(&face, &[], buffer);
+
+        // clusters are byte offsets into the string
+        let mut chars_before = vec![0; string.len() + 1];
+        for (count, (offset, _)) in string.char_indices().enumerate() {
+            chars_before[offset] = count;
+        }
+        // rusttype scales the height from ascent to descent to the pixel size
+        let metrics = font.v_metrics_unscaled();
+        let factor = scale.y / (metrics.ascent - metrics.descent);
+        output.glyph_infos().iter()
+            .zip(output.glyph_positions())
+            .map(|(info, position)| RunGlyph {
+                id: info.glyph_id,
+                cluster: chars_before[info.cluster as usize],
+                advance: position.x_advance as f32 * factor,
+                offset: [position.x_offset as f32 * factor, position.y_offset as f32 * factor]
+            })
+            .collect()
+    }
 
-            draw(&text, &self.system, target, mat, *color, params.scissor.clone())
-                .expect("Text drawing failed");
+    /// Shaped advance of every character in logical order, in raster pixels. Line breaks and tabs stay at zero.
+    fn shaped_advances(&self, key: &FaceKey, font: &rusttype::Font, scale: Scale, chars: &[char],
+                       levels: Option<&[(u8, u8)]>) -> Vec<f32> {
+        let levels = match levels {
+            Some(levels) => levels.iter().map(|&(level, _)| level).collect(),
+            None => vec![0; chars.len()]
+        };
+        let mut advances = vec![0.0; chars.len()];
+        for run in direction_runs(chars, &levels) {
+            if chars[run.start] != '\n' && chars[run.start] != '\t' {
+                let glyphs = self.shape(key, font, scale, &chars[run.clone()], levels[run.start] % 2 == 1);
+                advances[run.clone()].copy_from_slice(&char_advances(&glyphs, run.len()));
+            }
         }
+        advances
     }
 
-    pub fn get_string_bounds(&mut self, text: &str, params: &FontParameters) -> (f32, f32) {
-        let texture = self.get_or_load_texture(params.size, Self::supported_chars());
-        let text = TextDisplay::new(&self.system, &*texture, text);
-        let em = params.size as f32 / 2.0;
-        (text.get_width() * em, text.get_height() * em)
+    /// Shapes the runs of a line broken in logical order and lays them out in display order. The characters
+    /// get their boxes from the shaped advances, so kerning and letter spacing stay as they are drawn.
+    fn shape_line(&self, line: &mut TextLine, levels: Option<&[(u8, u8)]>, key: &FaceKey, font: &rusttype::Font,
+                  scale: Scale, unit: f32, letter_spacing: f32, bold: f32) {
+        let chars = line.glyphs.iter().map(|glyph| glyph.ch).collect::<Vec<_>>();
+        let glyph_levels = match levels {
+            Some(levels) => line_levels(line, levels),
+            None => vec![0; chars.len()]
+        };
+        let runs = direction_runs(&chars, &glyph_levels);
+        let run_levels = runs.iter().map(|run| glyph_levels[run.start]).collect::<Vec<_>>();
+
+        let mut boxes = Vec::with_capacity(chars.len());
+        let mut shaped = Vec::new();
+        let mut x = 0.0;
+        for r in visual_runs(&run_levels) {
+            let run = runs[r].clone();
+            let rtl = run_levels[r] % 2 == 1;
+            if chars[run.start] == '\t' {
+                // tabs keep the advance to their tab stop
+                let tab = line.glyphs[run.start];
+                boxes.push(LayoutGlyph { x, rtl, .. tab });
+                x += tab.advance;
+                continue;
+            }
+            let glyphs = self.shape(key, font, scale, &chars[run.clone()], rtl);
+            let sizes = cluster_sizes(&glyphs, run.len());
+            let advances = char_advances(&glyphs, run.len()).into_iter()
+                .map(|advance| advance * unit + letter_spacing)
+                .collect::<Vec<_>>();
+
+            let mut pen = x;
+            for (i, glyph) in glyphs.iter().enumerate() {
+                shaped.push(ShapedGlyph { id: glyph.id, x: pen + glyph.offset[0] * unit, y: glyph.offset[1] * unit });
+                pen += glyph.advance * unit;
+                // letter spacing goes after every character of a cluster once its last glyph is placed
+                if glyphs.get(i + 1).map_or(true, |next| next.cluster != glyph.cluster) {
+                    pen += letter_spacing * sizes[glyph.cluster] as f32;
+                }
+            }
+
+            // right to left characters are laid out from the right edge of their run
+            let width: f32 = advances.iter().sum();
+            let mut edge = if rtl { x + width } else { x };
+            let mut run_boxes = Vec::with_capacity(run.len());
+            for (glyph, &advance) in line.glyphs[run.clone()].iter().zip(&advances) {
+                let left = if rtl { edge - advance } else { edge };
+                edge = if rtl { left } else { left + advance };
+                run_boxes.push(LayoutGlyph { x: left, advance, rtl, .. *glyph });
+            }
+            if rtl {
+                run_boxes.reverse();
+            }
+            boxes.extend(run_boxes);
+            x += width;
+        }
+
+        // trailing whitespace hangs past the edge and does not count towards the width
+        line.width = boxes.iter()
+            .filter(|glyph| !glyph.ch.is_whitespace())
+            .map(|glyph| glyph.x + glyph.advance + bold)
+            .fold(0.0, f32::max);
+        line.glyphs = boxes;
+        line.shaped = shaped;
+    }
+
+    /// Rasterizes the glyphs `text` is drawn with at the current scale factor, for `FontManager::insert_glyphs`
+    /// to upload on the UI thread so drawing the text later does not stall on them.
+    pub fn rasterize<T>(&self, text: T, params: &FontParameters) -> RasterizedGlyphs where T: AsRef<str> {
+        let text = text.as_ref();
+        let face = self.resolve_face(params);
+        let font = self.font(&face.key);
+        let (size, sdf) = self.raster_size(params);
+        let scale = Scale::uniform(size as f32);
+        // shaped glyphs for layouts and the plain glyph of every character for rich text
+        let mut ids = self.layout_text(text, std::f32::INFINITY, params).lines.iter()
+            .flat_map(|line| line.shaped.iter().map(|glyph| glyph.id))
+            .chain(text.chars().filter(|&ch| ch != '\n' && ch != '\t').map(|ch| font.glyph(ch).id().0))
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+        let glyphs = ids.into_iter()
+            .map(|id| {
+                let key = GlyphKey { face: face.key.clone(), size, sdf, glyph: id };
+                let bitmap = if sdf { rasterize_sdf(&font, scale, GlyphId(id)) } else { rasterize(&font, scale, GlyphId(id)) };
+                (key, bitmap)
+            })
+            .collect();
+        RasterizedGlyphs { glyphs }
     }
 }
 
-/// Texture which contains the characters of the font.
-pub struct FontTexture {
-    texture: glium::texture::Texture2d,
-    character_infos: HashMap<char, CharacterInfos>,
+/// Face chosen for some `FontParameters`, along with the styles it lacks.
+struct ResolvedFace {
+    key: FaceKey,
+    faux_bold: bool,
+    faux_italic: bool
 }
 
-///
-#[derive(Debug)]
-pub enum Error {
-    /// A glyph for this character is not present in font.
-    NoGlyph(char),
-    /// An Error that comes directly from Rusttype.
-    RusttypeError(rusttype::Error),
+/// Glyph with the pen position it was placed at, in raster pixels from the start of the line.
+#[derive(Clone, Copy)]
+struct PlacedGlyph {
+    pen: f32,
+    /// Shift above the baseline in raster pixels
+    rise: f32,
+    glyph: CachedGlyph
 }
 
-impl From<rusttype::Error> for Error {
-    fn from(error: rusttype::Error) -> Self {
-        Error::RusttypeError(error)
+/// Glyph out of the shaper, `cluster` is the index of its first character in the shaped run.
+#[derive(Clone, Copy)]
+struct RunGlyph {
+    id: u32,
+    cluster: usize,
+    advance: f32,
+    offset: [f32; 2]
+}
+
+/// Glyph ready to be drawn, with its pen position on the baseline in logical units.
+#[derive(Clone, Copy)]
+struct GlyphQuad {
+    x: f32,
+    baseline: f32,
+    glyph: CachedGlyph,
+    style: GlyphStyle
+}
+
+/// Scale, faux styles and color of a glyph, lets glyphs of different faces and sizes share a draw call.
+#[derive(Clone, Copy)]
+struct GlyphStyle {
+    unit: f32,
+    bold_offset: f32,
+    shear: f32,
+    /// Multiplied with the color of the draw
+    tint: [f32; 4]
+}
+
+impl GlyphStyle {
+    fn new(face: &ResolvedFace, params: &FontParameters, unit: f32, tint: [f32; 4]) -> GlyphStyle {
+        GlyphStyle {
+            unit,
+            bold_offset: if face.faux_bold { params.size as f32 / BOLD_FACTOR } else { 0.0 },
+            shear: if face.faux_italic { ITALIC_SHEAR } else { 0.0 },
+            tint
+        }
     }
 }
 
-/// Object that contains the elements shared by all `TextDisplay` objects.
-///
-/// Required to create a `TextDisplay`.
-pub struct TextSystem {
-    context: Rc<Context>,
-    program: glium::Program,
+/// Solid rectangle drawn along with glyphs, such as an underline.
+#[derive(Clone, Copy)]
+struct SolidQuad {
+    bounds: [f32; 4],
+    tint: [f32; 4]
 }
 
-/// Object that will allow you to draw a text.
-pub struct TextDisplay<F> where F: Deref<Target=FontTexture> {
-    context: Rc<Context>,
-    texture: F,
-    vertex_buffer: Option<glium::VertexBuffer<VertexFormat>>,
-    index_buffer: Option<glium::IndexBuffer<u16>>,
-    total_text_width: f32,
-    text_height: f32,
-    is_empty: bool,
+/// Side of a string shortened by `FontManager::truncate_to_width`, where the ellipsis goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Truncation {
+    End, Middle, Start
 }
 
-// structure containing informations about a character of a font
-#[derive(Copy, Clone, Debug)]
-struct CharacterInfos {
-    // coordinates of the character top-left hand corner on the font's texture
-    tex_coords: (f32, f32),
+/// Span of `RichText` sharing a style, unset fields fall back to the parameters the text is drawn with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub color: Option<[f32; 4]>,
+    pub size: Option<u32>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub family: Option<String>,
+    pub underline: bool,
+    pub strikeout: bool
+}
+
+impl TextSpan {
+    pub fn new<T>(text: T) -> TextSpan where T: Into<String> {
+        TextSpan { text: text.into(), .. Default::default() }
+    }
+
+    pub fn color(mut self, color: [f32; 4]) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = Some(size);
+        self
+    }
 
-    // width and height of character in texture units
-    tex_size: (f32, f32),
+    pub fn bold(mut self) -> Self {
+        self.bold = Some(true);
+        self
+    }
 
-    // size of the character in EMs
-    size: (f32, f32),
+    pub fn italic(mut self) -> Self {
+        self.italic = Some(true);
+        self
+    }
 
-    // number of EMs between the bottom of the character and the base line of text
-    height_over_line: f32,
+    pub fn family<N>(mut self, family: N) -> Self where N: Into<String> {
+        self.family = Some(family.into());
+        self
+    }
 
-    // number of EMs at the left of the character
-    left_padding: f32,
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    pub fn strikeout(mut self) -> Self {
+        self.strikeout = true;
+        self
+    }
 
-    // number of EMs at the right of the character
-    right_padding: f32,
+    /// Parameters of the paragraph with the overrides of this span applied.
+    pub fn apply(&self, params: &FontParameters) -> FontParameters {
+        FontParameters {
+            color: self.color.unwrap_or(params.color),
+            size: self.size.unwrap_or(params.size),
+            bold: self.bold.unwrap_or(params.bold),
+            italic: self.italic.unwrap_or(params.italic),
+            family: self.family.clone().or_else(|| params.family.clone()),
+            underline: self.underline || params.underline,
+            strikeout: self.strikeout || params.strikeout,
+            .. params.clone()
+        }
+    }
 }
 
-struct TextureData {
-    data: Vec<f32>,
-    width: u32,
-    height: u32,
+/// Paragraph of differently styled spans, see `FontManager::draw_rich_text`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RichText {
+    pub spans: Vec<TextSpan>
 }
 
-impl<'a> glium::texture::Texture2dDataSource<'a> for &'a TextureData {
-    type Data = f32;
+impl RichText {
+    pub fn new() -> RichText {
+        RichText { spans: Vec::new() }
+    }
 
-    fn into_raw(self) -> glium::texture::RawImage2d<'a, f32> {
-        glium::texture::RawImage2d {
-            data: Cow::Borrowed(&self.data),
-            width: self.width,
-            height: self.height,
-            format: glium::texture::ClientFormat::F32,
-        }
+    pub fn span(mut self, span: TextSpan) -> Self {
+        self.spans.push(span);
+        self
+    }
+
+    /// Adds a span in the style of the paragraph.
+    pub fn text<T>(self, text: T) -> Self where T: Into<String> {
+        self.span(TextSpan::new(text))
+    }
+
+    pub fn plain_text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
     }
 }
 
-#[derive(Copy, Clone)]
-struct VertexFormat {
-    pos: [f32; 2],
-    texture_uv: [f32; 2],
+impl From<&str> for RichText {
+    fn from(text: &str) -> Self {
+        RichText::new().text(text)
+    }
 }
 
-implement_vertex!(VertexFormat, pos, texture_uv);
+impl From<String> for RichText {
+    fn from(text: String) -> Self {
+        RichText::new().text(text)
+    }
+}
+
+/// Rich text placed relative to its top left corner.
+struct RichLayout {
+    lines: Vec<RichLine>,
+    width: f32,
+    height: f32
+}
+
+struct RichLine {
+    quads: Vec<GlyphQuad>,
+    solids: Vec<SolidQuad>,
+    width: f32,
+    baseline: f32
+}
 
-impl FontTexture {
-    /// Vec<char> of complete ASCII range (from 0 to 255 bytes)
-    pub fn ascii_character_list() -> Vec<char> {
-        (0 .. 255).filter_map(::std::char::from_u32).collect()
+impl FontManager {
+    pub fn new(display: &Display) -> FontManager {
+        FontManager {
+            display: display.clone(),
+            system: TextSystem::new(display).expect("Unable to compile text shaders"),
+            library: FontLibrary::new(),
+            cache: GlyphCache::new(display),
+            vertices: None,
+            gpu_timer: None
+        }
     }
 
-    /// Creates a new texture representing a font stored in a `FontTexture`.
-    /// This function is very expensive as it needs to rasterize font into a
-    /// texture.  Complexity grows as `font_size**2 * characters_list.len()`.
-    /// **Avoid rasterizing everything at once as it will be slow and end up in
-    /// out of memory abort.**
-    pub fn new<R, F, I>(facade: &F, font: R, font_size: u32, characters_list: I)
-                        -> Result<FontTexture, Error>
-        where R: Read, F: Facade, I: IntoIterator<Item=char>
-    {
+    /// Registers a TTF or OTF file as the regular face of `name`, replacing any face registered before.
+    pub fn load_font<N, P>(&mut self, name: N, path: P) -> Result<(), Error> where N: Into<String>, P: AsRef<Path> {
+        self.load_font_face(name, false, false, path)
+    }
 
-        // building the freetype face object
-        let font: Vec<u8> = font.bytes().map(|c| c.unwrap()).collect();
+    pub fn load_font_bytes<N>(&mut self, name: N, bytes: Vec<u8>) -> Result<(), Error> where N: Into<String> {
+        self.load_font_face_bytes(name, false, false, bytes)
+    }
 
-        let collection = ::rusttype::FontCollection::from_bytes(&font[..])?;
-        let font = collection.into_font().unwrap();
+    /// Registers the bold and/or italic face of a family.
+    pub fn load_font_face<N, P>(&mut self, name: N, bold: bool, italic: bool, path: P) -> Result<(), Error>
+        where N: Into<String>, P: AsRef<Path> {
 
-        // building the infos
-        let (texture_data, chr_infos) =
-            build_font_image(&font, characters_list.into_iter(), font_size)?;
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        self.load_font_face_bytes(name, bold, italic, bytes)
+    }
 
-        // we load the texture in the display
-        let texture = glium::texture::Texture2d::new(facade, &texture_data).unwrap();
+    pub fn load_font_face_bytes<N>(&mut self, name: N, bold: bool, italic: bool, bytes: Vec<u8>) -> Result<(), Error>
+        where N: Into<String> {
 
-        Ok(FontTexture {
-            texture,
-            character_infos: chr_infos,
-        })
+        let data: Arc<[u8]> = bytes.into();
+        let font = rusttype::FontCollection::from_bytes(data.clone())?.into_font()?;
+        self.insert_face((name.into(), bold, italic), font, data, 0);
+        Ok(())
     }
-}
 
-/*impl glium::uniforms::AsUniformValue for FontTexture {
-    fn as_uniform_value(&self) -> glium::uniforms::UniformValue {
-        glium::uniforms::AsUniformValue::as_uniform_value(&self.texture)
+    /// Registers a face of a font collection, such as a `.ttc` file, by its index.
+    pub fn load_collection_face_bytes<N>(&mut self, name: N, bold: bool, italic: bool, bytes: Vec<u8>, index: usize)
+                                         -> Result<(), Error> where N: Into<String> {
+
+        let data: Arc<[u8]> = bytes.into();
+        let font = rusttype::FontCollection::from_bytes(data.clone())?.font_at(index)?;
+        self.insert_face((name.into(), bold, italic), font, data, index as u32);
+        Ok(())
     }
-}*/
 
-impl TextSystem {
-    /// Builds a new text system that must be used to build `TextDisplay` objects.
-    pub fn new<F>(facade: &F) -> TextSystem where F: Facade {
-        TextSystem {
-            context: facade.get_context().clone(),
-            program: program!(facade,
-                120 => {
-                    vertex: include_str!("resources/shaders/font.vsh"),
-                    fragment: include_str!("resources/shaders/font.fsh")
-                }
-            ).unwrap()
+    fn insert_face(&mut self, key: FaceKey, font: rusttype::Font<'static>, data: Arc<[u8]>, index: u32) {
+        if self.library.insert(key, font, data, index) {
+            // glyphs of the replaced face are still cached under the same key
+            self.cache.clear();
         }
     }
-}
 
-impl<F> TextDisplay<F> where F: Deref<Target=FontTexture> {
-    /// Builds a new text display that allows you to draw text.
-    pub fn new(system: &TextSystem, texture: F, text: &str) -> TextDisplay<F> {
-        let mut text_display = TextDisplay {
-            context: system.context.clone(),
-            texture,
-            vertex_buffer: None,
-            index_buffer: None,
-            total_text_width: 0.0,
-            text_height: 0.0,
-            is_empty: true,
-        };
+    /// Whether any face of the family was registered.
+    pub fn has_font<N>(&self, name: N) -> bool where N: AsRef<str> {
+        self.library.has_font(name)
+    }
 
-        text_display.set_text(text);
+    /// Usage of the glyph atlas since the last `reset_stats`.
+    pub fn stats(&self) -> GlyphCacheStats {
+        self.cache.stats()
+    }
 
-        text_display
+    pub fn reset_stats(&mut self) {
+        self.cache.reset_stats();
     }
 
-    /// Returns the width in GL units of the text.
-    pub fn get_width(&self) -> f32 {
-        self.total_text_width
+    /// Caps the memory of the glyph atlas, least recently used glyphs make room for new ones beyond it.
+    pub fn set_glyph_cache_budget(&mut self, bytes: usize) {
+        self.cache.set_memory_budget(bytes);
     }
 
-    /// Returns the height in GL units of the text.
-    pub fn get_height(&self) -> f32 {
-        self.text_height
+    pub fn glyph_cache(&self) -> &GlyphCache {
+        &self.cache
     }
 
-    /// Modifies the text on this display.
-    pub fn set_text(&mut self, text: &str) {
-        self.is_empty = true;
-        self.total_text_width = 0.0;
-        self.vertex_buffer = None;
-        self.index_buffer = None;
+    /// Handle to the faces for laying out text and rasterizing glyphs on other threads.
+    pub fn library(&self) -> FontLibrary {
+        self.library.clone()
+    }
 
-        // returning if no text
-        if text.is_empty() {
-            return;
+    /// Adds glyphs rasterized on another thread to the atlas, skipping ones already cached.
+    pub fn insert_glyphs(&mut self, glyphs: RasterizedGlyphs) {
+        self.cache.begin_use();
+        for (key, bitmap) in glyphs.glyphs {
+            if !self.cache.contains(&key) {
+                self.cache.get_or_insert(&key, move || bitmap);
+            }
         }
+    }
 
-        // these arrays will contain the vertex buffer and index buffer data
-        let mut vertex_buffer_data = Vec::with_capacity(text.len() * 4 * 4);
-        let mut index_buffer_data = Vec::with_capacity(text.len() * 6);
+    fn resolve_face(&self, params: &FontParameters) -> ResolvedFace {
+        self.library.resolve_face(params)
+    }
 
-        // iterating over the characters of the string
-        for character in text.chars() {
-            let infos = match self.texture.character_infos.get(&character) {
-                Some(infos) => infos,
-                None => continue,
-            };
+    /// Rasterizes distance fields of `chars` up front, so the first frame drawing them does not stall.
+    pub fn preload_sdf<N, I>(&mut self, family: N, bold: bool, italic: bool, chars: I)
+        where N: Into<String>, I: IntoIterator<Item=char> {
 
-            self.is_empty = false;
-
-            // adding the quad in the index buffer
-            {
-                let first_vertex_offset = vertex_buffer_data.len() as u16;
-                index_buffer_data.push(first_vertex_offset);
-                index_buffer_data.push(first_vertex_offset + 1);
-                index_buffer_data.push(first_vertex_offset + 2);
-                index_buffer_data.push(first_vertex_offset + 2);
-                index_buffer_data.push(first_vertex_offset + 1);
-                index_buffer_data.push(first_vertex_offset + 3);
-            }
+        let params = FontParameters { family: Some(family.into()), bold, italic, sdf: true, .. Default::default() };
+        let face = self.resolve_face(&params);
+        self.cache.begin_use();
+        let text = chars.into_iter().collect::<String>();
+        self.layout_line(&face.key, &params, &text);
+    }
 
-            //
-            self.total_text_width += infos.left_padding;
+    pub fn scale_factor(&self) -> f32 {
+        self.library.scale_factor()
+    }
 
-            // calculating coords
-            let left_coord = self.total_text_width;
-            let right_coord = left_coord + infos.size.0;
-            let top_coord = infos.height_over_line - infos.size.1;
-            let bottom_coord = infos.height_over_line;
+    /// Changes the number of physical pixels per logical pixel glyphs are rasterized for,
+    /// glyphs of the previous factor are dropped from the cache.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        if self.library.set_scale_factor(scale_factor) {
+            self.cache.clear();
+        }
+    }
 
-            // top-left vertex
-            vertex_buffer_data.push(VertexFormat {
-                pos: [left_coord, top_coord],
-                texture_uv: [infos.tex_coords.0, infos.tex_coords.1],
-            });
+    fn raster_size(&self, params: &FontParameters) -> (u32, bool) {
+        self.library.raster_size(params)
+    }
 
-            // top-right vertex
-            vertex_buffer_data.push(VertexFormat {
-                pos: [right_coord, top_coord],
-                texture_uv: [infos.tex_coords.0 + infos.tex_size.0, infos.tex_coords.1],
-            });
+    fn glyph_unit(&self, params: &FontParameters) -> f32 {
+        self.library.glyph_unit(params)
+    }
 
-            // bottom-left vertex
-            vertex_buffer_data.push(VertexFormat {
-                pos: [left_coord, bottom_coord],
-                texture_uv: [infos.tex_coords.0, infos.tex_coords.1 + infos.tex_size.1],
-            });
+    /// Shapes a single line of logical text, looks up or rasterizes its glyphs and advances the pen
+    /// over them in display order.
+    fn layout_line(&mut self, face: &FaceKey, params: &FontParameters, text: &str) -> (Vec<PlacedGlyph>, f32) {
+        let font = self.library.font(face);
+        let (size, sdf) = self.raster_size(params);
+        let scale = Scale::uniform(size as f32);
+        let chars = text.chars().collect::<Vec<_>>();
+        let levels = match bidi_levels(&chars) {
+            Some(levels) => levels.iter().map(|&(level, _)| level).collect(),
+            None => vec![0; chars.len()]
+        };
+        let runs = direction_runs(&chars, &levels);
+        let run_levels = runs.iter().map(|run| levels[run.start]).collect::<Vec<_>>();
+        let mut placed = Vec::with_capacity(chars.len());
+        let mut pen = 0.0;
+        let tab_stop = tab_stop(&font, scale, params);
+        let spacing = params.letter_spacing / self.glyph_unit(params);
+        for r in visual_runs(&run_levels) {
+            let run = runs[r].clone();
+            match chars[run.start] {
+                '\t' => pen += tab_stop - pen % tab_stop + spacing,
+                '\n' => {},
+                _ => {
+                    let glyphs = self.library.shape(face, &font, scale, &chars[run.clone()], run_levels[r] % 2 == 1);
+                    let sizes = cluster_sizes(&glyphs, run.len());
+                    for (i, shaped) in glyphs.iter().enumerate() {
+                        let key = GlyphKey { face: face.clone(), size, sdf, glyph: shaped.id };
+                        let id = GlyphId(shaped.id);
+                        let rasterized = self.cache.get_or_insert(&key, || if sdf {
+                            rasterize_sdf(&font, scale, id)
+                        } else {
+                            rasterize(&font, scale, id)
+                        });
+                        if let Some(glyph) = rasterized {
+                            placed.push(PlacedGlyph { pen: pen + shaped.offset[0], rise: shaped.offset[1], glyph });
+                        }
+                        pen += shaped.advance;
+                        if glyphs.get(i + 1).map_or(true, |next| next.cluster != shaped.cluster) {
+                            pen += spacing * sizes[shaped.cluster] as f32;
+                        }
+                    }
+                }
+            }
+        }
+        (placed, pen)
+    }
 
-            // bottom-right vertex
-            vertex_buffer_data.push(VertexFormat {
-                pos: [right_coord, bottom_coord],
-                texture_uv: [
-                    infos.tex_coords.0 + infos.tex_size.0,
-                    infos.tex_coords.1 + infos.tex_size.1
-                ],
-            });
+    pub fn draw_string<S, T>(&mut self, target: &mut S, text: T, x: f32, y: f32, viewport: Matrix4<f32>,
+                             params: &FontParameters) -> crate::error::Result<()>
+        where S: Surface, T: AsRef<str> {
 
-            // going to next char
-            self.total_text_width = right_coord + infos.right_padding;
+        let text = text.as_ref();
+        let face = self.resolve_face(params);
+        self.cache.begin_use();
 
-            if top_coord > self.text_height {
-                self.text_height = top_coord;
+        if params.orientation == TextOrientation::Vertical {
+            let (mut quads, w, h) = self.place_vertical(&face, params, text);
+            let x = match params.align_horizontal {
+                TextAlignHorizontal::Left => x,
+                TextAlignHorizontal::Right => x - w,
+                TextAlignHorizontal::Center => x - w / 2.0
+            };
+            let y = match params.align_vertical {
+                TextAlignVertical::Top => y,
+                TextAlignVertical::Bottom => y - h,
+                TextAlignVertical::Center => y - h / 2.0,
+                TextAlignVertical::Baseline => y - self.metrics(params).ascent
+            };
+            for quad in quads.iter_mut() {
+                quad.x += x;
+                quad.baseline += y;
             }
+            return self.draw_glyphs(target, &quads, &[], viewport, params);
         }
 
-        if !vertex_buffer_data.len() != 0 {
-            // building the vertex buffer
-            self.vertex_buffer = Some(glium::VertexBuffer::new(&self.context,
-                                                               &vertex_buffer_data).unwrap());
+        let mut lines = Vec::new();
+        let mut chars = text.chars().collect::<Vec<char>>();
+        let parts = chars.len() / params.width_limit + 1;
+
+        for i in 0..parts {
+            let mut line = String::new();
+            for j in 0..(chars.len().min(params.width_limit)) {
+                line.push(chars.remove(0));
+            }
+            lines.push(line);
+        }
 
-            // building the index buffer
-            self.index_buffer = Some(glium::IndexBuffer::new(&self.context,
-                                                             glium::index::PrimitiveType::TrianglesList,
-                                                             &index_buffer_data).unwrap());
+        let style = GlyphStyle::new(&face, params, self.glyph_unit(params), [1.0; 4]);
+        let bold_offset = style.bold_offset;
+        let laid_out = lines.iter()
+            .map(|line| self.layout_line(&face.key, params, line))
+            .collect::<Vec<_>>();
+        let unit = self.glyph_unit(params);
+        let mut quads = Vec::new();
+
+        for (i, (placed, advance)) in laid_out.iter().enumerate() {
+            let w = advance * unit + bold_offset;
+            let h = line_height(placed, unit);
+            let x = match params.align_horizontal {
+                TextAlignHorizontal::Left => x,
+                TextAlignHorizontal::Right => x - w,
+                TextAlignHorizontal::Center => x - w / 2.0
+            };
+            let y = match params.align_vertical {
+                TextAlignVertical::Top => y,
+                TextAlignVertical::Bottom => y - h,
+                TextAlignVertical::Center => y - h / 2.0,
+                TextAlignVertical::Baseline => y - params.size as f32 / 2.0 * 0.777777775
+            };
+            let baseline = y + params.size as f32 / 2.0 * (i as f32 * params.line_height + 0.777777775);
+            quads.extend(placed.iter().map(|placed| GlyphQuad {
+                x: x + placed.pen * unit, baseline: baseline - placed.rise * unit, glyph: placed.glyph, style
+            }));
         }
+        self.draw_glyphs(target, &quads, &[], viewport, params)
     }
-}
 
-/// Draws linear-filtered text.
-///
-/// ## About the matrix
-///
-/// The matrix must be column-major post-muliplying (which is the usual way to do in OpenGL).
-///
-/// One unit in height corresponds to a line of text, but the text can go above or under.
-/// The bottom of the line is at `0.0`, the top is at `1.0`.
-/// You need to adapt your matrix by taking these into consideration.
-pub fn draw<F, S: ?Sized, M>(
-    text: &TextDisplay<F>,
-    system: &TextSystem,
-    target: &mut S,
-    matrix: M,
-    color: [f32; 4],
-    scissor: Option<GLRect>
-) -> Result<(), glium::DrawError>
-    where S: glium::Surface,
-          M: Into<[[f32; 4]; 4]>,
-          F: Deref<Target=FontTexture>
-{
-    let behavior = glium::uniforms::SamplerBehavior {
-        magnify_filter: glium::uniforms::MagnifySamplerFilter::Linear,
-        minify_filter: glium::uniforms::MinifySamplerFilter::Linear,
-        .. Default::default()
-    };
+    /// Draws glyphs placed at pen positions on their baseline with a single draw call,
+    /// preceded by one more for each of the shadow and outline.
+    fn draw_glyphs<S>(&mut self, target: &mut S, quads: &[GlyphQuad], solids: &[SolidQuad],
+                      viewport: Matrix4<f32>, params: &FontParameters) -> crate::error::Result<()>
+        where S: Surface {
+
+        let solid = if solids.is_empty() { None } else { self.solid_glyph() };
+        // the atlas may have grown while the glyphs were looked up, coordinates are taken from its final size
+        let (atlas_w, atlas_h) = self.cache.dimensions();
+        let atlas = (atlas_w as f32, atlas_h as f32);
+        // effects are specified in logical units and applied in atlas pixels
+        let unit = self.glyph_unit(params);
+
+        if let Some(shadow) = params.shadow {
+            let blur = shadow.blur / unit;
+            let vertices = glyph_vertices(quads, solids, solid, atlas, blur, shadow.offset, true);
+            self.draw_vertices(target, &vertices, viewport, params, Some((shadow.color, 0.0, blur)))?;
+        }
+        if let Some(outline) = params.outline {
+            let width = outline.width / unit;
+            let vertices = glyph_vertices(quads, solids, solid, atlas, width, [0.0, 0.0], true);
+            self.draw_vertices(target, &vertices, viewport, params, Some((outline.color, width, 0.0)))?;
+        }
+        let vertices = glyph_vertices(quads, solids, solid, atlas, 0.0, [0.0, 0.0], false);
+        self.draw_vertices(target, &vertices, viewport, params, None)
+    }
 
-    let params = {
-        use glium::BlendingFunction::Addition;
-        use glium::LinearBlendingFactor::*;
+    /// Draws vertices from `glyph_vertices`, with the effect program when given its color, dilation and blur.
+    fn draw_vertices<S>(&mut self, target: &mut S, vertices: &[VertexFormat], viewport: Matrix4<f32>,
+                        params: &FontParameters, effect: Option<([f32; 4], f32, f32)>) -> crate::error::Result<()>
+        where S: Surface {
 
-        let blending_function = Addition {
-            source: SourceAlpha,
-            destination: OneMinusSourceAlpha
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        self.upload(vertices)?;
+        let buffer = self.vertices.as_ref().unwrap();
+        let slice = buffer.slice(0 .. vertices.len()).ok_or(crate::error::Error::BufferSlice)?;
+        let matrix: [[f32; 4]; 4] = viewport.into();
+        let behavior = glium::uniforms::SamplerBehavior {
+            magnify_filter: glium::uniforms::MagnifySamplerFilter::Linear,
+            minify_filter: glium::uniforms::MinifySamplerFilter::Linear,
+            .. Default::default()
         };
-
-        let blend = glium::Blend {
-            color: blending_function,
-            alpha: blending_function,
-            constant_value: (1.0, 1.0, 1.0, 1.0),
+        let tex = glium::uniforms::Sampler(self.cache.texture(), behavior);
+        let query = self.gpu_timer.as_ref().and_then(|timer| timer.query());
+        let draw_parameters = DrawParameters {
+            time_elapsed_query: query.as_deref(),
+            .. text_parameters(params.scissor, params.stencil)
         };
 
-        DrawParameters {
-            blend,
-            scissor,
-            .. Default::default()
+        match effect {
+            Some((color, dilate, blur)) => {
+                let (atlas_w, atlas_h) = self.cache.dimensions();
+                let uniforms = uniform! {
+                    mat: matrix,
+                    color: color,
+                    tex: tex,
+                    texel: [1.0 / atlas_w as f32, 1.0 / atlas_h as f32],
+                    dilate: dilate,
+                    blur: blur,
+                    sdf: params.sdf,
+                    spread: SDF_SPREAD as f32
+                };
+                target.draw(slice, NoIndices(PrimitiveType::TrianglesList), &self.system.effect_program, &uniforms,
+                            &draw_parameters)?;
+            },
+            None => {
+                let uniforms = uniform! {
+                    mat: matrix,
+                    color: params.color,
+                    tex: tex
+                };
+                let program = if params.sdf { &self.system.sdf_program } else { &self.system.program };
+                target.draw(slice, NoIndices(PrimitiveType::TrianglesList), program, &uniforms, &draw_parameters)?;
+            }
         }
-    };
-    draw_with_params(text, system, target, matrix, color, behavior, &params)
-}
-
-/// More advanced variant of `draw` which also takes sampler behavior and draw
-/// parameters.
-pub fn draw_with_params<F, S: ?Sized, M>(
-    text: &TextDisplay<F>,
-    system: &TextSystem,
-    target: &mut S,
-    matrix: M,
-    color: [f32; 4],
-    sampler_behavior: glium::uniforms::SamplerBehavior,
-    parameters: &DrawParameters
-) -> Result<(), glium::DrawError>
-    where S: glium::Surface,
-          M: Into<[[f32; 4]; 4]>,
-          F: Deref<Target=FontTexture>
-{
-    let matrix = matrix.into();
-
-    let &TextDisplay {
-        ref vertex_buffer,
-        ref index_buffer,
-        ref texture,
-        is_empty,
-        ..
-    } = text;
-
-    // returning if nothing to draw
-    if is_empty || vertex_buffer.is_none() || index_buffer.is_none() {
-        return Ok(());
-    }
-
-    let vertex_buffer = vertex_buffer.as_ref().unwrap();
-    let index_buffer = index_buffer.as_ref().unwrap();
-
-    let uniforms = uniform! {
-        mat: matrix,
-        color: color,
-        tex: glium::uniforms::Sampler(&texture.texture, sampler_behavior)
-    };
+        Ok(())
+    }
 
-    target.draw(vertex_buffer, index_buffer, &system.program, &uniforms, &parameters)
-}
+    /// Times the draw calls of text, see `FrameStats::gpu_time`.
+    pub fn set_gpu_timer(&mut self, timer: Option<Rc<GpuTimer>>) {
+        self.gpu_timer = timer;
+    }
 
-fn build_font_image<I>(font: &rusttype::Font, characters_list: I, font_size: u32)
-                       -> Result<(TextureData, HashMap<char, CharacterInfos>), Error>
-    where I: Iterator<Item=char> {
+    pub fn metrics(&self, params: &FontParameters) -> FontMetrics {
+        self.library.metrics(params)
+    }
 
-    use std::iter;
+    /// Kerning to the previous character and advance of `ch`, in raster pixels.
+    fn advance(font: &rusttype::Font, scale: Scale, previous: Option<char>, ch: char) -> (f32, f32) {
+        let kerning = previous.map_or(0.0, |previous| font.pair_kerning(scale, previous, ch));
+        (kerning, font.glyph(ch).scaled(scale).h_metrics().advance_width)
+    }
 
-    // a margin around each character to prevent artifacts
-    const MARGIN: u32 = 2;
+    /// Breaks the text into lines no wider than `max_width`, at whitespace and after hyphens where possible.
+    /// Explicit line breaks are kept. Nothing is rasterized until the layout is drawn.
+    pub fn layout_text<T>(&mut self, text: T, max_width: f32, params: &FontParameters) -> TextLayout where T: AsRef<str> {
+        self.library.layout_text(text, max_width, params)
+    }
 
-    // glyph size for characters not presented in font.
-    let invalid_character_width = font_size / 2;
+    fn finish_line(glyphs: Vec<LayoutGlyph>, start: usize, end: usize, bold: f32) -> TextLine {
+        // trailing whitespace hangs past the edge and does not count towards the width
+        let width = glyphs.iter().rev()
+            .find(|glyph| !glyph.ch.is_whitespace())
+            .map_or(0.0, |glyph| glyph.x + glyph.advance + bold);
+        TextLine { start, end, x: 0.0, y: 0.0, width, baseline: 0.0, glyphs, shaped: Vec::new() }
+    }
 
-    let size_estimation = characters_list.size_hint().1.unwrap_or(0);
+    /// Draws a layout with its top left corner at `x`, `y`. Color, clipping and stencil are taken
+    /// from `params`, everything else from the parameters it was laid out with.
+    pub fn draw_layout<S>(&mut self, target: &mut S, layout: &TextLayout, x: f32, y: f32, viewport: Matrix4<f32>,
+                          params: &FontParameters) -> crate::error::Result<()>
+        where S: Surface {
+
+        let params = FontParameters {
+            color: params.color,
+            scissor: params.scissor,
+            stencil: params.stencil,
+            .. layout.params.clone()
+        };
+        let face = self.resolve_face(&params);
+        let font = self.library.font(&face.key);
+        let style = GlyphStyle::new(&face, &params, self.glyph_unit(&params), [1.0; 4]);
+        let (size, sdf) = self.raster_size(&params);
+        let scale = Scale::uniform(size as f32);
+        self.cache.begin_use();
+
+        let mut quads = Vec::new();
+        for line in &layout.lines {
+            for glyph in &line.shaped {
+                let key = GlyphKey { face: face.key.clone(), size, sdf, glyph: glyph.id };
+                let id = GlyphId(glyph.id);
+                let cached = self.cache.get_or_insert(&key, || if sdf {
+                    rasterize_sdf(&font, scale, id)
+                } else {
+                    rasterize(&font, scale, id)
+                });
+                if let Some(cached) = cached {
+                    quads.push(GlyphQuad { x: x + line.x + glyph.x, baseline: y + line.baseline - glyph.y, glyph: cached, style });
+                }
+            }
+        }
+        self.draw_glyphs(target, &quads, &[], viewport, &params)
+    }
 
-    // this variable will store the texture data
-    // we set an arbitrary capacity that we think will match what we will need
-    let mut texture_data: Vec<f32> = Vec::with_capacity(
-        size_estimation * font_size as usize * font_size as usize
-    );
+    /// Opaque glyph of the atlas that underlines and other solid quads are drawn with.
+    fn solid_glyph(&mut self) -> Option<CachedGlyph> {
+        let key = GlyphKey { face: (String::new(), false, false), size: 0, sdf: false, glyph: 0 };
+        self.cache.get_or_insert(&key, || GlyphBitmap {
+            width: 4, height: 4, pixels: vec![255; 16], offset: [0.0, 0.0], advance: 0.0
+        })
+    }
+
+    /// Places the spans on shared baselines, lines only break at explicit line breaks.
+    fn place_rich_text(&mut self, text: &RichText, params: &FontParameters) -> RichLayout {
+        struct Line { quads: Vec<GlyphQuad>, solids: Vec<SolidQuad>, width: f32, ascent: f32, descent: f32 }
+        let new_line = || Line { quads: Vec::new(), solids: Vec::new(), width: 0.0, ascent: 0.0, descent: 0.0 };
+
+        self.cache.begin_use();
+        let mut lines = vec![new_line()];
+        for span in &text.spans {
+            let span_params = span.apply(params);
+            let face = self.resolve_face(&span_params);
+            let font = self.library.font(&face.key);
+            let style = GlyphStyle::new(&face, &span_params, self.glyph_unit(&span_params), span_params.color);
+            let (size, sdf) = self.raster_size(&span_params);
+            let scale = Scale::uniform(size as f32);
+            let metrics = font.v_metrics(scale);
+            let (ascent, descent) = (metrics.ascent * style.unit, (metrics.line_gap - metrics.descent) * style.unit);
+            let leading = (ascent + descent) * (span_params.line_height - 1.0) / 2.0;
+            let (ascent, descent) = (ascent + leading, descent + leading);
+            let tab_stop = tab_stop(&font, scale, &span_params) * style.unit;
+            let thickness = (span_params.size as f32 / 20.0 * RASTER_SCALE).max(1.0);
+
+            let mut previous = None;
+            let mut start = lines.last().unwrap().width;
+            let decorate = |line: &mut Line, start: f32| {
+                // underlines and strikeouts are offset from the baseline, which is only known once the line is complete
+                if span_params.underline {
+                    line.solids.push(SolidQuad { bounds: [start, ascent * 0.15, line.width - start, thickness], tint: span_params.color });
+                }
+                if span_params.strikeout {
+                    line.solids.push(SolidQuad { bounds: [start, -ascent * 0.3, line.width - start, thickness], tint: span_params.color });
+                }
+            };
+            for ch in span.text.chars() {
+                let line = lines.last_mut().unwrap();
+                line.ascent = line.ascent.max(ascent);
+                line.descent = line.descent.max(descent);
+                if ch == '\n' {
+                    decorate(line, start);
+                    lines.push(new_line());
+                    previous = None;
+                    start = 0.0;
+                    continue;
+                }
+                if ch == '\t' {
+                    line.width += tab_stop - line.width % tab_stop + span_params.letter_spacing;
+                    previous = None;
+                    continue;
+                }
+                let (kerning, _) = Self::advance(&font, scale, previous, ch);
+                previous = Some(ch);
+                let id = font.glyph(ch).id();
+                let key = GlyphKey { face: face.key.clone(), size, sdf, glyph: id.0 };
+                let cached = self.cache.get_or_insert(&key, || if sdf {
+                    rasterize_sdf(&font, scale, id)
+                } else {
+                    rasterize(&font, scale, id)
+                });
+                if let Some(glyph) = cached {
+                    let x = line.width + kerning * style.unit;
+                    // the baseline is filled in once the line is complete
+                    line.quads.push(GlyphQuad { x, baseline: 0.0, glyph, style });
+                    line.width = x + glyph.advance * style.unit + span_params.letter_spacing;
+                }
+            }
+            let line = lines.last_mut().unwrap();
+            line.ascent = line.ascent.max(ascent);
+            line.descent = line.descent.max(descent);
+            line.width += style.bold_offset;
+            decorate(line, start);
+        }
 
-    // the width is chosen more or less arbitrarily, because we can store
-    // everything as long as the texture is at least as wide as the widest
-    // character we just try to estimate a width so that width ~= height
-    let texture_width = get_nearest_po2(std::cmp::max(font_size * 2 as u32,
-                                                      ((((size_estimation as u32) * font_size * font_size) as f32).sqrt()) as u32));
+        let mut layout = RichLayout { lines: Vec::new(), width: 0.0, height: 0.0 };
+        for line in lines {
+            let baseline = layout.height + line.ascent;
+            layout.lines.push(RichLine {
+                quads: line.quads.into_iter().map(|quad| GlyphQuad { baseline, .. quad }).collect(),
+                solids: line.solids.into_iter().map(|solid| {
+                    let [x, y, w, h] = solid.bounds;
+                    SolidQuad { bounds: [x, baseline + y, w, h], .. solid }
+                }).collect(),
+                width: line.width,
+                baseline
+            });
+            layout.width = layout.width.max(line.width);
+            layout.height = baseline + line.descent;
+        }
+        layout
+    }
 
-    // we store the position of the "cursor" in the destination texture
-    // this cursor points to the top-left pixel of the next character to write on the texture
-    let mut cursor_offset = (0u32, 0u32);
+    pub fn get_rich_text_bounds(&mut self, text: &RichText, params: &FontParameters) -> (f32, f32) {
+        let layout = self.place_rich_text(text, params);
+        (layout.width, layout.height)
+    }
 
-    // number of rows to skip at next carriage return
-    let mut rows_to_skip = 0u32;
+    /// Draws all spans with a single draw call. `x` and `y` are aligned like in `draw_string`,
+    /// with each line aligned horizontally on its own and the whole paragraph vertically.
+    pub fn draw_rich_text<S>(&mut self, target: &mut S, text: &RichText, x: f32, y: f32, viewport: Matrix4<f32>,
+                             params: &FontParameters) -> crate::error::Result<()>
+        where S: Surface {
+
+        let layout = self.place_rich_text(text, params);
+        let y = match params.align_vertical {
+            TextAlignVertical::Top => y,
+            TextAlignVertical::Bottom => y - layout.height,
+            TextAlignVertical::Center => y - layout.height / 2.0,
+            TextAlignVertical::Baseline => y - layout.lines.first().map_or(0.0, |line| line.baseline)
+        };
+        let mut quads = Vec::new();
+        let mut solids = Vec::new();
+        for line in layout.lines {
+            let x = match params.align_horizontal {
+                TextAlignHorizontal::Left => x,
+                TextAlignHorizontal::Right => x - line.width,
+                TextAlignHorizontal::Center => x - line.width / 2.0
+            };
+            quads.extend(line.quads.into_iter().map(|quad| GlyphQuad { x: x + quad.x, baseline: y + quad.baseline, .. quad }));
+            solids.extend(line.solids.into_iter().map(|solid| {
+                let [sx, sy, w, h] = solid.bounds;
+                SolidQuad { bounds: [x + sx, y + sy, w, h], .. solid }
+            }));
+        }
+        // colors come from the spans
+        let params = FontParameters { color: [1.0; 4], .. params.clone() };
+        self.draw_glyphs(target, &quads, &solids, viewport, &params)
+    }
 
-    // now looping through the list of characters, filling the texture and returning the informations
-    let em_pixels = font_size as f32;
-    let characters_infos = characters_list.map(|character| {
-        struct Bitmap {
-            rows   : i32,
-            width  : i32,
-            buffer : Vec<u8>
+    /// Copies the vertices into the shared dynamic buffer, growing it when too small.
+    fn upload(&mut self, vertices: &[VertexFormat]) -> crate::error::Result<()> {
+        if self.vertices.as_ref().map_or(true, |buffer| buffer.len() < vertices.len()) {
+            let capacity = vertices.len().next_power_of_two().max(1024);
+            self.vertices = Some(VertexBuffer::empty_dynamic(&self.display, capacity)?);
         }
-        // loading wanted glyph in the font face
-        // hope scale will set the right pixel size
-        let scaled_glyph = font.glyph(character)
-            .scaled(::rusttype::Scale {x : font_size as f32, y : font_size as f32 });
-        let h_metrics = scaled_glyph.h_metrics();
-        let glyph = scaled_glyph
-            .positioned(::rusttype::Point {x : 0.0, y : 0.0 });
+        let buffer = self.vertices.as_ref().unwrap();
+        buffer.slice(0 .. vertices.len()).ok_or(crate::error::Error::BufferSlice)?.write(vertices);
+        Ok(())
+    }
 
-        let bb = if character == ' ' {
-            Some(Rect {
-                min: Point {x: 0, y: 0},
-                max: Point {x: invalid_character_width as i32 / 2, y: 0}
-            })
-        } else {
-            glyph.pixel_bounding_box()
-        };
-        // if no bounding box - we suppose that its invalid character but want it to be draw as empty quad
-        let bb = if let Some(bb) = bb {
-            bb
-        } else {
-            Rect {
-                min: Point {x: 0, y: 0},
-                max: Point {x: invalid_character_width as i32, y: 0}
+    /// Shortens the text with an ellipsis until it is at most `max_width` wide, text that fits is returned as is.
+    pub fn truncate_to_width<T>(&mut self, text: T, max_width: f32, mode: Truncation, params: &FontParameters) -> String
+        where T: AsRef<str> {
+
+        let text = text.as_ref();
+        if text.is_empty() || self.get_string_bounds(text, params).0 <= max_width {
+            return text.to_owned();
+        }
+        let chars = text.chars().collect::<Vec<_>>();
+        let face = self.resolve_face(params);
+        let ellipsis = if self.library.font(&face.key).glyph('…').id().0 != 0 { "…" } else { "..." };
+        let shorten = |kept: usize| -> String {
+            match mode {
+                Truncation::End => chars[.. kept].iter().collect::<String>() + ellipsis,
+                Truncation::Start => ellipsis.to_owned() + &chars[chars.len() - kept ..].iter().collect::<String>(),
+                Truncation::Middle => {
+                    let (head, tail) = (kept - kept / 2, kept / 2);
+                    chars[.. head].iter().collect::<String>() + ellipsis
+                        + &chars[chars.len() - tail ..].iter().collect::<String>()
+                }
             }
         };
+        // the most characters that still fit
+        let (mut low, mut high) = (0, chars.len() - 1);
+        while low < high {
+            let middle = (low + high + 1) / 2;
+            if self.get_string_bounds(&shorten(middle), params).0 <= max_width {
+                low = middle;
+            } else {
+                high = middle - 1;
+            }
+        }
+        shorten(low)
+    }
+
+    /// Stacks upright characters top to bottom, every line of the text becomes a column and
+    /// columns go left to right. Returns the glyphs relative to the top left corner and the size.
+    fn place_vertical(&mut self, face: &ResolvedFace, params: &FontParameters, text: &str) -> (Vec<GlyphQuad>, f32, f32) {
+        let font = self.library.font(&face.key);
+        let unit = self.glyph_unit(params);
+        let scale = Scale::uniform(self.raster_size(params).0 as f32);
+        let metrics = font.v_metrics(scale);
+        // every character takes a square em box
+        let em = (metrics.ascent - metrics.descent) * unit;
+        let step = em * params.line_height + params.letter_spacing;
+        let style = GlyphStyle::new(face, params, unit, [1.0; 4]);
+
+        let mut quads = Vec::new();
+        let mut height: f32 = 0.0;
+        let columns = text.split('\n').collect::<Vec<_>>();
+        for (column, line) in columns.iter().enumerate() {
+            let left = column as f32 * em * params.line_height;
+            let mut rows = 0;
+            for ch in line.chars() {
+                let mut buffer = [0; 4];
+                let (placed, advance) = self.layout_line(&face.key, params, ch.encode_utf8(&mut buffer));
+                let x = left + (em - advance * unit - style.bold_offset) / 2.0;
+                let baseline = rows as f32 * step + metrics.ascent * unit;
+                quads.extend(placed.iter().map(|placed| GlyphQuad {
+                    x: x + placed.pen * unit, baseline: baseline - placed.rise * unit, glyph: placed.glyph, style
+                }));
+                rows += 1;
+            }
+            if rows > 0 {
+                // no spacing after the last character
+                height = height.max(rows as f32 * step - params.letter_spacing);
+            }
+        }
+        let width = columns.len() as f32 * em * params.line_height - em * (params.line_height - 1.0);
+        (quads, width, height)
+    }
 
-        let mut buffer = vec![0; (bb.height() * bb.width()) as usize];
-
-        glyph.draw(|x, y, v| {
-            let x = x;
-            let y = y;
-            buffer[(y * bb.width() as u32 + x) as usize] = (v * 255.0) as u8;
-        });
-        let bitmap : Bitmap = Bitmap {
-            rows   : bb.height(),
-            width  : bb.width(),
-            buffer
+    pub fn get_string_bounds(&mut self, text: &str, params: &FontParameters) -> (f32, f32) {
+        let face = self.resolve_face(params);
+        self.cache.begin_use();
+        if params.orientation == TextOrientation::Vertical {
+            let (_, w, h) = self.place_vertical(&face, params, text);
+            return (w, h);
+        }
+        let (placed, advance) = self.layout_line(&face.key, params, text);
+        let unit = self.glyph_unit(params);
+        let bold = if face.faux_bold { params.size as f32 / BOLD_FACTOR } else { 0.0 };
+        (advance * unit + bold, line_height(&placed, unit))
+    }
+}
+
+/// Distance between tab stops in raster pixels.
+fn tab_stop(font: &rusttype::Font, scale: Scale, params: &FontParameters) -> f32 {
+    let space = font.glyph(' ').scaled(scale).h_metrics().advance_width;
+    (space * params.tab_width.max(1) as f32).max(1.0)
+}
+
+/// Embedding levels of every character and of the paragraph it belongs to, `None` for purely left to right text.
+fn bidi_levels(chars: &[char]) -> Option<Vec<(u8, u8)>> {
+    let text = chars.iter().collect::<String>();
+    let info = BidiInfo::new(&text, None);
+    if !info.levels.iter().any(|level| level.is_rtl()) {
+        return None;
+    }
+    let mut levels = Vec::with_capacity(chars.len());
+    for (offset, _) in text.char_indices() {
+        let paragraph = info.paragraphs.iter()
+            .find(|paragraph| paragraph.range.contains(&offset))
+            .map_or(0, |paragraph| paragraph.level.number());
+        levels.push((info.levels[offset].number(), paragraph));
+    }
+    Some(levels)
+}
+
+/// Embedding level of every glyph of a line broken in logical order. Inserted glyphs take the level
+/// before them and trailing whitespace the paragraph direction.
+fn line_levels(line: &TextLine, levels: &[(u8, u8)]) -> Vec<u8> {
+    let mut glyph_levels = Vec::with_capacity(line.glyphs.len());
+    let mut previous = 0;
+    for glyph in &line.glyphs {
+        let level = match levels.get(glyph.index) {
+            Some(&(level, _)) if !glyph.inserted => level,
+            _ => previous
         };
+        glyph_levels.push(level);
+        previous = level;
+    }
+    for (glyph, level) in line.glyphs.iter().zip(glyph_levels.iter_mut()).rev() {
+        if !glyph.ch.is_whitespace() {
+            break;
+        }
+        *level = levels.get(glyph.index).map_or(0, |&(_, paragraph)| paragraph);
+    }
+    glyph_levels
+}
 
-        // adding a left margin before our character to prevent artifacts
-        cursor_offset.0 += MARGIN;
-
-        // carriage return our cursor if we don't have enough room to write the next caracter
-        // we add a margin to prevent artifacts
-        if cursor_offset.0 + (bitmap.width as u32) + MARGIN >= texture_width {
-            assert!(bitmap.width as u32 <= texture_width);       // if this fails, we should increase texture_width
-            cursor_offset.0 = 0;
-            cursor_offset.1 += rows_to_skip;
-            rows_to_skip = 0;
-        }
-
-        // if the texture data buffer has not enough lines, adding some
-        if rows_to_skip < MARGIN + bitmap.rows as u32 {
-            let diff = MARGIN + (bitmap.rows as u32) - rows_to_skip;
-            rows_to_skip = MARGIN + bitmap.rows as u32;
-            texture_data.extend(iter::repeat(0.0).take((diff * texture_width) as usize));
-        }
-
-        // copying the data to the texture
-        let offset_x_before_copy = cursor_offset.0;
-        if bitmap.rows >= 1 {
-            let destination = &mut texture_data[(cursor_offset.0 + cursor_offset.1 * texture_width) as usize ..];
-            let source = &bitmap.buffer;
-            //ylet source = std::slice::from_raw_parts(source, destination.len());
-
-            for y in 0 .. bitmap.rows as u32 {
-                let source = &source[(y * bitmap.width as u32) as usize ..];
-                let destination = &mut destination[(y * texture_width) as usize ..];
-
-                for x in 0 .. bitmap.width {
-                    // the values in source are bytes between 0 and 255, but we want floats between 0 and 1
-                    let val: u8 = source[x as usize];
-                    let val = f32::from(val) / f32::from(std::u8::MAX);
-                    let dest = &mut destination[x as usize];
-                    *dest = val;
+/// Ranges of characters of the same embedding level, tabs and line breaks are runs of their own.
+fn direction_runs(chars: &[char], levels: &[u8]) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (i, &ch) in chars.iter().enumerate() {
+        let separate = |ch: char| ch == '\t' || ch == '\n';
+        match runs.last_mut() {
+            Some(run) if !separate(ch) && !separate(chars[run.start]) && levels[run.start] == levels[i] => run.end = i + 1,
+            _ => runs.push(i .. i + 1)
+        }
+    }
+    runs
+}
+
+/// Display order of runs with the given levels, reversing every sequence at or above each odd level.
+fn visual_runs(levels: &[u8]) -> Vec<usize> {
+    let highest = levels.iter().cloned().max().unwrap_or(0);
+    let lowest_odd = levels.iter().cloned().filter(|level| level % 2 == 1).min().unwrap_or(highest + 1);
+    let mut order = (0 .. levels.len()).collect::<Vec<_>>();
+    let mut level = highest;
+    while level >= lowest_odd && level > 0 {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] >= level {
+                let start = i;
+                while i < order.len() && levels[order[i]] >= level {
+                    i += 1;
                 }
+                order[start .. i].reverse();
+            } else {
+                i += 1;
             }
+        }
+        level -= 1;
+    }
+    order
+}
 
-            cursor_offset.0 += bitmap.width as u32;
-            debug_assert!(cursor_offset.0 <= texture_width);
-        }
-
-        // filling infos about that character
-        // tex_size and tex_coords are in pixels for the moment ; they will be divided
-        // by the texture dimensions later
-        Ok((character, CharacterInfos {
-            tex_size: (bitmap.width as f32, bitmap.rows as f32),
-            tex_coords: (offset_x_before_copy as f32, cursor_offset.1 as f32),
-            size: (bitmap.width as f32, bitmap.rows as f32),
-            left_padding: h_metrics.left_side_bearing as f32,
-            right_padding: (h_metrics.advance_width
-                - bitmap.width as f32
-                - h_metrics.left_side_bearing as f32) as f32 / 64.0,
-            height_over_line: bb.max.y as f32,
-        }))
-    }).collect::<Result<Vec<_>, Error>>()?;
-
-    // adding blank lines at the end until the height of the texture is a power of two
-    {
-        let current_height = texture_data.len() as u32 / texture_width;
-        let requested_height = get_nearest_po2(current_height);
-        texture_data.extend(iter::repeat(0.0).take((texture_width * (requested_height - current_height)) as usize));
-    }
-
-    // now our texture is finished
-    // we know its final dimensions, so we can divide all the pixels values into (0,1) range
-    assert!((texture_data.len() as u32 % texture_width) == 0);
-    let texture_height = (texture_data.len() as u32 / texture_width) as f32;
-    let float_texture_width = texture_width as f32;
-    let mut characters_infos = characters_infos.into_iter().map(|mut chr| {
-        chr.1.tex_size.0 /= float_texture_width;
-        chr.1.tex_size.1 /= texture_height;
-        chr.1.tex_coords.0 /= float_texture_width;
-        chr.1.tex_coords.1 /= texture_height;
-        chr.1.size.0 /= em_pixels;
-        chr.1.size.1 /= em_pixels;
-        chr.1.left_padding /= em_pixels;
-        chr.1.right_padding /= em_pixels;
-        chr.1.height_over_line /= em_pixels;
-        chr
-    }).collect::<HashMap<_, _>>();
-
-    // this HashMap will not be used mutably any more and it makes sense to
-    // compact it
-    characters_infos.shrink_to_fit();
-
-    // returning
-    Ok((TextureData {
-        data: texture_data,
-        width: texture_width,
-        height: texture_height as u32,
-    }, characters_infos))
-}
-
-/// Function that will calculate the nearest power of two.
-fn get_nearest_po2(mut x: u32) -> u32 {
-    assert!(x > 0);
-    x -= 1;
-    x = x | (x >> 1);
-    x = x | (x >> 2);
-    x = x | (x >> 4);
-    x = x | (x >> 8);
-    x = x | (x >> 16);
-    x + 1
+/// Number of characters in every cluster of a shaped run at the index of its first character, zero elsewhere.
+fn cluster_sizes(glyphs: &[RunGlyph], count: usize) -> Vec<usize> {
+    let mut sizes = vec![0; count];
+    for glyph in glyphs.iter().filter(|glyph| glyph.cluster < count) {
+        sizes[glyph.cluster] = 1;
+    }
+    let mut start = 0;
+    for i in 1 ..= count {
+        if i == count || sizes[i] != 0 {
+            sizes[start] = i - start;
+            start = i;
+        }
+    }
+    sizes
 }
 
-#[derive(Clone)]
+/// Advance of every character of a shaped run in raster pixels, characters of one cluster share it evenly.
+fn char_advances(glyphs: &[RunGlyph], count: usize) -> Vec<f32> {
+    let sizes = cluster_sizes(glyphs, count);
+    let mut totals = vec![0.0; count];
+    for glyph in glyphs.iter().filter(|glyph| glyph.cluster < count) {
+        totals[glyph.cluster] += glyph.advance;
+    }
+    let mut advances = vec![0.0; count];
+    for start in (0 .. count).filter(|&start| sizes[start] > 0) {
+        let share = totals[start] / sizes[start] as f32;
+        for advance in &mut advances[start .. start + sizes[start]] {
+            *advance = share;
+        }
+    }
+    advances
+}
+
+/// Glyphs the characters map to on their own with pair kerning, for faces the shaper cannot read.
+fn nominal_glyphs(font: &rusttype::Font, scale: Scale, text: &[char], rtl: bool) -> Vec<RunGlyph> {
+    let mut glyphs = text.iter().enumerate()
+        .map(|(i, &ch)| {
+            let kerning = text.get(i + 1).map_or(0.0, |&next| font.pair_kerning(scale, ch, next));
+            let glyph = font.glyph(ch).scaled(scale);
+            RunGlyph { id: glyph.id().0, cluster: i, advance: glyph.h_metrics().advance_width + kerning, offset: [0.0, 0.0] }
+        })
+        .collect::<Vec<_>>();
+    if rtl {
+        glyphs.reverse();
+    }
+    glyphs
+}
+
+/// Triangles of the glyph and solid quads. Quads are grown by `radius` atlas pixels on every side
+/// and moved by `offset` for effects, which also only keep the alpha of the tint so the effect color shows.
+fn glyph_vertices(quads: &[GlyphQuad], solids: &[SolidQuad], solid: Option<CachedGlyph>, atlas: (f32, f32),
+                  radius: f32, offset: [f32; 2], effect: bool) -> Vec<VertexFormat> {
+    let (atlas_w, atlas_h) = atlas;
+    let effect_tint = |tint: [f32; 4]| if effect { [1.0, 1.0, 1.0, tint[3]] } else { tint };
+    let mut vertices = Vec::with_capacity((quads.len() + solids.len()) * 6);
+
+    for &GlyphQuad { x, baseline, glyph, style } in quads {
+        let GlyphStyle { unit, bold_offset, shear, tint } = style;
+        let [u, v, gw, gh] = glyph.rect;
+        if gw == 0 || gh == 0 {
+            continue;
+        }
+        let grow = radius * unit;
+        let left = x + glyph.offset[0] * unit - grow + offset[0];
+        let top = baseline + glyph.offset[1] * unit - grow + offset[1];
+        let right = left + gw as f32 * unit + grow * 2.0;
+        let bottom = top + gh as f32 * unit + grow * 2.0;
+        let uv_rect = [
+            u as f32 / atlas_w, v as f32 / atlas_h,
+            (u + gw) as f32 / atlas_w, (v + gh) as f32 / atlas_h
+        ];
+        let uv = [
+            uv_rect[0] - radius / atlas_w, uv_rect[1] - radius / atlas_h,
+            uv_rect[2] + radius / atlas_w, uv_rect[3] + radius / atlas_h
+        ];
+        let tint = effect_tint(tint);
+        let passes = if bold_offset > 0.0 { 2 } else { 1 };
+        for pass in 0 .. passes {
+            let dx = pass as f32 * bold_offset;
+            let baseline = baseline + offset[1];
+            let corner = |px: f32, py: f32, tu: f32, tv: f32| VertexFormat {
+                pos: [px + dx + (baseline - py) * shear, py],
+                texture_uv: [tu, tv],
+                uv_rect,
+                tint
+            };
+            vertices.extend_from_slice(&[
+                corner(left, top, uv[0], uv[1]),
+                corner(right, top, uv[2], uv[1]),
+                corner(left, bottom, uv[0], uv[3]),
+                corner(left, bottom, uv[0], uv[3]),
+                corner(right, top, uv[2], uv[1]),
+                corner(right, bottom, uv[2], uv[3])
+            ]);
+        }
+    }
+
+    if let Some(solid) = solid {
+        // every corner samples the middle of the solid glyph
+        let [u, v, gw, gh] = solid.rect;
+        let texture_uv = [(u as f32 + gw as f32 / 2.0) / atlas_w, (v as f32 + gh as f32 / 2.0) / atlas_h];
+        let uv_rect = [u as f32 / atlas_w, v as f32 / atlas_h, (u + gw) as f32 / atlas_w, (v + gh) as f32 / atlas_h];
+        for &SolidQuad { bounds: [x, y, w, h], tint } in solids {
+            let (x, y) = (x + offset[0], y + offset[1]);
+            let tint = effect_tint(tint);
+            let corner = |px: f32, py: f32| VertexFormat { pos: [px, py], texture_uv, uv_rect, tint };
+            vertices.extend_from_slice(&[
+                corner(x, y), corner(x + w, y), corner(x, y + h),
+                corner(x, y + h), corner(x + w, y), corner(x + w, y + h)
+            ]);
+        }
+    }
+    vertices
+}
+
+/// Height used to align a line, only glyphs hanging entirely under the baseline extend it.
+fn line_height(placed: &[PlacedGlyph], unit: f32) -> f32 {
+    placed.iter()
+        .map(|placed| placed.glyph.offset[1] * unit)
+        .fold(0.0, f32::max)
+}
+
+fn rasterize(font: &rusttype::Font, scale: Scale, id: GlyphId) -> GlyphBitmap {
+    let glyph = font.glyph(id).scaled(scale);
+    let advance = glyph.h_metrics().advance_width;
+    let glyph = glyph.positioned(point(0.0, 0.0));
+    match glyph.pixel_bounding_box() {
+        Some(bb) => {
+            let (width, height) = (bb.width() as u32, bb.height() as u32);
+            let mut pixels = vec![0; (width * height) as usize];
+            glyph.draw(|x, y, v| {
+                pixels[(y * width + x) as usize] = (v * 255.0).round() as u8;
+            });
+            GlyphBitmap { width, height, pixels, offset: [bb.min.x as f32, bb.min.y as f32], advance }
+        },
+        None => GlyphBitmap { width: 0, height: 0, pixels: Vec::new(), offset: [0.0, 0.0], advance }
+    }
+}
+
+/// Rasterizes the glyph and stores the signed distance to its outline, 0.5 lies on the outline
+/// and values grow towards the inside. The bitmap is padded by `SDF_SPREAD` on every side.
+fn rasterize_sdf(font: &rusttype::Font, scale: Scale, id: GlyphId) -> GlyphBitmap {
+    let coverage = rasterize(font, scale, id);
+    if coverage.width == 0 || coverage.height == 0 {
+        return coverage;
+    }
+    let spread = SDF_SPREAD as i32;
+    let (columns, rows) = (coverage.width as i32, coverage.height as i32);
+    let inside = |x: i32, y: i32| {
+        x >= 0 && y >= 0 && x < columns && y < rows && coverage.pixels[(y * columns + x) as usize] >= 128
+    };
+    let (width, height) = (columns + spread * 2, rows + spread * 2);
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in -spread .. rows + spread {
+        for x in -spread .. columns + spread {
+            let state = inside(x, y);
+            let mut nearest = (spread * spread) as f32;
+            for dy in -spread ..= spread {
+                for dx in -spread ..= spread {
+                    let distance = (dx * dx + dy * dy) as f32;
+                    if distance < nearest && inside(x + dx, y + dy) != state {
+                        nearest = distance;
+                    }
+                }
+            }
+            let distance = nearest.sqrt() / spread as f32 * 0.5;
+            let value = if state { 0.5 + distance } else { 0.5 - distance };
+            pixels.push((value.max(0.0).min(1.0) * 255.0).round() as u8);
+        }
+    }
+    GlyphBitmap {
+        width: width as u32,
+        height: height as u32,
+        pixels,
+        offset: [coverage.offset[0] - spread as f32, coverage.offset[1] - spread as f32],
+        advance: coverage.advance
+    }
+}
+
+fn text_parameters(scissor: Option<GLRect>, stencil: Option<i32>) -> DrawParameters<'static> {
+    use glium::BlendingFunction::Addition;
+    use glium::LinearBlendingFactor::*;
+
+    // alpha composited with the over operator, see `render::alpha_blending`
+    let blend = glium::Blend {
+        color: Addition { source: SourceAlpha, destination: OneMinusSourceAlpha },
+        alpha: Addition { source: One, destination: OneMinusSourceAlpha },
+        constant_value: (1.0, 1.0, 1.0, 1.0),
+    };
+
+    let stencil = match stencil {
+        Some(reference) => glium::draw_parameters::Stencil {
+            test_clockwise: glium::StencilTest::IfEqual { mask: !0 },
+            test_counter_clockwise: glium::StencilTest::IfEqual { mask: !0 },
+            reference_value_clockwise: reference,
+            reference_value_counter_clockwise: reference,
+            .. Default::default()
+        },
+        None => Default::default()
+    };
+
+    DrawParameters {
+        blend,
+        scissor,
+        stencil,
+        .. Default::default()
+    }
+}
+
+///
+#[derive(Debug)]
+pub enum Error {
+    /// A glyph for this character is not present in font.
+    NoGlyph(char),
+    /// An Error that comes directly from Rusttype.
+    RusttypeError(rusttype::Error),
+    /// The font file could not be read.
+    Io(std::io::Error),
+    /// No installed font matches the requested family.
+    FamilyNotFound(String),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<rusttype::Error> for Error {
+    fn from(error: rusttype::Error) -> Self {
+        Error::RusttypeError(error)
+    }
+}
+
+/// Object that contains the elements shared by all text draws.
+pub struct TextSystem {
+    program: glium::Program,
+    sdf_program: glium::Program,
+    effect_program: glium::Program
+}
+
+#[derive(Copy, Clone)]
+struct VertexFormat {
+    pos: [f32; 2],
+    texture_uv: [f32; 2],
+    /// Bounds of the glyph in the atlas, effects sample nothing outside of them
+    uv_rect: [f32; 4],
+    tint: [f32; 4],
+}
+
+implement_vertex!(VertexFormat, pos, texture_uv, uv_rect, tint);
+
+impl TextSystem {
+    pub fn new<R>(renderer: &R) -> crate::error::Result<TextSystem> where R: Renderer<Program = glium::Program> {
+        let vertex = include_str!("resources/shaders/font.vsh");
+        Ok(TextSystem {
+            program: renderer.compile_program(vertex, include_str!("resources/shaders/font.fsh"), None)?,
+            sdf_program: renderer.compile_program(vertex, include_str!("resources/shaders/font_sdf.fsh"), None)?,
+            effect_program: renderer.compile_program(vertex, include_str!("resources/shaders/font_effect.fsh"), None)?
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextAlignHorizontal {
     Left, Right, Center
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextAlignVertical {
-    Top, Bottom, Center
-}
\ No newline at end of file
+    Top, Bottom, Center,
+    /// `y` is the baseline of the first line
+    Baseline
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::{FontLibrary, FontParameters};
+
+    #[test]
+    fn lays_out_text_on_another_thread() {
+        let library = FontLibrary::new();
+        let worker = library.clone();
+        let (layout, glyphs) = thread::spawn(move || {
+            let params = FontParameters::default();
+            (worker.layout_text("Hello, world", 1000.0, &params), worker.rasterize("Hello, world", &params))
+        }).join().expect("Layout thread panicked");
+
+        assert_eq!(layout.lines.len(), 1);
+        assert!(layout.width > 0.0);
+        // the space has no bitmap but is still cached for its advance
+        assert!(glyphs.len() >= "Helo, wrd".len());
+        assert_eq!(layout.width, library.layout_text("Hello, world", 1000.0, &FontParameters::default()).width);
+    }
+}
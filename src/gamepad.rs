@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use gilrs::{Gilrs, Button, Axis, EventType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAction {
+    Up, Down, Left, Right,
+    /// Behaves like pressing `Return` on the focused widget
+    Activate,
+    /// Behaves like pressing `Escape` on the focused widget
+    Cancel
+}
+
+impl GamepadAction {
+    pub fn direction(&self) -> Option<(f32, f32)> {
+        match self {
+            GamepadAction::Up => Some((0.0, -1.0)),
+            GamepadAction::Down => Some((0.0, 1.0)),
+            GamepadAction::Left => Some((-1.0, 0.0)),
+            GamepadAction::Right => Some((1.0, 0.0)),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GamepadMapping {
+    pub buttons: HashMap<Button, GamepadAction>,
+    pub left_stick: bool,
+    pub stick_threshold: f32
+}
+
+impl Default for GamepadMapping {
+    fn default() -> Self {
+        let mut buttons = HashMap::new();
+        buttons.insert(Button::DPadUp, GamepadAction::Up);
+        buttons.insert(Button::DPadDown, GamepadAction::Down);
+        buttons.insert(Button::DPadLeft, GamepadAction::Left);
+        buttons.insert(Button::DPadRight, GamepadAction::Right);
+        buttons.insert(Button::South, GamepadAction::Activate);
+        buttons.insert(Button::East, GamepadAction::Cancel);
+        GamepadMapping {
+            buttons,
+            left_stick: true,
+            stick_threshold: 0.5
+        }
+    }
+}
+
+/// Polls connected gamepads and translates their input into navigation actions.
+pub struct GamepadInput {
+    gilrs: Gilrs,
+    mapping: GamepadMapping,
+    stick: (i8, i8)
+}
+
+impl GamepadInput {
+    pub fn new(mapping: GamepadMapping) -> Result<GamepadInput, gilrs::Error> {
+        Ok(GamepadInput {
+            gilrs: Gilrs::new()?,
+            mapping,
+            stick: (0, 0)
+        })
+    }
+
+    pub fn mapping(&self) -> &GamepadMapping {
+        &self.mapping
+    }
+
+    pub fn set_mapping(&mut self, mapping: GamepadMapping) {
+        self.mapping = mapping;
+    }
+
+    pub fn poll(&mut self) -> Vec<GamepadAction> {
+        let mut actions = Vec::new();
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) | EventType::ButtonRepeated(button, _) => {
+                    if let Some(action) = self.mapping.buttons.get(&button) {
+                        actions.push(*action);
+                    }
+                },
+                EventType::AxisChanged(axis, value, _) if self.mapping.left_stick => {
+                    let threshold = self.mapping.stick_threshold;
+                    let step = if value >= threshold { 1 } else if value <= -threshold { -1 } else { 0 };
+                    match axis {
+                        Axis::LeftStickX => {
+                            if step != self.stick.0 && step != 0 {
+                                actions.push(if step > 0 { GamepadAction::Right } else { GamepadAction::Left });
+                            }
+                            self.stick.0 = step;
+                        },
+                        Axis::LeftStickY => {
+                            // gilrs reports stick up as positive
+                            if step != self.stick.1 && step != 0 {
+                                actions.push(if step > 0 { GamepadAction::Up } else { GamepadAction::Down });
+                            }
+                            self.stick.1 = step;
+                        },
+                        _ => {}
+                    }
+                },
+                _ => {}
+            }
+        }
+        actions
+    }
+}
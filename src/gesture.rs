@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::time::{Instant, Duration};
+
+use winit::event::TouchPhase;
+
+pub const TAP_MAX_DISTANCE: f32 = 10.0;
+pub const TAP_MAX_DURATION: Duration = Duration::from_millis(300);
+pub const DOUBLE_TAP_INTERVAL: Duration = Duration::from_millis(300);
+pub const LONG_PRESS_DURATION: Duration = Duration::from_millis(500);
+pub const SWIPE_MIN_DISTANCE: f32 = 50.0;
+/// Scale change of one line of a zoom wheel, see `GestureRecognizer::on_zoom_wheel`
+pub const ZOOM_WHEEL_STEP: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwipeDirection {
+    Left, Right, Up, Down
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    Tap { pos: (f32, f32) },
+    DoubleTap { pos: (f32, f32) },
+    LongPress { pos: (f32, f32) },
+    Swipe { start: (f32, f32), end: (f32, f32), direction: SwipeDirection },
+    /// `scale` is relative to the finger distance of the previous pinch step
+    Pinch { center: (f32, f32), scale: f32 },
+    /// `angle` is in radians, clockwise, relative to the finger angle of the previous rotate step
+    Rotate { center: (f32, f32), angle: f32 }
+}
+
+impl Gesture {
+    pub fn pos(&self) -> (f32, f32) {
+        match *self {
+            Gesture::Tap { pos } | Gesture::DoubleTap { pos } | Gesture::LongPress { pos } => pos,
+            Gesture::Swipe { start, .. } => start,
+            Gesture::Pinch { center, .. } | Gesture::Rotate { center, .. } => center
+        }
+    }
+}
+
+struct TouchTrack {
+    start: (f32, f32),
+    pos: (f32, f32),
+    started: Instant,
+    long_pressed: bool
+}
+
+/// Turns raw touch points into high level gestures.
+///
+/// Feed it with `on_touch` for every touch event and call `update` once per frame,
+/// long presses are only detected there. Trackpads report pinches as a wheel with control held,
+/// those go through `on_zoom_wheel`.
+pub struct GestureRecognizer {
+    touches: HashMap<u64, TouchTrack>,
+    last_tap: Option<(Instant, (f32, f32))>,
+    pinch_distance: Option<f32>,
+    pinch_angle: Option<f32>
+}
+
+impl GestureRecognizer {
+    pub fn new() -> GestureRecognizer {
+        GestureRecognizer {
+            touches: HashMap::new(),
+            last_tap: None,
+            pinch_distance: None,
+            pinch_angle: None
+        }
+    }
+
+    pub fn on_touch(&mut self, id: u64, phase: TouchPhase, pos: (f32, f32)) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+        match phase {
+            TouchPhase::Started => {
+                self.touches.insert(id, TouchTrack {
+                    start: pos,
+                    pos,
+                    started: Instant::now(),
+                    long_pressed: false
+                });
+                self.pinch_distance = self.finger_distance();
+                self.pinch_angle = self.finger_angle();
+            },
+            TouchPhase::Moved => {
+                if let Some(track) = self.touches.get_mut(&id) {
+                    track.pos = pos;
+                }
+                if let (Some(prev), Some(current)) = (self.pinch_distance, self.finger_distance()) {
+                    if prev > 0.0 && current != prev {
+                        gestures.push(Gesture::Pinch { center: self.finger_center(), scale: current / prev });
+                        self.pinch_distance = Some(current);
+                    }
+                }
+                if let (Some(prev), Some(current)) = (self.pinch_angle, self.finger_angle()) {
+                    // the shorter way around, atan2 jumps by a full turn at the negative x axis
+                    let mut angle = current - prev;
+                    if angle > PI {
+                        angle -= 2.0 * PI;
+                    } else if angle < -PI {
+                        angle += 2.0 * PI;
+                    }
+                    if angle != 0.0 {
+                        gestures.push(Gesture::Rotate { center: self.finger_center(), angle });
+                        self.pinch_angle = Some(current);
+                    }
+                }
+            },
+            TouchPhase::Ended => {
+                let pinching = self.touches.len() > 1;
+                if let Some(track) = self.touches.remove(&id) {
+                    if !pinching && !track.long_pressed {
+                        gestures.extend(self.finish_touch(track, pos));
+                    }
+                }
+                self.pinch_distance = self.finger_distance();
+                self.pinch_angle = self.finger_angle();
+            },
+            TouchPhase::Cancelled => {
+                self.touches.remove(&id);
+                self.pinch_distance = self.finger_distance();
+                self.pinch_angle = self.finger_angle();
+            }
+        }
+        gestures
+    }
+
+    /// Wheel scrolled by `lines` with control held, centered on the mouse at `pos`. Precision
+    /// trackpads send their pinches this way, positive lines zoom in.
+    pub fn on_zoom_wheel(&mut self, lines: f32, pos: (f32, f32)) -> Option<Gesture> {
+        if lines == 0.0 {
+            return None;
+        }
+        Some(Gesture::Pinch { center: pos, scale: (1.0 + ZOOM_WHEEL_STEP).powf(lines) })
+    }
+
+    pub fn update(&mut self) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+        if self.touches.len() == 1 {
+            for track in self.touches.values_mut() {
+                if !track.long_pressed && track.started.elapsed() >= LONG_PRESS_DURATION
+                    && distance(track.start, track.pos) <= TAP_MAX_DISTANCE {
+                    track.long_pressed = true;
+                    gestures.push(Gesture::LongPress { pos: track.pos });
+                }
+            }
+        }
+        gestures
+    }
+
+    pub fn reset(&mut self) {
+        self.touches.clear();
+        self.last_tap = None;
+        self.pinch_distance = None;
+        self.pinch_angle = None;
+    }
+
+    fn finish_touch(&mut self, track: TouchTrack, pos: (f32, f32)) -> Option<Gesture> {
+        let (dx, dy) = (pos.0 - track.start.0, pos.1 - track.start.1);
+        let moved = distance(track.start, pos);
+        if moved <= TAP_MAX_DISTANCE && track.started.elapsed() <= TAP_MAX_DURATION {
+            let now = Instant::now();
+            let double = match self.last_tap {
+                Some((time, last)) => now.duration_since(time) <= DOUBLE_TAP_INTERVAL
+                    && distance(last, pos) <= TAP_MAX_DISTANCE,
+                None => false
+            };
+            if double {
+                self.last_tap = None;
+                Some(Gesture::DoubleTap { pos })
+            } else {
+                self.last_tap = Some((now, pos));
+                Some(Gesture::Tap { pos })
+            }
+        } else if moved >= SWIPE_MIN_DISTANCE {
+            let direction = if dx.abs() > dy.abs() {
+                if dx > 0.0 { SwipeDirection::Right } else { SwipeDirection::Left }
+            } else {
+                if dy > 0.0 { SwipeDirection::Down } else { SwipeDirection::Up }
+            };
+            Some(Gesture::Swipe { start: track.start, end: pos, direction })
+        } else {
+            None
+        }
+    }
+
+    fn finger_distance(&self) -> Option<f32> {
+        if self.touches.len() == 2 {
+            let mut points = self.touches.values().map(|t| t.pos);
+            let a = points.next().unwrap();
+            let b = points.next().unwrap();
+            Some(distance(a, b))
+        } else {
+            None
+        }
+    }
+
+    /// Angle of the line from the first to the second finger, in touch id order so it does not flip
+    fn finger_angle(&self) -> Option<f32> {
+        if self.touches.len() == 2 {
+            let mut ids: Vec<_> = self.touches.keys().collect();
+            ids.sort();
+            let a = self.touches[ids[0]].pos;
+            let b = self.touches[ids[1]].pos;
+            Some((b.1 - a.1).atan2(b.0 - a.0))
+        } else {
+            None
+        }
+    }
+
+    fn finger_center(&self) -> (f32, f32) {
+        let count = self.touches.len().max(1) as f32;
+        let (x, y) = self.touches.values()
+            .fold((0.0, 0.0), |(x, y), t| (x + t.pos.0, y + t.pos.1));
+        (x / count, y / count)
+    }
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
@@ -0,0 +1,300 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use glium::Display;
+use glium::texture::{Texture2d, RawImage2d, ClientFormat};
+
+use crate::backend::Renderer;
+use crate::font::FaceKey;
+
+pub const ATLAS_WIDTH: u32 = 1024;
+pub const INITIAL_ATLAS_HEIGHT: u32 = 256;
+pub const MAX_ATLAS_HEIGHT: u32 = 2048;
+
+// empty pixels around each glyph so linear filtering does not pick up its neighbours
+const MARGIN: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub face: FaceKey,
+    pub size: u32,
+    /// Whether the glyph holds a distance field instead of coverage
+    pub sdf: bool,
+    /// Index of the glyph in its face, shaping can pick glyphs no single character maps to
+    pub glyph: u32
+}
+
+/// Coverage of a single glyph as produced by the rasterizer, rows go top to bottom.
+pub struct GlyphBitmap {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    /// Position of the top left pixel relative to the pen on the baseline
+    pub offset: [f32; 2],
+    pub advance: f32
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CachedGlyph {
+    /// Left, top, width and height of the glyph in atlas pixels
+    pub rect: [u32; 4],
+    pub offset: [f32; 2],
+    pub advance: f32,
+    shelf: Option<usize>,
+    last_used: u64
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GlyphCacheStats {
+    pub glyphs: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Glyphs dropped to make room for others
+    pub evictions: u64,
+    pub atlas_width: u32,
+    pub atlas_height: u32,
+    /// Bytes taken by the atlas texture, the same amount is kept in main memory
+    pub memory: usize,
+    /// Fraction of the atlas covered by shelves in use
+    pub occupancy: f32
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+    last_used: u64
+}
+
+/// Single texture shared by all fonts and sizes, glyphs are rasterized the first time they are drawn.
+///
+/// Glyphs are packed into shelves, rows of similar height. Once the atlas reached its maximum size
+/// the least recently used shelf is cleared to make room, the maximum size follows the memory budget.
+pub struct GlyphCache {
+    display: Display,
+    texture: Texture2d,
+    pixels: Vec<u8>,
+    height: u32,
+    max_height: u32,
+    shelves: Vec<Shelf>,
+    glyphs: HashMap<GlyphKey, CachedGlyph>,
+    tick: u64,
+    stats: GlyphCacheStats
+}
+
+impl GlyphCache {
+    pub fn new(display: &Display) -> GlyphCache {
+        GlyphCache {
+            display: display.clone(),
+            texture: Self::allocate(display, INITIAL_ATLAS_HEIGHT),
+            pixels: vec![0; (ATLAS_WIDTH * INITIAL_ATLAS_HEIGHT) as usize],
+            height: INITIAL_ATLAS_HEIGHT,
+            max_height: MAX_ATLAS_HEIGHT,
+            shelves: Vec::new(),
+            glyphs: HashMap::new(),
+            tick: 0,
+            stats: GlyphCacheStats::default()
+        }
+    }
+
+    /// Limits the atlas to `bytes`, rounded down to a height it grows to but never below its initial size.
+    /// A smaller atlas than the current one starts over empty.
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        let mut max_height = INITIAL_ATLAS_HEIGHT;
+        while max_height < MAX_ATLAS_HEIGHT && (ATLAS_WIDTH * max_height * 2) as usize <= bytes {
+            max_height *= 2;
+        }
+        self.max_height = max_height;
+        if self.height > max_height {
+            self.height = max_height;
+            self.texture = Self::allocate(&self.display, max_height);
+            self.pixels = vec![0; (ATLAS_WIDTH * max_height) as usize];
+            self.stats.evictions += self.glyphs.len() as u64;
+            self.clear();
+        }
+    }
+
+    pub fn memory_budget(&self) -> usize {
+        (ATLAS_WIDTH * self.max_height) as usize
+    }
+
+    pub fn stats(&self) -> GlyphCacheStats {
+        let used = self.shelves.iter()
+            .map(|shelf| shelf.x as u64 * shelf.height as u64)
+            .sum::<u64>();
+        GlyphCacheStats {
+            glyphs: self.glyphs.len(),
+            atlas_width: ATLAS_WIDTH,
+            atlas_height: self.height,
+            memory: (ATLAS_WIDTH * self.height) as usize,
+            occupancy: used as f32 / (ATLAS_WIDTH * self.height) as f32,
+            .. self.stats
+        }
+    }
+
+    /// Zeroes the hit, miss and eviction counters.
+    pub fn reset_stats(&mut self) {
+        self.stats = GlyphCacheStats::default();
+    }
+
+    fn allocate(display: &Display, height: u32) -> Texture2d {
+        display.create_coverage_texture(ATLAS_WIDTH, height)
+            .expect("Glyph atlas allocation failed")
+    }
+
+    pub fn texture(&self) -> &Texture2d {
+        &self.texture
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (ATLAS_WIDTH, self.height)
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    /// Starts a new use of the atlas, glyphs looked up since the last call are never evicted.
+    pub fn begin_use(&mut self) {
+        self.tick += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.glyphs.clear();
+        self.shelves.clear();
+        for pixel in self.pixels.iter_mut() {
+            *pixel = 0;
+        }
+        self.upload_all();
+    }
+
+    pub fn contains(&self, key: &GlyphKey) -> bool {
+        self.glyphs.contains_key(key)
+    }
+
+    /// Returns the cached glyph, rasterizing it on a miss. `None` if there was no room left for it.
+    pub fn get_or_insert<F>(&mut self, key: &GlyphKey, rasterize: F) -> Option<CachedGlyph>
+        where F: FnOnce() -> GlyphBitmap {
+
+        let tick = self.tick;
+        if let Some(glyph) = self.glyphs.get_mut(key) {
+            self.stats.hits += 1;
+            glyph.last_used = tick;
+            if let Some(shelf) = glyph.shelf {
+                self.shelves[shelf].last_used = tick;
+            }
+            return Some(*glyph);
+        }
+
+        self.stats.misses += 1;
+        let bitmap = rasterize();
+        let glyph = if bitmap.width == 0 || bitmap.height == 0 {
+            // whitespace takes no room in the atlas
+            CachedGlyph { rect: [0, 0, 0, 0], offset: bitmap.offset, advance: bitmap.advance, shelf: None, last_used: tick }
+        } else {
+            let (shelf, x, y) = self.allocate_rect(bitmap.width, bitmap.height)?;
+            self.write(x, y, &bitmap);
+            CachedGlyph {
+                rect: [x, y, bitmap.width, bitmap.height],
+                offset: bitmap.offset,
+                advance: bitmap.advance,
+                shelf: Some(shelf),
+                last_used: tick
+            }
+        };
+        self.glyphs.insert(key.clone(), glyph);
+        Some(glyph)
+    }
+
+    fn allocate_rect(&mut self, width: u32, height: u32) -> Option<(usize, u32, u32)> {
+        let (w, h) = (width + MARGIN, height + MARGIN);
+        if w > ATLAS_WIDTH || h > self.max_height {
+            return None;
+        }
+        let tick = self.tick;
+
+        // shelves up to a third taller than the glyph are used as is
+        let fitting = self.shelves.iter().position(|shelf| {
+            shelf.height >= h && shelf.height <= h + h / 3 && shelf.x + w <= ATLAS_WIDTH
+        });
+        let index = match fitting {
+            Some(index) => index,
+            None => {
+                let bottom = self.shelves.iter().map(|shelf| shelf.y + shelf.height).max().unwrap_or(0);
+                while bottom + h > self.height && self.height < self.max_height {
+                    self.grow();
+                }
+                if bottom + h <= self.height {
+                    self.shelves.push(Shelf { y: bottom, height: h, x: 0, last_used: tick });
+                    self.shelves.len() - 1
+                } else {
+                    self.evict(h)?
+                }
+            }
+        };
+        let shelf = &mut self.shelves[index];
+        let x = shelf.x;
+        shelf.x += w;
+        shelf.last_used = tick;
+        Some((index, x, shelf.y))
+    }
+
+    /// Clears the least recently used shelf tall enough for `height`, keeping glyphs in use.
+    fn evict(&mut self, height: u32) -> Option<usize> {
+        let tick = self.tick;
+        let index = self.shelves.iter().enumerate()
+            .filter(|(_, shelf)| shelf.height >= height && shelf.last_used < tick)
+            .min_by_key(|(_, shelf)| shelf.last_used)
+            .map(|(i, _)| i)?;
+        let before = self.glyphs.len();
+        self.glyphs.retain(|_, glyph| glyph.shelf != Some(index));
+        self.stats.evictions += (before - self.glyphs.len()) as u64;
+        let shelf = &mut self.shelves[index];
+        shelf.x = 0;
+        let (y, rows) = (shelf.y, shelf.height);
+        let range = (y * ATLAS_WIDTH) as usize .. ((y + rows) * ATLAS_WIDTH) as usize;
+        for pixel in &mut self.pixels[range.clone()] {
+            *pixel = 0;
+        }
+        self.texture.write(glium::Rect { left: 0, bottom: y, width: ATLAS_WIDTH, height: rows }, RawImage2d {
+            data: Cow::Borrowed(&self.pixels[range]),
+            width: ATLAS_WIDTH,
+            height: rows,
+            format: ClientFormat::U8
+        });
+        Some(index)
+    }
+
+    /// Doubles the atlas height, existing glyphs keep their pixel positions.
+    fn grow(&mut self) {
+        let height = (self.height * 2).min(self.max_height);
+        self.pixels.resize((ATLAS_WIDTH * height) as usize, 0);
+        self.height = height;
+        self.texture = Self::allocate(&self.display, height);
+        self.upload_all();
+    }
+
+    fn upload_all(&self) {
+        self.texture.write(glium::Rect { left: 0, bottom: 0, width: ATLAS_WIDTH, height: self.height }, RawImage2d {
+            data: Cow::Borrowed(&self.pixels[..]),
+            width: ATLAS_WIDTH,
+            height: self.height,
+            format: ClientFormat::U8
+        });
+    }
+
+    fn write(&mut self, x: u32, y: u32, bitmap: &GlyphBitmap) {
+        for row in 0 .. bitmap.height {
+            let source = &bitmap.pixels[(row * bitmap.width) as usize ..][.. bitmap.width as usize];
+            let start = ((y + row) * ATLAS_WIDTH + x) as usize;
+            self.pixels[start .. start + bitmap.width as usize].copy_from_slice(source);
+        }
+        // rows are uploaded top first, so texture coordinates grow downwards like atlas pixels
+        self.texture.write(glium::Rect { left: x, bottom: y, width: bitmap.width, height: bitmap.height }, RawImage2d {
+            data: Cow::Borrowed(&bitmap.pixels[..]),
+            width: bitmap.width,
+            height: bitmap.height,
+            format: ClientFormat::U8
+        });
+    }
+}
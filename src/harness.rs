@@ -0,0 +1,274 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use glium::{Display, Surface};
+use winit::event::{KeyboardInput, ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, ModifiersState};
+
+use crate::ui::{Widgets, WidgetEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    KeyboardChar(char),
+    KeyboardKey { scancode: u32, key: Option<VirtualKeyCode>, state: ElementState },
+    Modifiers(ModifiersState),
+    MouseButton { button: MouseButton, state: ElementState, pos: (f32, f32) },
+    MouseWheel(MouseScrollDelta),
+    MouseMove((f32, f32))
+}
+
+impl From<KeyboardInput> for InputEvent {
+    fn from(input: KeyboardInput) -> Self {
+        InputEvent::KeyboardKey { scancode: input.scancode, key: input.virtual_keycode, state: input.state }
+    }
+}
+
+impl From<ModifiersState> for InputEvent {
+    fn from(modifiers: ModifiersState) -> Self {
+        InputEvent::Modifiers(modifiers)
+    }
+}
+
+impl InputEvent {
+    /// Passes the event to the same `Widgets` handler the window loop calls for it.
+    pub fn dispatch<S>(&self, display: &Display, widgets: &mut Widgets<S>) -> Vec<WidgetEvent> where S: Surface {
+        match *self {
+            InputEvent::KeyboardChar(ch) => widgets.on_keyboard_char(display, ch),
+            InputEvent::KeyboardKey { scancode, key, state } => {
+                #[allow(deprecated)]
+                let input = KeyboardInput { scancode, state, virtual_keycode: key, modifiers: Default::default() };
+                widgets.on_keyboard_key(display, input)
+            },
+            InputEvent::Modifiers(modifiers) => widgets.on_modifiers_changed(display, modifiers),
+            InputEvent::MouseButton { button, state, pos } => widgets.on_mouse_button(display, button, state, pos),
+            InputEvent::MouseWheel(delta) => widgets.on_mouse_wheel(display, delta),
+            InputEvent::MouseMove(pos) => widgets.on_mouse_move(display, pos)
+        }
+    }
+
+    fn write<W>(&self, out: &mut W) -> io::Result<()> where W: Write {
+        match *self {
+            InputEvent::KeyboardChar(ch) => write!(out, "char {}", ch as u32),
+            InputEvent::KeyboardKey { scancode, key, state } => {
+                let key = key.map(|k| format!("{:?}", k)).unwrap_or("-".into());
+                write!(out, "key {} {} {}", scancode, key, write_state(state))
+            },
+            InputEvent::Modifiers(modifiers) => {
+                write!(out, "modifiers")?;
+                if modifiers.is_empty() {
+                    return write!(out, " -");
+                }
+                for (held, name) in &[(modifiers.shift(), "shift"), (modifiers.ctrl(), "ctrl"),
+                                      (modifiers.alt(), "alt"), (modifiers.logo(), "logo")] {
+                    if *held {
+                        write!(out, " {}", name)?;
+                    }
+                }
+                Ok(())
+            },
+            InputEvent::MouseButton { button, state, pos: (x, y) } => {
+                let button = match button {
+                    MouseButton::Left => "left".to_string(),
+                    MouseButton::Right => "right".to_string(),
+                    MouseButton::Middle => "middle".to_string(),
+                    MouseButton::Other(n) => n.to_string()
+                };
+                write!(out, "button {} {} {} {}", button, write_state(state), x, y)
+            },
+            InputEvent::MouseWheel(MouseScrollDelta::LineDelta(x, y)) => write!(out, "wheel lines {} {}", x, y),
+            InputEvent::MouseWheel(MouseScrollDelta::PixelDelta(pos)) => {
+                let (x, y): (f64, f64) = pos.into();
+                write!(out, "wheel pixels {} {}", x, y)
+            },
+            InputEvent::MouseMove((x, y)) => write!(out, "move {} {}", x, y)
+        }
+    }
+
+    fn parse(parts: &[&str]) -> Option<InputEvent> {
+        Some(match parts {
+            ["char", ch] => InputEvent::KeyboardChar(std::char::from_u32(ch.parse().ok()?)?),
+            ["key", scancode, key, state] => InputEvent::KeyboardKey {
+                scancode: scancode.parse().ok()?,
+                key: if *key == "-" { None } else { Some(key_from_name(key)?) },
+                state: parse_state(state)?
+            },
+            ["modifiers", "-"] => InputEvent::Modifiers(ModifiersState::empty()),
+            ["modifiers", names @ ..] => {
+                let mut modifiers = ModifiersState::empty();
+                for name in names {
+                    modifiers |= match *name {
+                        "shift" => ModifiersState::SHIFT,
+                        "ctrl" => ModifiersState::CTRL,
+                        "alt" => ModifiersState::ALT,
+                        "logo" => ModifiersState::LOGO,
+                        _ => return None
+                    };
+                }
+                InputEvent::Modifiers(modifiers)
+            },
+            ["button", button, state, x, y] => InputEvent::MouseButton {
+                button: match *button {
+                    "left" => MouseButton::Left,
+                    "right" => MouseButton::Right,
+                    "middle" => MouseButton::Middle,
+                    other => MouseButton::Other(other.parse().ok()?)
+                },
+                state: parse_state(state)?,
+                pos: (x.parse().ok()?, y.parse().ok()?)
+            },
+            ["wheel", "lines", x, y] => InputEvent::MouseWheel(
+                MouseScrollDelta::LineDelta(x.parse().ok()?, y.parse().ok()?)
+            ),
+            ["wheel", "pixels", x, y] => InputEvent::MouseWheel(
+                MouseScrollDelta::PixelDelta((x.parse::<f64>().ok()?, y.parse::<f64>().ok()?).into())
+            ),
+            ["move", x, y] => InputEvent::MouseMove((x.parse().ok()?, y.parse().ok()?)),
+            _ => return None
+        })
+    }
+}
+
+fn write_state(state: ElementState) -> &'static str {
+    match state {
+        ElementState::Pressed => "pressed",
+        ElementState::Released => "released"
+    }
+}
+
+fn parse_state(state: &str) -> Option<ElementState> {
+    match state {
+        "pressed" => Some(ElementState::Pressed),
+        "released" => Some(ElementState::Released),
+        _ => None
+    }
+}
+
+macro_rules! key_names {
+    ($($key:ident),*) => {
+        /// Keys are recorded by their variant name, which stays valid when winit reorders them.
+        fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+            match name {
+                $(stringify!($key) => Some(VirtualKeyCode::$key),)*
+                _ => None
+            }
+        }
+    };
+}
+
+key_names! {
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, A, B, C, D, E, F, G, H, I, J, K, L,
+    M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11,
+    F12, F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, Snapshot, Scroll, Pause,
+    Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down, Back, Return, Space,
+    Compose, Caret, Numlock, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6,
+    Numpad7, Numpad8, Numpad9, AbntC1, AbntC2, Add, Apostrophe, Apps, At, Ax, Backslash,
+    Calculator, Capital, Colon, Comma, Convert, Decimal, Divide, Equals, Grave, Kana, Kanji, LAlt,
+    LBracket, LControl, LShift, LWin, Mail, MediaSelect, MediaStop, Minus, Multiply, Mute,
+    MyComputer, NavigateForward, NavigateBackward, NextTrack, NoConvert, NumpadComma, NumpadEnter,
+    NumpadEquals, OEM102, Period, PlayPause, Power, PrevTrack, RAlt, RBracket, RControl, RShift,
+    RWin, Semicolon, Slash, Sleep, Stop, Subtract, Sysrq, Tab, Underline, Unlabeled, VolumeDown,
+    VolumeUp, Wake, WebBack, WebFavorites, WebForward, WebHome, WebRefresh, WebSearch, WebStop,
+    Yen, Copy, Paste, Cut
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordedEvent {
+    pub time: Duration,
+    pub event: InputEvent
+}
+
+/// Collects input events with their offset from the start of the recording.
+pub struct InputRecorder {
+    started: Instant,
+    events: Vec<RecordedEvent>
+}
+
+impl InputRecorder {
+    pub fn new() -> InputRecorder {
+        InputRecorder {
+            started: Instant::now(),
+            events: Vec::new()
+        }
+    }
+
+    pub fn record<E>(&mut self, event: E) where E: Into<InputEvent> {
+        self.events.push(RecordedEvent {
+            time: self.started.elapsed(),
+            event: event.into()
+        });
+    }
+
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    pub fn save<P>(&self, path: P) -> io::Result<()> where P: AsRef<Path> {
+        let mut out = BufWriter::new(File::create(path)?);
+        for recorded in &self.events {
+            write!(out, "{} ", recorded.time.as_micros())?;
+            recorded.event.write(&mut out)?;
+            writeln!(out)?;
+        }
+        out.flush()
+    }
+}
+
+/// Replays a recording against a `Widgets` tree through its public handlers, the display of
+/// a `Headless` context is enough to drive them without showing a window.
+pub struct Playback {
+    events: Vec<RecordedEvent>,
+    position: usize
+}
+
+impl Playback {
+    pub fn new(events: Vec<RecordedEvent>) -> Playback {
+        Playback { events, position: 0 }
+    }
+
+    pub fn load<P>(path: P) -> io::Result<Playback> where P: AsRef<Path> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut events = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            if parts.is_empty() {
+                continue;
+            }
+            let event = parts[0].parse::<u64>().ok()
+                .and_then(|time| Some(RecordedEvent {
+                    time: Duration::from_micros(time),
+                    event: InputEvent::parse(&parts[1..])?
+                }))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Bad input event at line {}", i + 1)))?;
+            events.push(event);
+        }
+        Ok(Playback::new(events))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.events.len()
+    }
+
+    /// Dispatches every event recorded up to `time` that was not dispatched yet.
+    pub fn advance<S>(&mut self, display: &Display, widgets: &mut Widgets<S>, time: Duration) -> Vec<WidgetEvent> where S: Surface {
+        let mut emitted = Vec::new();
+        while let Some(recorded) = self.events.get(self.position) {
+            if recorded.time > time {
+                break;
+            }
+            emitted.extend(recorded.event.dispatch(display, widgets));
+            self.position += 1;
+        }
+        emitted
+    }
+
+    pub fn replay<S>(&mut self, display: &Display, widgets: &mut Widgets<S>) -> Vec<WidgetEvent> where S: Surface {
+        self.advance(display, widgets, Duration::from_secs(u64::max_value()))
+    }
+
+    pub fn expect<S>(&mut self, display: &Display, widgets: &mut Widgets<S>, expected: &[WidgetEvent]) where S: Surface {
+        let emitted = self.replay(display, widgets);
+        assert_eq!(emitted.as_slice(), expected, "Replayed widget events differ from expected");
+    }
+}
@@ -0,0 +1,109 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use glium::Display;
+use glium::backend::glutin::DisplayCreationError;
+use glium::backend::glutin::glutin::{ContextBuilder, GlRequest, GlProfile, Api};
+use glium::framebuffer::{SimpleFrameBuffer, DepthStencilRenderBuffer};
+use glium::texture::{Texture2d, UncompressedFloatFormat, MipmapsOption, DepthStencilFormat};
+use image::RgbaImage;
+use winit::event_loop::EventLoop;
+use winit::window::WindowBuilder;
+use winit::dpi::LogicalSize;
+
+use crate::shader::ShaderManager;
+use crate::font::FontManager;
+use crate::texture::TextureManager;
+use crate::render::{Canvas, GeometryBuffers, image_from_rows};
+use crate::window::new_loop;
+use crate::error::Result;
+
+/// Off-screen rendering context for screenshot tests.
+///
+/// `Canvas` and the resource managers are bound to `glium::Display`, so the context is backed
+/// by a window that is never shown. Everything is drawn into a texture and read back as an image.
+pub struct Headless {
+    display: Display,
+    shaders: Rc<RefCell<ShaderManager>>,
+    fonts: Rc<RefCell<FontManager>>,
+    textures: Rc<RefCell<TextureManager>>,
+    buffers: Rc<RefCell<GeometryBuffers>>,
+    color: Texture2d,
+    depth: DepthStencilRenderBuffer,
+    _event_loop: EventLoop<()>
+}
+
+impl Headless {
+    pub fn new(width: u32, height: u32) -> std::result::Result<Headless, DisplayCreationError> {
+        let event_loop = new_loop();
+        let wb = WindowBuilder::new()
+            .with_visible(false)
+            .with_inner_size(LogicalSize::new(width, height));
+        let cb = ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGl, (2, 1)))
+            .with_gl_profile(GlProfile::Compatibility)
+            .with_depth_buffer(24)
+            .with_stencil_buffer(8)
+            .with_srgb(false);
+
+        let display = Display::new(wb, cb, &event_loop)?;
+
+        // the canvas works in logical units, so the target is sized to match them exactly
+        let factor = display.gl_window().window().scale_factor();
+        let w = (width as f64 * factor).round() as u32;
+        let h = (height as f64 * factor).round() as u32;
+
+        let color = Texture2d::empty_with_format(&display, UncompressedFloatFormat::U8U8U8U8,
+                                                 MipmapsOption::NoMipmap, w, h)
+            .expect("Headless color texture allocation failed");
+        let depth = DepthStencilRenderBuffer::new(&display, DepthStencilFormat::I24I8, w, h)
+            .expect("Headless depth buffer allocation failed");
+
+        let mut fonts = FontManager::new(&display);
+        fonts.set_scale_factor(factor as f32);
+
+        Ok(Headless {
+            shaders: Rc::new(RefCell::new(ShaderManager::new(&display))),
+            fonts: Rc::new(RefCell::new(fonts)),
+            textures: Rc::new(RefCell::new(TextureManager::new(&display))),
+            buffers: Rc::new(RefCell::new(GeometryBuffers::new())),
+            _event_loop: event_loop,
+            display,
+            color,
+            depth
+        })
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn shaders(&self) -> Rc<RefCell<ShaderManager>> {
+        self.shaders.clone()
+    }
+
+    pub fn fonts(&self) -> Rc<RefCell<FontManager>> {
+        self.fonts.clone()
+    }
+
+    pub fn textures(&self) -> Rc<RefCell<TextureManager>> {
+        self.textures.clone()
+    }
+
+    pub fn render<'a, F>(&'a self, draw: F) -> Result<RgbaImage>
+        where F: FnOnce(&mut Canvas<SimpleFrameBuffer<'a>>) -> Result<()> {
+
+        let target = SimpleFrameBuffer::with_depth_stencil_buffer(&self.display, &self.color, &self.depth)?;
+        let mut canvas = Canvas::with_buffers(
+            self.display.clone(), self.shaders.clone(), self.fonts.clone(), self.textures.clone(),
+            self.buffers.clone(), target
+        );
+        draw(&mut canvas)?;
+        canvas.into_inner()?;
+        Ok(self.read_image())
+    }
+
+    pub fn read_image(&self) -> RgbaImage {
+        image_from_rows(self.color.read())
+    }
+}
@@ -0,0 +1,136 @@
+//! Interned names of resources and widgets.
+//!
+//! Every distinct string is stored once for the lifetime of the process, so ids are copied,
+//! compared and hashed as a pointer and their text is read without locking.
+//!
+//! Interned strings are never freed: memory grows with the number of distinct names, not with
+//! how often they are used. Ids are meant for a fixed set of resource and widget names, text
+//! from users or names with counters and timestamps in them should stay plain `String`s.
+//! `interned_count` tells how many strings are held.
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+static REGISTRY: Mutex<Option<HashSet<&'static str>>> = Mutex::new(None);
+
+/// The single stored copy of `name`.
+fn intern(name: &str) -> &'static str {
+    let mut registry = REGISTRY.lock().expect("Intern registry poisoned");
+    let registry = registry.get_or_insert_with(HashSet::new);
+    match registry.get(name) {
+        Some(interned) => *interned,
+        None => {
+            let interned: &'static str = Box::leak(name.to_owned().into_boxed_str());
+            registry.insert(interned);
+            interned
+        }
+    }
+}
+
+/// Number of distinct strings interned so far.
+pub fn interned_count() -> usize {
+    REGISTRY.lock().expect("Intern registry poisoned").as_ref().map_or(0, |registry| registry.len())
+}
+
+macro_rules! interned_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone)]
+        pub struct $name(&'static str);
+
+        impl $name {
+            pub fn new<N>(name: N) -> $name where N: AsRef<str> {
+                $name(intern(name.as_ref()))
+            }
+
+            pub fn as_str(&self) -> &'static str {
+                self.0
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                std::ptr::eq(self.0, other.0)
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                (self.0.as_ptr() as usize).hash(state);
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl<'a> PartialEq<&'a str> for $name {
+            fn eq(&self, other: &&'a str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                self.0
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(self.0, f)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(name: &str) -> $name {
+                $name::new(name)
+            }
+        }
+
+        impl From<&String> for $name {
+            fn from(name: &String) -> $name {
+                $name::new(name)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(name: String) -> $name {
+                $name::new(name)
+            }
+        }
+
+        impl From<&$name> for $name {
+            fn from(id: &$name) -> $name {
+                *id
+            }
+        }
+    };
+}
+
+interned_id! {
+    /// Name of a texture or other resource, cheap to keep in widgets and compare every frame.
+    ResourceId
+}
+
+interned_id! {
+    /// Name a widget reports in its events.
+    WidgetId
+}
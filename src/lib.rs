@@ -1,9 +1,43 @@
+pub mod error;
+pub mod backend;
+pub mod intern;
 pub mod window;
 pub mod shader;
 pub mod texture;
+pub mod loader;
+pub mod atlas;
+pub mod animated;
+pub mod compressed;
+pub mod streaming;
+pub mod procedural;
 pub mod font;
+pub mod glyph_cache;
 pub mod render;
+pub mod postprocess;
+pub mod overlay;
+pub mod path;
+pub mod picture;
 pub mod ui;
+pub mod gesture;
+pub mod theme;
+pub mod clipboard;
+pub mod harness;
+pub mod context;
+pub mod panel;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+#[cfg(feature = "headless")]
+pub mod headless;
+#[cfg(feature = "system-fonts")]
+pub mod system_fonts;
+#[cfg(feature = "svg")]
+pub mod svg;
+#[cfg(feature = "dialogs")]
+pub mod dialog;
+#[cfg(feature = "tray")]
+pub mod tray;
+#[cfg(feature = "async")]
+pub mod tasks;
 
 pub use image;
 pub use cgmath;
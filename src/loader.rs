@@ -0,0 +1,246 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use image::DynamicImage;
+
+use crate::font::{self, FontManager};
+use crate::shader::{ShaderManager, ShaderError};
+use crate::texture::{TextureManager, TextureError};
+
+const MAX_WORKERS: usize = 4;
+
+#[derive(Debug, Clone)]
+enum ManifestEntry {
+    Texture { name: String, path: PathBuf },
+    Font { name: String, bold: bool, italic: bool, path: PathBuf },
+    Shader { name: String, vertex: PathBuf, fragment: PathBuf, geometry: Option<PathBuf> }
+}
+
+impl ManifestEntry {
+    fn name(&self) -> &str {
+        match self {
+            ManifestEntry::Texture { name, .. } | ManifestEntry::Font { name, .. } | ManifestEntry::Shader { name, .. } => name
+        }
+    }
+}
+
+/// Resources loaded by a `ResourceLoader`, in the order they are listed.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceManifest {
+    entries: Vec<ManifestEntry>
+}
+
+impl ResourceManifest {
+    pub fn new() -> ResourceManifest {
+        ResourceManifest::default()
+    }
+
+    pub fn texture<N, P>(mut self, name: N, path: P) -> Self where N: Into<String>, P: Into<PathBuf> {
+        self.entries.push(ManifestEntry::Texture { name: name.into(), path: path.into() });
+        self
+    }
+
+    pub fn font<N, P>(self, name: N, path: P) -> Self where N: Into<String>, P: Into<PathBuf> {
+        self.font_face(name, false, false, path)
+    }
+
+    pub fn font_face<N, P>(mut self, name: N, bold: bool, italic: bool, path: P) -> Self where N: Into<String>, P: Into<PathBuf> {
+        self.entries.push(ManifestEntry::Font { name: name.into(), bold, italic, path: path.into() });
+        self
+    }
+
+    /// Shaders are compiled from their files on the main thread and watched like `ShaderManager::register_files`.
+    pub fn shader<N, P>(mut self, name: N, vertex: P, fragment: P, geometry: Option<P>) -> Self
+        where N: Into<String>, P: Into<PathBuf> {
+
+        self.entries.push(ManifestEntry::Shader {
+            name: name.into(),
+            vertex: vertex.into(),
+            fragment: fragment.into(),
+            geometry: geometry.map(Into::into)
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub enum ResourceError {
+    Texture(String, TextureError),
+    Font(String, font::Error),
+    Shader(String, ShaderError)
+}
+
+impl ResourceError {
+    /// Name the resource was listed under
+    pub fn name(&self) -> &str {
+        match self {
+            ResourceError::Texture(name, _) | ResourceError::Font(name, _) | ResourceError::Shader(name, _) => name
+        }
+    }
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResourceError::Texture(name, e) => write!(f, "Loading texture {} failed: {}", name, e),
+            ResourceError::Font(name, e) => write!(f, "Loading font {} failed: {:?}", name, e),
+            ResourceError::Shader(name, e) => write!(f, "Loading shader {} failed: {}", name, e)
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {}
+
+/// How far a `ResourceLoader` got.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+    /// Name of the resource that finished last
+    pub current: Option<String>,
+    pub failed: usize
+}
+
+impl LoadProgress {
+    /// Share of the manifest done, between 0 and 1
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+
+    pub fn percent(&self) -> u32 {
+        (self.fraction() * 100.0).round() as u32
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.loaded >= self.total
+    }
+}
+
+/// File contents read by a worker, turned into GPU objects on the main thread.
+enum Decoded {
+    Image(Result<DynamicImage, TextureError>),
+    Font(std::io::Result<Vec<u8>>),
+    /// Read on the main thread with its includes
+    Shader
+}
+
+/// Reads and decodes the files of a manifest on worker threads, while `poll` uploads what they
+/// finished from the thread owning the display, so the window keeps drawing a loading screen.
+pub struct ResourceLoader {
+    entries: Vec<ManifestEntry>,
+    decoded: Receiver<(usize, Decoded)>,
+    progress: LoadProgress,
+    errors: Vec<ResourceError>,
+    on_progress: Option<Box<dyn FnMut(&LoadProgress)>>
+}
+
+impl ResourceLoader {
+    pub fn start(manifest: ResourceManifest) -> ResourceLoader {
+        let entries = manifest.entries;
+        let (sender, decoded) = channel();
+        let queue = Arc::new(Mutex::new(entries.iter().cloned().enumerate().collect::<VecDeque<_>>()));
+        for _ in 0..entries.len().min(MAX_WORKERS) {
+            let queue = queue.clone();
+            let sender = sender.clone();
+            thread::spawn(move || {
+                loop {
+                    let next = queue.lock().expect("Resource queue poisoned").pop_front();
+                    let (index, entry) = match next {
+                        Some(next) => next,
+                        None => break
+                    };
+                    let decoded = match entry {
+                        ManifestEntry::Texture { path, .. } => Decoded::Image(std::fs::read(&path)
+                            .map_err(TextureError::from)
+                            .and_then(|bytes| image::load_from_memory(&bytes).map_err(TextureError::from))),
+                        ManifestEntry::Font { path, .. } => Decoded::Font(std::fs::read(&path)),
+                        ManifestEntry::Shader { .. } => Decoded::Shader
+                    };
+                    // the loader may be dropped before it finishes
+                    if sender.send((index, decoded)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        let progress = LoadProgress { total: entries.len(), .. Default::default() };
+        ResourceLoader { entries, decoded, progress, errors: Vec::new(), on_progress: None }
+    }
+
+    /// Called by `poll` after every resource that finished, e.g. to update a `LoadingScreen`.
+    pub fn on_progress<F>(mut self, callback: F) -> Self where F: FnMut(&LoadProgress) + 'static {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    pub fn progress(&self) -> &LoadProgress {
+        &self.progress
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.progress.is_finished()
+    }
+
+    /// Resources that failed so far, the others are loaded regardless.
+    pub fn errors(&self) -> &[ResourceError] {
+        &self.errors
+    }
+
+    pub fn take_errors(&mut self) -> Vec<ResourceError> {
+        std::mem::replace(&mut self.errors, Vec::new())
+    }
+
+    /// Uploads the resources decoded since the last call, must be called on the thread owning the display.
+    /// Returns whether any finished.
+    pub fn poll(&mut self, shaders: &Rc<RefCell<ShaderManager>>, fonts: &Rc<RefCell<FontManager>>,
+                textures: &Rc<RefCell<TextureManager>>) -> bool {
+
+        let mut any = false;
+        while let Ok((index, decoded)) = self.decoded.try_recv() {
+            let error = match (&self.entries[index], decoded) {
+                (ManifestEntry::Texture { name, .. }, Decoded::Image(image)) => image
+                    .and_then(|image| textures.borrow_mut().insert_image(name.clone(), image).map(|_| ()))
+                    .err()
+                    .map(|e| ResourceError::Texture(name.clone(), e)),
+                (ManifestEntry::Font { name, bold, italic, .. }, Decoded::Font(bytes)) => bytes
+                    .map_err(font::Error::from)
+                    .and_then(|bytes| fonts.borrow_mut().load_font_face_bytes(name.clone(), *bold, *italic, bytes))
+                    .err()
+                    .map(|e| ResourceError::Font(name.clone(), e)),
+                (ManifestEntry::Shader { name, vertex, fragment, geometry }, _) => shaders.borrow_mut()
+                    .register_files(name.clone(), vertex.clone(), fragment.clone(), geometry.clone())
+                    .err()
+                    .map(|e| ResourceError::Shader(name.clone(), e)),
+                _ => unreachable!("Decoded resource does not match its manifest entry")
+            };
+            if let Some(error) = error {
+                self.progress.failed += 1;
+                self.errors.push(error);
+            }
+            self.progress.loaded += 1;
+            self.progress.current = Some(self.entries[index].name().to_owned());
+            if let Some(callback) = self.on_progress.as_mut() {
+                callback(&self.progress);
+            }
+            any = true;
+        }
+        any
+    }
+}
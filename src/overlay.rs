@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use glium::Surface;
+
+use crate::render::{Canvas, FrameStats};
+use crate::font::{FontParameters, TextAlignHorizontal};
+use crate::error::Result;
+
+// frames kept for the graph and the percentiles
+const HISTORY: usize = 120;
+const MARGIN: f32 = 8.0;
+const PADDING: f32 = 6.0;
+const WIDTH: f32 = HISTORY as f32 * 2.0;
+const LINE_HEIGHT: f32 = 14.0;
+const GRAPH_HEIGHT: f32 = 48.0;
+// frame time at the top of the graph, 30 frames per second
+const GRAPH_SCALE: f32 = 1.0 / 30.0;
+
+/// Frame timing and resource usage drawn over everything else by `Window::show`,
+/// see `WindowConfig::perf_overlay`.
+pub struct PerfOverlay {
+    visible: bool,
+    frames: VecDeque<FrameStats>
+}
+
+impl PerfOverlay {
+    pub fn new(visible: bool) -> PerfOverlay {
+        PerfOverlay { visible, frames: VecDeque::with_capacity(HISTORY) }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn record(&mut self, stats: FrameStats) {
+        if self.frames.len() == HISTORY {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(stats);
+    }
+
+    /// Frame time that `percentile` (between 0 and 1) of the recorded frames took at most.
+    pub fn frame_time_percentile(&self, percentile: f32) -> Duration {
+        let mut times = self.frames.iter().map(|frame| frame.frame_time).collect::<Vec<_>>();
+        if times.is_empty() {
+            return Duration::from_secs(0);
+        }
+        times.sort();
+        let index = ((times.len() - 1) as f32 * percentile.max(0.0).min(1.0)).round() as usize;
+        times[index]
+    }
+
+    pub fn draw<S>(&self, canvas: &mut Canvas<S>) -> Result<()> where S: Surface {
+        let last = self.frames.back().cloned().unwrap_or_default();
+        let textures = canvas.textures().borrow().stats();
+        let glyphs = canvas.fonts().borrow().glyph_cache().stats();
+        let ms = |time: Duration| time.as_secs_f32() * 1000.0;
+        let mib = |bytes: usize| bytes as f32 / (1024.0 * 1024.0);
+        let lines = [
+            format!("{:.1} fps  {:.2} ms  cpu {:.2} ms  gpu {}  finish {:.2} ms",
+                    last.fps, ms(last.frame_time), ms(last.cpu_time),
+                    last.gpu_time.map_or("-".to_owned(), |time| format!("{:.2} ms", ms(time))), ms(last.finish_time)),
+            format!("p50 {:.2} ms  p95 {:.2} ms  p99 {:.2} ms",
+                    ms(self.frame_time_percentile(0.5)), ms(self.frame_time_percentile(0.95)),
+                    ms(self.frame_time_percentile(0.99))),
+            format!("{} draws  {} vertices  {} binds", last.draw_calls, last.vertices, last.texture_binds),
+            format!("{} textures  {:.1} MiB", textures.textures.len(), mib(textures.memory)),
+            format!("{} glyphs  {:.1} MiB  {:.0}% used", glyphs.glyphs, mib(glyphs.memory), glyphs.occupancy * 100.0)
+        ];
+
+        let height = PADDING * 3.0 + GRAPH_HEIGHT + lines.len() as f32 * LINE_HEIGHT;
+        canvas.queue_rect([MARGIN, MARGIN, WIDTH + PADDING * 2.0, height], [0.0, 0.0, 0.0, 0.7]);
+
+        let (left, bottom) = (MARGIN + PADDING, MARGIN + PADDING + GRAPH_HEIGHT);
+        let bar = WIDTH / HISTORY as f32;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let time = frame.frame_time.as_secs_f32();
+            let h = (time / GRAPH_SCALE).min(1.0) * GRAPH_HEIGHT;
+            let color = if time <= 1.0 / 58.0 {
+                [0.3, 0.9, 0.3, 1.0]
+            } else if time <= 1.0 / 29.0 {
+                [0.9, 0.8, 0.2, 1.0]
+            } else {
+                [0.9, 0.3, 0.2, 1.0]
+            };
+            canvas.queue_rect([left + i as f32 * bar, bottom - h, bar, h], color);
+        }
+        // 60 frames per second
+        let target = bottom - (1.0 / 60.0) / GRAPH_SCALE * GRAPH_HEIGHT;
+        canvas.queue_rect([left, target, WIDTH, 1.0], [1.0, 1.0, 1.0, 0.4]);
+
+        let params = FontParameters {
+            size: 11,
+            color: [1.0, 1.0, 1.0, 1.0],
+            align_horizontal: TextAlignHorizontal::Left,
+            .. Default::default()
+        };
+        for (i, line) in lines.iter().enumerate() {
+            canvas.text(line, left, bottom + PADDING + i as f32 * LINE_HEIGHT, &params)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use std::rc::Rc;
+use glium::{Display, Surface, Program, DrawParameters, DrawError, Rect, BlitTarget, BlitMask};
+use glium::framebuffer::{SimpleFrameBuffer, MultiOutputFrameBuffer, DepthStencilRenderBuffer};
+use glium::texture::SrgbTexture2d;
+use glium::uniforms::{Uniforms, MagnifySamplerFilter};
+use glium::index::IndicesSource;
+use glium::vertex::MultiVerticesSource;
+use winit::event::{MouseButton, ElementState, MouseScrollDelta};
+
+use crate::error::Result;
+use crate::render::Canvas;
+use crate::texture::{TextureManager, TextureError};
+use crate::ui::{Widgets, WidgetEvent};
+
+/// Render target texture as a surface. Unlike a `SimpleFrameBuffer` it owns the texture, so a
+/// `Widgets` tree can be kept for it between frames.
+pub struct TextureSurface {
+    display: Display,
+    texture: Rc<Box<SrgbTexture2d>>,
+    depth: Rc<DepthStencilRenderBuffer>
+}
+
+impl TextureSurface {
+    /// Panics if `name` was not made by `TextureManager::create_render_target`.
+    pub fn new(textures: &TextureManager, name: &str) -> TextureSurface {
+        let (texture, depth) = textures.render_target(name)
+            .unwrap_or_else(|| panic!("Missing render target: {}", name));
+        TextureSurface { display: textures.display.clone(), texture, depth }
+    }
+
+    fn framebuffer(&self) -> SimpleFrameBuffer<'_> {
+        // framebuffer objects are cached by the context, so making one per call is cheap
+        SimpleFrameBuffer::with_depth_stencil_buffer(&self.display, &**self.texture, &*self.depth)
+            .expect("Render target framebuffer creation failed")
+    }
+}
+
+impl Surface for TextureSurface {
+    fn clear(&mut self, rect: Option<&Rect>, color: Option<(f32, f32, f32, f32)>, color_srgb: bool,
+             depth: Option<f32>, stencil: Option<i32>) {
+        self.framebuffer().clear(rect, color, color_srgb, depth, stencil);
+    }
+
+    fn get_dimensions(&self) -> (u32, u32) {
+        self.texture.dimensions()
+    }
+
+    fn get_depth_buffer_bits(&self) -> Option<u16> {
+        self.framebuffer().get_depth_buffer_bits()
+    }
+
+    fn get_stencil_buffer_bits(&self) -> Option<u16> {
+        self.framebuffer().get_stencil_buffer_bits()
+    }
+
+    fn draw<'a, 'b, V, I, U>(&mut self, vertices: V, indices: I, program: &Program, uniforms: &U,
+                             draw_parameters: &DrawParameters<'_>) -> std::result::Result<(), DrawError>
+        where V: MultiVerticesSource<'b>, I: Into<IndicesSource<'a>>, U: Uniforms {
+
+        self.framebuffer().draw(vertices, indices, program, uniforms, draw_parameters)
+    }
+
+    fn blit_buffers_from_frame(&self, source_rect: &Rect, target_rect: &BlitTarget,
+                               filter: MagnifySamplerFilter, mask: BlitMask) {
+        self.framebuffer().blit_buffers_from_frame(source_rect, target_rect, filter, mask)
+    }
+
+    fn blit_buffers_from_simple_framebuffer(&self, source: &SimpleFrameBuffer<'_>, source_rect: &Rect,
+                                            target_rect: &BlitTarget, filter: MagnifySamplerFilter, mask: BlitMask) {
+        self.framebuffer().blit_buffers_from_simple_framebuffer(source, source_rect, target_rect, filter, mask)
+    }
+
+    fn blit_buffers_from_multioutput_framebuffer(&self, source: &MultiOutputFrameBuffer<'_>, source_rect: &Rect,
+                                                 target_rect: &BlitTarget, filter: MagnifySamplerFilter, mask: BlitMask) {
+        self.framebuffer().blit_buffers_from_multioutput_framebuffer(source, source_rect, target_rect, filter, mask)
+    }
+
+    fn blit_color<S>(&self, source_rect: &Rect, target: &S, target_rect: &BlitTarget,
+                     filter: MagnifySamplerFilter) where S: Surface {
+        self.framebuffer().blit_color(source_rect, target, target_rect, filter)
+    }
+}
+
+/// Widgets drawn into a texture, for interactive UI on surfaces of a 3D scene such as screens or VR panels.
+///
+/// Every frame the panel is drawn with `draw` before the scene samples its texture. Pointer input
+/// comes from the application, which intersects its pointer ray with the surface and passes the
+/// texture coordinates of the hit to `pointer_move`, with `u` growing to the right and `v` upwards
+/// as sampled in GL. Keys go to `widgets_mut` directly while the panel has focus.
+pub struct UiPanel {
+    texture: String,
+    size: (f32, f32),
+    surface: Option<TextureSurface>,
+    widgets: Widgets<TextureSurface>,
+    /// Pointer in panel pixels, `None` while it is off the panel
+    pointer: Option<(f32, f32)>,
+    clear_color: [f32; 4]
+}
+
+impl UiPanel {
+    /// Creates a render target of `width` by `height` logical pixels registered as `name`, for the scene to sample.
+    pub fn new<N>(textures: &mut TextureManager, name: N, width: u32, height: u32) -> std::result::Result<UiPanel, TextureError>
+        where N: Into<String> {
+
+        let name = name.into();
+        textures.create_render_target(name.clone(), width, height)?;
+        Ok(UiPanel {
+            surface: Some(TextureSurface::new(textures, &name)),
+            texture: name,
+            size: (width as f32, height as f32),
+            widgets: Widgets::new(),
+            pointer: None,
+            clear_color: [0.0, 0.0, 0.0, 0.0]
+        })
+    }
+
+    /// Name of the texture the panel is drawn into.
+    pub fn texture(&self) -> &str {
+        &self.texture
+    }
+
+    /// Size in logical pixels, the space widget bounds are given in.
+    pub fn size(&self) -> (f32, f32) {
+        self.size
+    }
+
+    pub fn widgets(&self) -> &Widgets<TextureSurface> {
+        &self.widgets
+    }
+
+    pub fn widgets_mut(&mut self) -> &mut Widgets<TextureSurface> {
+        &mut self.widgets
+    }
+
+    /// Color the texture is cleared to before the widgets are drawn, transparent by default.
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.clear_color = color;
+    }
+
+    pub fn pointer(&self) -> Option<(f32, f32)> {
+        self.pointer
+    }
+
+    /// Panel pixels of GL texture coordinates.
+    pub fn uv_to_panel(&self, uv: (f32, f32)) -> (f32, f32) {
+        (uv.0 * self.size.0, (1.0 - uv.1) * self.size.1)
+    }
+
+    /// The pointer ray hit the panel at `uv`.
+    pub fn pointer_move(&mut self, display: &Display, uv: (f32, f32)) -> Vec<WidgetEvent> {
+        let pos = self.uv_to_panel(uv);
+        self.pointer = Some(pos);
+        self.widgets.on_mouse_move(display, pos)
+    }
+
+    /// The pointer ray left the panel, hovered widgets are left as if the mouse moved away.
+    pub fn pointer_leave(&mut self, display: &Display) -> Vec<WidgetEvent> {
+        if self.pointer.take().is_none() {
+            return vec![]
+        }
+        self.widgets.on_mouse_move(display, (-1.0, -1.0))
+    }
+
+    /// A button was pressed or released, at the last position given to `pointer_move`.
+    pub fn pointer_button(&mut self, display: &Display, button: MouseButton, state: ElementState) -> Vec<WidgetEvent> {
+        match self.pointer {
+            Some(pos) => self.widgets.on_mouse_button(display, button, state, pos),
+            // releases still reach the widgets so drags end off the panel
+            None if state == ElementState::Released => self.widgets.on_mouse_button(display, button, state, (-1.0, -1.0)),
+            None => vec![]
+        }
+    }
+
+    pub fn pointer_wheel(&mut self, display: &Display, delta: MouseScrollDelta) -> Vec<WidgetEvent> {
+        match self.pointer {
+            Some(_) => self.widgets.on_mouse_wheel(display, delta),
+            None => vec![]
+        }
+    }
+
+    /// Draws the widgets into the texture with the resources of `canvas`, before the scene samples it.
+    pub fn draw<S>(&mut self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> where S: Surface {
+        let surface = self.surface.take().expect("Panel surface missing");
+        let pointer = self.pointer.unwrap_or((-1.0, -1.0));
+        self.widgets.update(pointer, partial_ticks);
+        let widgets = &self.widgets;
+        let [r, g, b, a] = self.clear_color;
+        let result = canvas.render_to_surface(surface, |canvas| {
+            canvas.clear((r, g, b, a), 1.0)?;
+            widgets.draw(canvas, partial_ticks)
+        });
+        match result {
+            Ok(surface) => {
+                self.surface = Some(surface);
+                Ok(())
+            }
+            Err(e) => {
+                // the surface is lost with a failed draw, it is made again for the next one
+                self.surface = Some(TextureSurface::new(&canvas.textures().borrow(), &self.texture));
+                Err(e)
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineJoin {
+    Miter, Bevel, Round
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineCap {
+    Butt, Square, Round
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineStyle {
+    pub join: LineJoin,
+    pub cap: LineCap,
+    pub closed: bool,
+    /// Miters longer than `miter_limit * width / 2` fall back to bevel joins
+    pub miter_limit: f32
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        LineStyle {
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            closed: false,
+            miter_limit: 4.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubPath {
+    pub points: Vec<[f32; 2]>,
+    pub closed: bool
+}
+
+/// Vector path built from lines and bezier curves, curves are flattened as they are added.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    subpaths: Vec<SubPath>
+}
+
+impl Path {
+    pub fn new() -> Path {
+        Path { subpaths: Vec::new() }
+    }
+
+    pub fn subpaths(&self) -> &[SubPath] {
+        &self.subpaths
+    }
+
+    pub fn move_to(mut self, x: f32, y: f32) -> Self {
+        self.subpaths.push(SubPath { points: vec![[x, y]], closed: false });
+        self
+    }
+
+    pub fn line_to(mut self, x: f32, y: f32) -> Self {
+        self.current().points.push([x, y]);
+        self
+    }
+
+    pub fn quad_to(mut self, cx: f32, cy: f32, x: f32, y: f32) -> Self {
+        let current = self.current();
+        let p0 = *current.points.last().unwrap();
+        let (c, p1) = ([cx, cy], [x, y]);
+        let steps = curve_steps(length(sub(c, p0)) + length(sub(p1, c)));
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let u = 1.0 - t;
+            current.points.push([
+                u * u * p0[0] + 2.0 * u * t * c[0] + t * t * p1[0],
+                u * u * p0[1] + 2.0 * u * t * c[1] + t * t * p1[1]
+            ]);
+        }
+        self
+    }
+
+    pub fn cubic_to(mut self, c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32) -> Self {
+        let current = self.current();
+        let p0 = *current.points.last().unwrap();
+        let (c1, c2, p1) = ([c1x, c1y], [c2x, c2y], [x, y]);
+        let steps = curve_steps(length(sub(c1, p0)) + length(sub(c2, c1)) + length(sub(p1, c2)));
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            current.points.push([
+                a * p0[0] + b * c1[0] + c * c2[0] + d * p1[0],
+                a * p0[1] + b * c1[1] + c * c2[1] + d * p1[1]
+            ]);
+        }
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        if let Some(sub) = self.subpaths.last_mut() {
+            sub.closed = true;
+        }
+        self
+    }
+
+    fn current(&mut self) -> &mut SubPath {
+        if self.subpaths.last().map_or(true, |sub| sub.closed) {
+            let start = self.subpaths.last().map_or([0.0, 0.0], |sub| sub.points[0]);
+            self.subpaths.push(SubPath { points: vec![start], closed: false });
+        }
+        self.subpaths.last_mut().unwrap()
+    }
+}
+
+fn curve_steps(length: f32) -> usize {
+    ((length / 4.0).ceil() as usize).max(4).min(64)
+}
+
+/// Triangulates every sub path as a simple polygon using ear clipping.
+pub fn fill_path(path: &Path) -> Vec<[f32; 2]> {
+    let mut triangles = Vec::new();
+    for sub in path.subpaths() {
+        triangulate(&sub.points, &mut triangles);
+    }
+    triangles
+}
+
+pub fn stroke_path(path: &Path, width: f32, style: &LineStyle) -> Vec<[f32; 2]> {
+    let mut triangles = Vec::new();
+    for sub in path.subpaths() {
+        let style = LineStyle { closed: sub.closed, .. *style };
+        triangles.extend(stroke_polyline(&sub.points, width, &style));
+    }
+    triangles
+}
+
+fn triangulate(points: &[[f32; 2]], triangles: &mut Vec<[f32; 2]>) {
+    let mut polygon = points.to_vec();
+    polygon.dedup();
+    if polygon.len() > 1 && polygon.first() == polygon.last() {
+        polygon.pop();
+    }
+    if polygon.len() < 3 {
+        return;
+    }
+    let area: f32 = (0..polygon.len())
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum();
+    if area < 0.0 {
+        polygon.reverse();
+    }
+
+    let mut indices = (0..polygon.len()).collect::<Vec<_>>();
+    while indices.len() > 3 {
+        let n = indices.len();
+        let mut clipped = false;
+        for i in 0..n {
+            let (ia, ib, ic) = (indices[(i + n - 1) % n], indices[i], indices[(i + 1) % n]);
+            let (a, b, c) = (polygon[ia], polygon[ib], polygon[ic]);
+            if cross(a, b, c) <= 0.0 {
+                continue;
+            }
+            let contains = indices.iter()
+                .filter(|&&j| j != ia && j != ib && j != ic)
+                .any(|&j| in_triangle(polygon[j], a, b, c));
+            if !contains {
+                triangles.extend_from_slice(&[a, b, c]);
+                indices.remove(i);
+                clipped = true;
+                break;
+            }
+        }
+        // self intersecting polygons may have no ears left, drop a vertex to keep going
+        if !clipped {
+            indices.remove(0);
+        }
+    }
+    if indices.len() == 3 {
+        triangles.extend_from_slice(&[polygon[indices[0]], polygon[indices[1]], polygon[indices[2]]]);
+    }
+}
+
+fn cross(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+fn in_triangle(p: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+fn length(a: [f32; 2]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+/// Tessellates a polyline of the given width into a list of triangles.
+pub fn stroke_polyline(points: &[[f32; 2]], width: f32, style: &LineStyle) -> Vec<[f32; 2]> {
+    let mut points = points.to_vec();
+    points.dedup();
+    if style.closed && points.len() > 2 && points.first() == points.last() {
+        points.pop();
+    }
+
+    let mut triangles = Vec::new();
+    let hw = width / 2.0;
+    if points.len() < 2 || hw <= 0.0 {
+        return triangles;
+    }
+
+    let closed = style.closed && points.len() > 2;
+    let segments = if closed { points.len() } else { points.len() - 1 };
+
+    if !closed && style.cap == LineCap::Square {
+        let last = points.len() - 1;
+        let start = direction(points[1], points[0]);
+        let end = direction(points[last - 1], points[last]);
+        points[0] = add(points[0], scale(start, hw));
+        points[last] = add(points[last], scale(end, hw));
+    }
+
+    for i in 0..segments {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        let n = scale(normal(direction(a, b)), hw);
+        push_quad(&mut triangles, add(a, n), sub(a, n), add(b, n), sub(b, n));
+    }
+
+    let joins = if closed { 0..points.len() } else { 1..points.len() - 1 };
+    for i in joins {
+        let prev = points[(i + points.len() - 1) % points.len()];
+        let p = points[i];
+        let next = points[(i + 1) % points.len()];
+        push_join(&mut triangles, prev, p, next, hw, style);
+    }
+
+    if !closed && style.cap == LineCap::Round {
+        let last = points.len() - 1;
+        let start = normal(direction(points[0], points[1]));
+        let end = normal(direction(points[last - 1], points[last]));
+        push_arc(&mut triangles, points[0], hw, angle(start), PI);
+        push_arc(&mut triangles, points[last], hw, angle(end) + PI, PI);
+    }
+
+    triangles
+}
+
+fn push_join(triangles: &mut Vec<[f32; 2]>, prev: [f32; 2], p: [f32; 2], next: [f32; 2], hw: f32, style: &LineStyle) {
+    let da = direction(prev, p);
+    let db = direction(p, next);
+    let cross = da[0] * db[1] - da[1] * db[0];
+    if cross.abs() < 1e-6 {
+        return;
+    }
+    // the join only has to fill the gap on the outer side of the turn
+    let side = if cross > 0.0 { -1.0 } else { 1.0 };
+    let na = scale(normal(da), side);
+    let nb = scale(normal(db), side);
+    let outer_a = add(p, scale(na, hw));
+    let outer_b = add(p, scale(nb, hw));
+
+    match style.join {
+        LineJoin::Bevel => triangles.extend_from_slice(&[p, outer_a, outer_b]),
+        LineJoin::Miter => {
+            let miter = normalize(add(na, nb));
+            let length = hw / dot(miter, na);
+            if length > style.miter_limit * hw {
+                triangles.extend_from_slice(&[p, outer_a, outer_b]);
+            } else {
+                let tip = add(p, scale(miter, length));
+                triangles.extend_from_slice(&[p, outer_a, tip, p, tip, outer_b]);
+            }
+        },
+        LineJoin::Round => {
+            let start = angle(na);
+            let mut sweep = angle(nb) - start;
+            while sweep > PI {
+                sweep -= 2.0 * PI;
+            }
+            while sweep < -PI {
+                sweep += 2.0 * PI;
+            }
+            push_arc(triangles, p, hw, start, sweep);
+        }
+    }
+}
+
+pub(crate) fn push_arc(triangles: &mut Vec<[f32; 2]>, center: [f32; 2], radius: f32, start: f32, sweep: f32) {
+    let steps = ((sweep.abs() / (PI / 8.0)).ceil() as usize).max(2);
+    let point = |a: f32| [center[0] + radius * a.cos(), center[1] + radius * a.sin()];
+    for i in 0..steps {
+        let a0 = start + sweep * i as f32 / steps as f32;
+        let a1 = start + sweep * (i + 1) as f32 / steps as f32;
+        triangles.extend_from_slice(&[center, point(a0), point(a1)]);
+    }
+}
+
+fn push_quad(triangles: &mut Vec<[f32; 2]>, a0: [f32; 2], a1: [f32; 2], b0: [f32; 2], b1: [f32; 2]) {
+    triangles.extend_from_slice(&[a0, a1, b0, b0, a1, b1]);
+}
+
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f32; 2], s: f32) -> [f32; 2] {
+    [a[0] * s, a[1] * s]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn normalize(a: [f32; 2]) -> [f32; 2] {
+    let length = dot(a, a).sqrt();
+    if length > 0.0 { scale(a, 1.0 / length) } else { a }
+}
+
+fn direction(from: [f32; 2], to: [f32; 2]) -> [f32; 2] {
+    normalize(sub(to, from))
+}
+
+fn normal(d: [f32; 2]) -> [f32; 2] {
+    [-d[1], d[0]]
+}
+
+fn angle(v: [f32; 2]) -> f32 {
+    v[1].atan2(v[0])
+}
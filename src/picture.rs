@@ -0,0 +1,379 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+
+use glium::Surface;
+
+use crate::error::Result;
+use crate::font::{FontParameters, TextAlignHorizontal, TextAlignVertical};
+use crate::path::{Path, LineStyle, LineJoin, LineCap};
+use crate::render::{Canvas, PaintOptions};
+use crate::intern::ResourceId;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PictureCommand {
+    Rect { bounds: [f32; 4], color: [f32; 4] },
+    TexturedRect { texture: ResourceId, bounds: [f32; 4], uv: [f32; 4], color: [f32; 4] },
+    RoundedRect { bounds: [f32; 4], radius: f32, color: [f32; 4] },
+    Circle { center: [f32; 2], radius: f32, color: [f32; 4] },
+    FillPolygon { points: Vec<[f32; 2]>, color: [f32; 4] },
+    StrokePolyline { points: Vec<[f32; 2]>, width: f32, style: LineStyle, color: [f32; 4] },
+    Text {
+        text: String, pos: [f32; 2], size: u32, color: [f32; 4], bold: bool, italic: bool,
+        align_horizontal: TextAlignHorizontal, align_vertical: TextAlignVertical
+    },
+    PushClip([f32; 4]),
+    PopClip
+}
+
+/// Uniform scale followed by a translation, applied to a picture when it is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PictureTransform {
+    pub translate: [f32; 2],
+    pub scale: f32
+}
+
+impl Default for PictureTransform {
+    fn default() -> Self {
+        PictureTransform { translate: [0.0, 0.0], scale: 1.0 }
+    }
+}
+
+impl PictureTransform {
+    pub fn new(translate: [f32; 2], scale: f32) -> PictureTransform {
+        PictureTransform { translate, scale }
+    }
+
+    pub fn point(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        [x * self.scale + self.translate[0], y * self.scale + self.translate[1]]
+    }
+
+    pub fn rect(&self, [x, y, w, h]: [f32; 4]) -> [f32; 4] {
+        let [x, y] = self.point([x, y]);
+        [x, y, w * self.scale, h * self.scale]
+    }
+}
+
+/// Device independent recording of drawing commands in logical units.
+///
+/// Unlike a `DisplayList` nothing is tessellated, so a picture can be drawn onto any canvas
+/// at any scale and saved to a file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Picture {
+    commands: Vec<PictureCommand>
+}
+
+impl Picture {
+    pub fn new() -> Picture {
+        Picture { commands: Vec::new() }
+    }
+
+    pub fn commands(&self) -> &[PictureCommand] {
+        &self.commands
+    }
+
+    pub fn push(&mut self, command: PictureCommand) {
+        self.commands.push(command);
+    }
+
+    pub fn rect<B, C>(&mut self, bounds: B, color: C) where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+        self.push(PictureCommand::Rect { bounds: bounds.into(), color: color.into() });
+    }
+
+    pub fn textured_rect<T, B, C>(&mut self, texture: T, bounds: B, uv: [f32; 4], color: C)
+        where T: Into<ResourceId>, B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        self.push(PictureCommand::TexturedRect { texture: texture.into(), bounds: bounds.into(), uv, color: color.into() });
+    }
+
+    pub fn rounded_rect<B, C>(&mut self, bounds: B, radius: f32, color: C) where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+        self.push(PictureCommand::RoundedRect { bounds: bounds.into(), radius, color: color.into() });
+    }
+
+    pub fn circle<C>(&mut self, center: [f32; 2], radius: f32, color: C) where C: Into<[f32; 4]> {
+        self.push(PictureCommand::Circle { center, radius, color: color.into() });
+    }
+
+    pub fn fill_path<C>(&mut self, path: &Path, color: C) where C: Into<[f32; 4]> {
+        let color = color.into();
+        for sub in path.subpaths() {
+            self.push(PictureCommand::FillPolygon { points: sub.points.clone(), color });
+        }
+    }
+
+    pub fn stroke_path<C>(&mut self, path: &Path, width: f32, color: C, style: &LineStyle) where C: Into<[f32; 4]> {
+        let color = color.into();
+        for sub in path.subpaths() {
+            let style = LineStyle { closed: sub.closed, .. *style };
+            self.push(PictureCommand::StrokePolyline { points: sub.points.clone(), width, style, color });
+        }
+    }
+
+    pub fn text<T>(&mut self, text: T, x: f32, y: f32, params: &FontParameters) where T: Into<String> {
+        self.push(PictureCommand::Text {
+            text: text.into(),
+            pos: [x, y],
+            size: params.size,
+            color: params.color,
+            bold: params.bold,
+            italic: params.italic,
+            align_horizontal: params.align_horizontal,
+            align_vertical: params.align_vertical
+        });
+    }
+
+    pub fn push_clip<B>(&mut self, bounds: B) where B: Into<[f32; 4]> {
+        self.push(PictureCommand::PushClip(bounds.into()));
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.push(PictureCommand::PopClip);
+    }
+
+    pub fn draw<S>(&self, canvas: &mut Canvas<S>, transform: &PictureTransform) -> Result<()> where S: Surface {
+        let scale = transform.scale;
+        for command in &self.commands {
+            match command {
+                PictureCommand::Rect { bounds, color } =>
+                    canvas.queue_rect(transform.rect(*bounds), *color),
+                PictureCommand::TexturedRect { texture, bounds, uv, color } =>
+                    canvas.queue_textured_rect(*texture, transform.rect(*bounds), *uv, *color),
+                PictureCommand::RoundedRect { bounds, radius, color } =>
+                    canvas.rounded_rect(transform.rect(*bounds), radius * scale, *color)?,
+                PictureCommand::Circle { center, radius, color } =>
+                    canvas.circle(transform.point(*center), radius * scale, *color)?,
+                PictureCommand::FillPolygon { points, color } =>
+                    canvas.fill_path_with(&to_path(points, transform, true), *color, &PaintOptions::default())?,
+                PictureCommand::StrokePolyline { points, width, style, color } => {
+                    let path = to_path(points, transform, style.closed);
+                    canvas.stroke_path_with(&path, width * scale, *color, style, &PaintOptions::default())?
+                },
+                PictureCommand::Text { text, pos, size, color, bold, italic, align_horizontal, align_vertical } => {
+                    let [x, y] = transform.point(*pos);
+                    canvas.text(text, x, y, &FontParameters {
+                        size: ((*size as f32 * scale).round() as u32).max(1),
+                        color: *color,
+                        bold: *bold,
+                        italic: *italic,
+                        align_horizontal: *align_horizontal,
+                        align_vertical: *align_vertical,
+                        .. Default::default()
+                    })?
+                },
+                PictureCommand::PushClip(bounds) => canvas.push_clip(transform.rect(*bounds)),
+                PictureCommand::PopClip => canvas.pop_clip()
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save<P>(&self, path: P) -> io::Result<()> where P: AsRef<std::path::Path> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()
+    }
+
+    pub fn load<P>(path: P) -> io::Result<Picture> where P: AsRef<std::path::Path> {
+        Self::read(File::open(path)?)
+    }
+
+    /// Writes one command per line.
+    pub fn write<W>(&self, out: &mut W) -> io::Result<()> where W: Write {
+        for command in &self.commands {
+            match command {
+                PictureCommand::Rect { bounds, color } =>
+                    writeln!(out, "rect {} {}", floats(bounds), floats(color))?,
+                PictureCommand::TexturedRect { texture, bounds, uv, color } =>
+                    writeln!(out, "textured {} {} {} {}", floats(bounds), floats(uv), floats(color), escape(texture.as_str()))?,
+                PictureCommand::RoundedRect { bounds, radius, color } =>
+                    writeln!(out, "rounded {} {} {}", floats(bounds), radius, floats(color))?,
+                PictureCommand::Circle { center, radius, color } =>
+                    writeln!(out, "circle {} {} {}", floats(center), radius, floats(color))?,
+                PictureCommand::FillPolygon { points, color } =>
+                    writeln!(out, "fill {} {}", floats(color), points_to_string(points))?,
+                PictureCommand::StrokePolyline { points, width, style, color } => {
+                    let join = match style.join {
+                        LineJoin::Miter => "miter",
+                        LineJoin::Bevel => "bevel",
+                        LineJoin::Round => "round"
+                    };
+                    let cap = match style.cap {
+                        LineCap::Butt => "butt",
+                        LineCap::Square => "square",
+                        LineCap::Round => "round"
+                    };
+                    writeln!(out, "stroke {} {} {} {} {} {} {}", width, join, cap, style.closed, style.miter_limit,
+                             floats(color), points_to_string(points))?
+                },
+                PictureCommand::Text { text, pos, size, color, bold, italic, align_horizontal, align_vertical } => {
+                    let horizontal = match align_horizontal {
+                        TextAlignHorizontal::Left => "left",
+                        TextAlignHorizontal::Right => "right",
+                        TextAlignHorizontal::Center => "center"
+                    };
+                    let vertical = match align_vertical {
+                        TextAlignVertical::Top => "top",
+                        TextAlignVertical::Bottom => "bottom",
+                        TextAlignVertical::Center => "center",
+                        TextAlignVertical::Baseline => "baseline"
+                    };
+                    writeln!(out, "text {} {} {} {} {} {} {} {}", floats(pos), size, floats(color), bold, italic,
+                             horizontal, vertical, escape(text))?
+                },
+                PictureCommand::PushClip(bounds) => writeln!(out, "clip {}", floats(bounds))?,
+                PictureCommand::PopClip => writeln!(out, "unclip")?
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read<R>(input: R) -> io::Result<Picture> where R: Read {
+        let mut picture = Picture::new();
+        for (i, line) in BufReader::new(input).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let command = parse(&line)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Bad picture command at line {}", i + 1)))?;
+            picture.push(command);
+        }
+        Ok(picture)
+    }
+}
+
+fn to_path(points: &[[f32; 2]], transform: &PictureTransform, closed: bool) -> Path {
+    let mut path = Path::new();
+    for (i, point) in points.iter().enumerate() {
+        let [x, y] = transform.point(*point);
+        path = if i == 0 { path.move_to(x, y) } else { path.line_to(x, y) };
+    }
+    if closed { path.close() } else { path }
+}
+
+fn floats(values: &[f32]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+fn points_to_string(points: &[[f32; 2]]) -> String {
+    points.iter().map(|p| floats(p)).collect::<Vec<_>>().join(" ")
+}
+
+/// Free text is written last on its line, so only line breaks and backslashes need escaping.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => {}
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+    result
+}
+
+fn parse(line: &str) -> Option<PictureCommand> {
+    let (name, rest) = split_word(line);
+    let mut words = Words(rest);
+    Some(match name {
+        "rect" => PictureCommand::Rect { bounds: words.array()?, color: words.array()? },
+        "textured" => PictureCommand::TexturedRect {
+            bounds: words.array()?, uv: words.array()?, color: words.array()?, texture: unescape(words.0).into()
+        },
+        "rounded" => PictureCommand::RoundedRect { bounds: words.array()?, radius: words.parse()?, color: words.array()? },
+        "circle" => PictureCommand::Circle { center: words.array()?, radius: words.parse()?, color: words.array()? },
+        "fill" => PictureCommand::FillPolygon { color: words.array()?, points: words.points()? },
+        "stroke" => {
+            let width = words.parse()?;
+            let join = match words.word()? {
+                "miter" => LineJoin::Miter,
+                "bevel" => LineJoin::Bevel,
+                "round" => LineJoin::Round,
+                _ => return None
+            };
+            let cap = match words.word()? {
+                "butt" => LineCap::Butt,
+                "square" => LineCap::Square,
+                "round" => LineCap::Round,
+                _ => return None
+            };
+            let style = LineStyle { join, cap, closed: words.parse()?, miter_limit: words.parse()? };
+            PictureCommand::StrokePolyline { width, style, color: words.array()?, points: words.points()? }
+        },
+        "text" => {
+            let pos = words.array()?;
+            let size = words.parse()?;
+            let color = words.array()?;
+            let bold = words.parse()?;
+            let italic = words.parse()?;
+            let align_horizontal = match words.word()? {
+                "left" => TextAlignHorizontal::Left,
+                "right" => TextAlignHorizontal::Right,
+                "center" => TextAlignHorizontal::Center,
+                _ => return None
+            };
+            let align_vertical = match words.word()? {
+                "top" => TextAlignVertical::Top,
+                "bottom" => TextAlignVertical::Bottom,
+                "center" => TextAlignVertical::Center,
+                "baseline" => TextAlignVertical::Baseline,
+                _ => return None
+            };
+            PictureCommand::Text {
+                text: unescape(words.0), pos, size, color, bold, italic, align_horizontal, align_vertical
+            }
+        },
+        "clip" => PictureCommand::PushClip(words.array()?),
+        "unclip" => PictureCommand::PopClip,
+        _ => return None
+    })
+}
+
+fn split_word(line: &str) -> (&str, &str) {
+    match line.find(' ') {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => (line, "")
+    }
+}
+
+/// Reads space separated values from the front of a line, keeping the remainder for free text.
+struct Words<'a>(&'a str);
+
+impl<'a> Words<'a> {
+    fn word(&mut self) -> Option<&'a str> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let (word, rest) = split_word(self.0);
+        self.0 = rest;
+        Some(word)
+    }
+
+    fn parse<T>(&mut self) -> Option<T> where T: std::str::FromStr {
+        self.word()?.parse().ok()
+    }
+
+    fn array<A>(&mut self) -> Option<A> where A: Default + AsMut<[f32]> {
+        let mut array = A::default();
+        for value in array.as_mut() {
+            *value = self.parse()?;
+        }
+        Some(array)
+    }
+
+    fn points(&mut self) -> Option<Vec<[f32; 2]>> {
+        let mut points = Vec::new();
+        while !self.0.is_empty() {
+            points.push([self.parse()?, self.parse()?]);
+        }
+        Some(points)
+    }
+}
@@ -0,0 +1,167 @@
+use glium::{Display, Frame, Surface, VertexBuffer, DrawParameters, BlitTarget};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::Texture2d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction};
+
+use crate::backend::Renderer;
+use crate::shader::ShaderManager;
+use crate::texture::TextureManager;
+use crate::error::{Error, Result};
+use crate::intern::ResourceId;
+
+/// Full screen pass applied to the finished UI frame, see `WindowListener::post_effects`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostEffect {
+    Fxaa,
+    /// Gaussian blur, `radius` in physical pixels
+    Blur { radius: f32 },
+    /// Darkens the corners, `radius` is the distance from the center relative to the corners where it starts
+    Vignette { strength: f32, radius: f32 },
+    /// Remaps colors through a lookup texture made of square tiles laid out horizontally, one per blue level
+    ColorGrading { lut: ResourceId, strength: f32 },
+    /// Program registered in the `ShaderManager`, given the frame as `tex` and the pixel size as `texel`
+    Custom(ResourceId)
+}
+
+impl PostEffect {
+    pub fn color_grading<L>(lut: L, strength: f32) -> PostEffect where L: Into<ResourceId> {
+        PostEffect::ColorGrading { lut: lut.into(), strength }
+    }
+
+    pub fn custom<N>(program: N) -> PostEffect where N: Into<ResourceId> {
+        PostEffect::Custom(program.into())
+    }
+}
+
+#[derive(Copy, Clone)]
+struct PostVertex {
+    pos: [f32; 2],
+    uv: [f32; 2]
+}
+
+implement_vertex!(PostVertex, pos, uv);
+
+/// Single draw of an effect, blurs are split into a horizontal and a vertical pass.
+enum Pass {
+    Fxaa,
+    Blur { axis: [f32; 2], radius: f32 },
+    Vignette { strength: f32, radius: f32 },
+    ColorGrading { lut: ResourceId, strength: f32 },
+    Custom(ResourceId)
+}
+
+/// Offscreen buffers the effects are applied in, reallocated when the frame size changes.
+pub struct PostProcessor {
+    buffers: Vec<Texture2d>,
+    quad: Option<VertexBuffer<PostVertex>>
+}
+
+impl PostProcessor {
+    pub fn new() -> PostProcessor {
+        PostProcessor {
+            buffers: Vec::new(),
+            quad: None
+        }
+    }
+
+    /// Runs the effects in order over the contents of the frame and writes the result back into it.
+    pub fn apply(&mut self, display: &Display, frame: &mut Frame, effects: &[PostEffect], shaders: &ShaderManager,
+                 textures: &TextureManager) -> Result<()> {
+
+        let passes = effects.iter()
+            .flat_map(|effect| match effect {
+                PostEffect::Fxaa => vec![Pass::Fxaa],
+                PostEffect::Blur { radius } => vec![
+                    Pass::Blur { axis: [1.0, 0.0], radius: *radius },
+                    Pass::Blur { axis: [0.0, 1.0], radius: *radius }
+                ],
+                PostEffect::Vignette { strength, radius } => vec![Pass::Vignette { strength: *strength, radius: *radius }],
+                PostEffect::ColorGrading { lut, strength } => vec![Pass::ColorGrading { lut: *lut, strength: *strength }],
+                PostEffect::Custom(program) => vec![Pass::Custom(*program)]
+            })
+            .collect::<Vec<_>>();
+        if passes.is_empty() {
+            return Ok(());
+        }
+
+        let (w, h) = frame.get_dimensions();
+        if self.buffers.first().map_or(true, |buffer| buffer.dimensions() != (w, h)) {
+            self.buffers = vec![display.create_layer(w, h, false)?, display.create_layer(w, h, false)?];
+        }
+        if self.quad.is_none() {
+            self.quad = Some(VertexBuffer::new(display, &[
+                PostVertex { pos: [-1.0, -1.0], uv: [0.0, 0.0] },
+                PostVertex { pos: [1.0, -1.0], uv: [1.0, 0.0] },
+                PostVertex { pos: [-1.0, 1.0], uv: [0.0, 1.0] },
+                PostVertex { pos: [1.0, 1.0], uv: [1.0, 1.0] }
+            ])?);
+        }
+        let quad = self.quad.as_ref().expect("Post-processing quad is missing");
+
+        let whole = BlitTarget { left: 0, bottom: 0, width: w as i32, height: h as i32 };
+        frame.blit_whole_color_to(&self.buffers[0].as_surface(), &whole, MagnifySamplerFilter::Nearest);
+        for (i, pass) in passes.iter().enumerate() {
+            let source = &self.buffers[i % 2];
+            if i + 1 == passes.len() {
+                draw_pass(frame, quad, source, pass, shaders, textures)?;
+            } else {
+                let mut target = self.buffers[(i + 1) % 2].as_surface();
+                draw_pass(&mut target, quad, source, pass, shaders, textures)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn draw_pass<S>(target: &mut S, quad: &VertexBuffer<PostVertex>, source: &Texture2d, pass: &Pass,
+                shaders: &ShaderManager, textures: &TextureManager) -> Result<()> where S: Surface {
+
+    let (w, h) = source.dimensions();
+    let texel = [1.0 / w as f32, 1.0 / h as f32];
+    let tex = source.sampled()
+        .magnify_filter(MagnifySamplerFilter::Linear)
+        .minify_filter(MinifySamplerFilter::Linear)
+        .wrap_function(SamplerWrapFunction::Clamp);
+    let indices = NoIndices(PrimitiveType::TriangleStrip);
+    let params = DrawParameters::default();
+    let program = |name: &str| shaders.get(name).ok_or_else(|| Error::MissingProgram(name.to_owned()));
+    match pass {
+        Pass::Fxaa => {
+            let uniforms = uniform! { tex: tex, texel: texel };
+            target.draw(quad, &indices, &program("post_fxaa")?, &uniforms, &params)?;
+        },
+        Pass::Blur { axis, radius } => {
+            // 33 taps cover three standard deviations of the radius
+            let step = (radius / 16.0).max(1.0);
+            let uniforms = uniform! {
+                tex: tex,
+                direction: [axis[0] * texel[0] * step, axis[1] * texel[1] * step],
+                sigma: (radius / step / 3.0).max(0.5)
+            };
+            target.draw(quad, &indices, &program("post_blur")?, &uniforms, &params)?;
+        },
+        Pass::Vignette { strength, radius } => {
+            let uniforms = uniform! { tex: tex, strength: *strength, radius: *radius };
+            target.draw(quad, &indices, &program("post_vignette")?, &uniforms, &params)?;
+        },
+        Pass::ColorGrading { lut, strength } => {
+            let lut = textures.get(lut);
+            let size = lut.get_height().unwrap_or(1) as f32;
+            let uniforms = uniform! {
+                tex: tex,
+                lut: lut.sampled()
+                    .magnify_filter(MagnifySamplerFilter::Linear)
+                    .minify_filter(MinifySamplerFilter::Linear)
+                    .wrap_function(SamplerWrapFunction::Clamp),
+                size: size,
+                strength: *strength
+            };
+            target.draw(quad, &indices, &program("post_grade")?, &uniforms, &params)?;
+        },
+        Pass::Custom(name) => {
+            let uniforms = uniform! { tex: tex, texel: texel };
+            target.draw(quad, &indices, &program(name.as_str())?, &uniforms, &params)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::rc::Rc;
+
+use glium::texture::SrgbTexture2d;
+use image::{DynamicImage, RgbaImage, Rgba};
+
+use crate::texture::{TextureManager, TextureError};
+
+/// Recipe of a generated texture, colors are sRGB like everywhere else.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureSpec {
+    Solid([f32; 4]),
+    /// Gradient from the left to the right edge, rotated clockwise by `angle` radians
+    LinearGradient { from: [f32; 4], to: [f32; 4], angle: f32, size: (u32, u32) },
+    /// Gradient from the center to the inscribed circle, the corners keep the outer color
+    RadialGradient { inner: [f32; 4], outer: [f32; 4], size: (u32, u32) },
+    Checkerboard { even: [f32; 4], odd: [f32; 4], cell: u32, size: (u32, u32) },
+    /// Smooth value noise blending two colors, `scale` is the size of a noise cell in pixels
+    Noise { low: [f32; 4], high: [f32; 4], scale: f32, seed: u32, size: (u32, u32) }
+}
+
+impl TextureSpec {
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            TextureSpec::Solid(_) => (1, 1),
+            TextureSpec::LinearGradient { size, .. } => *size,
+            TextureSpec::RadialGradient { size, .. } => *size,
+            TextureSpec::Checkerboard { size, .. } => *size,
+            TextureSpec::Noise { size, .. } => *size
+        }
+    }
+
+    pub fn to_image(&self) -> RgbaImage {
+        let (w, h) = self.size();
+        RgbaImage::from_fn(w.max(1), h.max(1), |x, y| {
+            // sampled at pixel centers
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let color = match self {
+                TextureSpec::Solid(color) => *color,
+                TextureSpec::LinearGradient { from, to, angle, .. } => {
+                    let (sin, cos) = angle.sin_cos();
+                    let (dx, dy) = (px - w as f32 / 2.0, py - h as f32 / 2.0);
+                    // extent of the rect along the gradient direction
+                    let half = (w as f32 * cos.abs() + h as f32 * sin.abs()) / 2.0;
+                    let t = (dx * cos + dy * sin) / (half * 2.0) + 0.5;
+                    mix(*from, *to, t)
+                },
+                TextureSpec::RadialGradient { inner, outer, .. } => {
+                    let (dx, dy) = (px - w as f32 / 2.0, py - h as f32 / 2.0);
+                    let radius = w.min(h) as f32 / 2.0;
+                    mix(*inner, *outer, (dx * dx + dy * dy).sqrt() / radius)
+                },
+                TextureSpec::Checkerboard { even, odd, cell, .. } => {
+                    let cell = (*cell).max(1);
+                    if (x / cell + y / cell) % 2 == 0 { *even } else { *odd }
+                },
+                TextureSpec::Noise { low, high, scale, seed, .. } => {
+                    let scale = scale.max(1.0);
+                    mix(*low, *high, value_noise(px / scale, py / scale, *seed))
+                }
+            };
+            Rgba([
+                to_byte(color[0]), to_byte(color[1]), to_byte(color[2]), to_byte(color[3])
+            ])
+        })
+    }
+}
+
+impl TextureManager {
+    /// Renders the spec into a texture registered under `name`.
+    pub fn generate<N>(&mut self, name: N, spec: &TextureSpec) -> Result<Rc<Box<SrgbTexture2d>>, TextureError>
+        where N: Into<String> {
+
+        self.insert_image(name, DynamicImage::ImageRgba8(spec.to_image()))
+    }
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let t = t.max(0.0).min(1.0);
+    [
+        a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t, a[3] + (b[3] - a[3]) * t
+    ]
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+/// Pseudo random value in `0..1` for a lattice point.
+fn lattice(x: i32, y: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x27d4_eb2d) ^ (y as u32).wrapping_mul(0x1656_67b1) ^ seed.wrapping_mul(0x9e37_79b9);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    (h & 0xffff) as f32 / 65535.0
+}
+
+fn value_noise(x: f32, y: f32, seed: u32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+    let (ix, iy) = (x0 as i32, y0 as i32);
+    let top = lattice(ix, iy, seed) + (lattice(ix + 1, iy, seed) - lattice(ix, iy, seed)) * tx;
+    let bottom = lattice(ix, iy + 1, seed) + (lattice(ix + 1, iy + 1, seed) - lattice(ix, iy + 1, seed)) * tx;
+    top + (bottom - top) * ty
+}
@@ -1,15 +1,29 @@
 use std::ops::Mul;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::time::Duration;
 
-use glium::index::PrimitiveType;
-use glium::{VertexBuffer, IndexBuffer, Display, DrawParameters, Surface, Program, Rect};
-use glium::uniforms::Uniforms;
-use cgmath::{Matrix4, Point3, Transform};
+use glium::index::{PrimitiveType, IndicesSource, NoIndices};
+use glium::{VertexBuffer, IndexBuffer, Display, DrawParameters, Surface, Program, Rect, Blend, BlendingFunction,
+            LinearBlendingFactor, Depth, DepthTest};
+use glium::draw_parameters::{Stencil, StencilTest, StencilOperation, TimeElapsedQuery};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::Texture2d;
+use glium::uniforms::{Uniforms, UniformValue, MagnifySamplerFilter, SamplerWrapFunction};
+use glium::BlitTarget;
+use image::RgbaImage;
+use cgmath::{Matrix4, Point3, SquareMatrix, Transform, Vector3, Rad};
 
-use crate::font::{FontManager, FontParameters};
+use crate::font::{FontManager, FontParameters, TextLayout, TextAlignVertical, RichText, TextShadow, TextOutline, Truncation, FontMetrics};
 use crate::shader::ShaderManager;
-use crate::texture::TextureManager;
+use crate::texture::{TextureManager, Sprite};
+use crate::path::{self, Path, LineStyle, stroke_polyline};
+use crate::error::{Error, Result};
+use crate::clipboard::Clipboard;
+use crate::backend::Renderer;
+use crate::intern::ResourceId;
 use winit::dpi::LogicalSize;
 
 pub struct DrawBuffer {
@@ -20,7 +34,23 @@ pub struct DrawBuffer {
     normal: bool,
     texture: bool,
     primitive_type: Option<PrimitiveType>,
-    drawing: bool
+    drawing: bool,
+    index_format: IndexFormat
+}
+
+/// Element type of the index buffer a `DrawBuffer` is uploaded with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexFormat {
+    /// `U16` whenever every index fits, `U32` otherwise
+    Auto,
+    U16,
+    U32
+}
+
+impl Default for IndexFormat {
+    fn default() -> Self {
+        IndexFormat::Auto
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -76,17 +106,17 @@ impl DrawBuffer {
 
     pub fn draw_once<S, U>(primitive_type: &PrimitiveType, normal: bool, texture: bool,
                            display: &Display, target: &mut S, program: &glium::Program, uniform: &U,
-                           params: &DrawParameters, vertices: Vec<Vertex>)
+                           params: &DrawParameters, vertices: Vec<Vertex>) -> Result<()>
         where S: Surface, U: Uniforms {
 
         let mut buffer = Self::with_capacity(vertices.len());
-        buffer.start_drawing(primitive_type, normal, texture);
+        buffer.start_drawing(primitive_type, normal, texture)?;
 
         for vertex in vertices {
-            buffer.add_vertex(vertex);
+            buffer.add_vertex(vertex)?;
         }
 
-        buffer.draw(display, target, program, uniform, params);
+        buffer.draw(display, target, program, uniform, params)
     }
 
     pub fn with_capacity(initial_capacity: usize) -> DrawBuffer {
@@ -98,51 +128,106 @@ impl DrawBuffer {
             normal: false,
             texture: false,
             primitive_type: None,
-            drawing: false
+            drawing: false,
+            index_format: IndexFormat::Auto
         }
     }
 
-    pub fn start_drawing(&mut self, primitive_type: &PrimitiveType, normal: bool, texture: bool) {
-        if !self.drawing {
-            self.drawing = true;
-            self.primitive_type = Some(*primitive_type);
-            self.normal = normal;
-            self.texture = texture;
-        } else {
-            panic!("Already drawing!");
+    pub fn index_format(&self) -> IndexFormat {
+        self.index_format
+    }
+
+    pub fn set_index_format(&mut self, format: IndexFormat) {
+        self.index_format = format;
+    }
+
+    fn use_short_indices(&self) -> Result<bool> {
+        let fits = self.vertices.len() <= u16::max_value() as usize + 1;
+        match self.index_format {
+            IndexFormat::Auto => Ok(fits),
+            IndexFormat::U16 if !fits => Err(Error::IndexOverflow(self.vertices.len())),
+            IndexFormat::U16 => Ok(true),
+            IndexFormat::U32 => Ok(false)
+        }
+    }
+
+    pub fn start_drawing(&mut self, primitive_type: &PrimitiveType, normal: bool, texture: bool) -> Result<()> {
+        if self.drawing {
+            return Err(Error::AlreadyDrawing);
         }
+        self.drawing = true;
+        self.primitive_type = Some(*primitive_type);
+        self.normal = normal;
+        self.texture = texture;
+        Ok(())
     }
 
-    pub fn draw<U, S>(&self, display: &Display, target: &mut S, program: &glium::Program, uniform: &U, params: &DrawParameters)
+    pub fn draw<U, S>(&self, display: &Display, target: &mut S, program: &glium::Program, uniform: &U, params: &DrawParameters) -> Result<()>
         where U: glium::uniforms::Uniforms,
               S: Surface {
 
-        if self.drawing {
-            let ib = IndexBuffer::new(display, self.primitive_type.expect("Getting primitive type failed"), &self.indices).expect("IndexBuffer creation failed");
-            if self.texture {
-                let mut vertices: Vec<TexturedVertex> = Vec::with_capacity(self.vertices.capacity());
-                for v in &self.vertices {
-                    match v {
-                        &WrappedVertex::Textured(vtx) => vertices.push(vtx),
-                        _ => panic!("Illegal buffer state")
-                    }
-                }
-                let vb = VertexBuffer::new(display, &vertices).expect("VertexBuffer creation failed");
-                target.draw(&vb, &ib, program, uniform, params).expect("Target drawing failed");
-            } else {
-                let mut vertices: Vec<SimpleVertex> = Vec::with_capacity(self.vertices.capacity());
-                for v in &self.vertices {
-                    match v {
-                        &WrappedVertex::Simple(vtx) => vertices.push(vtx),
-                        _ => panic!("Illegal buffer state")
-                    }
-                }
-                let vb = VertexBuffer::new(display, &vertices).expect("VertexBuffer creation failed");
-                target.draw(&vb, &ib, program, uniform, params).expect("Target drawing failed");
-            };
+        self.draw_with(&mut GeometryBuffers::new(), display, target, program, uniform, params)
+    }
+
+    /// Uploads the geometry into `buffers`, reusing their storage, and draws it.
+    pub fn draw_with<U, S>(&self, buffers: &mut GeometryBuffers, display: &Display, target: &mut S,
+                           program: &glium::Program, uniform: &U, params: &DrawParameters) -> Result<()>
+        where U: glium::uniforms::Uniforms,
+              S: Surface {
+
+        let primitive_type = match self.primitive_type {
+            Some(primitive_type) if self.drawing => primitive_type,
+            _ => return Err(Error::NotDrawing)
+        };
+        if self.vertices.is_empty() || self.indices.is_empty() {
+            return Ok(());
+        }
+        let short_indices: Vec<u16>;
+        let ib: IndicesSource = if self.use_short_indices()? {
+            short_indices = self.indices.iter().map(|&i| i as u16).collect();
+            upload_indices(&mut buffers.short_indices, display, primitive_type, &short_indices)?.into()
+        } else {
+            upload_indices(&mut buffers.indices, display, primitive_type, &self.indices)?.into()
+        };
+        // vertices are checked against the format when added, so the buffer only holds one kind
+        if self.texture {
+            let vertices = self.vertices.iter().filter_map(|v| match v {
+                &WrappedVertex::Textured(vtx) => Some(vtx),
+                _ => None
+            }).collect::<Vec<TexturedVertex>>();
+            let vb = upload(&mut buffers.textured, display, &vertices)?;
+            target.draw(vb, ib, program, uniform, params)?;
         } else {
-            panic!("Not drawing!")
+            let vertices = self.vertices.iter().filter_map(|v| match v {
+                &WrappedVertex::Simple(vtx) => Some(vtx),
+                _ => None
+            }).collect::<Vec<SimpleVertex>>();
+            let vb = upload(&mut buffers.simple, display, &vertices)?;
+            target.draw(vb, ib, program, uniform, params)?;
+        }
+        Ok(())
+    }
+
+    /// Draws user defined vertices as they are, without going through the internal vertex formats.
+    pub fn draw_vertices<V, U, S>(buffers: &mut GeometryBuffers, display: &Display, target: &mut S,
+                                  primitive_type: &PrimitiveType, vertices: &[V], program: &glium::Program,
+                                  uniform: &U, params: &DrawParameters) -> Result<()>
+        where V: UiVertex, U: glium::uniforms::Uniforms, S: Surface {
+
+        if vertices.is_empty() {
+            return Ok(());
         }
+        let vb = upload(buffers.custom::<V>(), display, vertices)?;
+        target.draw(vb, NoIndices(*primitive_type), program, uniform, params)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.vertices.len()
     }
 
     pub fn reset(&mut self) {
@@ -153,48 +238,107 @@ impl DrawBuffer {
         self.drawing = false;
     }
 
-    pub fn add_multiple_vertices(&mut self, vertices: Vec<Vertex>, indices: Vec<u32>) {
+    /// Nothing is added when a vertex or index is rejected.
+    pub fn add_multiple_vertices(&mut self, vertices: Vec<Vertex>, indices: Vec<u32>) -> Result<()> {
         if !self.drawing {
-            panic!("Not drawing!");
+            return Err(Error::NotDrawing);
         }
         let offset = vertices.len() as u32;
+        if let Some(&index) = indices.iter().find(|&&index| index >= offset) {
+            return Err(Error::VertexIndex { index, vertices: offset });
+        }
+        for vertex in &vertices {
+            if !self.normal && vertex.normal.is_some() {
+                return Err(Error::VertexFormat("normal is not enabled for current drawing stage"));
+            }
+            if self.texture != vertex.texture_uv.is_some() {
+                return Err(Error::VertexFormat(if self.texture {
+                    "texture uv is missing"
+                } else {
+                    "texture is not enabled for current drawing stage"
+                }));
+            }
+        }
         for vertex in vertices {
             let pos = vertex.pos;
-            let normal = vertex.normal;
+            let normal = vertex.normal.unwrap_or([1.0, 1.0, 1.0]);
             let color = vertex.color.unwrap_or([1.0, 1.0, 1.0, 1.0]);
-            let texture_uv = vertex.texture_uv;
+            self.vertices.push(match vertex.texture_uv {
+                Some(texture_uv) => WrappedVertex::Textured(TexturedVertex { pos, color, normal, texture_uv }),
+                None => WrappedVertex::Simple(SimpleVertex { pos, normal, color })
+            });
+        }
+        self.indices.extend(indices.into_iter().map(|index| self.index + index));
+        self.index += offset;
+        Ok(())
+    }
 
-            if !self.normal && normal.is_some() {
-                panic!("Normal is not enabled for current drawing stage");
-            }
+    pub fn add_vertex(&mut self, vertex: Vertex) -> Result<()> {
+        self.add_multiple_vertices(vec![vertex], vec![0])
+    }
+}
 
-            if !self.texture && texture_uv.is_some() {
-                panic!("Texture is not enabled for current drawing stage");
-            }
+/// Dynamic GPU buffers that are grown on demand and reused between draws and frames.
+pub struct GeometryBuffers {
+    simple: Option<VertexBuffer<SimpleVertex>>,
+    textured: Option<VertexBuffer<TexturedVertex>>,
+    indices: Vec<IndexBuffer<u32>>,
+    short_indices: Vec<IndexBuffer<u16>>,
+    custom: HashMap<TypeId, Box<dyn Any>>
+}
 
-            if self.texture {
-                self.vertices.push(WrappedVertex::Textured(TexturedVertex {
-                    pos, color, normal: normal.unwrap_or([1.0, 1.0, 1.0]), texture_uv: texture_uv.expect("Texture uv getting failed")
-                }));
-            } else {
-                self.vertices.push(WrappedVertex::Simple(SimpleVertex {
-                    pos, normal: normal.unwrap_or([1.0, 1.0, 1.0]), color
-                }))
-            }
+/// Vertex types that can be drawn with `Canvas::custom_shape`, e.g. with extra attributes for custom shaders.
+pub trait UiVertex: glium::Vertex + Copy + 'static {}
+
+impl<T> UiVertex for T where T: glium::Vertex + Copy + 'static {}
+
+impl GeometryBuffers {
+    pub fn new() -> GeometryBuffers {
+        GeometryBuffers {
+            simple: None,
+            textured: None,
+            indices: Vec::new(),
+            short_indices: Vec::new(),
+            custom: HashMap::new()
         }
-        for index in indices {
-            if index < offset {
-                self.indices.push(self.index + index);
-            } else {
-                panic!("Illegal buffer index {} while amount of vertices is {}", index, offset);
+    }
+
+    fn custom<V>(&mut self) -> &mut Option<VertexBuffer<V>> where V: UiVertex {
+        self.custom.entry(TypeId::of::<V>())
+            .or_insert_with(|| Box::new(None::<VertexBuffer<V>>))
+            .downcast_mut()
+            .expect("Vertex buffer type mismatch")
+    }
+}
+
+fn upload_indices<'a, T>(buffers: &'a mut Vec<IndexBuffer<T>>, display: &Display, primitive_type: PrimitiveType,
+                         data: &[T]) -> Result<glium::index::IndexBufferSlice<'a, T>> where T: glium::index::Index {
+
+    let position = buffers.iter().position(|ib| ib.get_primitives_type() == primitive_type);
+    let position = match position {
+        Some(i) if buffers[i].len() >= data.len() => i,
+        found => {
+            let ib = IndexBuffer::empty_dynamic(display, primitive_type, data.len().next_power_of_two())?;
+            match found {
+                Some(i) => { buffers[i] = ib; i },
+                None => { buffers.push(ib); buffers.len() - 1 }
             }
         }
-        self.index += offset;
-    }
+    };
+    let slice = buffers[position].slice(0 .. data.len()).ok_or(Error::BufferSlice)?;
+    slice.write(data);
+    Ok(slice)
+}
 
-    pub fn add_vertex(&mut self, vertex: Vertex) {
-        self.add_multiple_vertices(vec![vertex], vec![0]);
+fn upload<'a, T>(buffer: &'a mut Option<VertexBuffer<T>>, display: &Display, data: &[T]) -> Result<glium::vertex::VertexBufferSlice<'a, T>>
+    where T: glium::Vertex + Copy {
+
+    if buffer.as_ref().map_or(true, |vb| vb.len() < data.len()) {
+        *buffer = Some(VertexBuffer::empty_dynamic(display, data.len().next_power_of_two())?);
     }
+    let slice = buffer.as_ref().unwrap().slice(0 .. data.len()).ok_or(Error::BufferSlice)?;
+    slice.write(data);
+    Ok(slice)
 }
 
 enum WrappedVertex {
@@ -221,46 +365,503 @@ struct TexturedVertex {
 
 implement_vertex!(TexturedVertex, pos, normal, color, texture_uv);
 
-/*pub struct AnimationFrame {
-    left: u32,
-    top: u32,
-    delay: f32
+fn textured_quad(bounds: [f32; 4], uv: [f32; 4], color: [f32; 4]) -> Vec<Vertex> {
+    let [x, y, w, h] = bounds;
+    let [u, v, uw, vh] = uv;
+    let top_left = Vertex::pos([x, y, 0.0]).color(color).uv([u, v]);
+    let top_right = Vertex::pos([x + w, y, 0.0]).color(color).uv([u + uw, v]);
+    let bottom_right = Vertex::pos([x + w, y + h, 0.0]).color(color).uv([u + uw, v + vh]);
+    let bottom_left = Vertex::pos([x, y + h, 0.0]).color(color).uv([u, v + vh]);
+    vec![top_left.clone(), top_right, bottom_right.clone(), top_left, bottom_right, bottom_left]
 }
 
-pub struct Animation {
-    texture: Texture2d,
-    frames: AnimationFrame,
-    current_frame: usize
-}
-
-impl Animation {
-    pub fn new<R>(r: R) -> ImageResult<Animation> where R: Read {
-        let mut decoder = image::gif::Decoder::new(r);
-        let raw_frames = decoder.into_frames()?;
-        let mut frames = Vec::new();
-        let mut pixels = Vec::new();
-        for frame in raw_frames {
-            frames.push(AnimationFrame {
-                left: frame.left(),
-                top: frame.top(),
-                delay: frame.delay().numer() as f32 / frame.delay().denom() as f32
-            });
+/// Quad covering a rounded box of the shape shader, padded by a pixel for the feathered edge.
+/// The shader reads a border width from the z coordinate, which is left at zero for a filled shape.
+fn shape_quad(center: [f32; 2], axis: [f32; 2], half: [f32; 2], radius: f32, color: [f32; 4]) -> Vec<Vertex> {
+    let [hw, hh] = [half[0] + 1.0, half[1] + 1.0];
+    let perp = [-axis[1], axis[0]];
+    let corner = |lx: f32, ly: f32| Vertex::pos([
+        center[0] + axis[0] * lx + perp[0] * ly,
+        center[1] + axis[1] * lx + perp[1] * ly,
+        0.0
+    ]).normal([half[0], half[1], radius]).color(color).uv([lx, ly]);
+    vec![
+        corner(-hw, -hh), corner(hw, -hh), corner(hw, hh),
+        corner(-hw, -hh), corner(hw, hh), corner(-hw, hh)
+    ]
+}
+
+/// Rounded box with radii given clockwise from the top left corner, split into one quad per corner
+/// since the shape shader takes a single radius.
+fn corner_quads([x, y, w, h]: [f32; 4], radii: [f32; 4], border: f32, color: [f32; 4]) -> Vec<Vertex> {
+    let [hx, hy] = [w / 2.0, h / 2.0];
+    let center = [x + hx, y + hy];
+    let max_radius = hx.min(hy);
+    let mut vertices = Vec::with_capacity(24);
+    for (&radius, &[sx, sy]) in radii.iter().zip(&[[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0f32]]) {
+        let radius = radius.max(0.0).min(max_radius);
+        let [ex, ey] = [sx * (hx + 1.0), sy * (hy + 1.0)];
+        let corner = |lx: f32, ly: f32| Vertex::pos([center[0] + lx, center[1] + ly, border])
+            .normal([hx, hy, radius]).color(color).uv([lx, ly]);
+        vertices.extend(vec![
+            corner(0.0, 0.0), corner(ex, 0.0), corner(ex, ey),
+            corner(0.0, 0.0), corner(ex, ey), corner(0.0, ey)
+        ]);
+    }
+    vertices
+}
+
+/// Converts rows read from OpenGL, which go bottom to top, into an image.
+pub(crate) fn image_from_rows(rows: Vec<Vec<(u8, u8, u8, u8)>>) -> RgbaImage {
+    let w = rows.first().map_or(0, |row| row.len()) as u32;
+    let mut image = RgbaImage::new(w, rows.len() as u32);
+    for (y, row) in rows.iter().rev().enumerate() {
+        for (x, &(r, g, b, a)) in row.iter().enumerate() {
+            image.put_pixel(x as u32, y as u32, image::Rgba([r, g, b, a]));
         }
     }
-}*/
+    image
+}
+
+fn overlaps(a: [f32; 4], b: [f32; 4]) -> bool {
+    a[0] < b[0] + b[2] && b[0] < a[0] + a[2] && a[1] < b[1] + b[3] && b[1] < a[1] + a[3]
+}
+
+fn union(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    let left = a[0].min(b[0]);
+    let top = a[1].min(b[1]);
+    let right = (a[0] + a[2]).max(b[0] + b[2]);
+    let bottom = (a[1] + a[3]).max(b[1] + b[3]);
+    [left, top, right - left, bottom - top]
+}
+
+fn intersect_rect(a: Rect, b: Rect) -> Rect {
+    let left = a.left.max(b.left);
+    let bottom = a.bottom.max(b.bottom);
+    let right = (a.left + a.width).min(b.left + b.width);
+    let top = (a.bottom + a.height).min(b.bottom + b.height);
+    Rect {
+        left, bottom,
+        width: right.saturating_sub(left), height: top.saturating_sub(bottom)
+    }
+}
 
 pub struct Canvas<S> where S: Surface {
     display: Display,
     shaders: Rc<RefCell<ShaderManager>>,
     fonts: Rc<RefCell<FontManager>>,
     textures: Rc<RefCell<TextureManager>>,
-    target: S
+    target: S,
+    clip_stack: Vec<[f32; 4]>,
+    clip_paths: Vec<Vec<[f32; 2]>>,
+    /// Accumulated transforms pushed with `push_transform`, the innermost last
+    transforms: Vec<Matrix4<f32>>,
+    layers: Vec<Layer>,
+    buffers: Rc<RefCell<GeometryBuffers>>,
+    batches: Vec<Batch>,
+    bound_texture: Option<ResourceId>,
+    stats: FrameStats,
+    recording: Vec<DisplayList>,
+    color_space: ColorSpace,
+    frame: FrameUniforms,
+    clipboard: Clipboard,
+    gpu_timer: Option<Rc<GpuTimer>>,
+    /// First quad that could not be queued, returned by the next `flush`
+    queue_error: Option<Error>
+}
+
+/// Values shared by every draw of a canvas, computed once when it is created. Programs drawn through
+/// the canvas receive them as the `mat`, `time`, `screen_size` and `scale_factor` uniforms.
+/// The GL 2.1 context has no uniform blocks, so they are set as plain uniforms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameUniforms {
+    /// Orthographic projection of logical pixels, origin at the top left, followed by the
+    /// transform of the canvas at the time of the draw
+    pub mat: [[f32; 4]; 4],
+    /// Seconds since the window was shown
+    pub time: f32,
+    /// Size of the target in logical pixels
+    pub screen_size: [f32; 2],
+    pub scale_factor: f32
+}
+
+impl Uniforms for FrameUniforms {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut visit: F) {
+        visit("mat", UniformValue::Mat4(self.mat));
+        visit("time", UniformValue::Float(self.time));
+        visit("screen_size", UniformValue::Vec2(self.screen_size));
+        visit("scale_factor", UniformValue::Float(self.scale_factor));
+    }
+}
+
+/// Frame uniforms followed by the ones of a draw, which take precedence.
+struct WithFrame<'u, U> {
+    frame: &'u FrameUniforms,
+    uniforms: &'u U
+}
+
+impl<'u, U> Uniforms for WithFrame<'u, U> where U: Uniforms {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut visit: F) {
+        self.frame.visit_values(&mut visit);
+        self.uniforms.visit_values(&mut visit);
+    }
+}
+
+struct Batch {
+    texture: Option<ResourceId>,
+    paint: PaintOptions,
+    scissor: Option<Rect>,
+    bounds: [f32; 4],
+    buffer: DrawBuffer
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    pub draw_calls: usize,
+    pub vertices: usize,
+    pub texture_binds: usize,
+    /// Queued rects that were merged into an earlier draw call instead of starting a new one
+    pub merged: usize,
+    /// Time spent updating and drawing the frame before presenting it, measured by `Window::show`
+    pub cpu_time: Duration,
+    /// CPU time blocked in `Frame::finish` swapping buffers, which includes waiting on vsync and
+    /// queued GPU work but is no GPU timing
+    pub finish_time: Duration,
+    /// Time since the previous frame started
+    pub frame_time: Duration,
+    /// GPU time of the draw calls of the previous frame, measured by `GpuTimer`. `None` where timer
+    /// queries are not supported or the results were not available yet
+    pub gpu_time: Option<Duration>,
+    /// Frames per second averaged over the last frames
+    pub fps: f32
+}
+
+/// Measures the GPU time of the draw calls of canvases and the text drawn with them, with a timer
+/// query around each draw call. The queries of a frame are read back at the end of the next one,
+/// so reading them does not wait for the GPU.
+pub struct GpuTimer {
+    display: Display,
+    supported: Cell<bool>,
+    frame: RefCell<Vec<Rc<TimeElapsedQuery>>>,
+    /// Queries of the previous frame, none before the first frame ended
+    pending: RefCell<Option<Vec<Rc<TimeElapsedQuery>>>>
+}
+
+impl GpuTimer {
+    pub fn new(display: &Display) -> GpuTimer {
+        GpuTimer {
+            display: display.clone(),
+            supported: Cell::new(true),
+            frame: RefCell::new(Vec::new()),
+            pending: RefCell::new(None)
+        }
+    }
+
+    /// Query to put into the parameters of the next draw call, `None` where timer queries are not supported.
+    pub fn query(&self) -> Option<Rc<TimeElapsedQuery>> {
+        if !self.supported.get() {
+            return None;
+        }
+        match TimeElapsedQuery::new(&self.display) {
+            Ok(query) => {
+                let query = Rc::new(query);
+                self.frame.borrow_mut().push(query.clone());
+                Some(query)
+            }
+            Err(_) => {
+                self.supported.set(false);
+                None
+            }
+        }
+    }
+
+    /// Starts the next frame, returning the GPU time of the previous one once all its queries have a result.
+    pub fn end_frame(&self) -> Option<Duration> {
+        let frame = std::mem::replace(&mut *self.frame.borrow_mut(), Vec::new());
+        let pending = self.pending.borrow_mut().replace(frame)?;
+        if !self.supported.get() || !pending.iter().all(|query| query.is_ready()) {
+            return None;
+        }
+        let mut nanos = 0;
+        for query in pending {
+            nanos += Rc::try_unwrap(query).ok()?.get() as u64;
+        }
+        Some(Duration::from_nanos(nanos))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Replace,
+    Alpha,
+    /// Alpha blending for colors already multiplied by their alpha
+    PremultipliedAlpha,
+    Additive,
+    Multiply
+}
+
+/// Blending of colors with straight alpha. Unlike `Blend::alpha_blending` the alpha channel is
+/// composited with the over operator, so a target cleared to transparent ends up with premultiplied
+/// colors and the coverage as alpha, which is what compositors expect from transparent windows.
+pub fn alpha_blending() -> Blend {
+    let addition = |source, destination| BlendingFunction::Addition { source, destination };
+    Blend {
+        color: addition(LinearBlendingFactor::SourceAlpha, LinearBlendingFactor::OneMinusSourceAlpha),
+        alpha: addition(LinearBlendingFactor::One, LinearBlendingFactor::OneMinusSourceAlpha),
+        constant_value: (0.0, 0.0, 0.0, 0.0)
+    }
+}
+
+impl BlendMode {
+    pub fn blend(&self) -> Blend {
+        let addition = |source, destination| BlendingFunction::Addition { source, destination };
+        match self {
+            BlendMode::Replace => Blend::default(),
+            BlendMode::Alpha => alpha_blending(),
+            BlendMode::PremultipliedAlpha => Blend {
+                color: addition(LinearBlendingFactor::One, LinearBlendingFactor::OneMinusSourceAlpha),
+                alpha: addition(LinearBlendingFactor::One, LinearBlendingFactor::OneMinusSourceAlpha),
+                constant_value: (0.0, 0.0, 0.0, 0.0)
+            },
+            BlendMode::Additive => Blend {
+                color: addition(LinearBlendingFactor::SourceAlpha, LinearBlendingFactor::One),
+                alpha: addition(LinearBlendingFactor::One, LinearBlendingFactor::One),
+                constant_value: (0.0, 0.0, 0.0, 0.0)
+            },
+            BlendMode::Multiply => Blend {
+                color: addition(LinearBlendingFactor::DestinationColor, LinearBlendingFactor::Zero),
+                alpha: addition(LinearBlendingFactor::Zero, LinearBlendingFactor::One),
+                constant_value: (0.0, 0.0, 0.0, 0.0)
+            }
+        }
+    }
+}
+
+/// Space in which vertex colors are blended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    /// Colors are written as given and blended in gamma space
+    Srgb,
+    /// Colors are decoded to linear before drawing, so blending is gamma-correct. The target has to be
+    /// an sRGB framebuffer to encode the result again, see `WindowListener::color_space`
+    Linear
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        ColorSpace::Srgb
+    }
+}
+
+pub fn srgb_to_linear([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    fn decode(c: f32) -> f32 {
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    }
+    [decode(r), decode(g), decode(b), a]
+}
+
+pub fn linear_to_srgb([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    fn encode(c: f32) -> f32 {
+        if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+    }
+    [encode(r), encode(g), encode(b), a]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StencilMode {
+    Disabled,
+    /// Writes the reference value wherever something is drawn
+    Write(i32),
+    /// Only draws where the stencil buffer holds the reference value
+    Equal(i32),
+    NotEqual(i32)
+}
+
+impl StencilMode {
+    pub fn stencil(&self) -> Stencil {
+        let with = |test, reference, operation| Stencil {
+            test_clockwise: test,
+            test_counter_clockwise: test,
+            reference_value_clockwise: reference,
+            reference_value_counter_clockwise: reference,
+            depth_pass_operation_clockwise: operation,
+            depth_pass_operation_counter_clockwise: operation,
+            .. Default::default()
+        };
+        match *self {
+            StencilMode::Disabled => Stencil::default(),
+            StencilMode::Write(reference) =>
+                with(StencilTest::AlwaysPass, reference, StencilOperation::Replace),
+            StencilMode::Equal(reference) =>
+                with(StencilTest::IfEqual { mask: !0 }, reference, StencilOperation::Keep),
+            StencilMode::NotEqual(reference) =>
+                with(StencilTest::IfNotEqual { mask: !0 }, reference, StencilOperation::Keep)
+        }
+    }
+}
+
+/// How canvas geometry is combined with the target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaintOptions {
+    pub blend: BlendMode,
+    pub depth_test: bool,
+    pub stencil: StencilMode
+}
+
+impl Default for PaintOptions {
+    fn default() -> Self {
+        PaintOptions {
+            blend: BlendMode::Alpha,
+            depth_test: false,
+            stencil: StencilMode::Disabled
+        }
+    }
+}
+
+impl PaintOptions {
+    pub fn blend(blend: BlendMode) -> PaintOptions {
+        PaintOptions { blend, .. Default::default() }
+    }
+
+    pub fn params(&self) -> DrawParameters<'static> {
+        DrawParameters {
+            blend: self.blend.blend(),
+            depth: if self.depth_test {
+                Depth { test: DepthTest::IfLessOrEqual, write: true, .. Default::default() }
+            } else {
+                Depth::default()
+            },
+            stencil: self.stencil.stencil(),
+            .. Default::default()
+        }
+    }
+}
+
+struct Layer {
+    texture: Texture2d,
+    opacity: f32,
+    tint: [f32; 4],
+    tone_mapping: Option<ToneMapping>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapOperator {
+    Clamp,
+    /// Extended Reinhard, colors at `ToneMapping::white` map to full intensity
+    Reinhard,
+    /// Filmic curve fitted to the ACES reference transform
+    Aces
+}
+
+/// Maps the unbounded colors of an HDR layer to the displayable range when it is popped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    pub exposure: f32,
+    pub white: f32
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping { operator: ToneMapOperator::Reinhard, exposure: 1.0, white: 4.0 }
+    }
+}
+
+#[derive(Clone)]
+enum DisplayCommand {
+    Quad { texture: Option<ResourceId>, paint: PaintOptions, bounds: [f32; 4], vertices: Vec<Vertex> },
+    Text { text: String, x: f32, y: f32, params: FontParameters },
+    TextLayout { layout: TextLayout, x: f32, y: f32, params: FontParameters },
+    RichText { text: RichText, x: f32, y: f32, params: FontParameters },
+    PushClip([f32; 4]),
+    PopClip,
+    PushClipPath(Vec<[f32; 2]>),
+    PopClipPath,
+    PushTransform(Matrix4<f32>),
+    PopTransform,
+    PushLayer { opacity: f32, tint: [f32; 4] },
+    PushHdrLayer(ToneMapping),
+    PopLayer
+}
+
+/// Tessellated output of a sequence of canvas calls, replayed without re-running the code that produced it.
+///
+/// Only queued rects, text, clips and layers can be recorded. Draws with caller supplied
+/// programs and uniforms make the list incomplete, such a list must not be replayed.
+#[derive(Clone)]
+pub struct DisplayList {
+    commands: Vec<DisplayCommand>,
+    complete: bool
+}
+
+impl DisplayList {
+    pub fn new() -> DisplayList {
+        DisplayList { commands: Vec::new(), complete: true }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
 }
 
 impl<S> Canvas<S> where S: Surface {
     pub fn new(display: Display, shaders: Rc<RefCell<ShaderManager>>, fonts: Rc<RefCell<FontManager>>,
                textures: Rc<RefCell<TextureManager>>, target: S) -> Canvas<S> {
-        Canvas { display, shaders, fonts, textures, target }
+        let buffers = Rc::new(RefCell::new(GeometryBuffers::new()));
+        Self::with_buffers(display, shaders, fonts, textures, buffers, target)
+    }
+
+    /// Creates a canvas that uploads geometry into `buffers`, which can be kept between frames.
+    pub fn with_buffers(display: Display, shaders: Rc<RefCell<ShaderManager>>, fonts: Rc<RefCell<FontManager>>,
+                        textures: Rc<RefCell<TextureManager>>, buffers: Rc<RefCell<GeometryBuffers>>,
+                        target: S) -> Canvas<S> {
+        Canvas {
+            display, shaders, fonts, textures, target,
+            clip_stack: Vec::new(),
+            clip_paths: Vec::new(),
+            transforms: Vec::new(),
+            layers: Vec::new(),
+            buffers,
+            batches: Vec::new(),
+            bound_texture: None,
+            stats: FrameStats::default(),
+            recording: Vec::new(),
+            color_space: ColorSpace::default(),
+            frame: FrameUniforms { mat: [[0.0; 4]; 4], time: 0.0, screen_size: [0.0; 2], scale_factor: 1.0 },
+            clipboard: Clipboard::system(),
+            gpu_timer: None,
+            queue_error: None
+        }.with_frame_uniforms()
+    }
+
+    fn with_frame_uniforms(mut self) -> Self {
+        let factor = self.display.gl_window().window().scale_factor() as f32;
+        let (w, h) = self.target.get_dimensions();
+        let (w, h) = (w as f32 / factor, h as f32 / factor);
+        self.frame = FrameUniforms {
+            mat: cgmath::ortho(0.0, w, h, 0.0, -0.1, 0.1).into(),
+            time: self.frame.time,
+            screen_size: [w, h],
+            scale_factor: factor
+        };
+        self
+    }
+
+    pub fn frame_uniforms(&self) -> FrameUniforms {
+        self.frame
+    }
+
+    /// Sets the `time` uniform, in seconds.
+    pub fn set_time(&mut self, time: f32) {
+        self.frame.time = time;
     }
 
     pub fn display(&self) -> Display {
@@ -279,19 +880,76 @@ impl<S> Canvas<S> where S: Surface {
         self.textures.clone()
     }
 
+    pub fn clipboard(&self) -> Clipboard {
+        self.clipboard.clone()
+    }
+
+    /// Shares the clipboard of the window instead of the one the canvas opens on its own.
+    pub fn set_clipboard(&mut self, clipboard: Clipboard) {
+        self.clipboard = clipboard;
+    }
+
+    /// Times the draw calls of this canvas, see `FrameStats::gpu_time`.
+    pub fn set_gpu_timer(&mut self, timer: Option<Rc<GpuTimer>>) {
+        self.gpu_timer = timer;
+    }
+
     pub fn dimensions(&self) -> (f32, f32) {
         let factor = self.scale_factor();
         let (w, h) = self.target.get_dimensions();
         (w as f32 / factor, h as f32 / factor)
     }
 
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Changes how colors passed to further draws are interpreted, they are always given in sRGB.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    fn working_color(&self, color: [f32; 4]) -> [f32; 4] {
+        match self.color_space {
+            ColorSpace::Srgb => color,
+            ColorSpace::Linear => srgb_to_linear(color)
+        }
+    }
+
     pub fn scale_factor(&self) -> f32 {
-        self.display.gl_window().window().scale_factor() as f32
+        self.frame.scale_factor
     }
 
+    /// Projection of the canvas followed by the current transform.
     pub fn viewport(&self) -> Matrix4<f32> {
-        let (w, h) = self.dimensions();
-        cgmath::ortho(0.0, w, h, 0.0, -0.1, 0.1)
+        Matrix4::from(self.frame.mat) * self.transform()
+    }
+
+    pub fn transform(&self) -> Matrix4<f32> {
+        self.transforms.last().cloned().unwrap_or_else(Matrix4::identity)
+    }
+
+    /// Applies `transform` on top of the current one to everything drawn until the matching `pop_transform`.
+    ///
+    /// Rect clips and scissors stay in window coordinates, text drawn under a transform ignores
+    /// `FontParameters::scissor`. Clip paths pushed under a transform are transformed with it.
+    pub fn push_transform(&mut self, transform: Matrix4<f32>) -> Result<()> {
+        self.flush()?;
+        self.record(DisplayCommand::PushTransform(transform));
+        let transform = self.transform() * transform;
+        self.transforms.push(transform);
+        Ok(())
+    }
+
+    pub fn pop_transform(&mut self) -> Result<()> {
+        self.flush()?;
+        self.transforms.pop().expect("Transform stack underflow");
+        self.record(DisplayCommand::PopTransform);
+        Ok(())
+    }
+
+    fn frame_uniforms(&self) -> FrameUniforms {
+        FrameUniforms { mat: self.viewport().into(), .. self.frame }
     }
 
     pub fn scissor<B>(&self, bounds: B) -> Rect where B: Into<[f32; 4]> {
@@ -304,20 +962,485 @@ impl<S> Canvas<S> where S: Surface {
         }
     }
 
-    pub fn clear(&mut self, color: (f32, f32, f32, f32), depth: f32) {
-        self.target.clear_color_and_depth(color, depth);
+    /// Restricts all further drawing to `bounds` intersected with the current clip.
+    pub fn push_clip<B>(&mut self, bounds: B) where B: Into<[f32; 4]> {
+        let (w, h) = self.dimensions();
+        let parent = self.clip_stack.last().cloned().unwrap_or([0.0, 0.0, w, h]);
+        let [x, y, w, h] = bounds.into();
+        let [px, py, pw, ph] = parent;
+        let left = x.max(px);
+        let top = y.max(py);
+        let right = (x + w).min(px + pw);
+        let bottom = (y + h).min(py + ph);
+        self.clip_stack.push([left, top, (right - left).max(0.0), (bottom - top).max(0.0)]);
+        self.record(DisplayCommand::PushClip([x, y, w, h]));
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.pop().expect("Clip stack underflow");
+        self.record(DisplayCommand::PopClip);
+    }
+
+    pub fn current_clip(&self) -> Option<[f32; 4]> {
+        self.clip_stack.last().cloned()
+    }
+
+    /// Restricts all further drawing to the filled `path` intersected with the current clip paths,
+    /// unlike `push_clip` this also works for rotated or curved shapes.
+    ///
+    /// Needs a stencil buffer on the target, layers have none and are not clipped by paths.
+    /// Clip paths must be popped before the rect clips pushed after them.
+    pub fn push_clip_path(&mut self, path: &Path) -> Result<()> {
+        let transform = self.transform();
+        let triangles = path::fill_path(path).into_iter()
+            .map(|[x, y]| {
+                let point = transform.transform_point(Point3::new(x, y, 0.0));
+                [point.x, point.y]
+            })
+            .collect();
+        self.push_clip_triangles(triangles)
+    }
+
+    fn push_clip_triangles(&mut self, triangles: Vec<[f32; 2]>) -> Result<()> {
+        if !self.recording.is_empty() {
+            self.record(DisplayCommand::PushClipPath(triangles.clone()));
+        }
+        let depth = self.clip_paths.len() as i32;
+        self.write_clip_stencil(triangles.clone(), depth, StencilOperation::Increment)?;
+        self.clip_paths.push(triangles);
+        Ok(())
+    }
+
+    pub fn pop_clip_path(&mut self) -> Result<()> {
+        let depth = self.clip_paths.len() as i32;
+        let triangles = self.clip_paths.pop().expect("Clip path stack underflow");
+        self.record(DisplayCommand::PopClipPath);
+        self.write_clip_stencil(triangles, depth, StencilOperation::Decrement)
+    }
+
+    /// Nested clip paths increment the stencil value inside them, only pixels inside all of them
+    /// hold the current depth.
+    fn write_clip_stencil(&mut self, triangles: Vec<[f32; 2]>, depth: i32, operation: StencilOperation) -> Result<()> {
+        let program = self.shaders().borrow().default();
+        let uniforms = uniform! {};
+        let params = DrawParameters {
+            color_mask: (false, false, false, false),
+            stencil: Stencil {
+                test_clockwise: StencilTest::IfEqual { mask: !0 },
+                test_counter_clockwise: StencilTest::IfEqual { mask: !0 },
+                reference_value_clockwise: depth,
+                reference_value_counter_clockwise: depth,
+                depth_pass_operation_clockwise: operation,
+                depth_pass_operation_counter_clockwise: operation,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
+        // stencil writes are not user draws, keep them from marking recorded lists incomplete,
+        // their triangles are already in window coordinates
+        let recording = std::mem::replace(&mut self.recording, Vec::new());
+        let transforms = std::mem::replace(&mut self.transforms, Vec::new());
+        let result = self.triangles(triangles, [1.0; 4], &program, &uniforms, &params);
+        self.recording = recording;
+        self.transforms = transforms;
+        result
+    }
+
+    fn clip_stencil(&self) -> Option<i32> {
+        if self.clip_paths.is_empty() {
+            None
+        } else {
+            Some(self.clip_paths.len() as i32)
+        }
+    }
+
+    /// Draws that do not use the stencil buffer themselves are restricted to the clip paths.
+    fn apply_clip_stencil(&self, params: &mut DrawParameters) {
+        if let Some(depth) = self.clip_stencil() {
+            if params.stencil.test_clockwise == StencilTest::AlwaysPass
+                && params.stencil.test_counter_clockwise == StencilTest::AlwaysPass {
+                params.stencil = StencilMode::Equal(depth).stencil();
+            }
+        }
+    }
+
+    fn clip_scissor(&self, scissor: Option<Rect>) -> Option<Rect> {
+        match (self.clip_stack.last(), scissor) {
+            (Some(clip), Some(scissor)) => Some(intersect_rect(self.scissor(*clip), scissor)),
+            (Some(clip), None) => Some(self.scissor(*clip)),
+            (None, scissor) => scissor
+        }
+    }
+
+    /// Redirects drawing into an offscreen layer until the matching `pop_layer`.
+    pub fn push_layer(&mut self, opacity: f32) -> Result<()> {
+        self.push_tinted_layer(opacity, [1.0; 4])
+    }
+
+    pub fn push_tinted_layer(&mut self, opacity: f32, tint: [f32; 4]) -> Result<()> {
+        self.flush()?;
+        let (w, h) = self.target.get_dimensions();
+        let texture = self.display.create_layer(w, h, false)?;
+        self.layers.push(Layer { texture, opacity, tint, tone_mapping: None });
+        self.record(DisplayCommand::PushLayer { opacity, tint });
+        Ok(())
+    }
+
+    /// Pushes a layer with half float color, so colors brighter than white survive blending.
+    /// Popping it applies `tone_mapping`, which lets UI drawn into an HDR scene follow its exposure.
+    pub fn push_hdr_layer(&mut self, tone_mapping: ToneMapping) -> Result<()> {
+        self.flush()?;
+        let (w, h) = self.target.get_dimensions();
+        let texture = self.display.create_layer(w, h, true)?;
+        self.layers.push(Layer { texture, opacity: 1.0, tint: [1.0; 4], tone_mapping: Some(tone_mapping) });
+        self.record(DisplayCommand::PushHdrLayer(tone_mapping));
+        Ok(())
+    }
+
+    /// Composites the topmost layer onto the layer below it, or onto the target.
+    pub fn pop_layer(&mut self) -> Result<()> {
+        self.flush()?;
+        let layer = self.layers.pop().expect("Layer stack underflow");
+        let (w, h) = self.dimensions();
+        let sampler = layer.texture.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Linear);
+        let params = DrawParameters {
+            blend: alpha_blending(),
+            .. Default::default()
+        };
+        let [r, g, b, a] = layer.tint;
+        let color = [r, g, b, a * layer.opacity];
+        // the composite is not a user draw, keep it from marking recorded lists incomplete
+        let recording = std::mem::replace(&mut self.recording, Vec::new());
+        // framebuffer textures are stored bottom-up
+        let uv = [0.0, 1.0, 1.0, -1.0];
+        let result = match layer.tone_mapping {
+            Some(tone_mapping) => {
+                let program = self.shaders().borrow().tonemap();
+                let operator = match tone_mapping.operator {
+                    ToneMapOperator::Clamp => 0,
+                    ToneMapOperator::Reinhard => 1,
+                    ToneMapOperator::Aces => 2
+                };
+                let uniforms = uniform! {
+                    tex: sampler,
+                    exposure: tone_mapping.exposure,
+                    white: tone_mapping.white,
+                    operator: operator,
+                    srgb: self.color_space == ColorSpace::Srgb
+                };
+                self.textured_rect_uv([0.0, 0.0, w, h], uv, color, &program, &uniforms, &params)
+            },
+            None => {
+                let program = self.shaders().borrow().textured();
+                let uniforms = uniform! {
+                    tex: sampler
+                };
+                self.textured_rect_uv([0.0, 0.0, w, h], uv, color, &program, &uniforms, &params)
+            }
+        };
+        self.recording = recording;
+        self.record(DisplayCommand::PopLayer);
+        result
+    }
+
+    /// Draws into a texture made by `TextureManager::create_render_target`, sharing the resources of
+    /// this canvas. Fails with `Error::MissingRenderTarget` if there is no such render target.
+    pub fn render_to_texture<T, F>(&mut self, name: T, draw: F) -> Result<()>
+        where T: AsRef<str>, F: for<'a> FnOnce(&mut Canvas<SimpleFrameBuffer<'a>>) -> Result<()> {
+
+        let name = name.as_ref();
+        let (texture, depth) = self.textures().borrow().render_target(name)
+            .ok_or_else(|| Error::MissingRenderTarget(name.to_owned()))?;
+        let target = SimpleFrameBuffer::with_depth_stencil_buffer(&self.display, &**texture, &*depth)?;
+        self.render_to_surface(target, draw)?;
+        Ok(())
+    }
+
+    /// Draws into another surface with the resources of this canvas, returning it once everything is flushed.
+    pub fn render_to_surface<T, F>(&mut self, target: T, draw: F) -> Result<T>
+        where T: Surface, F: FnOnce(&mut Canvas<T>) -> Result<()> {
+
+        // quads sampling the target must be drawn before it changes
+        self.flush()?;
+        self.invalidate_recording();
+        let mut canvas = Canvas::with_buffers(
+            self.display.clone(), self.shaders.clone(), self.fonts.clone(), self.textures.clone(),
+            self.buffers.clone(), target
+        );
+        canvas.color_space = self.color_space;
+        canvas.frame.time = self.frame.time;
+        canvas.clipboard = self.clipboard.clone();
+        canvas.gpu_timer = self.gpu_timer.clone();
+        draw(&mut canvas)?;
+        canvas.into_inner()
+    }
+
+    /// Starts recording canvas calls into a new display list, drawing continues as usual.
+    pub fn begin_recording(&mut self) {
+        self.recording.push(DisplayList::new());
+    }
+
+    /// Finishes the innermost recording, its commands are also kept by any enclosing recording.
+    pub fn end_recording(&mut self) -> DisplayList {
+        let list = self.recording.pop().expect("Display list recording underflow");
+        if let Some(parent) = self.recording.last_mut() {
+            parent.commands.extend(list.commands.iter().cloned());
+            parent.complete &= list.complete;
+        }
+        list
+    }
+
+    pub fn record_list<F>(&mut self, draw: F) -> Result<DisplayList> where F: FnOnce(&mut Self) -> Result<()> {
+        self.begin_recording();
+        let result = draw(self);
+        let list = self.end_recording();
+        result.map(|_| list)
+    }
+
+    pub fn replay(&mut self, list: &DisplayList) -> Result<()> {
+        for command in &list.commands {
+            match command {
+                DisplayCommand::Quad { texture, paint, bounds, vertices } =>
+                    self.queue(*texture, paint, *bounds, vertices.clone()),
+                DisplayCommand::Text { text, x, y, params } => self.text(text, *x, *y, params)?,
+                DisplayCommand::TextLayout { layout, x, y, params } => self.draw_text_layout(layout, *x, *y, params)?,
+                DisplayCommand::RichText { text, x, y, params } => self.rich_text(text, *x, *y, params)?,
+                DisplayCommand::PushClip(bounds) => self.push_clip(*bounds),
+                DisplayCommand::PopClip => self.pop_clip(),
+                DisplayCommand::PushClipPath(triangles) => self.push_clip_triangles(triangles.clone())?,
+                DisplayCommand::PopClipPath => self.pop_clip_path()?,
+                DisplayCommand::PushTransform(transform) => self.push_transform(*transform)?,
+                DisplayCommand::PopTransform => self.pop_transform()?,
+                DisplayCommand::PushLayer { opacity, tint } => self.push_tinted_layer(*opacity, *tint)?,
+                DisplayCommand::PushHdrLayer(tone_mapping) => self.push_hdr_layer(*tone_mapping)?,
+                DisplayCommand::PopLayer => self.pop_layer()?
+            }
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, command: DisplayCommand) {
+        if let Some(list) = self.recording.last_mut() {
+            list.commands.push(command);
+        }
+    }
+
+    fn invalidate_recording(&mut self) {
+        if let Some(list) = self.recording.last_mut() {
+            list.complete = false;
+        }
+    }
+
+    /// Queues an alpha blended rect drawn with the default shader. Consecutive queued rects
+    /// sharing the same texture and clip are drawn with a single draw call.
+    pub fn queue_rect<B, C>(&mut self, bounds: B, color: C) where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+        self.queue_rect_with(bounds, color, &PaintOptions::default());
+    }
+
+    pub fn queue_rect_with<B, C>(&mut self, bounds: B, color: C, paint: &PaintOptions)
+        where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        let [x, y, w, h] = bounds.into();
+        let color = self.working_color(color.into());
+        self.queue(None, paint, [x, y, w, h], vec![
+            Vertex::pos([x, y, 0.0]).color(color),
+            Vertex::pos([x + w, y, 0.0]).color(color),
+            Vertex::pos([x + w, y + h, 0.0]).color(color),
+            Vertex::pos([x, y + h, 0.0]).color(color),
+        ]);
+    }
+
+    pub fn queue_textured_rect<T, B, C>(&mut self, texture: T, bounds: B, uv: [f32; 4], color: C)
+        where T: Into<ResourceId>, B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        self.queue_textured_rect_with(texture, bounds, uv, color, &PaintOptions::default());
+    }
+
+    pub fn queue_textured_rect_with<T, B, C>(&mut self, texture: T, bounds: B, uv: [f32; 4], color: C,
+                                             paint: &PaintOptions)
+        where T: Into<ResourceId>, B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        let [x, y, w, h] = bounds.into();
+        let [u, v, uw, vh] = uv;
+        let color = self.working_color(color.into());
+        self.queue(Some(texture.into()), paint, [x, y, w, h], vec![
+            Vertex::pos([x, y, 0.0]).color(color).uv([u, v]),
+            Vertex::pos([x + w, y, 0.0]).color(color).uv([u + uw, v]),
+            Vertex::pos([x + w, y + h, 0.0]).color(color).uv([u + uw, v + vh]),
+            Vertex::pos([x, y + h, 0.0]).color(color).uv([u, v + vh]),
+        ]);
+    }
+
+    /// Queues a sprite, sprites of the same atlas are drawn together.
+    pub fn queue_sprite<B, C>(&mut self, sprite: &Sprite, bounds: B, color: C) where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+        self.queue_textured_rect(sprite.texture, bounds, sprite.uv, color);
+    }
+
+    /// Appends the quad to the latest batch with the same texture, paint options and clip, as long as
+    /// no batch queued after that one overlaps the quad, so painting order is preserved.
+    fn queue(&mut self, texture: Option<ResourceId>, paint: &PaintOptions, bounds: [f32; 4], quad: Vec<Vertex>) {
+        if !self.recording.is_empty() {
+            self.record(DisplayCommand::Quad {
+                texture, paint: *paint, bounds, vertices: quad.clone()
+            });
+        }
+        let scissor = self.clip_scissor(None);
+        let mut target = None;
+        for (i, batch) in self.batches.iter().enumerate().rev() {
+            if batch.texture == texture && batch.paint == *paint && batch.scissor == scissor {
+                target = Some(i);
+                break;
+            }
+            if overlaps(batch.bounds, bounds) {
+                break;
+            }
+        }
+        let batch = match target {
+            Some(i) => {
+                if i + 1 != self.batches.len() {
+                    self.stats.merged += 1;
+                }
+                let batch = &mut self.batches[i];
+                batch.bounds = union(batch.bounds, bounds);
+                batch
+            },
+            None => {
+                let mut buffer = DrawBuffer::new();
+                buffer.drawing = true;
+                buffer.primitive_type = Some(PrimitiveType::TrianglesList);
+                buffer.texture = texture.is_some();
+                self.batches.push(Batch { texture, paint: *paint, scissor, bounds, buffer });
+                self.batches.last_mut().unwrap()
+            }
+        };
+        if let Err(error) = batch.buffer.add_multiple_vertices(quad, vec![0, 1, 2, 0, 2, 3]) {
+            self.queue_error.get_or_insert(error);
+        }
+    }
+
+    /// Draws everything queued so far.
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(error) = self.queue_error.take() {
+            self.batches.clear();
+            return Err(error);
+        }
+        let batches = std::mem::replace(&mut self.batches, Vec::new());
+        if batches.is_empty() {
+            return Ok(());
+        }
+        for batch in batches {
+            let mut params = DrawParameters {
+                scissor: batch.scissor,
+                .. batch.paint.params()
+            };
+            self.apply_clip_stencil(&mut params);
+            match batch.texture {
+                Some(name) => {
+                    let program = self.shaders().borrow().textured();
+                    if self.bound_texture != Some(name) {
+                        self.stats.texture_binds += 1;
+                        self.bound_texture = Some(name);
+                    }
+                    let compressed = self.textures().borrow().get_compressed(name);
+                    if let Some(texture) = compressed {
+                        let uniforms = uniform! {
+                            tex: texture.sampled()
+                                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                                .minify_filter(glium::uniforms::MinifySamplerFilter::NearestMipmapNearest)
+                        };
+                        self.submit(&batch.buffer, &program, &uniforms, &params)?;
+                    } else {
+                        let texture = self.textures().borrow().get(name);
+                        let uniforms = uniform! {
+                            tex: texture.sampled()
+                                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                                .minify_filter(glium::uniforms::MinifySamplerFilter::NearestMipmapNearest)
+                        };
+                        self.submit(&batch.buffer, &program, &uniforms, &params)?;
+                    }
+                },
+                None => {
+                    let program = self.shaders().borrow().default();
+                    let uniforms = uniform! {};
+                    self.submit(&batch.buffer, &program, &uniforms, &params)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw statistics accumulated by this canvas, queued geometry is not counted until flushed.
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    fn draw_once<U>(&mut self, ty: &PrimitiveType, normal: bool, texture: bool, program: &Program, uniforms: &U,
+                    params: &DrawParameters, mut vertices: Vec<Vertex>) -> Result<()> where U: Uniforms {
+        self.flush()?;
+        self.invalidate_recording();
+        if self.color_space == ColorSpace::Linear {
+            for vertex in &mut vertices {
+                vertex.color = vertex.color.map(srgb_to_linear);
+            }
+        }
+        let mut params = params.clone();
+        params.scissor = self.clip_scissor(params.scissor);
+        self.apply_clip_stencil(&mut params);
+        if texture {
+            self.stats.texture_binds += 1;
+            self.bound_texture = None;
+        }
+        let mut buffer = DrawBuffer::with_capacity(vertices.len());
+        buffer.start_drawing(ty, normal, texture)?;
+        let indices = (0 .. vertices.len() as u32).collect();
+        buffer.add_multiple_vertices(vertices, indices)?;
+        self.submit(&buffer, program, uniforms, &params)
+    }
+
+    fn submit<U>(&mut self, buffer: &DrawBuffer, program: &Program, uniforms: &U, params: &DrawParameters) -> Result<()>
+        where U: Uniforms {
+
+        self.stats.draw_calls += 1;
+        self.stats.vertices += buffer.len();
+        let query = self.gpu_timer.as_ref().and_then(|timer| timer.query());
+        let params = &DrawParameters { time_elapsed_query: query.as_deref(), .. params.clone() };
+        let buffers = self.buffers.clone();
+        let mut buffers = buffers.borrow_mut();
+        let frame = self.frame_uniforms();
+        let uniforms = &WithFrame { frame: &frame, uniforms };
+        if let Some(layer) = self.layers.last() {
+            let mut target = SimpleFrameBuffer::new(&self.display, &layer.texture)?;
+            buffer.draw_with(&mut buffers, &self.display, &mut target, program, uniforms, params)
+        } else {
+            buffer.draw_with(&mut buffers, &self.display, &mut self.target, program, uniforms, params)
+        }
+    }
+
+    pub fn clear(&mut self, color: (f32, f32, f32, f32), depth: f32) -> Result<()> {
+        self.batches.clear();
+        self.invalidate_recording();
+        let [r, g, b, a] = self.working_color([color.0, color.1, color.2, color.3]);
+        let color = (r, g, b, a);
+        if let Some(layer) = self.layers.last() {
+            let (r, g, b, a) = color;
+            SimpleFrameBuffer::new(&self.display, &layer.texture)?.clear_color(r, g, b, a);
+        } else {
+            self.target.clear_all(color, depth, 0);
+        }
+        Ok(())
     }
 
     pub fn rect<B, C, U>(&mut self, bounds: B, color: C, program: &Program, uniforms: &U,
-                         params: &DrawParameters)
+                         params: &DrawParameters) -> Result<()>
         where B: Into<[f32; 4]>, C: Into<[f32; 4]>, U: Uniforms {
 
         let bounds = bounds.into();
         let color = color.into();
 
-        DrawBuffer::draw_once(
-            &PrimitiveType::TriangleFan, false, false, &self.display.clone(),
-            &mut self.target, program, uniforms, params,
+        self.draw_once(
+            &PrimitiveType::TriangleFan, false, false, program, uniforms, params,
             vec! [
                 Vertex::pos([bounds[0], bounds[1], 0.0]).color(color),
                 Vertex::pos([bounds[0] + bounds[2], bounds[1], 0.0]).color(color),
@@ -327,16 +1450,16 @@ impl<S> Canvas<S> where S: Surface {
         )
     }
 
+    /// One pixel `LineLoop` outline, use `border_rect` for outlines of a given width.
     pub fn frame<B, C, U>(&mut self, bounds: B, color: C, program: &Program, uniforms: &U,
-                          params: &DrawParameters)
+                          params: &DrawParameters) -> Result<()>
         where B: Into<[f32; 4]>, C: Into<[f32; 4]>, U: Uniforms {
 
         let bounds = bounds.into();
         let color = color.into();
 
-        DrawBuffer::draw_once(
-            &PrimitiveType::LineLoop, false, false, &self.display.clone(),
-            &mut self.target, program, uniforms, params,
+        self.draw_once(
+            &PrimitiveType::LineLoop, false, false, program, uniforms, params,
             vec! [
                 Vertex::pos([bounds[0], bounds[1], 0.0]).color(color),
                 Vertex::pos([bounds[0] + bounds[2], bounds[1], 0.0]).color(color),
@@ -347,66 +1470,559 @@ impl<S> Canvas<S> where S: Surface {
     }
 
     pub fn textured_rect<B, C, U>(&mut self, bounds: B, color: C, program: &Program, uniforms: &U,
-                                  params: &DrawParameters)
+                                  params: &DrawParameters) -> Result<()>
+        where B: Into<[f32; 4]>, C: Into<[f32; 4]>, U: Uniforms {
+
+        self.textured_rect_uv(bounds, [0.0, 0.0, 1.0, 1.0], color, program, uniforms, params)
+    }
+
+    pub fn textured_rect_uv<B, C, U>(&mut self, bounds: B, uv: [f32; 4], color: C, program: &Program, uniforms: &U,
+                                     params: &DrawParameters) -> Result<()>
         where B: Into<[f32; 4]>, C: Into<[f32; 4]>, U: Uniforms {
 
         let bounds = bounds.into();
         let color = color.into();
+        let [u, v, uw, vh] = uv;
 
-        DrawBuffer::draw_once(
-            &PrimitiveType::TriangleFan, false, true, &self.display.clone(),
-            &mut self.target, program, uniforms, params,
+        self.draw_once(
+            &PrimitiveType::TriangleFan, false, true, program, uniforms, params,
             vec! [
-                Vertex::pos([bounds[0], bounds[1], 0.0]).color(color).uv([0.0, 0.0]),
-                Vertex::pos([bounds[0] + bounds[2], bounds[1], 0.0]).color(color).uv([1.0, 0.0]),
-                Vertex::pos([bounds[0] + bounds[2], bounds[1] + bounds[3], 0.0]).color(color).uv([1.0, 1.0]),
-                Vertex::pos([bounds[0], bounds[1] + bounds[3], 0.0]).color(color).uv([0.0, 1.0]),
+                Vertex::pos([bounds[0], bounds[1], 0.0]).color(color).uv([u, v]),
+                Vertex::pos([bounds[0] + bounds[2], bounds[1], 0.0]).color(color).uv([u + uw, v]),
+                Vertex::pos([bounds[0] + bounds[2], bounds[1] + bounds[3], 0.0]).color(color).uv([u + uw, v + vh]),
+                Vertex::pos([bounds[0], bounds[1] + bounds[3], 0.0]).color(color).uv([u, v + vh]),
             ]
         )
     }
 
     pub fn fill_textured_rect<T, B, C>(&mut self, texture: T, bounds: B, color: C, program: &Program,
-                                       params: &DrawParameters)
+                                       params: &DrawParameters) -> Result<()>
         where B: Into<[f32; 4]>, C: Into<[f32; 4]>, T: AsRef<str> {
 
         let texture = self.textures().borrow().get(texture);
-        let mat = self.viewport();
+        let uniforms = uniform! {
+            tex: texture.sampled()
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                .minify_filter(glium::uniforms::MinifySamplerFilter::NearestMipmapNearest)
+        };
+
+        self.textured_rect(bounds, color, program, &uniforms, &params)
+    }
+
+    /// Repeats `texture` across `bounds` in tiles of `tile_size`, shifted by `offset`.
+    pub fn tiled_rect<T, B>(&mut self, texture: T, bounds: B, tile_size: [f32; 2], offset: [f32; 2]) -> Result<()>
+        where T: AsRef<str>, B: Into<[f32; 4]> {
+
+        let texture = self.textures().borrow().get(texture);
+        let [x, y, w, h] = bounds.into();
+        let [tile_w, tile_h] = tile_size;
+        let uv = [-offset[0] / tile_w, -offset[1] / tile_h, w / tile_w, h / tile_h];
 
+        let program = self.shaders().borrow().textured();
+        let uniforms = uniform! {
+            tex: texture.sampled()
+                .wrap_function(SamplerWrapFunction::Repeat)
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
+        };
+        let params = DrawParameters {
+            blend: alpha_blending(),
+            .. Default::default()
+        };
+        self.textured_rect_uv([x, y, w, h], uv, [1.0; 4], &program, &uniforms, &params)
+    }
+
+    pub fn sprite<B, C>(&mut self, sprite: &Sprite, bounds: B, color: C, program: &Program,
+                        params: &DrawParameters) -> Result<()>
+        where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        let texture = self.textures().borrow().get(&sprite.texture);
         let uniforms = uniform! {
-            mat: Into::<[[f32; 4]; 4]>::into(mat),
             tex: texture.sampled()
                 .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
                 .minify_filter(glium::uniforms::MinifySamplerFilter::NearestMipmapNearest)
         };
 
-        self.textured_rect(bounds, color, program, &uniforms, &params);
+        self.textured_rect_uv(bounds, sprite.uv, color, program, &uniforms, params)
     }
 
-    pub fn generic_shape<U>(&mut self, ty: &PrimitiveType, vertices: Vec<Vertex>, texture: bool,
-                            normal: bool, program: &Program, uniforms: &U, params: &DrawParameters) where U: Uniforms {
-        DrawBuffer::draw_once(ty, normal, texture, &self.display.clone(),
-                              &mut self.target, program, uniforms, params, vertices
+    pub fn nine_patch<T, B, C>(&mut self, texture: T, bounds: B, insets: [f32; 4], color: C, program: &Program,
+                               params: &DrawParameters) -> Result<()>
+        where B: Into<[f32; 4]>, C: Into<[f32; 4]>, T: AsRef<str> {
+
+        let texture = self.textures().borrow().get(texture);
+        let (tex_w, tex_h) = texture.dimensions();
+        let [x, y, w, h] = bounds.into();
+        let [left, top, right, bottom] = insets;
+        let color = color.into();
+
+        // borders keep their texture size unless the bounds are too small to fit them
+        let sx = (w / (left + right)).min(1.0);
+        let sy = (h / (top + bottom)).min(1.0);
+        let xs = [x, x + left * sx, x + w - right * sx, x + w];
+        let ys = [y, y + top * sy, y + h - bottom * sy, y + h];
+        let us = [0.0, left / tex_w as f32, 1.0 - right / tex_w as f32, 1.0];
+        let vs = [0.0, top / tex_h as f32, 1.0 - bottom / tex_h as f32, 1.0];
+
+        let mut vertices = Vec::with_capacity(9 * 6);
+        for row in 0..3 {
+            for col in 0..3 {
+                vertices.extend(textured_quad(
+                    [xs[col], ys[row], xs[col + 1] - xs[col], ys[row + 1] - ys[row]],
+                    [us[col], vs[row], us[col + 1] - us[col], vs[row + 1] - vs[row]],
+                    color
+                ));
+            }
+        }
+
+        let uniforms = uniform! {
+            tex: texture.sampled()
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                .minify_filter(glium::uniforms::MinifySamplerFilter::NearestMipmapNearest)
+        };
+
+        self.draw_once(
+            &PrimitiveType::TrianglesList, false, true, program, &uniforms, params, vertices
+        )
+    }
+
+    /// Anti-aliased rect with rounded corners, edges are feathered over a pixel by the shape shader.
+    pub fn rounded_rect<B, C>(&mut self, bounds: B, radius: f32, color: C) -> Result<()>
+        where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        let [x, y, w, h] = bounds.into();
+        let radius = radius.max(0.0).min(w.min(h) / 2.0);
+        let vertices = shape_quad([x + w / 2.0, y + h / 2.0], [1.0, 0.0], [w / 2.0, h / 2.0], radius, color.into());
+        self.shapes(vertices)
+    }
+
+    /// Queues an outline of `width` kept inside `bounds`, built from quads so it does not depend
+    /// on the line widths supported by the driver.
+    pub fn border_rect<B, C>(&mut self, bounds: B, width: f32, color: C) where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+        let [x, y, w, h] = bounds.into();
+        let color = color.into();
+        let width = width.max(0.0).min(w.min(h) / 2.0);
+        if width == 0.0 {
+            return;
+        }
+        self.queue_rect([x, y, w, width], color);
+        self.queue_rect([x, y + h - width, w, width], color);
+        self.queue_rect([x, y + width, width, h - width * 2.0], color);
+        self.queue_rect([x + w - width, y + width, width, h - width * 2.0], color);
+    }
+
+    /// Anti-aliased outline of a rounded box, falls back to `border_rect` without a radius.
+    pub fn rounded_border_rect<B, C>(&mut self, bounds: B, width: f32, radius: f32, color: C) -> Result<()>
+        where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        let [x, y, w, h] = bounds.into();
+        let radius = radius.max(0.0).min(w.min(h) / 2.0);
+        if radius == 0.0 {
+            self.border_rect([x, y, w, h], width, color);
+            return Ok(());
+        }
+        let width = width.max(0.0).min(w.min(h) / 2.0);
+        if width == 0.0 {
+            return Ok(());
+        }
+        let mut vertices = shape_quad([x + w / 2.0, y + h / 2.0], [1.0, 0.0], [w / 2.0, h / 2.0], radius, color.into());
+        for vertex in &mut vertices {
+            vertex.pos[2] = width;
+        }
+        self.shapes(vertices)
+    }
+
+    /// Rounded box with its own radius per corner, clockwise from the top left one.
+    pub fn rounded_rect_corners<B, C>(&mut self, bounds: B, radii: [f32; 4], color: C) -> Result<()>
+        where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        self.shapes(corner_quads(bounds.into(), radii, 0.0, color.into()))
+    }
+
+    pub fn rounded_border_rect_corners<B, C>(&mut self, bounds: B, width: f32, radii: [f32; 4], color: C) -> Result<()>
+        where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        let [x, y, w, h] = bounds.into();
+        let width = width.max(0.0).min(w.min(h) / 2.0);
+        if width == 0.0 {
+            return Ok(());
+        }
+        self.shapes(corner_quads([x, y, w, h], radii, width, color.into()))
+    }
+
+    /// Queues a rect blending linearly from `from` to `to`, top to bottom or left to right.
+    pub fn gradient_rect<B, C>(&mut self, bounds: B, from: C, to: C, vertical: bool)
+        where B: Into<[f32; 4]>, C: Into<[f32; 4]> {
+
+        let [x, y, w, h] = bounds.into();
+        let from = self.working_color(from.into());
+        let to = self.working_color(to.into());
+        let (top_right, bottom_left) = if vertical { (from, to) } else { (to, from) };
+        self.queue(None, &PaintOptions::default(), [x, y, w, h], vec![
+            Vertex::pos([x, y, 0.0]).color(from),
+            Vertex::pos([x + w, y, 0.0]).color(top_right),
+            Vertex::pos([x + w, y + h, 0.0]).color(to),
+            Vertex::pos([x, y + h, 0.0]).color(bottom_left),
+        ]);
+    }
+
+    pub fn circle<C>(&mut self, center: [f32; 2], radius: f32, color: C) -> Result<()> where C: Into<[f32; 4]> {
+        let vertices = shape_quad(center, [1.0, 0.0], [radius, radius], radius, color.into());
+        self.shapes(vertices)
+    }
+
+    /// Anti-aliased polyline, every segment is a capsule so joins and caps are round.
+    pub fn smooth_polyline<C>(&mut self, points: &[[f32; 2]], width: f32, color: C) -> Result<()>
+        where C: Into<[f32; 4]> {
+
+        let color = color.into();
+        let hw = width / 2.0;
+        let mut vertices = Vec::new();
+        for segment in points.windows(2) {
+            let ([ax, ay], [bx, by]) = (segment[0], segment[1]);
+            let (dx, dy) = (bx - ax, by - ay);
+            let length = (dx * dx + dy * dy).sqrt();
+            let axis = if length > 0.0 { [dx / length, dy / length] } else { [1.0, 0.0] };
+            let center = [(ax + bx) / 2.0, (ay + by) / 2.0];
+            vertices.extend(shape_quad(center, axis, [length / 2.0 + hw, hw], hw, color));
+        }
+        self.shapes(vertices)
+    }
+
+    /// Polyline expanded into quads by a geometry shader, for plots with many points. Falls back to
+    /// `smooth_polyline` where geometry shaders are not supported.
+    pub fn wide_polyline<C>(&mut self, points: &[[f32; 2]], width: f32, color: C) -> Result<()>
+        where C: Into<[f32; 4]> {
+
+        let program = match self.shaders().borrow().wide_line() {
+            Some(program) => program,
+            None => return self.smooth_polyline(points, width, color)
+        };
+        if points.len() < 2 {
+            return Ok(());
+        }
+        let color = color.into();
+        let vertices = points.iter()
+            .map(|&[x, y]| Vertex::pos([x, y, 0.0]).color(color))
+            .collect();
+        let uniforms = uniform! { width: width };
+        let params = DrawParameters {
+            blend: alpha_blending(),
+            .. Default::default()
+        };
+        self.draw_once(&PrimitiveType::LineStrip, false, false, &program, &uniforms, &params, vertices)
+    }
+
+    /// Round point sprites of `size` expanded by a geometry shader, drawn as circles where geometry
+    /// shaders are not supported.
+    pub fn point_sprites<C>(&mut self, points: &[[f32; 2]], size: f32, color: C) -> Result<()>
+        where C: Into<[f32; 4]> {
+
+        let color = color.into();
+        let program = match self.shaders().borrow().point_sprite() {
+            Some(program) => program,
+            None => {
+                let radius = size / 2.0;
+                let vertices = points.iter()
+                    .flat_map(|&center| shape_quad(center, [1.0, 0.0], [radius, radius], radius, color))
+                    .collect();
+                return self.shapes(vertices);
+            }
+        };
+        let vertices = points.iter()
+            .map(|&[x, y]| Vertex::pos([x, y, 0.0]).color(color))
+            .collect();
+        let uniforms = uniform! { size: size };
+        let params = DrawParameters {
+            blend: alpha_blending(),
+            .. Default::default()
+        };
+        self.draw_once(&PrimitiveType::Points, false, false, &program, &uniforms, &params, vertices)
+    }
+
+    fn shapes(&mut self, vertices: Vec<Vertex>) -> Result<()> {
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        let program = self.shaders().borrow().shape();
+        let uniforms = uniform! {};
+        let params = DrawParameters {
+            blend: alpha_blending(),
+            .. Default::default()
+        };
+        self.draw_once(&PrimitiveType::TrianglesList, true, true, &program, &uniforms, &params, vertices)
+    }
+
+    pub fn polyline<C, U>(&mut self, points: &[[f32; 2]], width: f32, color: C, style: &LineStyle,
+                          program: &Program, uniforms: &U, params: &DrawParameters) -> Result<()>
+        where C: Into<[f32; 4]>, U: Uniforms {
+
+        let triangles = stroke_polyline(points, width, style);
+        self.triangles(triangles, color.into(), program, uniforms, params)
+    }
+
+    pub fn fill_path<C, U>(&mut self, path: &Path, color: C, program: &Program, uniforms: &U,
+                           params: &DrawParameters) -> Result<()>
+        where C: Into<[f32; 4]>, U: Uniforms {
+
+        let triangles = path::fill_path(path);
+        self.triangles(triangles, color.into(), program, uniforms, params)
+    }
+
+    pub fn stroke_path<C, U>(&mut self, path: &Path, width: f32, color: C, style: &LineStyle,
+                             program: &Program, uniforms: &U, params: &DrawParameters) -> Result<()>
+        where C: Into<[f32; 4]>, U: Uniforms {
+
+        let triangles = path::stroke_path(path, width, style);
+        self.triangles(triangles, color.into(), program, uniforms, params)
+    }
+
+    pub fn fill_path_with<C>(&mut self, path: &Path, color: C, paint: &PaintOptions) -> Result<()>
+        where C: Into<[f32; 4]> {
+
+        let triangles = path::fill_path(path);
+        self.paint_triangles(triangles, color.into(), paint)
+    }
+
+    pub fn stroke_path_with<C>(&mut self, path: &Path, width: f32, color: C, style: &LineStyle,
+                               paint: &PaintOptions) -> Result<()>
+        where C: Into<[f32; 4]> {
+
+        let triangles = path::stroke_path(path, width, style);
+        self.paint_triangles(triangles, color.into(), paint)
+    }
+
+    fn paint_triangles(&mut self, triangles: Vec<[f32; 2]>, color: [f32; 4], paint: &PaintOptions) -> Result<()> {
+        let program = self.shaders().borrow().default();
+        let uniforms = uniform! {};
+        self.triangles(triangles, color, &program, &uniforms, &paint.params())
+    }
+
+    fn triangles<U>(&mut self, triangles: Vec<[f32; 2]>, color: [f32; 4], program: &Program, uniforms: &U,
+                    params: &DrawParameters) -> Result<()> where U: Uniforms {
+        if triangles.is_empty() {
+            return Ok(());
+        }
+        let vertices = triangles.into_iter()
+            .map(|[x, y]| Vertex::pos([x, y, 0.0]).color(color))
+            .collect::<Vec<_>>();
+
+        self.draw_once(
+            &PrimitiveType::TrianglesList, false, false, program, uniforms, params, vertices
         )
     }
 
+    pub fn generic_shape<U>(&mut self, ty: &PrimitiveType, vertices: Vec<Vertex>, texture: bool,
+                            normal: bool, program: &Program, uniforms: &U, params: &DrawParameters) -> Result<()>
+        where U: Uniforms {
+
+        self.draw_once(ty, normal, texture, program, uniforms, params, vertices)
+    }
+
+    /// Draws vertices of a user defined format, the program has to match its attributes.
+    pub fn custom_shape<V, U>(&mut self, ty: &PrimitiveType, vertices: &[V], program: &Program, uniforms: &U,
+                              params: &DrawParameters) -> Result<()>
+        where V: UiVertex, U: Uniforms {
+
+        self.flush()?;
+        self.invalidate_recording();
+        let mut params = params.clone();
+        params.scissor = self.clip_scissor(params.scissor);
+        self.apply_clip_stencil(&mut params);
+        self.bound_texture = None;
+        self.stats.draw_calls += 1;
+        self.stats.vertices += vertices.len();
+        let query = self.gpu_timer.as_ref().and_then(|timer| timer.query());
+        params.time_elapsed_query = query.as_deref();
+        let buffers = self.buffers.clone();
+        let mut buffers = buffers.borrow_mut();
+        let frame = self.frame_uniforms();
+        let uniforms = &WithFrame { frame: &frame, uniforms };
+        if let Some(layer) = self.layers.last() {
+            let mut target = SimpleFrameBuffer::new(&self.display, &layer.texture)?;
+            DrawBuffer::draw_vertices(&mut buffers, &self.display, &mut target, ty, vertices, program, uniforms, &params)
+        } else {
+            DrawBuffer::draw_vertices(&mut buffers, &self.display, &mut self.target, ty, vertices, program, uniforms, &params)
+        }
+    }
+
+    /// Reads back the pixels of `bounds` drawn so far, from the current layer if one is pushed.
+    pub fn read_pixels<B>(&mut self, bounds: B) -> Result<RgbaImage> where B: Into<[f32; 4]> {
+        self.flush()?;
+        let rect = self.scissor(bounds);
+        let texture = self.display.create_layer(rect.width, rect.height, false)?;
+        let copy = SimpleFrameBuffer::new(&self.display, &texture)?;
+        let target = BlitTarget { left: 0, bottom: 0, width: rect.width as i32, height: rect.height as i32 };
+        if let Some(layer) = self.layers.last() {
+            SimpleFrameBuffer::new(&self.display, &layer.texture)?
+                .blit_color(&rect, &copy, &target, MagnifySamplerFilter::Nearest);
+        } else {
+            self.target.blit_color(&rect, &copy, &target, MagnifySamplerFilter::Nearest);
+        }
+        Ok(image_from_rows(texture.read()))
+    }
+
+    /// Parameters of a text draw with the canvas clip and color space applied.
+    fn text_parameters(&self, params: &FontParameters) -> FontParameters {
+        // the scissor of the parameters is in the transformed space and cannot follow it
+        let scissor = if self.transforms.is_empty() { params.scissor } else { None };
+        FontParameters {
+            scissor: self.clip_scissor(scissor),
+            stencil: params.stencil.or(self.clip_stencil()),
+            color: self.working_color(params.color),
+            shadow: params.shadow.map(|shadow| TextShadow { color: self.working_color(shadow.color), .. shadow }),
+            outline: params.outline.map(|outline| TextOutline { color: self.working_color(outline.color), .. outline }),
+            .. params.clone()
+        }
+    }
+
     pub fn get_text_size<T>(&self, text: T, params: &FontParameters) -> (f32, f32) where T: AsRef<str> {
         let fonts = self.fonts();
         let mut fonts = fonts.borrow_mut();
         fonts.get_string_bounds(text.as_ref(), params)
     }
 
-    pub fn text<T>(&mut self, text: T, x: f32, y: f32, params: &FontParameters)
+    pub fn font_metrics(&self, params: &FontParameters) -> FontMetrics {
+        self.fonts.borrow().metrics(params)
+    }
+
+    pub fn truncate_text<T>(&self, text: T, max_width: f32, mode: Truncation, params: &FontParameters) -> String
         where T: AsRef<str> {
 
+        let fonts = self.fonts();
+        let mut fonts = fonts.borrow_mut();
+        fonts.truncate_to_width(text, max_width, mode, params)
+    }
+
+    pub fn text<T>(&mut self, text: T, x: f32, y: f32, params: &FontParameters) -> Result<()>
+        where T: AsRef<str> {
+
+        self.flush()?;
         let text = text.as_ref();
+        if !self.recording.is_empty() {
+            self.record(DisplayCommand::Text { text: text.to_owned(), x, y, params: params.clone() });
+        }
         let viewport = self.viewport();
         let fonts = self.fonts().clone();
         let mut fonts = fonts.borrow_mut();
+        let params = self.text_parameters(params);
+
+        if let Some(layer) = self.layers.last() {
+            let mut target = SimpleFrameBuffer::new(&self.display, &layer.texture)?;
+            fonts.draw_string(&mut target, text, x, y, viewport, &params)?;
+        } else {
+            fonts.draw_string(&mut self.target, text, x, y, viewport, &params)?;
+        }
+        self.stats.draw_calls += 1;
+        self.stats.texture_binds += 1;
+        self.bound_texture = None;
+        Ok(())
+    }
+
+    /// Draws text turned clockwise by `angle` radians around its anchor at `x`, `y`.
+    /// Rotated text stays sharpest with `FontParameters::sdf`, it is only clipped by the canvas
+    /// clips, use a clip path to clip it to a rotated shape.
+    pub fn text_rotated<T>(&mut self, text: T, x: f32, y: f32, angle: f32, params: &FontParameters) -> Result<()>
+        where T: AsRef<str> {
+
+        if angle == 0.0 {
+            return self.text(text, x, y, params);
+        }
+        self.push_transform(
+            Matrix4::from_translation(Vector3::new(x, y, 0.0))
+                * Matrix4::from_angle_z(Rad(angle))
+                * Matrix4::from_translation(Vector3::new(-x, -y, 0.0))
+        )?;
+        let result = self.text(text, x, y, params);
+        self.pop_transform()?;
+        result
+    }
 
-        fonts.draw_string(&mut self.target, text, x, y, viewport, params);
+    pub fn layout_text<T>(&self, text: T, max_width: f32, params: &FontParameters) -> TextLayout where T: AsRef<str> {
+        let fonts = self.fonts();
+        let mut fonts = fonts.borrow_mut();
+        fonts.layout_text(text, max_width, params)
+    }
+
+    /// Draws a layout from `layout_text` with its top left corner at `x`, `y`.
+    pub fn draw_text_layout(&mut self, layout: &TextLayout, x: f32, y: f32, params: &FontParameters) -> Result<()> {
+        self.flush()?;
+        if !self.recording.is_empty() {
+            self.record(DisplayCommand::TextLayout { layout: layout.clone(), x, y, params: params.clone() });
+        }
+        let viewport = self.viewport();
+        let fonts = self.fonts().clone();
+        let mut fonts = fonts.borrow_mut();
+        let params = self.text_parameters(params);
+
+        if let Some(layer) = self.layers.last() {
+            let mut target = SimpleFrameBuffer::new(&self.display, &layer.texture)?;
+            fonts.draw_layout(&mut target, layout, x, y, viewport, &params)?;
+        } else {
+            fonts.draw_layout(&mut self.target, layout, x, y, viewport, &params)?;
+        }
+        self.stats.draw_calls += 1;
+        self.stats.texture_binds += 1;
+        self.bound_texture = None;
+        Ok(())
+    }
+
+    pub fn get_rich_text_size(&self, text: &RichText, params: &FontParameters) -> (f32, f32) {
+        let fonts = self.fonts();
+        let mut fonts = fonts.borrow_mut();
+        fonts.get_rich_text_bounds(text, params)
+    }
+
+    /// Draws spans of differently styled text as one paragraph, aligned like `text`.
+    pub fn rich_text(&mut self, text: &RichText, x: f32, y: f32, params: &FontParameters) -> Result<()> {
+        self.flush()?;
+        if !self.recording.is_empty() {
+            self.record(DisplayCommand::RichText { text: text.clone(), x, y, params: params.clone() });
+        }
+        let viewport = self.viewport();
+        let fonts = self.fonts().clone();
+        let mut fonts = fonts.borrow_mut();
+        let mut text = text.clone();
+        for span in text.spans.iter_mut() {
+            span.color = span.color.map(|color| self.working_color(color));
+        }
+        let params = self.text_parameters(params);
+
+        if let Some(layer) = self.layers.last() {
+            let mut target = SimpleFrameBuffer::new(&self.display, &layer.texture)?;
+            fonts.draw_rich_text(&mut target, &text, x, y, viewport, &params)?;
+        } else {
+            fonts.draw_rich_text(&mut self.target, &text, x, y, viewport, &params)?;
+        }
+        self.stats.draw_calls += 1;
+        self.stats.texture_binds += 1;
+        self.bound_texture = None;
+        Ok(())
+    }
+
+    /// Wraps the text to the width of `bounds` and aligns the lines inside it. With an ellipsis and
+    /// no line limit in `params.wrap`, as many lines are kept as fit the height of `bounds`.
+    pub fn text_wrapped<T, B>(&mut self, text: T, bounds: B, params: &FontParameters) -> Result<()>
+        where T: AsRef<str>, B: Into<[f32; 4]> {
+
+        let [x, y, w, h] = bounds.into();
+        let mut layout = self.layout_text(text.as_ref(), w, params);
+        if params.wrap.ellipsis && params.wrap.max_lines.is_none() && layout.height > h {
+            let mut limited = params.clone();
+            limited.wrap.max_lines = Some((h / layout.line_height).floor().max(1.0) as usize);
+            layout = self.layout_text(text.as_ref(), w, &limited);
+        }
+        let y = match params.align_vertical {
+            // the first baseline sits below the top of the bounds like for `Top`
+            TextAlignVertical::Top | TextAlignVertical::Baseline => y,
+            TextAlignVertical::Bottom => y + h - layout.height,
+            TextAlignVertical::Center => y + (h - layout.height) / 2.0
+        };
+        self.draw_text_layout(&layout, x, y, params)
     }
 
-    pub fn into_inner(self) -> S {
-        self.target
+    pub fn into_inner(mut self) -> Result<S> {
+        self.flush()?;
+        Ok(self.target)
     }
 }
\ No newline at end of file
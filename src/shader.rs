@@ -1,43 +1,452 @@
-use glium::program::Program;
+use glium::program::{Program, ProgramCreationError};
 use glium::Display;
 
 use std::rc::Rc;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, Duration, SystemTime};
+use std::fmt;
+
+use crate::backend::Renderer;
+use crate::error::Error;
+
+/// Programs of the manager that `load_from_dir` replaces
+const BUILT_IN: [&str; 9] = [
+    "font", "default", "textured", "shape", "tonemap", "post_fxaa", "post_blur", "post_vignette", "post_grade"
+];
+
+/// Programs with a geometry stage, only compiled where the context supports one
+const GEOMETRY_BUILT_IN: [&str; 2] = ["wide_line", "point_sprite"];
+
+// how often watched files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
 
 #[macro_export]
 macro_rules! shader {
     ($display:expr, $name:literal) => {{
-        use crate::glium::program::Program;
-        Program::from_source($display,
+        use $crate::backend::Renderer;
+        Renderer::compile_program($display,
             &include_str!(concat!("resources/shaders/", $name, ".vsh")),
             &include_str!(concat!("resources/shaders/", $name, ".fsh")),
             None
         ).expect(concat!("Unable to compile `", $name, "` shader"))
     }};
+    ($display:expr, $name:literal, geometry) => {{
+        use $crate::backend::Renderer;
+        Renderer::compile_program($display,
+            &include_str!(concat!("resources/shaders/", $name, ".vsh")),
+            &include_str!(concat!("resources/shaders/", $name, ".fsh")),
+            Some(&include_str!(concat!("resources/shaders/", $name, ".gsh")))
+        ).expect(concat!("Unable to compile `", $name, "` shader"))
+    }};
 }
 
+/// Compiled programs by name. Programs are GL objects bound to the context, so the manager is
+/// shared as `Rc<RefCell<ShaderManager>>` on the thread of its display and never sent to others.
 pub struct ShaderManager {
     display: Display,
-    programs: HashMap<String, Rc<Box<Program>>>
+    programs: HashMap<String, Rc<Box<Program>>>,
+    watched: HashMap<String, WatchedProgram>,
+    last_poll: Instant,
+    /// Sources available to `#include` by name before looking next to the including file
+    includes: HashMap<String, String>,
+    defines: Vec<(String, String)>,
+    /// Sources of every compiled program, kept to build its variants
+    sources: HashMap<String, ProgramSource>,
+    /// Programs compiled with extra defines, keyed by `variant_key`
+    variants: HashMap<String, Rc<Box<Program>>>
+}
+
+#[derive(Clone)]
+struct ProgramSource {
+    vertex: StageSource,
+    fragment: StageSource,
+    geometry: Option<StageSource>
+}
+
+/// Source files of a program compiled with `register_files`, with the time they were last compiled.
+struct WatchedProgram {
+    vertex: PathBuf,
+    fragment: PathBuf,
+    geometry: Option<PathBuf>,
+    modified: Option<SystemTime>
+}
+
+impl WatchedProgram {
+    fn modified(&self) -> Option<SystemTime> {
+        std::iter::once(&self.vertex).chain(Some(&self.fragment)).chain(self.geometry.as_ref())
+            .filter_map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+            .max()
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    /// A source file could not be read.
+    Io(PathBuf, std::io::Error),
+    /// An included source is missing or includes itself.
+    Include(String),
+    /// No program of that name was compiled, so it has no variants.
+    Unknown(String),
+    /// The program needs a stage the context does not support.
+    Unsupported(String),
+    /// The driver rejected the program, its info log is split into diagnostics.
+    Compile { program: String, diagnostics: Vec<ShaderDiagnostic>, error: ProgramCreationError },
+    /// The renderer failed for another reason.
+    Renderer(Error)
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io(path, e) => write!(f, "Shader reading failed: {}: {}", path.display(), e),
+            ShaderError::Include(reason) => write!(f, "Shader include failed: {}", reason),
+            ShaderError::Unknown(name) => write!(f, "Unknown shader `{}`", name),
+            ShaderError::Unsupported(reason) => write!(f, "Unsupported shader: {}", reason),
+            ShaderError::Renderer(e) => write!(f, "{}", e),
+            ShaderError::Compile { program, diagnostics, error } => {
+                write!(f, "Unable to compile `{}` shader", program)?;
+                if diagnostics.is_empty() {
+                    write!(f, ": {}", error)
+                } else {
+                    for diagnostic in diagnostics {
+                        write!(f, "\n{}", diagnostic)?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShaderStage {
+    Vertex, Fragment, Geometry
+}
+
+/// Message of the driver's info log. Lines refer to the file or include they came from before preprocessing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderDiagnostic {
+    /// Unknown for link errors
+    pub stage: Option<ShaderStage>,
+    /// Path of the source file, name of the include, or the program name and stage for sources given as strings
+    pub file: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Source of one stage before preprocessing.
+#[derive(Clone)]
+struct StageSource {
+    code: String,
+    /// Reported as the file of diagnostics
+    file: String,
+    /// Where includes missing from the registered ones are looked for
+    dir: Option<PathBuf>
+}
+
+impl StageSource {
+    fn file(code: String, path: &Path) -> StageSource {
+        StageSource { code, file: path.display().to_string(), dir: path.parent().map(Path::to_path_buf) }
+    }
+}
+
+/// Preprocessed source of one stage, with the file and line every line of it came from.
+struct Expanded {
+    code: String,
+    lines: Vec<(String, u32)>
+}
+
+/// Outcome of recompiling a program whose source files changed, a failed compilation keeps the previous program.
+#[derive(Debug)]
+pub struct ShaderReloaded {
+    pub name: String,
+    pub result: Result<(), ShaderError>
 }
 
 impl ShaderManager {
+    /// Panics if a built-in program does not compile, see `try_new`.
     pub fn new(display: &Display) -> ShaderManager {
-        let mut programs = HashMap::new();
-        programs.insert("font".into(), Rc::new(Box::new(
-            shader!(display, "font")
-        )));
-        programs.insert("default".into(), Rc::new(Box::new(
-            shader!(display, "default")
-        )));
-        programs.insert("textured".into(), Rc::new(Box::new(
-            shader!(display, "textured")
-        )));
-
-        ShaderManager {
+        match Self::try_new(display) {
+            Ok(manager) => manager,
+            Err(error) => panic!("{}", error)
+        }
+    }
+
+    pub fn try_new(display: &Display) -> Result<ShaderManager, ShaderError> {
+        let mut manager = ShaderManager {
             display: display.clone(),
-            programs
+            programs: HashMap::new(),
+            watched: HashMap::new(),
+            last_poll: Instant::now(),
+            includes: HashMap::new(),
+            defines: Vec::new(),
+            sources: HashMap::new(),
+            variants: HashMap::new()
+        };
+        manager.add_include("color.glsl", include_str!("resources/shaders/color.glsl"));
+        for name in &BUILT_IN {
+            let (vertex, fragment) = built_in_sources(name);
+            let vertex = StageSource { code: vertex.to_owned(), file: format!("{}.vsh", name), dir: None };
+            let fragment = StageSource { code: fragment.to_owned(), file: format!("{}.fsh", name), dir: None };
+            manager.compile((*name).to_owned(), ProgramSource { vertex, fragment, geometry: None })?;
+        }
+        if manager.supports_geometry() {
+            for name in &GEOMETRY_BUILT_IN {
+                let (vertex, fragment, geometry) = built_in_geometry_sources(name);
+                let vertex = StageSource { code: vertex.to_owned(), file: "wide.vsh".to_owned(), dir: None };
+                let fragment = StageSource { code: fragment.to_owned(), file: format!("{}.fsh", name), dir: None };
+                let geometry = StageSource { code: geometry.to_owned(), file: format!("{}.gsh", name), dir: None };
+                manager.compile((*name).to_owned(), ProgramSource { vertex, fragment, geometry: Some(geometry) })?;
+            }
         }
+        Ok(manager)
+    }
+
+    /// Whether programs may have a geometry stage, which needs OpenGL 3.2 or `GL_ARB_geometry_shader4`.
+    pub fn supports_geometry(&self) -> bool {
+        self.display.supports_geometry_shaders()
+    }
+
+    /// Makes `source` available to `#include "<name>"` in programs compiled afterwards.
+    pub fn add_include<N, S>(&mut self, name: N, source: S) where N: Into<String>, S: Into<String> {
+        self.includes.insert(name.into(), source.into());
+    }
+
+    /// Defines a macro for all programs compiled afterwards, inserted right after their `#version` line.
+    pub fn set_define<N, V>(&mut self, name: N, value: V) where N: Into<String>, V: ToString {
+        let name = name.into();
+        let value = value.to_string();
+        match self.defines.iter_mut().find(|(n, _)| *n == name) {
+            Some(define) => define.1 = value,
+            None => self.defines.push((name, value))
+        }
+    }
+
+    pub fn remove_define<N>(&mut self, name: N) where N: AsRef<str> {
+        self.defines.retain(|(n, _)| n != name.as_ref());
+    }
+
+    /// Compiles a program and registers it under `name`, replacing any program of that name.
+    pub fn register<N>(&mut self, name: N, vertex: &str, fragment: &str, geometry: Option<&str>)
+        -> Result<Rc<Box<Program>>, ShaderError> where N: Into<String> {
+
+        let name = name.into();
+        let vertex = StageSource { code: vertex.to_owned(), file: format!("{} vertex", name), dir: None };
+        let fragment = StageSource { code: fragment.to_owned(), file: format!("{} fragment", name), dir: None };
+        let geometry = geometry.map(|code| StageSource { code: code.to_owned(), file: format!("{} geometry", name), dir: None });
+        self.compile(name, ProgramSource { vertex, fragment, geometry })
+    }
+
+    /// Compiles the program registered as `name` with additional defines, given as `NAME` or `NAME=VALUE`,
+    /// so `#ifdef` blocks can switch features on and off in one source. Variants are cached under
+    /// every combination of defines and compiled again when the program is replaced.
+    pub fn variant<N>(&mut self, name: N, defines: &[&str]) -> Result<Rc<Box<Program>>, ShaderError> where N: AsRef<str> {
+        let name = name.as_ref();
+        let mut defines = defines.iter()
+            .map(|define| match define.find('=') {
+                Some(split) => (define[.. split].trim().to_owned(), define[split + 1 ..].trim().to_owned()),
+                None => (define.trim().to_owned(), "1".to_owned())
+            })
+            .collect::<Vec<_>>();
+        defines.sort();
+        defines.dedup_by(|a, b| a.0 == b.0);
+        if defines.is_empty() {
+            return self.get(name).ok_or_else(|| ShaderError::Unknown(name.to_owned()));
+        }
+        let key = variant_key(name, &defines);
+        if let Some(program) = self.variants.get(&key) {
+            return Ok(program.clone());
+        }
+        let source = self.sources.get(name).ok_or_else(|| ShaderError::Unknown(name.to_owned()))?;
+        let program = Rc::new(Box::new(self.build(&key, source, &defines)?));
+        self.variants.insert(key, program.clone());
+        Ok(program)
+    }
+
+    fn compile(&mut self, name: String, source: ProgramSource) -> Result<Rc<Box<Program>>, ShaderError> {
+        let program = Rc::new(Box::new(self.build(&name, &source, &[])?));
+        // variants of the replaced program are stale
+        let prefix = format!("{}#", name);
+        self.variants.retain(|key, _| !key.starts_with(&prefix));
+        self.programs.insert(name.clone(), program.clone());
+        self.sources.insert(name, source);
+        Ok(program)
+    }
+
+    fn build(&self, name: &str, source: &ProgramSource, defines: &[(String, String)]) -> Result<Program, ShaderError> {
+        if source.geometry.is_some() && !self.supports_geometry() {
+            return Err(ShaderError::Unsupported(format!("`{}` has a geometry stage, which the context does not support", name)));
+        }
+        let vertex = self.preprocess(&source.vertex, defines)?;
+        let fragment = self.preprocess(&source.fragment, defines)?;
+        let geometry = match &source.geometry {
+            Some(source) => Some(self.preprocess(source, defines)?),
+            None => None
+        };
+        self.display.compile_program(&vertex.code, &fragment.code, geometry.as_ref().map(|g| g.code.as_str()))
+            .map_err(|error| match error {
+                Error::Program(error) => {
+                    let diagnostics = diagnostics(name, &error, &vertex, &fragment, geometry.as_ref());
+                    ShaderError::Compile { program: name.to_owned(), diagnostics, error }
+                },
+                error => ShaderError::Renderer(error)
+            })
+    }
+
+    /// Expands `#include "<name>"` lines and inserts the defines after the `#version` line, which has to stay first.
+    /// `variant` defines follow the global ones.
+    fn preprocess(&self, source: &StageSource, variant: &[(String, String)]) -> Result<Expanded, ShaderError> {
+        let mut expanded = Expanded { code: String::new(), lines: Vec::new() };
+        self.expand(&source.code, &source.file, source.dir.as_deref(), &mut Vec::new(), &mut expanded)?;
+        let version_end = expanded.code.lines().next()
+            .filter(|line| line.trim_start().starts_with("#version"))
+            .map_or(0, |line| (line.len() + 1).min(expanded.code.len()));
+        let defines = self.defines.iter().chain(variant)
+            .map(|(name, value)| format!("#define {} {}\n", name, value))
+            .collect::<String>();
+        expanded.code.insert_str(version_end, &defines);
+        let at = if version_end > 0 { 1 } else { 0 };
+        for i in 0 .. self.defines.len() + variant.len() {
+            expanded.lines.insert(at + i, ("<defines>".to_owned(), i as u32 + 1));
+        }
+        Ok(expanded)
+    }
+
+    fn expand(&self, source: &str, file: &str, dir: Option<&Path>, stack: &mut Vec<String>, output: &mut Expanded)
+        -> Result<(), ShaderError> {
+
+        for (number, line) in source.lines().enumerate() {
+            let name = match include_name(line) {
+                Some(name) => name,
+                None => {
+                    output.code.push_str(line);
+                    output.code.push('\n');
+                    output.lines.push((file.to_owned(), number as u32 + 1));
+                    continue;
+                }
+            };
+            if stack.iter().any(|n| n == name) {
+                return Err(ShaderError::Include(format!("`{}` includes itself", name)));
+            }
+            let (source, dir) = match self.includes.get(name) {
+                Some(source) => (source.clone(), None),
+                None => {
+                    let path = dir.map(|dir| dir.join(name))
+                        .ok_or_else(|| ShaderError::Include(format!("`{}` is not registered", name)))?;
+                    let source = std::fs::read_to_string(&path).map_err(|e| ShaderError::Io(path.clone(), e))?;
+                    (source, path.parent().map(Path::to_path_buf))
+                }
+            };
+            stack.push(name.to_owned());
+            self.expand(&source, name, dir.as_deref(), stack, output)?;
+            stack.pop();
+        }
+        Ok(())
+    }
+
+    /// Compiles a program from source files, which are compiled again by `poll_changes` when they change.
+    pub fn register_files<N, P>(&mut self, name: N, vertex: P, fragment: P, geometry: Option<P>)
+        -> Result<Rc<Box<Program>>, ShaderError> where N: Into<String>, P: Into<PathBuf> {
+
+        let name = name.into();
+        let mut watched = WatchedProgram {
+            vertex: vertex.into(),
+            fragment: fragment.into(),
+            geometry: geometry.map(Into::into),
+            modified: None
+        };
+        watched.modified = watched.modified();
+        let result = self.compile_files(&name, &watched);
+        // watched even when broken, so fixing the files recovers it
+        self.watched.insert(name, watched);
+        result
+    }
+
+    /// Replaces the built-in programs with `<name>.vsh` and `<name>.fsh` from `dir`, where present,
+    /// and watches them for changes. Built-in programs that fail to compile are kept.
+    pub fn load_from_dir<P>(&mut self, dir: P) -> Vec<ShaderReloaded> where P: AsRef<Path> {
+        let dir = dir.as_ref();
+        let mut reloaded = Vec::new();
+        for name in &BUILT_IN {
+            // post-processing passes share one vertex shader
+            let vertex = dir.join(format!("{}.vsh", if name.starts_with("post_") { "post" } else { name }));
+            let fragment = dir.join(format!("{}.fsh", name));
+            if vertex.exists() && fragment.exists() {
+                let result = self.register_files(*name, vertex, fragment, None).map(|_| ());
+                reloaded.push(ShaderReloaded { name: (*name).to_owned(), result });
+            }
+        }
+        if self.supports_geometry() {
+            for name in &GEOMETRY_BUILT_IN {
+                let vertex = dir.join("wide.vsh");
+                let fragment = dir.join(format!("{}.fsh", name));
+                let geometry = dir.join(format!("{}.gsh", name));
+                if vertex.exists() && fragment.exists() && geometry.exists() {
+                    let result = self.register_files(*name, vertex, fragment, Some(geometry)).map(|_| ());
+                    reloaded.push(ShaderReloaded { name: (*name).to_owned(), result });
+                }
+            }
+        }
+        reloaded
+    }
+
+    /// Recompiles watched programs whose files changed since they were last compiled. Files are
+    /// checked at most every quarter of a second.
+    pub fn poll_changes(&mut self) -> Vec<ShaderReloaded> {
+        if self.watched.is_empty() || self.last_poll.elapsed() < WATCH_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+        let changed = self.watched.iter()
+            .filter_map(|(name, watched)| {
+                let modified = watched.modified();
+                if modified > watched.modified { Some((name.clone(), modified)) } else { None }
+            })
+            .collect::<Vec<_>>();
+        let mut reloaded = Vec::new();
+        for (name, modified) in changed {
+            let watched = self.watched.remove(&name).expect("Watched shader is missing");
+            let result = self.compile_files(&name, &watched).map(|_| ());
+            self.watched.insert(name.clone(), WatchedProgram { modified, .. watched });
+            reloaded.push(ShaderReloaded { name, result });
+        }
+        reloaded
+    }
+
+    fn compile_files(&mut self, name: &str, watched: &WatchedProgram) -> Result<Rc<Box<Program>>, ShaderError> {
+        let read = |path: &PathBuf| std::fs::read_to_string(path).map_err(|e| ShaderError::Io(path.clone(), e));
+        let vertex = read(&watched.vertex)?;
+        let fragment = read(&watched.fragment)?;
+        let geometry = match &watched.geometry {
+            Some(path) => Some(StageSource::file(read(path)?, path)),
+            None => None
+        };
+        self.compile(name.to_owned(), ProgramSource {
+            vertex: StageSource::file(vertex, &watched.vertex),
+            fragment: StageSource::file(fragment, &watched.fragment),
+            geometry
+        })
+    }
+
+    pub fn get<N>(&self, name: N) -> Option<Rc<Box<Program>>> where N: AsRef<str> {
+        self.programs.get(name.as_ref()).cloned()
     }
 
     pub fn font(&self) -> Rc<Box<Program>> {
@@ -51,4 +460,153 @@ impl ShaderManager {
     pub fn textured(&self) -> Rc<Box<Program>> {
         self.programs.get("textured".into()).cloned().expect("Textured shader is missing")
     }
-}
\ No newline at end of file
+
+    /// Anti-aliased rounded boxes described by a signed distance field, see `Canvas::rounded_rect`
+    pub fn shape(&self) -> Rc<Box<Program>> {
+        self.programs.get("shape".into()).cloned().expect("Shape shader is missing")
+    }
+
+    /// Composites HDR layers, see `Canvas::push_hdr_layer`
+    pub fn tonemap(&self) -> Rc<Box<Program>> {
+        self.programs.get("tonemap".into()).cloned().expect("Tone mapping shader is missing")
+    }
+
+    /// Expands lines into quads of the `width` uniform on the GPU, `None` without geometry shader support
+    pub fn wide_line(&self) -> Option<Rc<Box<Program>>> {
+        self.programs.get("wide_line").cloned()
+    }
+
+    /// Expands points into round sprites of the `size` uniform on the GPU, `None` without geometry shader support
+    pub fn point_sprite(&self) -> Option<Rc<Box<Program>>> {
+        self.programs.get("point_sprite").cloned()
+    }
+}
+
+fn built_in_sources(name: &str) -> (&'static str, &'static str) {
+    match name {
+        "font" => (include_str!("resources/shaders/font.vsh"), include_str!("resources/shaders/font.fsh")),
+        "default" => (include_str!("resources/shaders/default.vsh"), include_str!("resources/shaders/default.fsh")),
+        "textured" => (include_str!("resources/shaders/textured.vsh"), include_str!("resources/shaders/textured.fsh")),
+        "shape" => (include_str!("resources/shaders/shape.vsh"), include_str!("resources/shaders/shape.fsh")),
+        "tonemap" => (include_str!("resources/shaders/tonemap.vsh"), include_str!("resources/shaders/tonemap.fsh")),
+        "post_fxaa" => (include_str!("resources/shaders/post.vsh"), include_str!("resources/shaders/post_fxaa.fsh")),
+        "post_blur" => (include_str!("resources/shaders/post.vsh"), include_str!("resources/shaders/post_blur.fsh")),
+        "post_vignette" => (include_str!("resources/shaders/post.vsh"), include_str!("resources/shaders/post_vignette.fsh")),
+        "post_grade" => (include_str!("resources/shaders/post.vsh"), include_str!("resources/shaders/post_grade.fsh")),
+        _ => unreachable!("Unknown built-in shader `{}`", name)
+    }
+}
+
+fn built_in_geometry_sources(name: &str) -> (&'static str, &'static str, &'static str) {
+    let vertex = include_str!("resources/shaders/wide.vsh");
+    match name {
+        "wide_line" => (vertex, include_str!("resources/shaders/wide_line.fsh"), include_str!("resources/shaders/wide_line.gsh")),
+        "point_sprite" => (vertex, include_str!("resources/shaders/point_sprite.fsh"), include_str!("resources/shaders/point_sprite.gsh")),
+        _ => unreachable!("Unknown built-in shader `{}`", name)
+    }
+}
+
+/// Cache key of a variant, `name#A#B=2` for sorted defines.
+fn variant_key(name: &str, defines: &[(String, String)]) -> String {
+    let mut key = name.to_owned();
+    for (define, value) in defines {
+        key.push('#');
+        key.push_str(define);
+        if value != "1" {
+            key.push('=');
+            key.push_str(value);
+        }
+    }
+    key
+}
+
+/// Name of the file an `#include "<name>"` line refers to.
+fn include_name(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix("#include")?.trim();
+    rest.strip_prefix('"').and_then(|rest| rest.strip_suffix('"'))
+        .or_else(|| rest.strip_prefix('<').and_then(|rest| rest.strip_suffix('>')))
+}
+
+/// Splits the info log of a failed compilation, mapping lines back through the preprocessed sources.
+fn diagnostics(program: &str, error: &ProgramCreationError, vertex: &Expanded, fragment: &Expanded, geometry: Option<&Expanded>)
+    -> Vec<ShaderDiagnostic> {
+
+    // the stage is only named by the description, the variant fields differ between glium versions
+    let message = error.to_string();
+    let (description, log) = match message.find(": ") {
+        Some(split) => (&message[.. split], &message[split + 2 ..]),
+        None => return Vec::new()
+    };
+    let description = description.to_lowercase();
+    let (stage, expanded) = if description.contains("vertex") {
+        (Some(ShaderStage::Vertex), Some(vertex))
+    } else if description.contains("fragment") {
+        (Some(ShaderStage::Fragment), Some(fragment))
+    } else if description.contains("geometry") {
+        (Some(ShaderStage::Geometry), geometry)
+    } else {
+        (None, None)
+    };
+    log.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (line_number, column, text) = match parse_log_line(line) {
+                Some(parsed) => parsed,
+                None => (None, None, line.to_owned())
+            };
+            let origin = line_number.and_then(|n| expanded?.lines.get(n.checked_sub(1)? as usize));
+            ShaderDiagnostic {
+                stage,
+                file: origin.map_or_else(|| program.to_owned(), |(file, _)| file.clone()),
+                line: origin.map(|(_, line)| *line).or(line_number),
+                column,
+                message: text
+            }
+        })
+        .collect()
+}
+
+/// Line, column and message of an info log line in the formats of common drivers:
+/// `0:12(5): error: ...` (Mesa), `0(12) : error C0000: ...` (Nvidia) and `ERROR: 0:12: ...` (AMD, Apple).
+fn parse_log_line(line: &str) -> Option<(Option<u32>, Option<u32>, String)> {
+    let mut rest = line;
+    let mut severity = "";
+    for prefix in &["ERROR:", "WARNING:"] {
+        if let Some(stripped) = rest.strip_prefix(prefix) {
+            severity = *prefix;
+            rest = stripped.trim_start();
+        }
+    }
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    // the index of the source string, always zero for a single source
+    let end = digits(rest);
+    if end == 0 {
+        return None;
+    }
+    rest = &rest[end ..];
+    let (line_number, column);
+    if let Some(after) = rest.strip_prefix(':') {
+        let end = digits(after);
+        line_number = after[.. end].parse().ok();
+        rest = &after[end ..];
+        column = match rest.strip_prefix('(') {
+            Some(after) => {
+                let end = digits(after);
+                rest = after[end ..].strip_prefix(')').unwrap_or(&after[end ..]);
+                after[.. end].parse().ok()
+            },
+            None => None
+        };
+    } else if let Some(after) = rest.strip_prefix('(') {
+        let end = digits(after);
+        line_number = after[.. end].parse().ok();
+        rest = after[end ..].strip_prefix(')').unwrap_or(&after[end ..]);
+        column = None;
+    } else {
+        return None;
+    }
+    let text = rest.trim_start().strip_prefix(':').unwrap_or(rest).trim();
+    let text = if severity.is_empty() { text.to_owned() } else { format!("{} {}", severity.to_lowercase(), text) };
+    Some((line_number, column, text))
+}
@@ -0,0 +1,56 @@
+use std::rc::Rc;
+
+use glium::Rect;
+use glium::texture::{RawImage2d, SrgbTexture2d, MipmapsOption};
+
+use crate::texture::{TextureManager, TextureError};
+
+/// Texture whose pixels are replaced in place every frame, for video and camera previews.
+/// It stays registered under its name, so it is drawn like any other texture.
+pub struct StreamingTexture {
+    name: String,
+    texture: Rc<Box<SrgbTexture2d>>,
+    size: (u32, u32)
+}
+
+impl StreamingTexture {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Uploads RGBA pixels into the `[x, y, width, height]` region, rows going from top to bottom.
+    /// Panics if the region is out of bounds or `pixels` does not fill it.
+    pub fn write_region(&self, region: [u32; 4], pixels: Vec<u8>) {
+        let [x, y, w, h] = region;
+        assert!(x + w <= self.size.0 && y + h <= self.size.1, "Region out of texture bounds");
+        assert_eq!(pixels.len(), w as usize * h as usize * 4, "Pixel buffer does not match the region");
+        let rect = Rect { left: x, bottom: y, width: w, height: h };
+        self.texture.write(rect, RawImage2d::from_raw_rgba(pixels, (w, h)));
+    }
+
+    /// Replaces the whole frame.
+    pub fn write_frame(&self, pixels: Vec<u8>) {
+        self.write_region([0, 0, self.size.0, self.size.1], pixels);
+    }
+}
+
+impl TextureManager {
+    /// Allocates a transparent streaming texture of `size` pixels and registers it under `name`.
+    pub fn create_streaming<N>(&mut self, name: N, size: (u32, u32)) -> Result<StreamingTexture, TextureError>
+        where N: Into<String> {
+
+        let name = name.into();
+        let pixels = vec![0; size.0 as usize * size.1 as usize * 4];
+        // mipmaps would have to be regenerated after every upload
+        let texture = SrgbTexture2d::with_mipmaps(&self.display, RawImage2d::from_raw_rgba(pixels, size),
+                                                  MipmapsOption::NoMipmap)?;
+        let texture = Rc::new(Box::new(texture));
+        self.unload(&name);
+        self.textures.insert(name.clone(), texture.clone());
+        Ok(StreamingTexture { name, texture, size })
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use glium::texture::SrgbTexture2d;
+use usvg::{Tree, Options, FitTo};
+
+use crate::backend::{Renderer, TextureData};
+use crate::texture::{TextureManager, TextureError};
+
+/// Parsed document of a texture loaded with `load_svg`, kept to rasterize it again at another scale.
+pub(crate) struct SvgSource {
+    tree: Tree,
+    size: (u32, u32)
+}
+
+impl TextureManager {
+    /// Rasterizes an SVG file into a texture of `size` logical pixels, times the current scale factor.
+    pub fn load_svg<N, P>(&mut self, name: N, path: P, size: (u32, u32)) -> Result<Rc<Box<SrgbTexture2d>>, TextureError>
+        where N: Into<String>, P: AsRef<Path> {
+
+        let bytes = std::fs::read(path)?;
+        self.load_svg_bytes(name, &bytes, size)
+    }
+
+    pub fn load_svg_bytes<N>(&mut self, name: N, bytes: &[u8], size: (u32, u32)) -> Result<Rc<Box<SrgbTexture2d>>, TextureError>
+        where N: Into<String> {
+
+        let tree = Tree::from_data(bytes, &Options::default()).map_err(TextureError::Svg)?;
+        let name = name.into();
+        let texture = Rc::new(Box::new(self.rasterize_svg(&tree, size)?));
+        self.svgs.insert(name.clone(), SvgSource { tree, size });
+        self.textures.insert(name, texture.clone());
+        Ok(texture)
+    }
+
+    fn rasterize_svg(&self, tree: &Tree, size: (u32, u32)) -> Result<SrgbTexture2d, TextureError> {
+        let factor = self.scale_factor();
+        let w = (size.0 as f32 * factor).round().max(1.0) as u32;
+        let h = (size.1 as f32 * factor).round().max(1.0) as u32;
+        let mut pixmap = tiny_skia::Pixmap::new(w, h).expect("Invalid SVG size");
+        resvg::render(tree, FitTo::Size(w, h), pixmap.as_mut());
+
+        // the rasterizer produces premultiplied colors
+        let mut pixels = pixmap.data().to_vec();
+        for pixel in pixels.chunks_mut(4) {
+            let alpha = pixel[3] as u32;
+            if alpha > 0 && alpha < 255 {
+                for channel in &mut pixel[.. 3] {
+                    *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+                }
+            }
+        }
+        Ok(self.display.create_texture(TextureData::rgba(pixels, (w, h)))?)
+    }
+
+    /// Replaces the textures of all loaded SVGs with ones matching the current scale factor.
+    pub(crate) fn rasterize_svgs(&mut self) {
+        let mut textures = Vec::new();
+        for (name, source) in &self.svgs {
+            match self.rasterize_svg(&source.tree, source.size) {
+                Ok(texture) => textures.push((name.clone(), texture)),
+                // the texture of the previous scale stays in use
+                Err(_) => ()
+            }
+        }
+        for (name, texture) in textures {
+            self.textures.insert(name, Rc::new(Box::new(texture)));
+        }
+    }
+}
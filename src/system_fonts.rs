@@ -0,0 +1,103 @@
+use fontdb::{Database, Family, Query, Style, Weight, ID};
+
+use crate::font::{FontManager, Error};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SystemFamily {
+    Named(String),
+    /// Sans serif family the system uses for its own interface
+    Ui,
+    Serif,
+    /// Preferred monospace family, or any installed monospace font
+    Monospace
+}
+
+/// Fonts installed on the system, enumerated once when created.
+pub struct SystemFonts {
+    database: Database
+}
+
+impl SystemFonts {
+    /// Scans the system font directories, which can take a moment with many fonts installed.
+    pub fn load() -> SystemFonts {
+        let mut database = Database::new();
+        database.load_system_fonts();
+        SystemFonts { database }
+    }
+
+    /// Names of all installed families, sorted.
+    pub fn families(&self) -> Vec<String> {
+        let mut families = self.database.faces().iter()
+            .map(|face| face.family.clone())
+            .collect::<Vec<_>>();
+        families.sort();
+        families.dedup();
+        families
+    }
+
+    pub fn monospace_families(&self) -> Vec<String> {
+        let mut families = self.database.faces().iter()
+            .filter(|face| face.monospaced)
+            .map(|face| face.family.clone())
+            .collect::<Vec<_>>();
+        families.sort();
+        families.dedup();
+        families
+    }
+
+    pub fn has_family(&self, family: &SystemFamily) -> bool {
+        self.find(family, false, false).is_some()
+    }
+
+    /// Face of the family with exactly the requested style, nearest matches of other styles are ignored.
+    fn find(&self, family: &SystemFamily, bold: bool, italic: bool) -> Option<ID> {
+        let families = [match family {
+            SystemFamily::Named(name) => Family::Name(name),
+            SystemFamily::Ui => Family::SansSerif,
+            SystemFamily::Serif => Family::Serif,
+            SystemFamily::Monospace => Family::Monospace
+        }];
+        let query = Query {
+            families: &families,
+            weight: if bold { Weight::BOLD } else { Weight::NORMAL },
+            style: if italic { Style::Italic } else { Style::Normal },
+            .. Query::default()
+        };
+        let matches = |id: ID| self.database.face(id).map_or(false, |face| {
+            (face.weight.0 >= Weight::SEMIBOLD.0) == bold && (face.style != Style::Normal) == italic
+        });
+        match self.database.query(&query) {
+            Some(id) if matches(id) => Some(id),
+            _ if *family == SystemFamily::Monospace => {
+                // the generic monospace family may name a font that is not installed
+                self.database.faces().iter()
+                    .find(|face| face.monospaced && matches(face.id))
+                    .map(|face| face.id)
+            },
+            _ => None
+        }
+    }
+
+    fn face_data(&self, id: ID) -> Option<(Vec<u8>, usize)> {
+        self.database.with_face_data(id, |data, index| (data.to_vec(), index as usize))
+    }
+}
+
+impl FontManager {
+    /// Registers the faces of an installed family under `name`. Styles the family lacks are faked
+    /// like for any other family.
+    pub fn load_system_font<N>(&mut self, name: N, fonts: &SystemFonts, family: &SystemFamily) -> Result<(), Error>
+        where N: Into<String> {
+
+        let name = name.into();
+        if fonts.find(family, false, false).is_none() {
+            return Err(Error::FamilyNotFound(format!("{:?}", family)));
+        }
+        for &(bold, italic) in &[(false, false), (true, false), (false, true), (true, true)] {
+            if let Some((bytes, index)) = fonts.find(family, bold, italic).and_then(|id| fonts.face_data(id)) {
+                self.load_collection_face_bytes(name.clone(), bold, italic, bytes, index)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Sender, Receiver};
+use futures::future::{BoxFuture, FutureExt};
+use futures::executor::ThreadPool;
+
+use crate::window::WindowHandle;
+
+/// Executor running the futures of `UiTasks`, implemented for the futures thread pool and,
+/// with the `tokio` feature, for tokio runtime handles. The tokio runtime itself is up to the application.
+pub trait Spawner {
+    fn spawn_boxed(&self, future: BoxFuture<'static, ()>);
+}
+
+impl Spawner for ThreadPool {
+    fn spawn_boxed(&self, future: BoxFuture<'static, ()>) {
+        self.spawn_ok(future);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Spawner for tokio::runtime::Handle {
+    fn spawn_boxed(&self, future: BoxFuture<'static, ()>) {
+        self.spawn(future);
+    }
+}
+
+type Completion = (u64, Box<dyn Any + Send>);
+type Continuation = Box<dyn FnOnce(Box<dyn Any + Send>, &WindowHandle)>;
+/// Called from the thread of a task once its result was sent, to wake the UI thread for it.
+pub(crate) type TaskWaker = Arc<dyn Fn() + Send + Sync>;
+
+/// Futures running off the UI thread whose results are handed back to it, see `WindowHandle::spawn`.
+pub struct UiTasks {
+    spawner: Box<dyn Spawner>,
+    sender: Sender<Completion>,
+    receiver: Receiver<Completion>,
+    waker: Option<TaskWaker>,
+    /// Tasks still running, with what to do on the UI thread once they finish
+    continuations: HashMap<u64, Option<Continuation>>,
+    next_id: u64
+}
+
+impl UiTasks {
+    /// Runs the futures on a thread pool with a thread per CPU.
+    pub fn new() -> UiTasks {
+        UiTasks::with_spawner(ThreadPool::new().expect("Failed to start task thread pool"))
+    }
+
+    pub fn with_spawner<S>(spawner: S) -> UiTasks where S: Spawner + 'static {
+        let (sender, receiver) = mpsc::channel();
+        UiTasks {
+            spawner: Box::new(spawner),
+            sender,
+            receiver,
+            waker: None,
+            continuations: HashMap::new(),
+            next_id: 0
+        }
+    }
+
+    /// Tasks that have not finished or whose result was not handed back yet.
+    pub fn pending(&self) -> usize {
+        self.continuations.len()
+    }
+
+    pub(crate) fn set_waker(&mut self, waker: Option<TaskWaker>) {
+        self.waker = waker;
+    }
+
+    fn spawn<F>(&mut self, future: F) -> u64 where F: Future + Send + 'static, F::Output: Send + 'static {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.continuations.insert(id, None);
+        let sender = self.sender.clone();
+        let waker = self.waker.clone();
+        self.spawner.spawn_boxed(async move {
+            let result: Box<dyn Any + Send> = Box::new(future.await);
+            // the window may be gone by the time the task finishes
+            if sender.send((id, result)).is_ok() {
+                if let Some(waker) = waker {
+                    waker();
+                }
+            }
+        }.boxed());
+        id
+    }
+
+    /// Takes the continuations of finished tasks, they are called without the tasks borrowed
+    /// as they may spawn new ones.
+    fn take_finished(&mut self) -> Vec<(Continuation, Box<dyn Any + Send>)> {
+        let mut finished = Vec::new();
+        while let Ok((id, result)) = self.receiver.try_recv() {
+            if let Some(Some(continuation)) = self.continuations.remove(&id) {
+                finished.push((continuation, result));
+            }
+        }
+        finished
+    }
+}
+
+impl Default for UiTasks {
+    fn default() -> Self {
+        UiTasks::new()
+    }
+}
+
+/// Task started with `WindowHandle::spawn`, its result is dropped unless `then_ui` is called.
+#[must_use = "the result of the task is dropped unless `then_ui` is called"]
+pub struct UiTask<R> {
+    id: u64,
+    tasks: Rc<RefCell<UiTasks>>,
+    result: PhantomData<fn() -> R>
+}
+
+impl<R> UiTask<R> where R: Send + 'static {
+    pub(crate) fn spawn<F>(tasks: Rc<RefCell<UiTasks>>, future: F) -> UiTask<R> where F: Future<Output = R> + Send + 'static {
+        let id = tasks.borrow_mut().spawn(future);
+        UiTask { id, tasks, result: PhantomData }
+    }
+
+    /// Calls `then` on the UI thread with the result of the task, so it may touch widgets and GL resources.
+    pub fn then_ui<F>(self, then: F) where F: FnOnce(R, &WindowHandle) + 'static {
+        let continuation: Continuation = Box::new(move |result, window| {
+            let result = result.downcast::<R>().expect("Task result of an unexpected type");
+            then(*result, window);
+        });
+        if let Some(slot) = self.tasks.borrow_mut().continuations.get_mut(&self.id) {
+            *slot = Some(continuation);
+        }
+    }
+}
+
+/// Hands the results of finished tasks to their continuations, returns whether any finished.
+pub(crate) fn poll(tasks: &RefCell<UiTasks>, window: &WindowHandle) -> bool {
+    let finished = tasks.borrow_mut().take_finished();
+    let any = !finished.is_empty();
+    for (continuation, result) in finished {
+        continuation(result, window);
+    }
+    any
+}
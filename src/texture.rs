@@ -1,65 +1,578 @@
-use glium::texture::{Texture2d, RawImage2d, SrgbTexture2d};
+use glium::texture::{SrgbTexture2d, CompressedSrgbTexture2d, TextureCreationError};
+use glium::framebuffer::DepthStencilRenderBuffer;
 use glium::Display;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
-use std::path::Path;
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::fmt;
+use std::borrow::Cow;
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::thread;
 
-use image::{self, ImageFormat, GenericImageView, ColorType};
+use image::{self, ImageFormat, GenericImageView, ColorType, DynamicImage};
 
+use crate::backend::{Renderer, TextureData, PixelFormat};
+use crate::intern::ResourceId;
+use crate::error::Error;
+
+/// Textures by name. Textures are GL objects bound to the context, so the manager is shared as
+/// `Rc<RefCell<TextureManager>>` on the thread of its display; images are decoded off it with `load_async`.
 pub struct TextureManager {
     pub display: Display,
-    pub textures: HashMap<String, Rc<Box<SrgbTexture2d>>>
+    pub textures: HashMap<String, Rc<Box<SrgbTexture2d>>>,
+    /// Block compressed textures, only drawn by the batched quads of a canvas
+    pub compressed: HashMap<String, Rc<Box<CompressedSrgbTexture2d>>>,
+    /// Returned in place of missing textures when set
+    fallback: Option<Rc<Box<SrgbTexture2d>>>,
+    placeholder: Option<Rc<Box<SrgbTexture2d>>>,
+    loading: HashSet<String>,
+    decoded: (Sender<(String, Result<DynamicImage, TextureError>)>, Receiver<(String, Result<DynamicImage, TextureError>)>),
+    scale_factor: f32,
+    /// Bytes of the base level of compressed textures
+    compressed_sizes: HashMap<String, usize>,
+    /// Tick of the last lookup of each texture, for evicting the least recently used ones
+    last_used: RefCell<HashMap<String, u64>>,
+    clock: Cell<u64>,
+    budget: Option<usize>,
+    evictions: u64,
+    /// Depth and stencil buffers of textures created with `create_render_target`
+    render_targets: HashMap<String, Rc<DepthStencilRenderBuffer>>,
+    #[cfg(feature = "svg")]
+    pub(crate) svgs: HashMap<String, crate::svg::SvgSource>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureInfo {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Estimated video memory including mipmaps
+    pub memory: usize,
+    pub compressed: bool
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextureStats {
+    /// Registered textures, largest first
+    pub textures: Vec<TextureInfo>,
+    pub memory: usize,
+    pub budget: Option<usize>,
+    pub evictions: u64
+}
+
+/// Outcome of `TextureManager::load_async`, reported once the texture is uploaded or failed to load.
+#[derive(Debug)]
+pub struct TextureLoaded {
+    pub name: String,
+    pub result: Result<(), TextureError>
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    /// No texture was loaded under this name.
+    Missing(String),
+    /// The image file could not be read.
+    Io(std::io::Error),
+    /// The file is not an image in a supported format.
+    Decode(image::ImageError),
+    /// The texture could not be created on the GPU.
+    Allocation(TextureCreationError),
+    /// The renderer failed otherwise, such as creating the depth buffer of a render target.
+    Renderer(Error),
+    /// The file is not a DDS or KTX2 container of a supported compressed format.
+    UnsupportedCompression(String),
+    /// The SVG document could not be parsed.
+    #[cfg(feature = "svg")]
+    Svg(usvg::Error)
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Missing(name) => write!(f, "Missing texture: {}", name),
+            TextureError::Io(e) => write!(f, "Image reading failed: {}", e),
+            TextureError::Decode(e) => write!(f, "Image decoding failed: {}", e),
+            TextureError::Allocation(e) => write!(f, "Texture allocation failed: {}", e),
+            TextureError::Renderer(e) => write!(f, "{}", e),
+            TextureError::UnsupportedCompression(reason) => write!(f, "Unsupported compressed texture: {}", reason),
+            #[cfg(feature = "svg")]
+            TextureError::Svg(e) => write!(f, "SVG parsing failed: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<std::io::Error> for TextureError {
+    fn from(error: std::io::Error) -> Self {
+        TextureError::Io(error)
+    }
 }
 
+impl From<image::ImageError> for TextureError {
+    fn from(error: image::ImageError) -> Self {
+        TextureError::Decode(error)
+    }
+}
+
+impl From<Error> for TextureError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Texture(e) => TextureError::Allocation(e),
+            e => TextureError::Renderer(e)
+        }
+    }
+}
+
+impl From<TextureCreationError> for TextureError {
+    fn from(error: TextureCreationError) -> Self {
+        TextureError::Allocation(error)
+    }
+}
+
+/// Region of a texture, `uv` is `[u, v, width, height]` in normalized texture coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    pub texture: ResourceId,
+    pub uv: [f32; 4]
+}
+
+impl Sprite {
+    pub fn new<T>(texture: T, uv: [f32; 4]) -> Sprite where T: Into<ResourceId> {
+        Sprite { texture: texture.into(), uv }
+    }
+
+    pub fn whole<T>(texture: T) -> Sprite where T: Into<ResourceId> {
+        Self::new(texture, [0.0, 0.0, 1.0, 1.0])
+    }
+
+    /// Builds a sprite from a pixel region of an atlas with the given dimensions.
+    pub fn from_pixels<T>(texture: T, region: [u32; 4], atlas_size: (u32, u32)) -> Sprite where T: Into<ResourceId> {
+        let (w, h) = (atlas_size.0 as f32, atlas_size.1 as f32);
+        Self::new(texture, [
+            region[0] as f32 / w, region[1] as f32 / h,
+            region[2] as f32 / w, region[3] as f32 / h
+        ])
+    }
+}
+
+/// Registers `resources/<name>.png` of the calling crate, embedded at compile time. Panics if it can not be decoded.
 #[macro_export]
 macro_rules! texture {
-    ($manager:expr, $name:literal) => {{
-        use crate::image::{self, GenericImageView};
-        static IMAGE_BUF: &'static [u8] = include_bytes!(concat!("resources/", $name, ".png"));
-        let manager = $manager;
-        let image = image::load_from_memory_with_format(&IMAGE_BUF, image::ImageFormat::Png)
-            .expect("Image loading failed");
-        let size = image.dimensions();
-        let image = crate::glium::texture::RawImage2d::from_raw_rgba(image.raw_pixels(), size);
-        let texture = crate::glium::texture::SrgbTexture2d::new(manager.display, image).expect("Texture allocation failed");
-        manager.textures.insert($name.into(), std::rc::Rc::new(Box::new(texture)));
-    }};
+    ($manager:expr, $name:literal) => {
+        $manager.load_bytes($name, include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/", $name, ".png")))
+            .expect("Texture loading failed")
+    };
+}
+
+/// Embeds the listed image files of a directory of the calling crate into a `TextureBundle`, named
+/// after the files without their extension.
+///
+/// ```ignore
+/// let bundle = include_textures!("resources/textures", ["button.png", "icons/close.png"]);
+/// textures.load_bundle(&bundle)?;
+/// ```
+#[macro_export]
+macro_rules! include_textures {
+    ($dir:literal, [$($file:literal),* $(,)?]) => {
+        $crate::texture::TextureBundle::new()
+            $(.file($file, &include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir, "/", $file))[..]))*
+    };
+}
+
+/// Encoded images registered together by `TextureManager::load_bundle`.
+#[derive(Debug, Clone, Default)]
+pub struct TextureBundle {
+    entries: Vec<(String, Cow<'static, [u8]>)>
+}
+
+impl TextureBundle {
+    pub fn new() -> TextureBundle {
+        TextureBundle::default()
+    }
+
+    pub fn entry<N, B>(mut self, name: N, bytes: B) -> Self where N: Into<String>, B: Into<Cow<'static, [u8]>> {
+        self.entries.push((name.into(), bytes.into()));
+        self
+    }
+
+    /// Adds an entry named after the file path without its extension, `icons/close.png` becomes `icons/close`.
+    pub fn file<B>(self, file: &str, bytes: B) -> Self where B: Into<Cow<'static, [u8]>> {
+        let name = match file.rfind('.') {
+            Some(dot) if !file[dot ..].contains('/') => &file[.. dot],
+            _ => file
+        };
+        self.entry(name, bytes)
+    }
+
+    /// Reads every image file under `dir` at runtime, for iterating on assets without rebuilding.
+    pub fn from_dir<P>(dir: P) -> Result<TextureBundle, TextureError> where P: AsRef<Path> {
+        let dir = dir.as_ref();
+        let mut bundle = TextureBundle::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            for entry in std::fs::read_dir(&current)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if ImageFormat::from_path(&path).is_ok() {
+                    let relative = path.strip_prefix(dir).unwrap_or(&path);
+                    let file = relative.to_string_lossy().replace('\\', "/");
+                    bundle = bundle.file(&file, std::fs::read(&path)?);
+                }
+            }
+        }
+        Ok(bundle)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, _)| name.as_str())
+    }
 }
 
 impl TextureManager {
     pub fn new(display: &Display) -> TextureManager {
         TextureManager {
             display: display.clone(),
-            textures: HashMap::new()
+            textures: HashMap::new(),
+            compressed: HashMap::new(),
+            fallback: None,
+            placeholder: None,
+            loading: HashSet::new(),
+            decoded: channel(),
+            scale_factor: 1.0,
+            compressed_sizes: HashMap::new(),
+            last_used: RefCell::new(HashMap::new()),
+            clock: Cell::new(0),
+            budget: None,
+            evictions: 0,
+            render_targets: HashMap::new(),
+            #[cfg(feature = "svg")]
+            svgs: HashMap::new()
+        }
+    }
+
+    /// Unregisters a texture, its memory is released once nothing else holds it.
+    pub fn unload<T>(&mut self, name: T) -> bool where T: AsRef<str> {
+        let name = name.as_ref();
+        self.last_used.borrow_mut().remove(name);
+        self.compressed_sizes.remove(name);
+        self.render_targets.remove(name);
+        #[cfg(feature = "svg")]
+        self.svgs.remove(name);
+        let texture = self.textures.remove(name).is_some();
+        self.compressed.remove(name).is_some() || texture
+    }
+
+    /// Limits the estimated memory of registered textures, the least recently used ones are unloaded
+    /// past it. Textures held outside the manager are never unloaded.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.budget = bytes;
+        self.trim();
+    }
+
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Unloads least recently used textures until the memory budget is met, or nothing else can be unloaded.
+    pub fn trim(&mut self) {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return
+        };
+        let textures = self.stats().textures;
+        let mut memory = textures.iter().map(|info| info.memory).sum::<usize>();
+        if memory <= budget {
+            return;
+        }
+        let mut candidates = {
+            let mut last_used = self.last_used.borrow_mut();
+            let clock = self.clock.get();
+            textures.into_iter()
+                .filter(|info| !self.is_held(&info.name))
+                // textures registered without a lookup yet count as just used
+                .map(|info| (*last_used.entry(info.name.clone()).or_insert(clock), info))
+                .collect::<Vec<_>>()
+        };
+        candidates.sort_by_key(|(last_used, _)| *last_used);
+        for (_, info) in candidates {
+            if memory <= budget {
+                break;
+            }
+            self.unload(&info.name);
+            memory -= info.memory;
+            self.evictions += 1;
+        }
+    }
+
+    /// Whether anything besides the manager holds the texture, including it being the fallback.
+    /// Render targets are always held, their contents could not be loaded again.
+    fn is_held(&self, name: &str) -> bool {
+        if self.render_targets.contains_key(name) {
+            return true;
         }
+        let texture = self.textures.get(name).map(Rc::strong_count);
+        let compressed = self.compressed.get(name).map(Rc::strong_count);
+        texture.or(compressed).map_or(false, |count| count > 1)
     }
 
+    pub fn stats(&self) -> TextureStats {
+        let mut textures = self.textures.iter()
+            .map(|(name, texture)| {
+                let (width, height) = (texture.get_width(), texture.get_height().unwrap_or(1));
+                TextureInfo {
+                    name: name.clone(),
+                    width,
+                    height,
+                    memory: mipmapped_size(width, height, texture.get_mipmap_levels()),
+                    compressed: false
+                }
+            })
+            .chain(self.compressed.iter().map(|(name, texture)| TextureInfo {
+                name: name.clone(),
+                width: texture.get_width(),
+                height: texture.get_height().unwrap_or(1),
+                memory: self.compressed_sizes.get(name).cloned().unwrap_or(0),
+                compressed: true
+            }))
+            .collect::<Vec<_>>();
+        textures.sort_by(|a, b| b.memory.cmp(&a.memory));
+        TextureStats {
+            memory: textures.iter().map(|info| info.memory).sum(),
+            textures,
+            budget: self.budget,
+            evictions: self.evictions
+        }
+    }
+
+    fn touch(&self, name: &str) {
+        let clock = self.clock.get() + 1;
+        self.clock.set(clock);
+        self.last_used.borrow_mut().insert(name.to_owned(), clock);
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Changes the number of physical pixels per logical pixel, scalable images are rasterized again to match.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        if scale_factor > 0.0 && scale_factor != self.scale_factor {
+            self.scale_factor = scale_factor;
+            #[cfg(feature = "svg")]
+            self.rasterize_svgs();
+        }
+    }
+
+    /// Returns the texture, or the missing texture fallback. Panics if it is missing and there is no fallback.
     pub fn get<T>(&self, name: T) -> Rc<Box<SrgbTexture2d>> where T: AsRef<str> {
-        self.textures.get(name.as_ref()).cloned().expect(&format!("Missing texture: {}", name.as_ref()))
+        match self.try_get(name) {
+            Ok(texture) => texture,
+            Err(error) => panic!("{}", error)
+        }
+    }
+
+    pub fn try_get<T>(&self, name: T) -> Result<Rc<Box<SrgbTexture2d>>, TextureError> where T: AsRef<str> {
+        let name = name.as_ref();
+        self.touch(name);
+        self.textures.get(name).or(self.fallback.as_ref()).cloned()
+            .ok_or_else(|| TextureError::Missing(name.to_owned()))
     }
 
+    /// Loads the texture unless one is already registered under `name`. Images that fail to load
+    /// give the missing texture fallback, if any.
     pub fn get_or_load<P>(&mut self, name: String, path: P) -> Option<Rc<Box<SrgbTexture2d>>> where P: AsRef<Path> {
+        if let Some(texture) = self.textures.get(&name) {
+            return Some(texture.clone());
+        }
+        self.load(name, path).ok().or_else(|| self.fallback.clone())
+    }
+
+    /// Loads an image file and registers it under `name`, replacing any texture of that name.
+    pub fn load<N, P>(&mut self, name: N, path: P) -> Result<Rc<Box<SrgbTexture2d>>, TextureError>
+        where N: Into<String>, P: AsRef<Path> {
+
+        let bytes = std::fs::read(path)?;
+        self.load_bytes(name, &bytes)
+    }
+
+    /// Registers an encoded image of any supported format under `name`.
+    pub fn load_bytes<N>(&mut self, name: N, bytes: &[u8]) -> Result<Rc<Box<SrgbTexture2d>>, TextureError>
+        where N: Into<String> {
+
+        let image = image::load_from_memory(bytes)?;
+        self.insert_image(name, image)
+    }
+
+    /// Registers every image of the bundle, stopping at the first one that fails to decode.
+    pub fn load_bundle(&mut self, bundle: &TextureBundle) -> Result<(), TextureError> {
+        for (name, bytes) in &bundle.entries {
+            self.load_bytes(name.clone(), bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Registers an image generated or decoded by the application under `name`.
+    pub fn insert_image<N>(&mut self, name: N, image: DynamicImage) -> Result<Rc<Box<SrgbTexture2d>>, TextureError>
+        where N: Into<String> {
+
+        self.insert_raw(name.into(), texture_data(image))
+    }
+
+    /// Registers tightly packed RGBA pixels, rows going from top to bottom. Panics if `pixels`
+    /// does not hold exactly `size` pixels.
+    pub fn insert_raw_rgba<N>(&mut self, name: N, pixels: Vec<u8>, size: (u32, u32)) -> Result<Rc<Box<SrgbTexture2d>>, TextureError>
+        where N: Into<String> {
+
+        self.insert_raw(name.into(), TextureData::rgba(pixels, size))
+    }
+
+    fn insert_raw(&mut self, name: String, data: TextureData) -> Result<Rc<Box<SrgbTexture2d>>, TextureError> {
+        let texture = Rc::new(Box::new(self.display.create_texture(data)?));
+        self.compressed.remove(&name);
+        self.render_targets.remove(&name);
+        self.textures.insert(name, texture.clone());
+        self.trim();
+        Ok(texture)
+    }
+
+    /// Reads and decodes an image on a worker thread. Until `poll_async` uploads it, the name refers to
+    /// the missing texture fallback or a transparent pixel, unless a texture was already registered under it.
+    pub fn load_async<N, P>(&mut self, name: N, path: P) where N: Into<String>, P: Into<PathBuf> {
+        let name = name.into();
+        let path = path.into();
         if !self.textures.contains_key(&name) {
-            let image = image::open(path.as_ref())
-                .expect(&format!("Image loading failed: {}", name));
-
-            let size = image.dimensions();
-            let has_alpha = match image.color() {
-                ColorType::Bgra8 => true,
-                ColorType::La8 => true,
-                ColorType::La16 => true,
-                ColorType::Rgba8 => true,
-                ColorType::Rgba16 => true,
-                _ => false
-            };
-            let image: RawImage2d<u8> = if has_alpha {
-                RawImage2d::from_raw_rgba(image.to_rgba().into_raw(), size)
-            } else {
-                RawImage2d::from_raw_rgb(image.to_rgb().into_raw(), size)
-            };
-            let texture = SrgbTexture2d::new(&self.display, image).expect("Texture allocation failed");
-            self.textures.insert(name.clone(), Rc::new(Box::new(texture)));
-        }
-        self.textures.get(&name).cloned()
-    }
-}
\ No newline at end of file
+            let placeholder = self.placeholder();
+            self.textures.insert(name.clone(), placeholder);
+        }
+        self.loading.insert(name.clone());
+        let sender = self.decoded.0.clone();
+        thread::spawn(move || {
+            let result = std::fs::read(&path)
+                .map_err(TextureError::from)
+                .and_then(|bytes| image::load_from_memory(&bytes).map_err(TextureError::from));
+            // the manager may be gone by the time the image is decoded
+            let _ = sender.send((name, result));
+        });
+    }
+
+    pub fn is_loading<T>(&self, name: T) -> bool where T: AsRef<str> {
+        self.loading.contains(name.as_ref())
+    }
+
+    /// Uploads the images decoded since the last call, must be called on the thread owning the display.
+    /// Textures that failed to load are unregistered if they still show their placeholder.
+    pub fn poll_async(&mut self) -> Vec<TextureLoaded> {
+        let mut loaded = Vec::new();
+        while let Ok((name, result)) = self.decoded.1.try_recv() {
+            self.loading.remove(&name);
+            let result = result.and_then(|image| {
+                let texture = self.display.create_texture(texture_data(image))?;
+                self.textures.insert(name.clone(), Rc::new(Box::new(texture)));
+                Ok(())
+            });
+            if result.is_err() {
+                let placeholder = self.placeholder();
+                if self.textures.get(&name).map_or(false, |texture| Rc::ptr_eq(texture, &placeholder)) {
+                    self.textures.remove(&name);
+                }
+            }
+            loaded.push(TextureLoaded { name, result });
+        }
+        if !loaded.is_empty() {
+            self.trim();
+        }
+        loaded
+    }
+
+    /// Creates a transparent texture of `width` by `height` logical pixels for `Canvas::render_to_texture`
+    /// to draw into, which is otherwise used like any other texture.
+    pub fn create_render_target<N>(&mut self, name: N, width: u32, height: u32) -> Result<Rc<Box<SrgbTexture2d>>, TextureError>
+        where N: Into<String> {
+
+        let w = (width as f32 * self.scale_factor).round().max(1.0) as u32;
+        let h = (height as f32 * self.scale_factor).round().max(1.0) as u32;
+        let (texture, depth) = self.display.create_render_target(w, h)?;
+
+        let name = name.into();
+        let texture = Rc::new(Box::new(texture));
+        self.compressed.remove(&name);
+        self.textures.insert(name.clone(), texture.clone());
+        self.render_targets.insert(name, Rc::new(depth));
+        Ok(texture)
+    }
+
+    pub(crate) fn render_target(&self, name: &str) -> Option<(Rc<Box<SrgbTexture2d>>, Rc<DepthStencilRenderBuffer>)> {
+        let depth = self.render_targets.get(name)?.clone();
+        Some((self.textures.get(name)?.clone(), depth))
+    }
+
+    pub fn get_compressed<T>(&self, name: T) -> Option<Rc<Box<CompressedSrgbTexture2d>>> where T: AsRef<str> {
+        let name = name.as_ref();
+        let texture = self.compressed.get(name).cloned();
+        if texture.is_some() {
+            self.touch(name);
+        }
+        texture
+    }
+
+    pub(crate) fn set_compressed_size(&mut self, name: &str, bytes: usize) {
+        self.compressed_sizes.insert(name.to_owned(), bytes);
+    }
+
+    fn placeholder(&mut self) -> Rc<Box<SrgbTexture2d>> {
+        if let Some(fallback) = &self.fallback {
+            return fallback.clone();
+        }
+        let display = &self.display;
+        self.placeholder.get_or_insert_with(|| {
+            let image = TextureData::rgba(vec![0, 0, 0, 0], (1, 1));
+            Rc::new(Box::new(display.create_texture(image).expect("Texture allocation failed")))
+        }).clone()
+    }
+
+    /// Enables returning a magenta checkerboard for missing textures and failed loads instead of panicking.
+    pub fn set_missing_texture_fallback(&mut self, enabled: bool) {
+        self.fallback = if enabled {
+            let pixels = (0 .. 8 * 8).flat_map(|i| {
+                if (i % 8 / 4 + i / 8 / 4) % 2 == 0 { vec![255, 0, 255, 255] } else { vec![0, 0, 0, 255] }
+            }).collect::<Vec<u8>>();
+            let image = TextureData::rgba(pixels, (8, 8));
+            Some(Rc::new(Box::new(self.display.create_texture(image).expect("Texture allocation failed"))))
+        } else {
+            None
+        };
+    }
+}
+
+/// Bytes of an RGBA texture with the given number of mipmap levels.
+fn mipmapped_size(width: u32, height: u32, levels: u32) -> usize {
+    let (mut w, mut h) = (width as usize, height as usize);
+    let mut size = 0;
+    for _ in 0 .. levels.max(1) {
+        size += w * h * 4;
+        w = (w / 2).max(1);
+        h = (h / 2).max(1);
+    }
+    size
+}
+
+/// Pixels of a decoded image, without an alpha channel if the image has none.
+fn texture_data(image: DynamicImage) -> TextureData {
+    let size = image.dimensions();
+    let has_alpha = match image.color() {
+        ColorType::Bgra8 => true,
+        ColorType::La8 => true,
+        ColorType::La16 => true,
+        ColorType::Rgba8 => true,
+        ColorType::Rgba16 => true,
+        _ => false
+    };
+    if has_alpha {
+        TextureData::new(image.to_rgba().into_raw(), size, PixelFormat::Rgba8)
+    } else {
+        TextureData::new(image.to_rgb().into_raw(), size, PixelFormat::Rgb8)
+    }
+}
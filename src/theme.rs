@@ -0,0 +1,95 @@
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub text: [f32; 4],
+    pub placeholder: [f32; 4],
+    pub caret: [f32; 4],
+    pub focus: [f32; 4]
+}
+
+impl Palette {
+    pub fn high_contrast() -> Palette {
+        Palette {
+            text: [1.0, 1.0, 1.0, 1.0],
+            placeholder: [0.8, 0.8, 0.8, 1.0],
+            caret: [1.0, 1.0, 0.0, 1.0],
+            focus: [1.0, 1.0, 0.0, 1.0]
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            text: [1.0, 1.0, 1.0, 1.0],
+            placeholder: [0.2, 0.2, 0.2, 1.0],
+            caret: [1.0, 1.0, 1.0, 1.0],
+            focus: [1.0, 1.0, 1.0, 0.5]
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Accessibility {
+    /// Replaces the theme palette with `Palette::high_contrast`
+    pub high_contrast: bool,
+    /// Animated widgets must jump straight to their final state instead of transitioning
+    pub reduced_motion: bool
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Theme {
+    pub palette: Palette,
+    pub accessibility: Accessibility
+}
+
+thread_local! {
+    static CURRENT: RefCell<Theme> = RefCell::new(Theme::default());
+    static GENERATION: Cell<u64> = Cell::new(0);
+}
+
+impl Theme {
+    pub fn current() -> Theme {
+        CURRENT.with(|theme| theme.borrow().clone())
+    }
+
+    pub fn set(theme: Theme) {
+        CURRENT.with(|current| *current.borrow_mut() = theme);
+        GENERATION.with(|generation| generation.set(generation.get() + 1));
+    }
+
+    pub fn modify<F>(f: F) where F: FnOnce(&mut Theme) {
+        CURRENT.with(|current| f(&mut *current.borrow_mut()));
+        GENERATION.with(|generation| generation.set(generation.get() + 1));
+    }
+
+    /// Counts the changes of the current theme, display lists recorded with another generation
+    /// hold colors of an outdated theme.
+    pub fn generation() -> u64 {
+        GENERATION.with(|generation| generation.get())
+    }
+
+    pub fn palette(&self) -> Palette {
+        if self.accessibility.high_contrast {
+            Palette::high_contrast()
+        } else {
+            self.palette.clone()
+        }
+    }
+
+    pub fn reduced_motion(&self) -> bool {
+        self.accessibility.reduced_motion
+    }
+
+    /// Length widgets give an animation or transition of `duration`, zero under reduced motion so
+    /// they jump straight to their final state.
+    pub fn transition(&self, duration: Duration) -> Duration {
+        if self.accessibility.reduced_motion {
+            Duration::from_secs(0)
+        } else {
+            duration
+        }
+    }
+}
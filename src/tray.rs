@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fmt;
+use image::DynamicImage;
+use tray_icon::{TrayIcon, TrayIconBuilder, Icon, BadIcon, ClickEvent};
+use tray_icon::menu::{Menu, MenuItem, PredefinedMenuItem, MenuEvent};
+
+#[derive(Debug)]
+pub enum TrayError {
+    Icon(BadIcon),
+    Create(tray_icon::Error)
+}
+
+impl fmt::Display for TrayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrayError::Icon(e) => write!(f, "invalid tray icon: {}", e),
+            TrayError::Create(e) => write!(f, "unable to create tray icon: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for TrayError {}
+
+impl From<BadIcon> for TrayError {
+    fn from(e: BadIcon) -> Self {
+        TrayError::Icon(e)
+    }
+}
+
+impl From<tray_icon::Error> for TrayError {
+    fn from(e: tray_icon::Error) -> Self {
+        TrayError::Create(e)
+    }
+}
+
+/// Interaction with the tray icon, positions are in physical screen pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrayEvent {
+    Click { pos: (f64, f64) },
+    RightClick { pos: (f64, f64) },
+    DoubleClick { pos: (f64, f64) },
+    /// A menu item was chosen, `id` is the one it was added with
+    Menu { id: String }
+}
+
+enum TrayMenuEntry {
+    Item { id: String, label: String, enabled: bool },
+    Separator
+}
+
+pub struct TrayBuilder {
+    icon: DynamicImage,
+    tooltip: Option<String>,
+    entries: Vec<TrayMenuEntry>,
+    restore_on_click: bool
+}
+
+impl TrayBuilder {
+    pub fn new(icon: DynamicImage) -> TrayBuilder {
+        TrayBuilder {
+            icon,
+            tooltip: None,
+            entries: Vec::new(),
+            restore_on_click: true
+        }
+    }
+
+    pub fn tooltip<T>(mut self, tooltip: T) -> TrayBuilder where T: Into<String> {
+        self.tooltip = Some(tooltip.into());
+        self
+    }
+
+    pub fn item<I, L>(mut self, id: I, label: L, enabled: bool) -> TrayBuilder where I: Into<String>, L: Into<String> {
+        self.entries.push(TrayMenuEntry::Item { id: id.into(), label: label.into(), enabled });
+        self
+    }
+
+    pub fn separator(mut self) -> TrayBuilder {
+        self.entries.push(TrayMenuEntry::Separator);
+        self
+    }
+
+    /// Whether clicking the icon shows a window hidden with `WindowHandle::hide_to_tray`, enabled by default
+    pub fn restore_on_click(mut self, restore_on_click: bool) -> TrayBuilder {
+        self.restore_on_click = restore_on_click;
+        self
+    }
+
+    /// Has to be called on the thread running the window, the icon is removed when the tray is dropped.
+    pub fn build(self) -> Result<Tray, TrayError> {
+        let menu = Menu::new();
+        let mut items = HashMap::new();
+        for entry in self.entries {
+            match entry {
+                TrayMenuEntry::Item { id, label, enabled } => {
+                    let item = MenuItem::new(label, enabled, None);
+                    items.insert(item.id(), id);
+                    let _ = menu.append(&item);
+                }
+                TrayMenuEntry::Separator => {
+                    let _ = menu.append(&PredefinedMenuItem::separator());
+                }
+            }
+        }
+        let mut builder = TrayIconBuilder::new()
+            .with_icon(native_icon(&self.icon)?)
+            .with_menu(Box::new(menu));
+        if let Some(tooltip) = self.tooltip {
+            builder = builder.with_tooltip(tooltip);
+        }
+        Ok(Tray {
+            icon: builder.build()?,
+            items,
+            restore_on_click: self.restore_on_click
+        })
+    }
+}
+
+/// Icon in the system tray with its context menu, see `WindowHandle::set_tray`.
+pub struct Tray {
+    icon: TrayIcon,
+    /// Ids the application gave to menu items by the ids of the native items
+    items: HashMap<u32, String>,
+    restore_on_click: bool
+}
+
+impl Tray {
+    pub fn set_icon(&mut self, icon: &DynamicImage) -> Result<(), TrayError> {
+        self.icon.set_icon(Some(native_icon(icon)?))?;
+        Ok(())
+    }
+
+    pub fn set_tooltip<T>(&mut self, tooltip: Option<T>) -> Result<(), TrayError> where T: AsRef<str> {
+        self.icon.set_tooltip(tooltip)?;
+        Ok(())
+    }
+
+    pub fn restores_on_click(&self) -> bool {
+        self.restore_on_click
+    }
+
+    /// Takes the events that arrived since the last call, menu items of other trays are skipped.
+    pub fn poll(&self) -> Vec<TrayEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = tray_icon::TrayEvent::receiver().try_recv() {
+            let pos = (event.x, event.y);
+            events.push(match event.event {
+                ClickEvent::Left => TrayEvent::Click { pos },
+                ClickEvent::Right => TrayEvent::RightClick { pos },
+                ClickEvent::Double => TrayEvent::DoubleClick { pos }
+            });
+        }
+        while let Ok(event) = MenuEvent::receiver().try_recv() {
+            if let Some(id) = self.items.get(&event.id) {
+                events.push(TrayEvent::Menu { id: id.clone() });
+            }
+        }
+        events
+    }
+}
+
+fn native_icon(image: &DynamicImage) -> Result<Icon, BadIcon> {
+    let image = image.to_rgba();
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height)
+}
@@ -1,30 +1,79 @@
-use glium::{Surface, DrawParameters, Blend, Display, Frame};
-use glium::glutin::event::{MouseButton, ElementState, KeyboardInput, MouseScrollDelta, VirtualKeyCode};
+use glium::{Surface, DrawParameters, Display, Frame};
+use glium::glutin::event::{MouseButton, ElementState, KeyboardInput, MouseScrollDelta, VirtualKeyCode, ModifiersState};
 use glium::glutin::window::CursorIcon;
 
-use clipboard::{ClipboardProvider, ClipboardContext};
 
-use crate::render::{Canvas, Vertex};
-use crate::font::{FontParameters, TextAlignVertical, TextAlignHorizontal};
-use crate::window::{WindowListener, Window};
+use crate::render::{Canvas, Vertex, DisplayList, alpha_blending};
+use crate::intern::{ResourceId, WidgetId};
+use crate::error::Result;
+use crate::font::{FontParameters, TextAlignVertical, TextAlignHorizontal, Truncation, TextLayout};
+use crate::window::{WindowListener, Window, WindowCommand, ResizeEdge};
+use crate::gesture::Gesture;
+use crate::theme::Theme;
+use crate::clipboard::Clipboard;
+use crate::loader::LoadProgress;
+use crate::animated::AnimatedTexture;
+use crate::streaming::StreamingTexture;
+#[cfg(feature = "gamepad")]
+use crate::gamepad::GamepadAction;
 use image::DynamicImage;
 use std::thread::JoinHandle;
 use std::str::FromStr;
 use cgmath::{Vector2, InnerSpace, MetricSpace};
 use glium::index::PrimitiveType;
 use std::any::Any;
-use std::time::Instant;
+use std::sync::Arc;
+use std::fmt;
+use std::time::{Instant, Duration};
+use std::cell::{Cell, RefCell};
+
+// logical pixels scrolled per line of a mouse wheel
+const DEFAULT_SCROLL_LINE_HEIGHT: f32 = 40.0;
+const CARET_BLINK_PERIOD: Duration = Duration::from_millis(1000);
+const PROGRESS_TRANSITION: Duration = Duration::from_millis(250);
+
+/// Scrolled distance in logical pixels, positive `y` scrolls up and positive `x` to the left.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScrollDelta {
+    pub x: f32,
+    pub y: f32
+}
+
+impl ScrollDelta {
+    /// Converts lines of a mouse wheel with `line_height`, trackpads report pixels already.
+    pub fn from_wheel(delta: MouseScrollDelta, line_height: f32) -> ScrollDelta {
+        match delta {
+            MouseScrollDelta::LineDelta(x, y) => ScrollDelta { x: x * line_height, y: y * line_height },
+            MouseScrollDelta::PixelDelta(pos) => ScrollDelta { x: pos.x as f32, y: pos.y as f32 }
+        }
+    }
+}
 
 pub struct Widgets<S> where S: Surface {
     widgets: Vec<Box<dyn Widget<S>>>,
-    focus: usize
+    focus: usize,
+    dirty: bool,
+    display_lists: RefCell<Vec<Option<DisplayList>>>,
+    /// `Theme::generation` the display lists were recorded with
+    theme_generation: Cell<u64>,
+    modifiers: ModifiersState,
+    scroll_line_height: f32,
+    clipboard: Clipboard,
+    window_focused: bool
 }
 
 impl<S> Widgets<S> where S: Surface {
     pub fn new() -> Widgets<S> {
         Widgets {
             widgets: Vec::new(),
-            focus: 0
+            focus: 0,
+            dirty: true,
+            display_lists: RefCell::new(Vec::new()),
+            theme_generation: Cell::new(Theme::generation()),
+            modifiers: ModifiersState::empty(),
+            scroll_line_height: DEFAULT_SCROLL_LINE_HEIGHT,
+            clipboard: Clipboard::system(),
+            window_focused: true
         }
     }
 
@@ -60,8 +109,11 @@ impl<S> Widgets<S> where S: Surface {
         None
     }
 
-    pub fn add<W>(&mut self, widget: W) where W: 'static + Widget<S> {
+    pub fn add<W>(&mut self, mut widget: W) where W: 'static + Widget<S> {
+        widget.set_clipboard(self.clipboard.clone());
         self.widgets.push(Box::new(widget));
+        self.display_lists.borrow_mut().push(None);
+        self.dirty = true;
     }
 
     pub fn tab_focus(&mut self, next: bool) -> Vec<WidgetEvent> {
@@ -77,6 +129,51 @@ impl<S> Widgets<S> where S: Surface {
         events
     }
 
+    pub fn focus_towards(&mut self, direction: (f32, f32)) -> Vec<WidgetEvent> {
+        if self.widgets.is_empty() {
+            return vec![];
+        }
+        let current = self.focus % self.widgets.len();
+        let (x, y, w, h) = self.widgets[current].get_bounds();
+        let from = (x + w / 2.0, y + h / 2.0);
+        let mut best: Option<(usize, f32)> = None;
+        for (i, e) in self.widgets.iter().enumerate() {
+            if i == current {
+                continue;
+            }
+            let (x, y, w, h) = e.get_bounds();
+            let (dx, dy) = (x + w / 2.0 - from.0, y + h / 2.0 - from.1);
+            let along = dx * direction.0 + dy * direction.1;
+            if along <= 0.0 {
+                continue;
+            }
+            let across = (dx * direction.1 - dy * direction.0).abs();
+            let score = along + across * 2.0;
+            if best.map_or(true, |(_, s)| score < s) {
+                best = Some((i, score));
+            }
+        }
+        match best {
+            Some((i, _)) => self.change_focus(Some(i)),
+            None => vec![]
+        }
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn on_gamepad_action(&mut self, display: &Display, action: GamepadAction) -> Vec<WidgetEvent> {
+        if let Some(direction) = action.direction() {
+            return self.focus_towards(direction);
+        }
+        let key = match action {
+            GamepadAction::Activate => VirtualKeyCode::Return,
+            GamepadAction::Cancel => VirtualKeyCode::Escape,
+            _ => return vec![]
+        };
+        let mut events = self.on_keyboard_key(display, key_input(key, ElementState::Pressed));
+        events.extend(self.on_keyboard_key(display, key_input(key, ElementState::Released)));
+        events
+    }
+
     fn change_focus(&mut self, id: Option<usize>) -> Vec<WidgetEvent> {
         let mut events = Vec::new();
 
@@ -85,7 +182,7 @@ impl<S> Widgets<S> where S: Surface {
                 let focus = i == id;
                 if focus != e.is_focused() {
                     e.set_focused(focus);
-                    events.push(WidgetEvent::FocusChanged { id: e.get_id().clone(), focus });
+                    events.push(WidgetEvent::FocusChanged { id: e.get_id(), focus });
                 }
             }
             self.focus = id;
@@ -99,10 +196,94 @@ impl<S> Widgets<S> where S: Surface {
         }
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) {
-        for e in self.widgets.iter() {
-            e.draw(canvas, partial_ticks);
+    pub fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
+        for i in 0..self.widgets.len() {
+            self.draw_widget(i, canvas, partial_ticks)?;
         }
+        Ok(())
+    }
+
+    /// Replays the display list recorded the last time the widget was drawn, unless it is dirty.
+    fn draw_widget(&self, i: usize, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
+        if self.theme_changed() {
+            self.theme_generation.set(Theme::generation());
+            for list in self.display_lists.borrow_mut().iter_mut() {
+                *list = None;
+            }
+        }
+        let e = &self.widgets[i];
+        let mut lists = self.display_lists.borrow_mut();
+        let list = &mut lists[i];
+        let replay = match list {
+            Some(list) => !e.is_dirty() && list.is_complete(),
+            None => false
+        };
+        if replay {
+            canvas.replay(list.as_ref().unwrap())
+        } else {
+            *list = Some(canvas.record_list(|canvas| e.draw(canvas, partial_ticks))?);
+            Ok(())
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty || self.theme_changed() || self.widgets.iter().any(|e| e.is_dirty())
+    }
+
+    fn theme_changed(&self) -> bool {
+        self.theme_generation.get() != Theme::generation()
+    }
+
+    /// Union of the bounds of every dirty widget, `None` when nothing has to be redrawn.
+    pub fn damage(&self) -> Option<[f32; 4]> {
+        let mut damage: Option<[f32; 4]> = None;
+        let all = self.dirty || self.theme_changed();
+        for e in self.widgets.iter().filter(|e| all || e.is_dirty()) {
+            let (x, y, w, h) = e.get_bounds();
+            damage = Some(match damage {
+                Some([dx, dy, dw, dh]) => {
+                    let (x0, y0) = (dx.min(x), dy.min(y));
+                    let (x1, y1) = ((dx + dw).max(x + w), (dy + dh).max(y + h));
+                    [x0, y0, x1 - x0, y1 - y0]
+                },
+                None => [x, y, w, h]
+            });
+        }
+        damage
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Call once a frame was drawn, clean widgets replay their display lists on the next one.
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+        for e in self.widgets.iter_mut() {
+            e.set_dirty(false);
+        }
+    }
+
+    /// Redraws only the widgets overlapping the damaged region, clipped to it.
+    ///
+    /// The target must keep its contents between frames (a layer or a render texture),
+    /// window back buffers are undefined after a swap and have to be redrawn with `draw`.
+    pub fn draw_damaged(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
+        if let Some(damage) = self.damage() {
+            let [dx, dy, dw, dh] = damage;
+            canvas.push_clip(damage);
+            for (i, e) in self.widgets.iter().enumerate() {
+                let (x, y, w, h) = e.get_bounds();
+                if x < dx + dw && dx < x + w && y < dy + dh && dy < y + h {
+                    if let Err(e) = self.draw_widget(i, canvas, partial_ticks) {
+                        canvas.pop_clip();
+                        return Err(e);
+                    }
+                }
+            }
+            canvas.pop_clip();
+        }
+        Ok(())
     }
 
     fn propagate_event<P>(&mut self, propagator: P) -> Vec<WidgetEvent> where P: Fn(&mut dyn Widget<S>) -> Vec<WidgetEvent> {
@@ -131,12 +312,48 @@ impl<S> Widgets<S> where S: Surface {
         self.propagate_event(move |e| e.on_keyboard_key(input))
     }
 
+    /// Modifier keys held down, as last passed to `on_modifiers_changed`.
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    pub fn on_modifiers_changed(&mut self, display: &Display, modifiers: ModifiersState) -> Vec<WidgetEvent> {
+        self.modifiers = modifiers;
+        self.propagate_event(move |e| e.on_modifiers_changed(modifiers))
+    }
+
     pub fn on_mouse_button(&mut self, display: &Display, button: MouseButton,
                            state: ElementState, pos: (f32, f32)) -> Vec<WidgetEvent> {
         self.propagate_event(move |e| e.on_mouse_button(button, state, pos))
     }
 
+    pub fn clipboard(&self) -> Clipboard {
+        self.clipboard.clone()
+    }
+
+    /// Replaces the clipboard of every widget, e.g. with `WindowHandle::clipboard` or `Clipboard::memory` in tests.
+    pub fn set_clipboard(&mut self, clipboard: Clipboard) {
+        for e in self.widgets.iter_mut() {
+            e.set_clipboard(clipboard.clone());
+        }
+        self.clipboard = clipboard;
+    }
+
+    pub fn scroll_line_height(&self) -> f32 {
+        self.scroll_line_height
+    }
+
+    /// Logical pixels scrolled per line of a mouse wheel.
+    pub fn set_scroll_line_height(&mut self, line_height: f32) {
+        self.scroll_line_height = line_height;
+    }
+
+    /// Passes the wheel delta to widgets in logical pixels. A vertical wheel scrolls horizontally while shift is held.
     pub fn on_mouse_wheel(&mut self, display: &Display, delta: MouseScrollDelta) -> Vec<WidgetEvent> {
+        let mut delta = ScrollDelta::from_wheel(delta, self.scroll_line_height);
+        if self.modifiers.shift() && delta.x == 0.0 {
+            delta = ScrollDelta { x: delta.y, y: 0.0 };
+        }
         self.propagate_event(move |e| e.on_mouse_wheel(delta))
     }
 
@@ -144,6 +361,96 @@ impl<S> Widgets<S> where S: Surface {
         self.propagate_event(move |e| e.on_mouse_move(pos))
     }
 
+    /// Passes the gesture to the topmost widget under its position, which reports it as `WidgetEvent::Gesture`.
+    pub fn on_gesture(&mut self, display: &Display, gesture: Gesture) -> Vec<WidgetEvent> {
+        let pos = gesture.pos();
+        let index = match self.widgets.iter().rposition(|e| Widget::<S>::is_mouse_over(&**e, pos)) {
+            Some(index) => index,
+            None => return Vec::new()
+        };
+        let widget = &mut self.widgets[index];
+        let mut events = vec![WidgetEvent::Gesture { id: widget.get_id(), gesture }];
+        let mut focus = None;
+        for event in widget.on_gesture(gesture) {
+            match event {
+                WidgetEvent::FocusChanged { focus: true, .. } => focus = Some(index),
+                WidgetEvent::FocusChanged { .. } => (),
+                event => events.push(event)
+            }
+        }
+        events.extend(self.change_focus(focus));
+        events
+    }
+
+    pub fn is_window_focused(&self) -> bool {
+        self.window_focused
+    }
+
+    /// Lets widgets drop presses and drags whose release goes to another window once this one loses focus.
+    pub fn on_window_focused(&mut self, display: &Display, focused: bool) -> Vec<WidgetEvent> {
+        self.window_focused = focused;
+        let mut events = Vec::new();
+        if !focused && !self.modifiers.is_empty() {
+            // keys released elsewhere are never reported
+            events.extend(self.on_modifiers_changed(display, ModifiersState::empty()));
+        }
+        events.extend(self.propagate_event(move |e| e.on_window_focused(focused)));
+        events.push(WidgetEvent::WindowFocusChanged { focused });
+        events
+    }
+
+    /// Lets widgets lay themselves out for the new logical size of the window.
+    pub fn on_resized(&mut self, display: &Display, dimensions: (f32, f32)) -> Vec<WidgetEvent> {
+        self.discard_display_lists();
+        self.propagate_event(move |e| e.on_resized(dimensions))
+    }
+
+    /// Recorded draws hold glyphs rasterized for the old scale, so everything is drawn again.
+    pub fn on_scale_factor_changed(&mut self, display: &Display, scale_factor: f64) {
+        self.discard_display_lists();
+    }
+
+    fn discard_display_lists(&mut self) {
+        self.dirty = true;
+        for list in self.display_lists.borrow_mut().iter_mut() {
+            *list = None;
+        }
+    }
+
+    pub fn inject_mouse_move(&mut self, x: f32, y: f32) -> Vec<WidgetEvent> {
+        self.propagate_event(move |e| e.on_mouse_move((x, y)))
+    }
+
+    pub fn inject_mouse_click(&mut self, x: f32, y: f32) -> Vec<WidgetEvent> {
+        let pos = (x, y);
+        let mut events = self.inject_mouse_move(x, y);
+        events.extend(self.propagate_event(move |e| e.on_mouse_button(MouseButton::Left, ElementState::Pressed, pos)));
+        events.extend(self.propagate_event(move |e| e.on_mouse_button(MouseButton::Left, ElementState::Released, pos)));
+        events
+    }
+
+    pub fn inject_text<T>(&mut self, text: T) -> Vec<WidgetEvent> where T: AsRef<str> {
+        let mut events = Vec::new();
+        for ch in text.as_ref().chars() {
+            events.extend(self.propagate_event(move |e| e.on_keyboard_char(ch)));
+        }
+        events
+    }
+
+    pub fn inject_key(&mut self, key: VirtualKeyCode) -> Vec<WidgetEvent> {
+        let mut events = self.propagate_event(move |e| e.on_keyboard_key(key_input(key, ElementState::Pressed)));
+        events.extend(self.propagate_event(move |e| e.on_keyboard_key(key_input(key, ElementState::Released))));
+        events
+    }
+
+    pub fn is_mouse_over(&self, mouse_pos: (f32, f32)) -> bool {
+        self.widgets.iter().any(|e| Widget::<S>::is_mouse_over(&**e, mouse_pos))
+    }
+
+    pub fn has_focus(&self) -> bool {
+        self.widgets.iter().any(|e| e.is_focused())
+    }
+
     pub fn get_cursor(&self, mouse_pos: (f32, f32)) -> CursorIcon {
         for e in self.widgets.iter().rev() {
             if Widget::<S>::is_mouse_over(&**e, mouse_pos) {
@@ -156,10 +463,20 @@ impl<S> Widgets<S> where S: Surface {
     }
 }
 
+#[allow(deprecated)]
+fn key_input(key: VirtualKeyCode, state: ElementState) -> KeyboardInput {
+    KeyboardInput {
+        scancode: 0,
+        state,
+        virtual_keycode: Some(key),
+        modifiers: Default::default()
+    }
+}
+
 pub trait Widget<S> where S: Surface {
     fn as_any(&self) -> &dyn Any;
     fn as_mut_any(&mut self) -> &mut dyn Any;
-    fn get_id(&self) -> &String;
+    fn get_id(&self) -> WidgetId;
     fn get_bounds(&self) -> (f32, f32, f32, f32);
     fn get_cursor(&self, mouse: (f32, f32)) -> Option<CursorIcon> {
         None
@@ -172,57 +489,101 @@ pub trait Widget<S> where S: Surface {
     fn is_focused(&self) -> bool;
     fn set_focused(&mut self, focused: bool);
     fn on_mouse_button(&mut self, button: MouseButton, state: ElementState, pos: (f32, f32)) -> Vec<WidgetEvent> { vec![] }
-    fn on_mouse_wheel(&mut self, delta: MouseScrollDelta) -> Vec<WidgetEvent> { vec![] }
+    fn on_mouse_wheel(&mut self, delta: ScrollDelta) -> Vec<WidgetEvent> { vec![] }
     fn on_mouse_move(&mut self, pos: (f32, f32)) -> Vec<WidgetEvent> { vec![] }
     fn on_keyboard_key(&mut self, input: KeyboardInput) -> Vec<WidgetEvent> { vec![] }
     fn on_keyboard_char(&mut self, ch: char) -> Vec<WidgetEvent> { vec![] }
+    /// Modifier keys held down changed, widgets keep them for shortcuts of later events
+    fn on_modifiers_changed(&mut self, modifiers: ModifiersState) -> Vec<WidgetEvent> { vec![] }
+    fn on_gesture(&mut self, gesture: Gesture) -> Vec<WidgetEvent> { vec![] }
+    /// The window gained or lost keyboard focus, presses and drags are cancelled on losing it
+    fn on_window_focused(&mut self, focused: bool) -> Vec<WidgetEvent> { vec![] }
+    /// The window was resized, `dimensions` are its new logical size
+    fn on_resized(&mut self, dimensions: (f32, f32)) -> Vec<WidgetEvent> { vec![] }
+    /// Clipboard of the `Widgets` the widget was added to
+    fn set_clipboard(&mut self, clipboard: Clipboard) {}
     fn update(&mut self, mouse_pos: (f32, f32), partial_ticks: f32) {}
-    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) where S: Surface;
+    /// Whether the widget changed since it was last drawn, widgets that do not track it are always redrawn
+    fn is_dirty(&self) -> bool {
+        true
+    }
+    fn set_dirty(&mut self, dirty: bool) {}
+    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> where S: Surface;
 }
 
 #[derive(Clone)]
 pub enum Background {
-    Texture(String),
-    Color([f32; 4])
+    Texture(ResourceId),
+    Color([f32; 4]),
+    /// Texture with border insets in texture pixels (left, top, right, bottom), only the center is stretched
+    NinePatch(ResourceId, [f32; 4]),
+    /// Color with corner radii clockwise from the top left corner
+    Rounded([f32; 4], [f32; 4]),
+    Gradient { from: [f32; 4], to: [f32; 4], vertical: bool },
+    /// Another background with an outline drawn inside its bounds
+    Bordered { background: Box<Background>, width: f32, radii: [f32; 4], color: [f32; 4] },
+    /// Animation showing its current frame, widgets using it have to be redrawn every frame
+    Animated(AnimatedTexture)
 }
 
 impl Background {
-    pub fn draw<S>(&self, canvas: &mut Canvas<S>, bounds: [f32; 4], color: [f32;4], partial_ticks: f32) where S: Surface {
-        let viewport: [[f32; 4]; 4] = canvas.viewport().into();
-        let params = DrawParameters {
-            blend: Blend::alpha_blending(),
-            .. Default::default()
-        };
+    /// Whether the background changes every frame, so widgets drawing it have to be redrawn.
+    /// Animations stay on their first frame under reduced motion.
+    pub fn is_animated(&self) -> bool {
+        match self {
+            Background::Animated(animation) => Theme::current().transition(animation.duration()) > Duration::from_secs(0),
+            Background::Bordered { background, .. } => background.is_animated(),
+            _ => false
+        }
+    }
+
+    pub fn draw<S>(&self, canvas: &mut Canvas<S>, bounds: [f32; 4], color: [f32;4], partial_ticks: f32) -> Result<()>
+        where S: Surface {
+
         match self {
             Background::Texture(texture) => {
-                let texture = canvas.textures().borrow().get(texture);
+                canvas.queue_textured_rect(*texture, bounds, [0.0, 0.0, 1.0, 1.0], color);
+            },
+            Background::NinePatch(texture, insets) => {
                 let program = canvas.shaders().borrow().textured();
-                let viewport: [[f32; 4]; 4] = canvas.viewport().into();
                 let params = DrawParameters {
-                    blend: Blend::alpha_blending(),
+                    blend: alpha_blending(),
                     .. Default::default()
                 };
-                let uniforms = uniform! {
-                    mat: viewport,
-                    tex: texture.sampled()
-                        .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
-                        .minify_filter(glium::uniforms::MinifySamplerFilter::NearestMipmapNearest)
-                };
-                canvas.textured_rect(bounds, color, &program, &uniforms, &params);
+                canvas.nine_patch(texture, bounds, *insets, color, &program, &params)?;
             },
             Background::Color(color) => {
-                let program = canvas.shaders().borrow().default();
-                let uniforms = uniform! {
-                    mat: viewport
-                };
-                canvas.rect(bounds, *color, &program, &uniforms, &params);
+                canvas.queue_rect(bounds, *color);
+            },
+            Background::Rounded(color, radii) => {
+                canvas.rounded_rect_corners(bounds, *radii, *color)?;
+            },
+            Background::Gradient { from, to, vertical } => {
+                canvas.gradient_rect(bounds, *from, *to, *vertical);
+            },
+            Background::Bordered { background, width, radii, color: border_color } => {
+                background.draw(canvas, bounds, color, partial_ticks)?;
+                if radii.iter().all(|&r| r <= 0.0) {
+                    canvas.border_rect(bounds, *width, *border_color);
+                } else {
+                    canvas.rounded_border_rect_corners(bounds, *width, *radii, *border_color)?;
+                }
             },
+            Background::Animated(animation) => {
+                let frame = if Theme::current().transition(animation.duration()) > Duration::from_secs(0) {
+                    animation.current_frame()
+                } else {
+                    &animation.frames()[0]
+                };
+                canvas.queue_sprite(&frame.sprite, bounds, color);
+            }
         }
+        Ok(())
     }
 }
 
 pub struct Button {
-    id: String,
+    id: WidgetId,
     label: String,
     bounds: (f32, f32, f32, f32),
     pressed: bool,
@@ -230,7 +591,8 @@ pub struct Button {
     focused: bool,
     background: Background,
     color: [f32; 4],
-    icon: Option<String>
+    icon: Option<ResourceId>,
+    dirty: bool
 }
 
 impl<S> Widget<S> for Button where S: Surface {
@@ -242,8 +604,8 @@ impl<S> Widget<S> for Button where S: Surface {
         self
     }
 
-    fn get_id(&self) -> &String {
-        &self.id
+    fn get_id(&self) -> WidgetId {
+        self.id
     }
 
     fn get_bounds(&self) -> (f32, f32, f32, f32) {
@@ -263,17 +625,27 @@ impl<S> Widget<S> for Button where S: Surface {
     }
 
     fn set_focused(&mut self, focused: bool) {
+        self.dirty |= self.focused != focused;
         self.focused = focused;
     }
 
+    fn is_dirty(&self) -> bool {
+        self.dirty || self.background.is_animated()
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
     fn on_mouse_button(&mut self, button: MouseButton, state: ElementState, pos: (f32, f32)) -> Vec<WidgetEvent> {
         let mut clicked = false;
         if self.hover {
             match button {
                 MouseButton::Left => {
+                    self.dirty = true;
                     if state == ElementState::Pressed {
                         self.pressed = true;
-                        return vec![WidgetEvent::FocusChanged { id: Widget::<S>::get_id(self).clone(), focus: true }];
+                        return vec![WidgetEvent::FocusChanged { id: Widget::<S>::get_id(self), focus: true }];
                     } else {
                         self.pressed = false;
                         clicked = true;
@@ -283,63 +655,69 @@ impl<S> Widget<S> for Button where S: Surface {
             }
         }
         if clicked {
-            return vec![WidgetEvent::ButtonClicked { id: Widget::<S>::get_id(self).clone() }];
+            return vec![WidgetEvent::ButtonClicked { id: Widget::<S>::get_id(self) }];
         }
         vec![]
     }
 
     fn on_mouse_move(&mut self, pos: (f32, f32)) -> Vec<WidgetEvent> {
-        self.hover = Widget::<S>::is_mouse_over(self, pos);
+        let hover = Widget::<S>::is_mouse_over(self, pos);
+        self.dirty |= hover != self.hover;
+        self.hover = hover;
+        vec![]
+    }
+
+    fn on_window_focused(&mut self, focused: bool) -> Vec<WidgetEvent> {
+        if !focused {
+            self.dirty |= self.pressed || self.hover;
+            self.pressed = false;
+            self.hover = false;
+        }
         vec![]
     }
 
     fn on_keyboard_key(&mut self, input: KeyboardInput) -> Vec<WidgetEvent> {
         let KeyboardInput { virtual_keycode, state, .. } = input;
         if self.focused && Some(VirtualKeyCode::Return) == virtual_keycode {
+            self.dirty = true;
             if state == ElementState::Pressed {
                 self.pressed = true;
             } else {
                 self.pressed = false;
-                return vec![WidgetEvent::ButtonClicked{ id: Widget::<S>::get_id(self).clone() }]
+                return vec![WidgetEvent::ButtonClicked{ id: Widget::<S>::get_id(self) }]
             }
         }
         vec![]
     }
 
-    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) {
+    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
         let (x, y, w, h) = Widget::<S>::get_bounds(self);
         let bounds = [x, y, w, h];
-        self.background.draw(canvas, bounds, self.color, partial_ticks);
-        if let Some(icon) = self.icon.as_ref() {
-            let texture = canvas.textures().borrow().get(icon);
-            let program = canvas.shaders().borrow().textured();
-            let viewport: [[f32; 4]; 4] = canvas.viewport().into();
-            let params = DrawParameters {
-                blend: Blend::alpha_blending(),
-                .. Default::default()
-            };
-            let uniforms = uniform! {
-                mat: viewport,
-                tex: texture.sampled()
-                    .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
-                    .minify_filter(glium::uniforms::MinifySamplerFilter::NearestMipmapNearest)
-            };
+        self.background.draw(canvas, bounds, self.color, partial_ticks)?;
+        if let Some(icon) = self.icon {
             let size = w.min(h);
-            canvas.textured_rect([x, y, size, size], self.color, &program, &uniforms, &params);
+            canvas.queue_textured_rect(icon, [x, y, size, size], [0.0, 0.0, 1.0, 1.0], self.color);
         }
-        canvas.text(&self.label, x + w / 2.0, y + h / 4.0, &FontParameters {
-            color: [1.0; 4],
+        let palette = Theme::current().palette();
+        let font_params = FontParameters {
+            color: palette.text,
             align_horizontal: TextAlignHorizontal::Center,
-            align_vertical: TextAlignVertical::Center,
+            align_vertical: TextAlignVertical::Baseline,
             .. Default::default()
-        });
+        };
+        let label = canvas.truncate_text(&self.label, w - 10.0, Truncation::End, &font_params);
+        canvas.text(label, x + w / 2.0, centered_baseline(canvas, y, h, &font_params), &font_params)?;
+        if self.focused {
+            canvas.border_rect(bounds, 2.0, palette.focus);
+        }
+        Ok(())
     }
 }
 
 impl Button {
     pub fn new<I, T>(id: I, label: T, x: f32, y: f32, w: f32, h: f32, background: Background,
                      color: Option<[f32; 4]>, icon: Option<&str>) -> Button
-        where I: Into<String>, T: Into<String> {
+        where I: Into<WidgetId>, T: Into<String> {
 
         Button {
             id: id.into(),
@@ -350,7 +728,8 @@ impl Button {
             focused: false,
             background,
             color: color.unwrap_or([1.0; 4]),
-            icon: icon.map(|i|i.to_owned())
+            icon: icon.map(ResourceId::new),
+            dirty: true
         }
     }
 }
@@ -358,15 +737,23 @@ impl Button {
 pub type TextMask = dyn Fn(&String, bool) -> String + 'static + Send + Sync;
 
 pub struct TextField {
-    id: String,
+    id: WidgetId,
     placeholder: String,
     value: String,
+    /// Character index the caret is placed before
+    caret: usize,
+    /// Layout of the text as last drawn with the x of its origin, to move and place the caret on screen
+    drawn: RefCell<Option<(TextLayout, f32)>>,
     filter: Option<TextFilter>,
     mask: Option<Box<TextMask>>,
     focused: bool,
     bounds: (f32, f32, f32, f32),
     background: Background,
-    last_input_changed: Instant
+    last_input_changed: Instant,
+    modifiers: ModifiersState,
+    clipboard: Clipboard,
+    window_focused: bool,
+    dirty: bool
 }
 
 impl<S> Widget<S> for TextField where S: Surface {
@@ -378,8 +765,8 @@ impl<S> Widget<S> for TextField where S: Surface {
         self
     }
 
-    fn get_id(&self) -> &String {
-        &self.id
+    fn get_id(&self) -> WidgetId {
+        self.id
     }
 
     fn get_bounds(&self) -> (f32, f32, f32, f32) {
@@ -399,47 +786,88 @@ impl<S> Widget<S> for TextField where S: Surface {
     }
 
     fn set_focused(&mut self, focused: bool) {
+        self.dirty |= self.focused != focused;
         self.focused = focused;
     }
 
+    fn is_dirty(&self) -> bool {
+        // the caret blinks while focused
+        self.dirty || (self.focused && self.window_focused) || self.background.is_animated()
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
     fn on_mouse_button(&mut self, button: MouseButton, state: ElementState, pos: (f32, f32)) -> Vec<WidgetEvent> {
         if Widget::<S>::is_mouse_over(self, pos) {
             if button == MouseButton::Left && state == ElementState::Pressed {
+                if let Some((layout, origin)) = &*self.drawn.borrow() {
+                    self.caret = layout.hit_test(pos.0 - origin, 0.0).min(self.value.chars().count());
+                }
                 self.focused = true;
-                return vec![WidgetEvent::FocusChanged { id: Widget::<S>::get_id(self).clone(), focus: true }];
+                self.dirty = true;
+                self.last_input_changed = Instant::now();
+                return vec![WidgetEvent::FocusChanged { id: Widget::<S>::get_id(self), focus: true }];
             }
         } else {
+            self.dirty |= self.focused;
             self.focused = false;
         }
         vec![]
     }
 
     fn on_keyboard_key(&mut self, input: KeyboardInput) -> Vec<WidgetEvent> {
-        let KeyboardInput { virtual_keycode, state, modifiers, .. } = input;
+        let KeyboardInput { virtual_keycode, state, .. } = input;
         if self.focused && state == ElementState::Pressed {
             match virtual_keycode {
                 Some(VirtualKeyCode::Back) => {
-                    if !self.value.is_empty() {
-                        self.value.pop();
+                    if self.caret > 0 {
+                        self.caret -= 1;
+                        let offset = self.byte_offset(self.caret);
+                        self.value.remove(offset);
                         self.last_input_changed = Instant::now();
+                        self.dirty = true;
                         return vec![WidgetEvent::TextValueChanged {
-                            id: Widget::<S>::get_id(self).clone(), value: self.value.clone()
+                            id: Widget::<S>::get_id(self), value: self.value.clone()
                         }];
                     }
                 },
-                Some(VirtualKeyCode::Escape) => { self.focused = false; },
+                Some(VirtualKeyCode::Escape) => {
+                    self.focused = false;
+                    self.dirty = true;
+                },
                 Some(VirtualKeyCode::Delete) => {
                     self.value.clear();
+                    self.caret = 0;
+                    self.last_input_changed = Instant::now();
+                    self.dirty = true;
+                },
+                // the caret moves on screen, which goes backwards through right to left text
+                Some(VirtualKeyCode::Left) | Some(VirtualKeyCode::Right) => {
+                    self.caret = self.step_caret(virtual_keycode == Some(VirtualKeyCode::Right));
                     self.last_input_changed = Instant::now();
+                    self.dirty = true;
                 },
-                Some(VirtualKeyCode::V) => {
-                    if modifiers.ctrl() {
-                        let mut clipboard: ClipboardContext = ClipboardProvider::new().expect("Failed to access clipboard");
-                        let contents = clipboard.get_contents().expect("Failed to get clipboard contents");
-                        self.value.push_str(&contents);
+                Some(VirtualKeyCode::Home) | Some(VirtualKeyCode::End) => {
+                    self.caret = if virtual_keycode == Some(VirtualKeyCode::Home) { 0 } else { self.value.chars().count() };
+                    self.last_input_changed = Instant::now();
+                    self.dirty = true;
+                },
+                Some(VirtualKeyCode::C) => {
+                    // masked values such as passwords stay out of the clipboard
+                    if self.modifiers.ctrl() && self.mask.is_none() && !self.value.is_empty() {
+                        let _ = self.clipboard.set_text(self.value.clone());
+                    }
+                }
+                Some(VirtualKeyCode::V) if self.modifiers.ctrl() => {
+                    // nothing is pasted when the clipboard is empty or cannot be read
+                    if let Ok(contents) = self.clipboard.get_text() {
+                        self.insert_text(&contents);
                         self.last_input_changed = Instant::now();
+                        self.dirty = true;
                         return vec![WidgetEvent::TextValueChanged {
-                            id: Widget::<S>::get_id(self).clone(), value: self.value.clone()
+                            id: Widget::<S>::get_id(self), value: self.value.clone()
                         }];
                     }
                 }
@@ -449,6 +877,27 @@ impl<S> Widget<S> for TextField where S: Surface {
         vec![]
     }
 
+    fn on_modifiers_changed(&mut self, modifiers: ModifiersState) -> Vec<WidgetEvent> {
+        self.modifiers = modifiers;
+        vec![]
+    }
+
+    fn set_clipboard(&mut self, clipboard: Clipboard) {
+        self.clipboard = clipboard;
+    }
+
+    fn on_window_focused(&mut self, focused: bool) -> Vec<WidgetEvent> {
+        self.window_focused = focused;
+        if focused {
+            // the caret shows right away when coming back
+            self.last_input_changed = Instant::now();
+        } else {
+            self.modifiers = ModifiersState::empty();
+        }
+        self.dirty = true;
+        vec![]
+    }
+
     fn on_keyboard_char(&mut self, ch: char) -> Vec<WidgetEvent> {
         if self.focused && (ch == ' ' || !ch.is_control()) {
             if let Some(filter) = &self.filter {
@@ -456,69 +905,101 @@ impl<S> Widget<S> for TextField where S: Surface {
                     return vec![];
                 }
             }
-            self.value.push(ch);
+            let mut buffer = [0; 4];
+            self.insert_text(ch.encode_utf8(&mut buffer));
             self.last_input_changed = Instant::now();
+            self.dirty = true;
             return vec![WidgetEvent::TextValueChanged {
-                id: Widget::<S>::get_id(self).clone(), value: self.value.clone()
+                id: Widget::<S>::get_id(self), value: self.value.clone()
             }];
         }
         vec![]
     }
 
-    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) {
+    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
         let (x, y, w, h) = Widget::<S>::get_bounds(self);
         let bounds = [x, y, w, h];
-        let default_program = canvas.shaders().borrow().default();
-        let viewport: [[f32; 4]; 4] = canvas.viewport().into();
-        let uniforms = uniform! {
-            mat: viewport
-        };
-        let params = DrawParameters {
-            blend: Blend::alpha_blending(),
-            line_width: Some(1.0), //FIXME 1.2
-            .. Default::default()
-        };
-        self.background.draw(canvas, bounds, [1.0; 4], partial_ticks);
-        let mut text = self.get_display_text();
-        let (mut text_w, text_h) = canvas.get_text_size(&text, &Default::default());
-        while text_w > w - 10.0 {
-            if self.focused {
-                text.remove(0);
-            } else {
-                text.pop();
-            }
-            let (w, _) = canvas.get_text_size(&text, &Default::default());
-            text_w = w;
-        }
+        self.background.draw(canvas, bounds, [1.0; 4], partial_ticks)?;
+        let theme = Theme::current();
+        let palette = theme.palette();
         let font_params = FontParameters {
-            color: if self.value.is_empty() { [0.2, 0.2, 0.2, 1.0] } else { [1.0; 4] },
+            color: if self.value.is_empty() { palette.placeholder } else { palette.text },
             align_horizontal: TextAlignHorizontal::Left,
-            align_vertical: TextAlignVertical::Center,
+            align_vertical: TextAlignVertical::Baseline,
             .. Default::default()
         };
-        canvas.text(text, x + 5.0, y + h / 4.0, &font_params);
-        if self.focused && Instant::now().duration_since(self.last_input_changed).subsec_millis() < 500 {
-            let offset = if self.value.is_empty() { 0.0 } else { text_w } + 4.0;
-            canvas.rect([x + offset, y + 2.0, 2.0, h - 4.0], [1.0; 4], &default_program, &uniforms, &params);
+        let baseline = centered_baseline(canvas, y, h, &font_params);
+        let layout = canvas.layout_text(self.get_display_text(), std::f32::INFINITY, &font_params);
+        let caret_x = if self.value.is_empty() { 0.0 } else { layout.caret_position(self.caret).0 };
+        // the text scrolls to keep the caret in the field
+        let origin = x + 5.0 - (caret_x - (w - 10.0)).max(0.0);
+        let top = baseline - layout.lines.first().map_or(0.0, |line| line.baseline);
+        canvas.push_clip(bounds);
+        canvas.draw_text_layout(&layout, origin, top, &font_params)?;
+        canvas.pop_clip();
+        // the caret stays visible without blinking under reduced motion
+        let period = theme.transition(CARET_BLINK_PERIOD).as_millis();
+        let blink = period == 0 || self.last_input_changed.elapsed().as_millis() % period < period / 2;
+        if self.focused && self.window_focused && blink {
+            canvas.queue_rect([origin + caret_x - 1.0, y + 2.0, 2.0, h - 4.0], palette.caret);
         }
+        *self.drawn.borrow_mut() = Some((layout, origin));
+        if self.focused {
+            canvas.border_rect(bounds, 2.0, palette.focus);
+        }
+        Ok(())
     }
 }
 
+/// Baseline that centers a line of text vertically between `y` and `y + h`.
+fn centered_baseline<S>(canvas: &Canvas<S>, y: f32, h: f32, params: &FontParameters) -> f32 where S: Surface {
+    let metrics = canvas.font_metrics(params);
+    y + (h + metrics.ascent + metrics.descent) / 2.0
+}
+
 impl TextField {
     pub fn new<I, P, V>(id: I, placeholder: P, value: V, x: f32, y: f32, w: f32, h: f32, background: Background,
                            filter: Option<TextFilter>, mask: Option<Box<TextMask>>) -> TextField
-        where I: Into<String>, P: Into<String>, V: Into<String> {
+        where I: Into<WidgetId>, P: Into<String>, V: Into<String> {
 
+        let value = value.into();
         TextField {
             id: id.into(),
             placeholder: placeholder.into(),
-            value: value.into(),
+            caret: value.chars().count(),
+            value,
+            drawn: RefCell::new(None),
             filter,
             mask,
             focused: false,
             bounds: (x, y, w, h),
             background,
-            last_input_changed: Instant::now()
+            last_input_changed: Instant::now(),
+            modifiers: ModifiersState::empty(),
+            clipboard: Clipboard::system(),
+            window_focused: true,
+            dirty: true
+        }
+    }
+
+    fn byte_offset(&self, index: usize) -> usize {
+        self.value.char_indices().nth(index).map_or(self.value.len(), |(offset, _)| offset)
+    }
+
+    fn insert_text(&mut self, text: &str) {
+        let offset = self.byte_offset(self.caret);
+        self.value.insert_str(offset, text);
+        self.caret += text.chars().count();
+    }
+
+    /// Caret moved to the next boundary on screen, see `TextLayout::move_caret`.
+    fn step_caret(&self, right: bool) -> usize {
+        let count = self.value.chars().count();
+        match &*self.drawn.borrow() {
+            Some((layout, _)) if count > 0 => layout.move_caret(self.caret, right).min(count),
+            // nothing drawn yet tells the direction of the text
+            _ if right => (self.caret + 1).min(count),
+            _ => self.caret.saturating_sub(1)
         }
     }
 
@@ -534,13 +1015,14 @@ impl TextField {
 }
 
 pub struct ScrollBar {
-    id: String,
+    id: WidgetId,
     steps: u32,
     value: f32,
     max: f32,
     focused: bool,
     bounds: (f32, f32, f32, f32),
-    color: [f32; 4]
+    color: [f32; 4],
+    dirty: bool
 }
 
 impl<S> Widget<S> for ScrollBar where S: Surface {
@@ -552,8 +1034,8 @@ impl<S> Widget<S> for ScrollBar where S: Surface {
         self
     }
 
-    fn get_id(&self) -> &String {
-        &self.id
+    fn get_id(&self) -> WidgetId {
+        self.id
     }
 
     fn get_bounds(&self) -> (f32, f32, f32, f32) {
@@ -573,9 +1055,18 @@ impl<S> Widget<S> for ScrollBar where S: Surface {
     }
 
     fn set_focused(&mut self, focused: bool) {
+        self.dirty |= self.focused != focused;
         self.focused = focused;
     }
 
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
     fn on_mouse_button(&mut self, button: MouseButton, state: ElementState, pos: (f32, f32)) -> Vec<WidgetEvent> {
         if Widget::<S>::is_mouse_over(self, pos) {
             if button == MouseButton::Left {
@@ -585,20 +1076,23 @@ impl<S> Widget<S> for ScrollBar where S: Surface {
                     let (x, y, w, h) = Widget::<S>::get_bounds(self);
                     let value = ((mouse_x - x) / w * self.max).max(0.0).min(self.max);
                     self.value = value;
-                    let id = Widget::<S>::get_id(self).clone();
+                    self.dirty = true;
+                    let id = Widget::<S>::get_id(self);
                     return vec![
-                        WidgetEvent::FocusChanged { id: id.clone(), focus: true },
+                        WidgetEvent::FocusChanged { id, focus: true },
                         WidgetEvent::ScrollValueChanged { id, value: self.value, max: self.max, steps: self.steps }
                     ];
                 } else if state == ElementState::Released {
                     self.focused = false;
-                    let id = Widget::<S>::get_id(self).clone();
+                    self.dirty = true;
+                    let id = Widget::<S>::get_id(self);
                     return vec![
-                        WidgetEvent::FocusChanged { id: id.clone(), focus: false },
+                        WidgetEvent::FocusChanged { id, focus: false },
                     ];
                 }
             }
         } else {
+            self.dirty |= self.focused;
             self.focused = false;
         }
         vec![]
@@ -610,8 +1104,9 @@ impl<S> Widget<S> for ScrollBar where S: Surface {
             let (x, y, w, h) = Widget::<S>::get_bounds(self);
             let value = ((mouse_x - x) / w * self.max).max(0.0).min(self.max);
             if value != self.value {
-                let id = Widget::<S>::get_id(self).clone();
+                let id = Widget::<S>::get_id(self);
                 self.value = value;
+                self.dirty = true;
                 return vec![
                     WidgetEvent::ScrollValueChanged { id, value: self.value, max: self.max, steps: self.steps }
                 ];
@@ -620,52 +1115,56 @@ impl<S> Widget<S> for ScrollBar where S: Surface {
         vec![]
     }
 
-    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) {
+    fn on_window_focused(&mut self, focused: bool) -> Vec<WidgetEvent> {
+        // the thumb is dragged while focused, the release would be missed
+        if !focused && self.focused {
+            self.focused = false;
+            self.dirty = true;
+        }
+        vec![]
+    }
+
+    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
         let (x, y, w, h) = Widget::<S>::get_bounds(self);
         let bounds = [x, y, w, h];
-        let default_program = canvas.shaders().borrow().default();
-        let viewport: [[f32; 4]; 4] = canvas.viewport().into();
-        let uniforms = uniform! {
-            mat: viewport
-        };
-        let params = DrawParameters {
-            blend: Blend::alpha_blending(),
-            .. Default::default()
-        };
 
         let sp = self.value / self.max;
         let sw = w / (self.steps as f32 + 1.0);
         let sx = (w * sp - sw / 2.0).max(0.0).min(w - sw);
 
         if sx > 0.0 {
-            canvas.rect([x, y + h / 4.0, sx, h / 2.0], self.color, &default_program, &uniforms, &params);
+            canvas.queue_rect([x, y + h / 4.0, sx, h / 2.0], self.color);
         }
         if sx < w - sw {
-            canvas.rect([x + sx + sw, y + h / 4.0, w - sx - sw, h / 2.0], self.color, &default_program, &uniforms, &params);
+            canvas.queue_rect([x + sx + sw, y + h / 4.0, w - sx - sw, h / 2.0], self.color);
         }
-        canvas.rect([x + sx, y, sw, h], self.color, &default_program, &uniforms, &params);
+        canvas.queue_rect([x + sx, y, sw, h], self.color);
+        Ok(())
     }
 }
 
 impl ScrollBar {
     pub fn new<I, C>(id: I, value: f32, max: f32, steps: u32, x: f32, y: f32, w: f32, h: f32, color: C) -> ScrollBar
-        where I: Into<String>, C: Into<[f32;4]> {
+        where I: Into<WidgetId>, C: Into<[f32;4]> {
 
         ScrollBar {
             id: id.into(),
             value, max, steps,
             focused: false,
             bounds: (x, y, w, h),
-            color: color.into()
+            color: color.into(),
+            dirty: true
         }
     }
 
     pub fn set_value(&mut self, value: f32) {
         self.value = value.min(self.max).max(0.0);
+        self.dirty = true;
     }
 
     pub fn set_ratio_value(&mut self, ratio: f32) {
         self.value = ratio.max(0.0).min(1.0) * self.max;
+        self.dirty = true;
     }
 
     pub fn get_value(&self) -> f32 {
@@ -673,11 +1172,592 @@ impl ScrollBar {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum WidgetEvent {
-    ButtonClicked { id: String },
-    TextValueChanged { id: String, value: String },
-    ScrollValueChanged { id: String, value: f32, max: f32, steps: u32 },
-    FocusChanged { id: String, focus: bool }
+    ButtonClicked { id: WidgetId },
+    TextValueChanged { id: WidgetId, value: String },
+    ScrollValueChanged { id: WidgetId, value: f32, max: f32, steps: u32 },
+    FocusChanged { id: WidgetId, focus: bool },
+    Gesture { id: WidgetId, gesture: Gesture },
+    /// Custom chrome asks the window to move, resize, minimize, maximize or close, see `WindowHandle::apply`
+    WindowCommand { id: WidgetId, command: WindowCommand },
+    WindowFocusChanged { focused: bool },
+    /// Emitted by widgets outside of the crate, see `WidgetEvent::custom`
+    Custom { id: WidgetId, payload: EventPayload }
+}
+
+impl WidgetEvent {
+    pub fn custom<I, T>(id: I, payload: T) -> WidgetEvent where I: Into<WidgetId>, T: Any + Send + Sync {
+        WidgetEvent::Custom { id: id.into(), payload: EventPayload(Arc::new(payload)) }
+    }
+
+    /// Payload of a custom event if it is a `T`.
+    pub fn payload<T>(&self) -> Option<&T> where T: Any {
+        match self {
+            WidgetEvent::Custom { payload, .. } => payload.downcast_ref(),
+            _ => None
+        }
+    }
+}
+
+/// Value carried by `WidgetEvent::Custom`. Clones share it, and payloads are only equal to their clones.
+#[derive(Clone)]
+pub struct EventPayload(Arc<dyn Any + Send + Sync>);
+
+impl EventPayload {
+    pub fn downcast_ref<T>(&self) -> Option<&T> where T: Any {
+        self.0.downcast_ref()
+    }
+
+    pub fn is<T>(&self) -> bool where T: Any {
+        self.0.is::<T>()
+    }
+}
+
+impl PartialEq for EventPayload {
+    fn eq(&self, other: &EventPayload) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for EventPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventPayload(..)")
+    }
+}
+
+/// Shows frames pushed by the application, scaled to fit its bounds with the aspect ratio kept.
+pub struct VideoView {
+    id: WidgetId,
+    bounds: (f32, f32, f32, f32),
+    stream: StreamingTexture,
+    focused: bool,
+    dirty: bool
+}
+
+impl<S> Widget<S> for VideoView where S: Surface {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn get_bounds(&self) -> (f32, f32, f32, f32) {
+        self.bounds
+    }
+
+    fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
+    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
+        let (x, y, w, h) = self.bounds;
+        canvas.queue_rect([x, y, w, h], [0.0, 0.0, 0.0, 1.0]);
+        let (fw, fh) = self.stream.size();
+        let scale = (w / fw as f32).min(h / fh as f32);
+        let (vw, vh) = (fw as f32 * scale, fh as f32 * scale);
+        let bounds = [x + (w - vw) / 2.0, y + (h - vh) / 2.0, vw, vh];
+        canvas.queue_textured_rect(self.stream.name(), bounds, [0.0, 0.0, 1.0, 1.0], [1.0; 4]);
+        Ok(())
+    }
+}
+
+impl VideoView {
+    pub fn new<I>(id: I, x: f32, y: f32, w: f32, h: f32, stream: StreamingTexture) -> VideoView where I: Into<WidgetId> {
+        VideoView {
+            id: id.into(),
+            bounds: (x, y, w, h),
+            stream,
+            focused: false,
+            dirty: true
+        }
+    }
+
+    pub fn stream(&self) -> &StreamingTexture {
+        &self.stream
+    }
+
+    /// Replaces the shown frame with RGBA pixels of the stream size.
+    pub fn push_frame(&mut self, pixels: Vec<u8>) {
+        self.stream.write_frame(pixels);
+        self.dirty = true;
+    }
+}
+
+/// Progress bar with the resource loaded last, shown while a `ResourceLoader` runs.
+pub struct LoadingScreen {
+    id: WidgetId,
+    bounds: (f32, f32, f32, f32),
+    progress: LoadProgress,
+    /// Fraction the bar grows from towards the progress since it last changed
+    previous: f32,
+    changed: Instant,
+    color: [f32; 4],
+    focused: bool,
+    dirty: bool
+}
+
+impl<S> Widget<S> for LoadingScreen where S: Surface {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn get_bounds(&self) -> (f32, f32, f32, f32) {
+        self.bounds
+    }
+
+    fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty || self.changed.elapsed() < Theme::current().transition(PROGRESS_TRANSITION)
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
+    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
+        let (x, y, w, h) = self.bounds;
+        let theme = Theme::current();
+        let palette = theme.palette();
+        let bar_h = (h / 3.0).min(8.0);
+        let bar_y = y + h - bar_h;
+        canvas.border_rect([x, bar_y, w, bar_h], 1.0, self.color);
+        canvas.queue_rect([x, bar_y, w * self.shown_fraction(&theme), bar_h], self.color);
+        let font_params = FontParameters {
+            color: palette.text,
+            align_horizontal: TextAlignHorizontal::Center,
+            align_vertical: TextAlignVertical::Baseline,
+            .. Default::default()
+        };
+        let label = match &self.progress.current {
+            Some(current) => format!("{} {}%", current, self.progress.percent()),
+            None => format!("{}%", self.progress.percent())
+        };
+        let label = canvas.truncate_text(&label, w, Truncation::Middle, &font_params);
+        canvas.text(label, x + w / 2.0, centered_baseline(canvas, y, h - bar_h, &font_params), &font_params)?;
+        Ok(())
+    }
+}
+
+impl LoadingScreen {
+    pub fn new<I>(id: I, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) -> LoadingScreen where I: Into<WidgetId> {
+        LoadingScreen {
+            id: id.into(),
+            bounds: (x, y, w, h),
+            progress: LoadProgress::default(),
+            previous: 0.0,
+            changed: Instant::now(),
+            color,
+            focused: false,
+            dirty: true
+        }
+    }
+
+    pub fn progress(&self) -> &LoadProgress {
+        &self.progress
+    }
+
+    pub fn set_progress(&mut self, progress: &LoadProgress) {
+        if self.progress != *progress {
+            self.previous = self.shown_fraction(&Theme::current());
+            self.changed = Instant::now();
+            self.progress = progress.clone();
+            self.dirty = true;
+        }
+    }
+
+    /// Filled part of the bar, growing towards the progress since it changed.
+    fn shown_fraction(&self, theme: &Theme) -> f32 {
+        let duration = theme.transition(PROGRESS_TRANSITION);
+        let t = if duration > Duration::from_secs(0) {
+            (self.changed.elapsed().as_secs_f32() / duration.as_secs_f32()).min(1.0)
+        } else {
+            1.0
+        };
+        self.previous + (self.progress.fraction() - self.previous) * t
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TitleButton {
+    Minimize, Maximize, Close
+}
+
+impl TitleButton {
+    fn command(&self) -> WindowCommand {
+        match self {
+            TitleButton::Minimize => WindowCommand::Minimize,
+            TitleButton::Maximize => WindowCommand::ToggleMaximize,
+            TitleButton::Close => WindowCommand::Close
+        }
+    }
+}
+
+/// Title bar of an undecorated window: dragging it moves the window,
+/// the buttons at its right end minimize, maximize and close it.
+pub struct TitleBar {
+    id: WidgetId,
+    title: String,
+    bounds: (f32, f32, f32, f32),
+    background: Background,
+    /// Mouse position the drag started at, relative to the window
+    drag: Option<(f32, f32)>,
+    hovered: Option<TitleButton>,
+    pressed: Option<TitleButton>,
+    focused: bool,
+    dirty: bool
+}
+
+impl<S> Widget<S> for TitleBar where S: Surface {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn get_bounds(&self) -> (f32, f32, f32, f32) {
+        self.bounds
+    }
+
+    fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    fn on_mouse_button(&mut self, button: MouseButton, state: ElementState, pos: (f32, f32)) -> Vec<WidgetEvent> {
+        if button != MouseButton::Left {
+            return vec![]
+        }
+        match state {
+            ElementState::Pressed => {
+                if !Widget::<S>::is_mouse_over(self, pos) {
+                    return vec![]
+                }
+                self.pressed = self.button_at(pos);
+                self.dirty = true;
+                if self.pressed.is_none() {
+                    // the anchor only moves the window where the system can not
+                    self.drag = Some(pos);
+                    return vec![WidgetEvent::WindowCommand { id: self.id, command: WindowCommand::Drag }]
+                }
+            }
+            ElementState::Released => {
+                self.drag = None;
+                if let Some(pressed) = self.pressed.take() {
+                    self.dirty = true;
+                    if self.button_at(pos) == Some(pressed) {
+                        return vec![WidgetEvent::WindowCommand { id: self.id, command: pressed.command() }]
+                    }
+                }
+            }
+        }
+        vec![]
+    }
+
+    fn on_mouse_move(&mut self, pos: (f32, f32)) -> Vec<WidgetEvent> {
+        let hovered = if Widget::<S>::is_mouse_over(self, pos) { self.button_at(pos) } else { None };
+        if hovered != self.hovered {
+            self.hovered = hovered;
+            self.dirty = true;
+        }
+        if let Some((ax, ay)) = self.drag {
+            // the window follows the mouse, so the anchor stays put in window coordinates
+            let (dx, dy) = (pos.0 - ax, pos.1 - ay);
+            if dx != 0.0 || dy != 0.0 {
+                return vec![WidgetEvent::WindowCommand { id: self.id, command: WindowCommand::Move(dx, dy) }]
+            }
+        }
+        vec![]
+    }
+
+    fn on_window_focused(&mut self, focused: bool) -> Vec<WidgetEvent> {
+        if !focused {
+            self.dirty |= self.pressed.is_some() || self.hovered.is_some();
+            self.drag = None;
+            self.pressed = None;
+            self.hovered = None;
+        }
+        vec![]
+    }
+
+    fn on_resized(&mut self, dimensions: (f32, f32)) -> Vec<WidgetEvent> {
+        let (x, y, _, h) = self.bounds;
+        self.bounds = (x, y, dimensions.0 - x, h);
+        self.dirty = true;
+        vec![]
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty || self.background.is_animated()
+    }
+
+    fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
+    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
+        let (x, y, w, h) = self.bounds;
+        self.background.draw(canvas, [x, y, w, h], [1.0; 4], partial_ticks)?;
+        let palette = Theme::current().palette();
+        let font_params = FontParameters {
+            color: palette.text,
+            align_horizontal: TextAlignHorizontal::Left,
+            align_vertical: TextAlignVertical::Baseline,
+            .. Default::default()
+        };
+        let title = canvas.truncate_text(&self.title, w - h * 3.0 - 20.0, Truncation::End, &font_params);
+        canvas.text(title, x + 10.0, centered_baseline(canvas, y, h, &font_params), &font_params)?;
+        for button in &[TitleButton::Minimize, TitleButton::Maximize, TitleButton::Close] {
+            let [bx, by, bw, bh] = self.button_bounds(*button);
+            if self.pressed == Some(*button) {
+                canvas.queue_rect([bx, by, bw, bh], [1.0, 1.0, 1.0, 0.3]);
+            } else if self.hovered == Some(*button) {
+                let hover = if *button == TitleButton::Close { [0.9, 0.1, 0.1, 0.8] } else { [1.0, 1.0, 1.0, 0.15] };
+                canvas.queue_rect([bx, by, bw, bh], hover);
+            }
+            let (cx, cy, r) = (bx + bw / 2.0, by + bh / 2.0, bh / 6.0);
+            match button {
+                TitleButton::Minimize => canvas.queue_rect([cx - r, cy, r * 2.0, 1.0], palette.text),
+                TitleButton::Maximize => canvas.border_rect([cx - r, cy - r, r * 2.0, r * 2.0], 1.0, palette.text),
+                TitleButton::Close => {
+                    canvas.smooth_polyline(&[[cx - r, cy - r], [cx + r, cy + r]], 1.0, palette.text)?;
+                    canvas.smooth_polyline(&[[cx + r, cy - r], [cx - r, cy + r]], 1.0, palette.text)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TitleBar {
+    pub fn new<I, T>(id: I, title: T, x: f32, y: f32, w: f32, h: f32, background: Background) -> TitleBar
+        where I: Into<WidgetId>, T: Into<String> {
+
+        TitleBar {
+            id: id.into(),
+            title: title.into(),
+            bounds: (x, y, w, h),
+            background,
+            drag: None,
+            hovered: None,
+            pressed: None,
+            focused: false,
+            dirty: true
+        }
+    }
+
+    pub fn set_title<T>(&mut self, title: T) where T: Into<String> {
+        self.title = title.into();
+        self.dirty = true;
+    }
+
+    /// Buttons are squares of the bar height laid out from its right end
+    fn button_bounds(&self, button: TitleButton) -> [f32; 4] {
+        let (x, y, w, h) = self.bounds;
+        let slot = match button {
+            TitleButton::Minimize => 3.0,
+            TitleButton::Maximize => 2.0,
+            TitleButton::Close => 1.0
+        };
+        [x + w - h * slot, y, h, h]
+    }
+
+    fn button_at(&self, pos: (f32, f32)) -> Option<TitleButton> {
+        [TitleButton::Minimize, TitleButton::Maximize, TitleButton::Close].iter()
+            .find(|b| {
+                let [x, y, w, h] = self.button_bounds(**b);
+                pos.0 >= x && pos.0 <= x + w && pos.1 >= y && pos.1 <= y + h
+            })
+            .cloned()
+    }
+}
+
+/// Invisible hit zones along the edges of an undecorated window that resize it when dragged.
+/// Should be added after other widgets so its cursors win, with the title bar kept clear of
+/// its `width` so that grabbing the top edge does not drag the window as well.
+pub struct ResizeBorder {
+    id: WidgetId,
+    bounds: (f32, f32, f32, f32),
+    width: f32,
+    /// Edge being dragged and the mouse position of the last emitted resize
+    drag: Option<(ResizeEdge, (f32, f32))>,
+    focused: bool
+}
+
+impl<S> Widget<S> for ResizeBorder where S: Surface {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn get_id(&self) -> WidgetId {
+        self.id
+    }
+
+    fn get_bounds(&self) -> (f32, f32, f32, f32) {
+        self.bounds
+    }
+
+    fn get_cursor(&self, mouse: (f32, f32)) -> Option<CursorIcon> {
+        self.drag.map(|(edge, _)| edge).or_else(|| self.edge_at(mouse)).map(|edge| edge.cursor())
+    }
+
+    fn is_mouse_over(&self, mouse: (f32, f32)) -> bool {
+        self.drag.is_some() || self.edge_at(mouse).is_some()
+    }
+
+    fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    fn on_mouse_button(&mut self, button: MouseButton, state: ElementState, pos: (f32, f32)) -> Vec<WidgetEvent> {
+        if button == MouseButton::Left {
+            self.drag = match state {
+                ElementState::Pressed => self.edge_at(pos).map(|edge| (edge, pos)),
+                ElementState::Released => None
+            };
+            if let (ElementState::Pressed, Some((edge, _))) = (state, self.drag) {
+                return vec![WidgetEvent::WindowCommand { id: self.id, command: WindowCommand::DragResize(edge) }]
+            }
+        }
+        vec![]
+    }
+
+    fn on_mouse_move(&mut self, pos: (f32, f32)) -> Vec<WidgetEvent> {
+        if let Some((edge, (ax, ay))) = self.drag {
+            let (horizontal, vertical) = edge.directions();
+            let dx = if horizontal != 0 { pos.0 - ax } else { 0.0 };
+            let dy = if vertical != 0 { pos.1 - ay } else { 0.0 };
+            if dx != 0.0 || dy != 0.0 {
+                // edges at the origin move the window with them, so the mouse keeps its window
+                // coordinates there, while on the far edges it moves along with the edge
+                let anchor = (if horizontal > 0 { pos.0 } else { ax }, if vertical > 0 { pos.1 } else { ay });
+                self.drag = Some((edge, anchor));
+                return vec![WidgetEvent::WindowCommand { id: self.id, command: WindowCommand::Resize(edge, dx, dy) }]
+            }
+        }
+        vec![]
+    }
+
+    fn on_window_focused(&mut self, focused: bool) -> Vec<WidgetEvent> {
+        if !focused {
+            self.drag = None;
+        }
+        vec![]
+    }
+
+    fn on_resized(&mut self, dimensions: (f32, f32)) -> Vec<WidgetEvent> {
+        self.bounds = (0.0, 0.0, dimensions.0, dimensions.1);
+        vec![]
+    }
+
+    fn is_dirty(&self) -> bool {
+        false
+    }
+
+    fn draw(&self, canvas: &mut Canvas<S>, partial_ticks: f32) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ResizeBorder {
+    /// `dimensions` are the logical window size, `width` the thickness of the hit zones
+    pub fn new<I>(id: I, dimensions: (f32, f32), width: f32) -> ResizeBorder where I: Into<WidgetId> {
+        ResizeBorder {
+            id: id.into(),
+            bounds: (0.0, 0.0, dimensions.0, dimensions.1),
+            width,
+            drag: None,
+            focused: false
+        }
+    }
+
+    pub fn edge_at(&self, pos: (f32, f32)) -> Option<ResizeEdge> {
+        let (x, y, w, h) = self.bounds;
+        let (mx, my) = pos;
+        if mx < x || my < y || mx > x + w || my > y + h {
+            return None
+        }
+        // corners grab a bit more than the edges to be easier to hit
+        let corner = self.width * 2.0;
+        let left = mx - x < self.width;
+        let right = x + w - mx < self.width;
+        let top = my - y < self.width;
+        let bottom = y + h - my < self.width;
+        let near_left = mx - x < corner;
+        let near_right = x + w - mx < corner;
+        let near_top = my - y < corner;
+        let near_bottom = y + h - my < corner;
+        if (top && near_left) || (left && near_top) {
+            Some(ResizeEdge::TopLeft)
+        } else if (top && near_right) || (right && near_top) {
+            Some(ResizeEdge::TopRight)
+        } else if (bottom && near_left) || (left && near_bottom) {
+            Some(ResizeEdge::BottomLeft)
+        } else if (bottom && near_right) || (right && near_bottom) {
+            Some(ResizeEdge::BottomRight)
+        } else if left {
+            Some(ResizeEdge::Left)
+        } else if right {
+            Some(ResizeEdge::Right)
+        } else if top {
+            Some(ResizeEdge::Top)
+        } else if bottom {
+            Some(ResizeEdge::Bottom)
+        } else {
+            None
+        }
+    }
 }
 
 pub fn is_valid_number<N: FromStr>(c: char, v: &String) -> bool {
@@ -1,109 +1,1365 @@
-use glium::{Display, Frame, Surface};
+use glium::{Display, Frame};
 use std::rc::Rc;
-use std::cell::RefCell;
-use std::time::{SystemTime, Instant, Duration};
-use image::{DynamicImage, GenericImageView};
-use winit::window::{WindowBuilder, Icon};
-use crate::shader::ShaderManager;
-use crate::font::FontManager;
-use crate::texture::TextureManager;
-use crate::render::Canvas;
-use winit::event::{Event, WindowEvent, KeyboardInput, MouseButton, MouseScrollDelta, ElementState, StartCause};
-use winit::event_loop::{EventLoop, ControlFlow};
+use std::cell::{Cell, RefCell};
+use std::time::{Instant, Duration};
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use winit::window::{WindowBuilder, Icon, BadIcon, CursorIcon, Fullscreen};
+use crate::shader::{ShaderManager, ShaderReloaded, ShaderError};
+use crate::font::{self, FontManager};
+use crate::texture::{TextureManager, TextureLoaded, TextureError};
+use crate::render::{Canvas, GeometryBuffers, FrameStats, GpuTimer, ColorSpace, image_from_rows};
+use crate::error::{Error, Result};
+use crate::postprocess::{PostProcessor, PostEffect};
+use crate::overlay::PerfOverlay;
+use crate::gesture::{GestureRecognizer, Gesture};
+use crate::clipboard::Clipboard;
+use crate::intern::ResourceId;
+use crate::loader::{ResourceLoader, ResourceManifest, LoadProgress, ResourceError};
+#[cfg(feature = "dialogs")]
+use crate::dialog::{self, FileDialog, MessageBox};
+#[cfg(any(feature = "dialogs", feature = "persistence"))]
+use std::path::PathBuf;
+#[cfg(feature = "tray")]
+use crate::tray::{Tray, TrayEvent};
+#[cfg(feature = "async")]
+use crate::tasks::{self, UiTasks, UiTask, Spawner, TaskWaker};
+#[cfg(feature = "async")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "gamepad")]
+use crate::gamepad::{GamepadInput, GamepadMapping, GamepadAction};
+use winit::event::{Event, WindowEvent, KeyboardInput, MouseButton, MouseScrollDelta, ElementState, StartCause, Touch, TouchPhase, VirtualKeyCode, ModifiersState, DeviceEvent};
+use winit::event_loop::{EventLoop, EventLoopProxy, EventLoopClosed, ControlFlow};
 use glium::backend::glutin::glutin::{ContextBuilder, GlRequest, Robustness, GlProfile, Api};
-use winit::monitor::MonitorHandle;
+use glium::backend::glutin::DisplayCreationError;
+use glium::SwapBuffersError;
+use winit::monitor::{MonitorHandle, VideoMode};
+use winit::error::ExternalError;
 use std::collections::VecDeque;
 use winit::platform::desktop::EventLoopExtDesktop;
 
-use winit::dpi::{LogicalSize, PhysicalSize, Position, LogicalPosition};
+use winit::dpi::{LogicalSize, PhysicalSize, Position, LogicalPosition, PhysicalPosition};
+use std::fmt;
 
 pub struct Window;
 
+// ticks run at most per frame, time missed beyond them after a stall is dropped
+const MAX_CATCH_UP_TICKS: u32 = 10;
+// the event loop is woken this early for a frame and spins for the rest, sleeping is not precise enough
+const PACING_SPIN: Duration = Duration::from_millis(2);
+// trackpads scroll in pixels, this many of them zoom as much as one wheel line
+const PIXELS_PER_ZOOM_LINE: f32 = 50.0;
+
+/// When `Window::show` draws frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PresentMode {
+    /// Updates and draws at 60 frames per second whether anything changed or not
+    Continuous,
+    /// Sleeps until input, a user event or `WindowHandle::request_redraw`, for tool-style applications
+    /// that are idle most of the time. Animations have to request a redraw every frame they run.
+    Reactive
+}
+
+/// Answer of `WindowListener::on_closing`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CloseAction {
+    Close,
+    /// Keeps the window open, e.g. to ask about unsaved changes first and close it later with `WindowHandle::close`
+    Cancel
+}
+
+/// Monitor a window opens on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MonitorChoice {
+    /// The one the system puts the window on
+    Current,
+    Primary,
+    /// Index into `WindowHandle::monitors`
+    Index(usize),
+    /// Monitor of the name, the current one if none has it
+    Named(String)
+}
+
+impl Default for MonitorChoice {
+    fn default() -> Self {
+        MonitorChoice::Current
+    }
+}
+
+/// Outer position, inner size and maximized state of a window, in physical pixels of the desktop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowGeometry {
+    pub position: (i32, i32),
+    pub size: (u32, u32),
+    pub maximized: bool
+}
+
+// how often a reactive loop checks on resources while any are loading
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+// physical pixels along the top edge that have to be on a monitor for the window to be grabbed there
+const GEOMETRY_GRIP: i32 = 32;
+
+impl WindowGeometry {
+    /// Checks saved geometry against the monitors connected now, which may have changed since it was saved.
+    /// `None` unless enough of the top edge is on a monitor to move the window, otherwise the size is
+    /// shrunk to fit that monitor.
+    pub fn fit_to(&self, monitors: &[MonitorInfo]) -> Option<WindowGeometry> {
+        let (x, y) = self.position;
+        let (w, h) = self.size;
+        if w == 0 || h == 0 {
+            return None
+        }
+        monitors.iter()
+            .find(|monitor| {
+                let (mx, my) = monitor.position;
+                let (mw, mh) = (monitor.size.0 as i32, monitor.size.1 as i32);
+                let overlap = (x + w as i32).min(mx + mw) - x.max(mx);
+                overlap >= GEOMETRY_GRIP && y + GEOMETRY_GRIP > my && y < my + mh - GEOMETRY_GRIP
+            })
+            .map(|monitor| WindowGeometry {
+                position: self.position,
+                size: (w.min(monitor.size.0), h.min(monitor.size.1)),
+                maximized: self.maximized
+            })
+    }
+}
+
+/// Where `Window::show` keeps the window geometry between runs, see `WindowConfig::remember_geometry`.
+#[derive(Clone)]
+pub enum GeometryStore {
+    /// JSON file, written when the window closes
+    #[cfg(feature = "persistence")]
+    File(PathBuf),
+    Callbacks {
+        load: Rc<dyn Fn() -> Option<WindowGeometry>>,
+        save: Rc<dyn Fn(WindowGeometry)>
+    }
+}
+
+impl GeometryStore {
+    /// Missing or unreadable geometry is `None`, the window opens with its configured placement then.
+    pub fn load(&self) -> Option<WindowGeometry> {
+        match self {
+            #[cfg(feature = "persistence")]
+            GeometryStore::File(path) => std::fs::read_to_string(path).ok()
+                .and_then(|json| serde_json::from_str(&json).ok()),
+            GeometryStore::Callbacks { load, .. } => load()
+        }
+    }
+
+    pub fn save(&self, geometry: WindowGeometry) -> std::io::Result<()> {
+        match self {
+            #[cfg(feature = "persistence")]
+            GeometryStore::File(path) => {
+                let json = serde_json::to_string_pretty(&geometry)?;
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(path, json)
+            }
+            GeometryStore::Callbacks { save, .. } => {
+                save(geometry);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Where a window opens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Placement {
+    Centered,
+    /// Outer position in logical pixels relative to the monitor
+    Position(i32, i32),
+    /// Maximized on the monitor
+    Maximized,
+    /// Geometry saved from an earlier run, the monitor choice is ignored
+    Geometry(WindowGeometry)
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Placement::Centered
+    }
+}
+
+/// Description of a connected monitor, see `WindowHandle::monitors`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    /// Resolution in physical pixels
+    pub size: (u32, u32),
+    /// Top left corner on the desktop in physical pixels
+    pub position: (i32, i32),
+    pub scale_factor: f64,
+    /// Highest refresh rate in Hz at the current resolution, if the monitor reports its video modes
+    pub refresh_rate: Option<u16>,
+    handle: MonitorHandle
+}
+
+impl MonitorInfo {
+    fn new(handle: MonitorHandle) -> MonitorInfo {
+        let size = handle.size();
+        let refresh_rate = handle.video_modes()
+            .filter(|mode| mode.size() == size)
+            .map(|mode| mode.refresh_rate())
+            .max();
+        MonitorInfo {
+            name: handle.name(),
+            size: size.into(),
+            position: handle.position().into(),
+            scale_factor: handle.scale_factor(),
+            refresh_rate,
+            handle
+        }
+    }
+
+    /// Logical pixels per inch, taking 96 as the density at a scale factor of 1
+    pub fn dpi(&self) -> f64 {
+        96.0 * self.scale_factor
+    }
+
+    pub fn handle(&self) -> &MonitorHandle {
+        &self.handle
+    }
+}
+
+/// Settings of a window shown with `Window::show`.
+#[derive(Clone)]
+pub struct WindowConfig {
+    pub title: String,
+    /// Inner size in logical pixels
+    pub size: (u32, u32),
+    pub min_size: Option<(u32, u32)>,
+    pub max_size: Option<(u32, u32)>,
+    pub icon: Option<DynamicImage>,
+    pub decorated: bool,
+    pub resizable: bool,
+    pub always_on_top: bool,
+    /// Framebuffer with an alpha channel the desktop shows through, cleared to transparent every frame
+    pub transparent: bool,
+    /// Mouse input goes to the windows below, see `WindowHandle::set_click_through`
+    pub click_through: bool,
+    /// MSAA samples per pixel, `WindowListener::multisampling` when not set
+    pub samples: Option<u16>,
+    /// sRGB framebuffer, requested for `ColorSpace::Linear` listeners when not set
+    pub srgb: Option<bool>,
+    pub placement: Placement,
+    pub monitor: MonitorChoice,
+    /// Geometry of the last run replaces the placement when it still fits the monitors, and is saved on close
+    pub geometry_store: Option<GeometryStore>,
+    pub vsync: bool,
+    /// Frames per second drawn at most, independently of vsync. Unlimited when not set
+    pub max_fps: Option<u32>,
+    /// Rate of `WindowListener::on_tick` per second, `partial_ticks` passed to the listener are counted in them
+    pub tps: u32,
+    pub present_mode: PresentMode,
+    /// Shows frame timing and cache sizes over the UI, see `PerfOverlay`
+    pub perf_overlay: bool,
+    /// Key toggling the performance overlay
+    pub perf_overlay_key: Option<VirtualKeyCode>
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            title: String::new(),
+            size: (800, 600),
+            min_size: None,
+            max_size: None,
+            icon: None,
+            decorated: true,
+            resizable: true,
+            always_on_top: false,
+            transparent: false,
+            click_through: false,
+            samples: None,
+            srgb: None,
+            placement: Placement::Centered,
+            monitor: MonitorChoice::Current,
+            geometry_store: None,
+            vsync: true,
+            max_fps: Some(60),
+            tps: 20,
+            present_mode: PresentMode::Continuous,
+            perf_overlay: false,
+            perf_overlay_key: Some(VirtualKeyCode::F3)
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn new() -> WindowConfig {
+        WindowConfig::default()
+    }
+
+    pub fn title<T>(mut self, title: T) -> Self where T: Into<String> {
+        self.title = title.into();
+        self
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.size = (width, height);
+        self
+    }
+
+    pub fn min_size(mut self, width: u32, height: u32) -> Self {
+        self.min_size = Some((width, height));
+        self
+    }
+
+    pub fn max_size(mut self, width: u32, height: u32) -> Self {
+        self.max_size = Some((width, height));
+        self
+    }
+
+    pub fn icon(mut self, icon: DynamicImage) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    pub fn decorated(mut self, decorated: bool) -> Self {
+        self.decorated = decorated;
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn always_on_top(mut self, always_on_top: bool) -> Self {
+        self.always_on_top = always_on_top;
+        self
+    }
+
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    pub fn click_through(mut self, click_through: bool) -> Self {
+        self.click_through = click_through;
+        self
+    }
+
+    /// Undecorated, transparent, always on top and click-through, for HUDs and streaming overlays.
+    /// Click-through is only supported on Windows and silently left off elsewhere, which
+    /// `WindowHandle::is_click_through` reports once the window is shown.
+    pub fn overlay(self) -> Self {
+        self.decorated(false).transparent(true).always_on_top(true).click_through(true)
+    }
+
+    pub fn samples(mut self, samples: u16) -> Self {
+        self.samples = Some(samples);
+        self
+    }
+
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = Some(srgb);
+        self
+    }
+
+    pub fn placement(mut self, placement: Placement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Outer position in logical pixels relative to the monitor.
+    pub fn position(self, x: i32, y: i32) -> Self {
+        self.placement(Placement::Position(x, y))
+    }
+
+    pub fn centered(self) -> Self {
+        self.placement(Placement::Centered)
+    }
+
+    pub fn maximized(self) -> Self {
+        self.placement(Placement::Maximized)
+    }
+
+    pub fn geometry(self, geometry: WindowGeometry) -> Self {
+        self.placement(Placement::Geometry(geometry))
+    }
+
+    /// Reopens the window where it was closed, keeping its geometry in a JSON file.
+    #[cfg(feature = "persistence")]
+    pub fn remember_geometry<P>(mut self, path: P) -> Self where P: Into<PathBuf> {
+        self.geometry_store = Some(GeometryStore::File(path.into()));
+        self
+    }
+
+    /// Reopens the window where it was closed, keeping its geometry wherever the application keeps its settings.
+    pub fn geometry_callbacks<L, S>(mut self, load: L, save: S) -> Self
+        where L: Fn() -> Option<WindowGeometry> + 'static, S: Fn(WindowGeometry) + 'static {
+
+        self.geometry_store = Some(GeometryStore::Callbacks { load: Rc::new(load), save: Rc::new(save) });
+        self
+    }
+
+    /// Index into `WindowHandle::monitors`.
+    pub fn monitor(mut self, index: usize) -> Self {
+        self.monitor = MonitorChoice::Index(index);
+        self
+    }
+
+    pub fn monitor_named<T>(mut self, name: T) -> Self where T: Into<String> {
+        self.monitor = MonitorChoice::Named(name.into());
+        self
+    }
+
+    pub fn primary_monitor(mut self) -> Self {
+        self.monitor = MonitorChoice::Primary;
+        self
+    }
+
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    pub fn max_fps(mut self, max_fps: Option<u32>) -> Self {
+        self.max_fps = max_fps;
+        self
+    }
+
+    pub fn tps(mut self, tps: u32) -> Self {
+        self.tps = tps;
+        self
+    }
+
+    pub fn present_mode(mut self, mode: PresentMode) -> Self {
+        self.present_mode = mode;
+        self
+    }
+
+    pub fn perf_overlay(mut self, visible: bool) -> Self {
+        self.perf_overlay = visible;
+        self
+    }
+
+    pub fn perf_overlay_key(mut self, key: Option<VirtualKeyCode>) -> Self {
+        self.perf_overlay_key = key;
+        self
+    }
+}
+
+/// Reason `Window::show` returned early.
+#[derive(Debug)]
+pub enum WindowError {
+    /// The window or its GL context could not be created, e.g. without vsync or multisampling support
+    Context(DisplayCreationError),
+    Icon(BadIcon),
+    /// A built-in or listener program failed to compile
+    Shader(ShaderError),
+    Texture(TextureError),
+    Font(font::Error),
+    /// Presenting a frame failed, usually because the context was lost
+    SwapBuffers(SwapBuffersError)
+}
+
+impl fmt::Display for WindowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowError::Context(e) => write!(f, "Display creation failed: {}", e),
+            WindowError::Icon(e) => write!(f, "Bad icon: {}", e),
+            WindowError::Shader(e) => write!(f, "{}", e),
+            WindowError::Texture(e) => write!(f, "{}", e),
+            WindowError::Font(e) => write!(f, "Font loading failed: {:?}", e),
+            WindowError::SwapBuffers(e) => write!(f, "Swapping buffers failed: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for WindowError {}
+
+impl From<DisplayCreationError> for WindowError {
+    fn from(error: DisplayCreationError) -> Self {
+        WindowError::Context(error)
+    }
+}
+
+impl From<BadIcon> for WindowError {
+    fn from(error: BadIcon) -> Self {
+        WindowError::Icon(error)
+    }
+}
+
+impl From<ShaderError> for WindowError {
+    fn from(error: ShaderError) -> Self {
+        WindowError::Shader(error)
+    }
+}
+
+impl From<TextureError> for WindowError {
+    fn from(error: TextureError) -> Self {
+        WindowError::Texture(error)
+    }
+}
+
+impl From<font::Error> for WindowError {
+    fn from(error: font::Error) -> Self {
+        WindowError::Font(error)
+    }
+}
+
+impl From<SwapBuffersError> for WindowError {
+    fn from(error: SwapBuffersError) -> Self {
+        WindowError::SwapBuffers(error)
+    }
+}
+
+/// Events of the loop run by `Window::show`: user events and wake ups for work finished off the UI thread.
+pub(crate) enum LoopEvent<T> {
+    User(T),
+    /// A task spawned with `WindowHandle::spawn` finished
+    #[cfg(feature = "async")]
+    TaskFinished
+}
+
+/// Sends user events to `WindowListener::on_user_event` from any thread, waking the event loop.
+pub struct WindowProxy<T> where T: 'static {
+    proxy: EventLoopProxy<LoopEvent<T>>
+}
+
+impl<T> Clone for WindowProxy<T> where T: 'static {
+    fn clone(&self) -> Self {
+        WindowProxy { proxy: self.proxy.clone() }
+    }
+}
+
+impl<T> WindowProxy<T> where T: 'static {
+    /// Fails with the event once the window was closed.
+    pub fn send_event(&self, event: T) -> std::result::Result<(), EventLoopClosed<T>> {
+        self.proxy.send_event(LoopEvent::User(event)).map_err(|EventLoopClosed(event)| match event {
+            LoopEvent::User(event) => EventLoopClosed(event),
+            #[cfg(feature = "async")]
+            LoopEvent::TaskFinished => unreachable!("Only user events are sent through a WindowProxy")
+        })
+    }
+}
+
+/// Controls the window shown by `Window::show` from listener callbacks. Sizes and positions are in logical pixels.
+#[derive(Clone)]
+pub struct WindowHandle {
+    display: Display,
+    state: Rc<HandleState>
+}
+
+#[derive(Default)]
+struct HandleState {
+    close_requested: Cell<bool>,
+    exit_code: Cell<i32>,
+    redraw_requested: Cell<bool>,
+    cursor: RefCell<Cursor>,
+    cursor_override: RefCell<Option<Cursor>>,
+    modifiers: Cell<ModifiersState>,
+    cursor_mode: Cell<CursorMode>,
+    maximized: Cell<bool>,
+    /// Geometry before maximizing, saved in place of the maximized one
+    restored: Cell<Option<WindowGeometry>>,
+    hidden_to_tray: Cell<bool>,
+    click_through: Cell<bool>,
+    /// The system moves or resizes the window since the last `WindowCommand::Drag` or `DragResize`
+    system_drag: Cell<bool>,
+    clipboard: Clipboard,
+    #[cfg(feature = "tray")]
+    tray: RefCell<Option<Tray>>,
+    /// Started with the first spawned task
+    #[cfg(feature = "async")]
+    tasks: RefCell<Option<Rc<RefCell<UiTasks>>>>,
+    /// Wakes the event loop of `Window::show` once a task finished
+    #[cfg(feature = "async")]
+    task_waker: RefCell<Option<TaskWaker>>,
+    /// Size and position before entering fullscreen, restored when leaving it
+    windowed: Cell<Option<(PhysicalSize<u32>, Option<PhysicalPosition<i32>>)>>
+}
+
+/// How the window treats the mouse cursor, see `WindowHandle::set_cursor_mode`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CursorMode {
+    Normal,
+    /// Invisible over the window, but free to leave it
+    Hidden,
+    /// Kept inside the window
+    Confined,
+    /// Hidden and kept in the window, for camera controls driven by `WindowListener::on_raw_mouse_motion`
+    Captured
+}
+
+impl Default for CursorMode {
+    fn default() -> Self {
+        CursorMode::Normal
+    }
+}
+
+impl CursorMode {
+    fn is_visible(&self) -> bool {
+        *self == CursorMode::Normal || *self == CursorMode::Confined
+    }
+}
+
+/// Mouse cursor shown over the window, see `WindowListener::cursor`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cursor {
+    Icon(CursorIcon),
+    /// Texture drawn at the mouse in place of the system cursor, `hotspot` is the point of it placed
+    /// at the mouse. Sizes are in logical pixels.
+    Image { texture: ResourceId, size: (f32, f32), hotspot: (f32, f32) }
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor::Icon(CursorIcon::Default)
+    }
+}
+
+impl From<CursorIcon> for Cursor {
+    fn from(icon: CursorIcon) -> Self {
+        Cursor::Icon(icon)
+    }
+}
+
+/// Edge or corner of the window moved by a `ResizeBorder`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeEdge {
+    Left, Right, Top, Bottom, TopLeft, TopRight, BottomLeft, BottomRight
+}
+
+impl ResizeEdge {
+    /// Direction the edge moves along each axis, negative for the ones at the window origin
+    pub fn directions(&self) -> (i32, i32) {
+        match self {
+            ResizeEdge::Left => (-1, 0),
+            ResizeEdge::Right => (1, 0),
+            ResizeEdge::Top => (0, -1),
+            ResizeEdge::Bottom => (0, 1),
+            ResizeEdge::TopLeft => (-1, -1),
+            ResizeEdge::TopRight => (1, -1),
+            ResizeEdge::BottomLeft => (-1, 1),
+            ResizeEdge::BottomRight => (1, 1)
+        }
+    }
+
+    #[cfg(feature = "drag-resize")]
+    fn resize_direction(&self) -> winit::window::ResizeDirection {
+        use winit::window::ResizeDirection;
+        match self {
+            ResizeEdge::Left => ResizeDirection::West,
+            ResizeEdge::Right => ResizeDirection::East,
+            ResizeEdge::Top => ResizeDirection::North,
+            ResizeEdge::Bottom => ResizeDirection::South,
+            ResizeEdge::TopLeft => ResizeDirection::NorthWest,
+            ResizeEdge::TopRight => ResizeDirection::NorthEast,
+            ResizeEdge::BottomLeft => ResizeDirection::SouthWest,
+            ResizeEdge::BottomRight => ResizeDirection::SouthEast
+        }
+    }
+
+    pub fn cursor(&self) -> CursorIcon {
+        match self {
+            ResizeEdge::Left => CursorIcon::WResize,
+            ResizeEdge::Right => CursorIcon::EResize,
+            ResizeEdge::Top => CursorIcon::NResize,
+            ResizeEdge::Bottom => CursorIcon::SResize,
+            ResizeEdge::TopLeft => CursorIcon::NwResize,
+            ResizeEdge::TopRight => CursorIcon::NeResize,
+            ResizeEdge::BottomLeft => CursorIcon::SwResize,
+            ResizeEdge::BottomRight => CursorIcon::SeResize
+        }
+    }
+}
+
+/// Window operation requested by custom chrome of an undecorated window, see `WindowHandle::apply`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowCommand {
+    /// Lets the system move the window with the mouse, sent when the left button is pressed
+    Drag,
+    /// Lets the system move the edge with the mouse, sent when the left button is pressed. Needs the
+    /// `drag-resize` feature, otherwise the window follows the `Resize` commands
+    DragResize(ResizeEdge),
+    /// Moves the window by logical pixels, the unit of the mouse positions widgets receive.
+    /// Ignored while the system moves the window after a `Drag`
+    Move(f32, f32),
+    /// Moves an edge of the window by logical pixels, ignored while the system moves it after a `DragResize`
+    Resize(ResizeEdge, f32, f32),
+    Minimize,
+    ToggleMaximize,
+    Close
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FullscreenMode {
+    Windowed,
+    /// Covers the monitor the window is on without changing its video mode
+    Borderless,
+    /// Switches the monitor to the video mode, see `WindowHandle::video_mode`
+    Exclusive(VideoMode)
+}
+
+impl WindowHandle {
+    fn new(display: Display) -> WindowHandle {
+        WindowHandle { display, state: Rc::new(HandleState::default()) }
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// System clipboard shared by the canvases of the window, hand it to `Widgets::set_clipboard`.
+    pub fn clipboard(&self) -> Clipboard {
+        self.state.clipboard.clone()
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.display.gl_window().window().set_title(title);
+    }
+
+    pub fn set_size(&self, width: u32, height: u32) {
+        self.display.gl_window().window().set_inner_size(LogicalSize::new(width, height));
+    }
+
+    pub fn set_position(&self, x: i32, y: i32) {
+        self.display.gl_window().window().set_outer_position(LogicalPosition::new(x, y));
+    }
+
+    pub fn set_min_size(&self, size: Option<(u32, u32)>) {
+        self.display.gl_window().window().set_min_inner_size(size.map(|(w, h)| LogicalSize::new(w, h)));
+    }
+
+    pub fn set_max_size(&self, size: Option<(u32, u32)>) {
+        self.display.gl_window().window().set_max_inner_size(size.map(|(w, h)| LogicalSize::new(w, h)));
+    }
+
+    /// Draws the next frame even if `WindowListener::needs_redraw` returns `false`, and wakes a
+    /// `PresentMode::Reactive` loop for it.
+    pub fn request_redraw(&self) {
+        self.state.redraw_requested.set(true);
+    }
+
+    /// Kept until `WindowListener::cursor` or the override asks for another one.
+    pub fn set_cursor_icon(&self, cursor: CursorIcon) {
+        self.apply_cursor(Cursor::Icon(cursor));
+    }
+
+    /// Shows `cursor` whatever `WindowListener::cursor` returns, e.g. a busy cursor while loading. `None` clears it.
+    pub fn set_cursor_override(&self, cursor: Option<Cursor>) {
+        *self.state.cursor_override.borrow_mut() = cursor;
+    }
+
+    /// Cursor shown over the window.
+    pub fn cursor(&self) -> Cursor {
+        self.state.cursor.borrow().clone()
+    }
+
+    /// Calls into the window only if the cursor differs from the shown one, which is not cheap on every platform.
+    fn apply_cursor(&self, cursor: Cursor) {
+        if *self.state.cursor.borrow() == cursor {
+            return;
+        }
+        let gl_window = self.display.gl_window();
+        let window = gl_window.window();
+        match &cursor {
+            Cursor::Icon(icon) => {
+                window.set_cursor_icon(*icon);
+                window.set_cursor_visible(self.cursor_mode().is_visible());
+            }
+            // drawn by the frame loop
+            Cursor::Image { .. } => window.set_cursor_visible(false)
+        }
+        *self.state.cursor.borrow_mut() = cursor;
+        self.request_redraw();
+    }
+
+    pub fn set_icon(&self, icon: Option<&DynamicImage>) -> std::result::Result<(), BadIcon> {
+        let icon = match icon {
+            Some(icon) => {
+                let (w, h) = icon.dimensions();
+                Some(Icon::from_rgba(icon.to_rgba().into_raw(), w, h)?)
+            }
+            None => None
+        };
+        self.display.gl_window().window().set_window_icon(icon);
+        Ok(())
+    }
+
+    pub fn minimize(&self) {
+        self.display.gl_window().window().set_minimized(true);
+    }
+
+    pub fn maximize(&self) {
+        if !self.state.maximized.get() {
+            self.state.restored.set(Some(self.geometry()));
+        }
+        self.display.gl_window().window().set_maximized(true);
+        self.state.maximized.set(true);
+    }
+
+    pub fn restore(&self) {
+        let gl_window = self.display.gl_window();
+        gl_window.window().set_minimized(false);
+        gl_window.window().set_maximized(false);
+        self.state.maximized.set(false);
+    }
+
+    pub fn set_visible(&self, visible: bool) {
+        self.display.gl_window().window().set_visible(visible);
+    }
+
+    /// Replaces the tray icon, `None` removes it. Has no effect on the visibility of the window.
+    #[cfg(feature = "tray")]
+    pub fn set_tray(&self, tray: Option<Tray>) {
+        *self.state.tray.borrow_mut() = tray;
+    }
+
+    /// Hides the window while the application keeps running, usually from `WindowListener::on_closing`
+    /// returning `CloseAction::Cancel`.
+    /// Without a tray icon there is no way for the user to bring it back.
+    pub fn hide_to_tray(&self) {
+        self.set_visible(false);
+        self.state.hidden_to_tray.set(true);
+    }
+
+    pub fn restore_from_tray(&self) {
+        if self.state.hidden_to_tray.replace(false) {
+            self.set_visible(true);
+            self.display.gl_window().window().set_minimized(false);
+            self.request_redraw();
+        }
+    }
+
+    pub fn is_hidden_to_tray(&self) -> bool {
+        self.state.hidden_to_tray.get()
+    }
+
+    #[cfg(feature = "tray")]
+    fn poll_tray(&self) -> Vec<TrayEvent> {
+        match self.state.tray.borrow().as_ref() {
+            Some(tray) => {
+                let events = tray.poll();
+                if tray.restores_on_click() && events.iter().any(|e| match e {
+                    TrayEvent::Click { .. } | TrayEvent::DoubleClick { .. } => true,
+                    _ => false
+                }) {
+                    self.restore_from_tray();
+                }
+                events
+            }
+            None => Vec::new()
+        }
+    }
+
+    /// Runs `future` off the UI thread, on a thread pool unless `set_spawner` chose another executor.
+    /// Its result comes back to the UI thread through `UiTask::then_ui`.
+    #[cfg(feature = "async")]
+    pub fn spawn<F>(&self, future: F) -> UiTask<F::Output> where F: Future + Send + 'static, F::Output: Send + 'static {
+        UiTask::spawn(self.tasks(), future)
+    }
+
+    /// Runs later tasks with `spawner`, such as a tokio runtime handle. Results of running tasks are dropped.
+    #[cfg(feature = "async")]
+    pub fn set_spawner<S>(&self, spawner: S) where S: Spawner + 'static {
+        let mut tasks = UiTasks::with_spawner(spawner);
+        tasks.set_waker(self.state.task_waker.borrow().clone());
+        *self.state.tasks.borrow_mut() = Some(Rc::new(RefCell::new(tasks)));
+    }
+
+    #[cfg(feature = "async")]
+    pub fn pending_tasks(&self) -> usize {
+        self.state.tasks.borrow().as_ref().map(|tasks| tasks.borrow().pending()).unwrap_or(0)
+    }
+
+    #[cfg(feature = "async")]
+    fn tasks(&self) -> Rc<RefCell<UiTasks>> {
+        self.state.tasks.borrow_mut()
+            .get_or_insert_with(|| {
+                let mut tasks = UiTasks::new();
+                tasks.set_waker(self.state.task_waker.borrow().clone());
+                Rc::new(RefCell::new(tasks))
+            })
+            .clone()
+    }
+
+    #[cfg(feature = "async")]
+    fn poll_tasks(&self) -> bool {
+        let tasks = self.state.tasks.borrow().clone();
+        match tasks {
+            Some(tasks) => tasks::poll(&tasks, self),
+            None => false
+        }
+    }
+
+    /// Carries out a command of a `TitleBar` or `ResizeBorder`.
+    pub fn apply(&self, command: WindowCommand) {
+        let gl_window = self.display.gl_window();
+        let window = gl_window.window();
+        let scale_factor = window.scale_factor();
+        let physical = |delta: f32| (delta as f64 * scale_factor).round() as i32;
+        match command {
+            WindowCommand::Drag => self.state.system_drag.set(window.drag_window().is_ok()),
+            #[cfg(feature = "drag-resize")]
+            WindowCommand::DragResize(edge) => {
+                self.state.system_drag.set(window.drag_resize_window(edge.resize_direction()).is_ok());
+            }
+            #[cfg(not(feature = "drag-resize"))]
+            WindowCommand::DragResize(_) => self.state.system_drag.set(false),
+            WindowCommand::Move(..) | WindowCommand::Resize(..) if self.state.system_drag.get() => {}
+            WindowCommand::Move(dx, dy) => {
+                if let Ok(position) = window.outer_position() {
+                    window.set_outer_position(PhysicalPosition::new(position.x + physical(dx), position.y + physical(dy)));
+                }
+            }
+            WindowCommand::Resize(edge, dx, dy) => {
+                let (horizontal, vertical) = edge.directions();
+                let size = window.inner_size();
+                let width = (size.width as i32 + physical(dx) * horizontal).max(1);
+                let height = (size.height as i32 + physical(dy) * vertical).max(1);
+                window.set_inner_size(PhysicalSize::new(width as u32, height as u32));
+                // edges at the origin keep the opposite edge in place
+                if horizontal < 0 || vertical < 0 {
+                    if let Ok(position) = window.outer_position() {
+                        let x = if horizontal < 0 { size.width as i32 - width } else { 0 };
+                        let y = if vertical < 0 { size.height as i32 - height } else { 0 };
+                        window.set_outer_position(PhysicalPosition::new(position.x + x, position.y + y));
+                    }
+                }
+            }
+            WindowCommand::Minimize => self.minimize(),
+            WindowCommand::ToggleMaximize => {
+                if self.state.maximized.get() {
+                    self.restore();
+                } else {
+                    self.maximize();
+                }
+            }
+            WindowCommand::Close => self.close()
+        }
+    }
+
+    /// Stops `Window::show` before the next frame, without asking `WindowListener::on_closing`.
+    pub fn close(&self) {
+        self.state.close_requested.set(true);
+    }
+
+    /// Closes the window like `close`, with `code` returned by `Window::show`.
+    pub fn exit(&self, code: i32) {
+        self.state.exit_code.set(code);
+        self.close();
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.state.exit_code.get()
+    }
+
+    pub fn set_always_on_top(&self, always_on_top: bool) {
+        self.display.gl_window().window().set_always_on_top(always_on_top);
+    }
+
+    /// Lets mouse input pass through to the windows below. Only supported on Windows, returns
+    /// whether the window changed.
+    pub fn set_click_through(&self, click_through: bool) -> bool {
+        let changed = set_click_through(self.display.gl_window().window(), click_through);
+        if changed {
+            self.state.click_through.set(click_through);
+        }
+        changed
+    }
+
+    /// Whether mouse input currently passes through, false where click-through is unsupported.
+    pub fn is_click_through(&self) -> bool {
+        self.state.click_through.get()
+    }
+
+    /// Picked files arrive in `WindowListener::on_user_event` as the event made by `event`.
+    #[cfg(feature = "dialogs")]
+    pub fn open_file_dialog<T, F>(&self, proxy: &WindowProxy<T>, options: FileDialog, event: F)
+        where T: Send + 'static, F: FnOnce(Vec<PathBuf>) -> T + Send + 'static {
+
+        dialog::open_file_dialog(proxy, options, event);
+    }
+
+    #[cfg(feature = "dialogs")]
+    pub fn save_file_dialog<T, F>(&self, proxy: &WindowProxy<T>, options: FileDialog, event: F)
+        where T: Send + 'static, F: FnOnce(Option<PathBuf>) -> T + Send + 'static {
+
+        dialog::save_file_dialog(proxy, options, event);
+    }
+
+    #[cfg(feature = "dialogs")]
+    pub fn message_box<T, F>(&self, proxy: &WindowProxy<T>, message: MessageBox, event: F)
+        where T: Send + 'static, F: FnOnce(bool) -> T + Send + 'static {
+
+        dialog::message_box(proxy, message, event);
+    }
+
+    /// Grabbing the cursor is not supported on every platform, the mode is unchanged if it fails.
+    pub fn set_cursor_mode(&self, mode: CursorMode) -> std::result::Result<(), ExternalError> {
+        let gl_window = self.display.gl_window();
+        let window = gl_window.window();
+        window.set_cursor_grab(mode == CursorMode::Confined || mode == CursorMode::Captured)?;
+        let image = if let Cursor::Image { .. } = *self.state.cursor.borrow() { true } else { false };
+        window.set_cursor_visible(mode.is_visible() && !image);
+        self.state.cursor_mode.set(mode);
+        Ok(())
+    }
+
+    pub fn cursor_mode(&self) -> CursorMode {
+        self.state.cursor_mode.get()
+    }
+
+    /// Modifier keys held down, tracked from `WindowEvent::ModifiersChanged`.
+    pub fn modifiers(&self) -> ModifiersState {
+        self.state.modifiers.get()
+    }
+
+    pub fn is_close_requested(&self) -> bool {
+        self.state.close_requested.get()
+    }
+
+    pub fn fullscreen_mode(&self) -> FullscreenMode {
+        match self.display.gl_window().window().fullscreen() {
+            Some(Fullscreen::Exclusive(mode)) => FullscreenMode::Exclusive(mode),
+            Some(Fullscreen::Borderless(_)) => FullscreenMode::Borderless,
+            None => FullscreenMode::Windowed
+        }
+    }
+
+    /// Switches the window to `mode` on the monitor it is on. Going back to `Windowed` restores
+    /// the size and position it had before.
+    pub fn set_fullscreen(&self, mode: FullscreenMode) {
+        let gl_window = self.display.gl_window();
+        let window = gl_window.window();
+        let windowed = window.fullscreen().is_none();
+        match mode {
+            FullscreenMode::Windowed => {
+                window.set_fullscreen(None);
+                if let Some((size, position)) = self.state.windowed.take() {
+                    window.set_inner_size(size);
+                    if let Some(position) = position {
+                        window.set_outer_position(position);
+                    }
+                }
+            }
+            mode => {
+                if windowed {
+                    self.state.windowed.set(Some((window.inner_size(), window.outer_position().ok())));
+                }
+                window.set_fullscreen(Some(match mode {
+                    FullscreenMode::Exclusive(video_mode) => Fullscreen::Exclusive(video_mode),
+                    _ => Fullscreen::Borderless(window.current_monitor())
+                }));
+            }
+        }
+    }
+
+    /// Switches between windowed and borderless fullscreen, as F11 does in most applications.
+    pub fn toggle_fullscreen(&self) {
+        match self.fullscreen_mode() {
+            FullscreenMode::Windowed => self.set_fullscreen(FullscreenMode::Borderless),
+            _ => self.set_fullscreen(FullscreenMode::Windowed)
+        }
+    }
+
+    /// Connected monitors, in the order `WindowConfig::monitor` indexes them.
+    pub fn monitors(&self) -> Vec<MonitorInfo> {
+        self.display.gl_window().window().available_monitors().map(MonitorInfo::new).collect()
+    }
+
+    pub fn current_monitor(&self) -> MonitorInfo {
+        MonitorInfo::new(self.display.gl_window().window().current_monitor())
+    }
+
+    pub fn primary_monitor(&self) -> MonitorInfo {
+        MonitorInfo::new(self.display.gl_window().window().primary_monitor())
+    }
+
+    /// Centers the window on the monitor, restoring it first if it is maximized.
+    pub fn move_to_monitor(&self, monitor: &MonitorInfo) {
+        if self.state.maximized.get() {
+            self.restore();
+        }
+        let gl_window = self.display.gl_window();
+        let window = gl_window.window();
+        let (outer_w, outer_h): (u32, u32) = window.outer_size().into();
+        let (x, y) = monitor.position;
+        let (w, h) = monitor.size;
+        window.set_outer_position(PhysicalPosition::new(
+            x + (w as i32 - outer_w as i32) / 2, y + (h as i32 - outer_h as i32) / 2
+        ));
+    }
+
+    /// Current geometry, to reopen the window the same way with `WindowConfig::geometry`. Maximized
+    /// and fullscreen windows report the geometry they return to. Only maximizing through the handle
+    /// is tracked, as the system does not report it.
+    pub fn geometry(&self) -> WindowGeometry {
+        let maximized = self.state.maximized.get();
+        if let Some((size, position)) = self.state.windowed.get() {
+            return WindowGeometry {
+                position: position.map(|p| p.into()).unwrap_or((0, 0)),
+                size: size.into(),
+                maximized
+            }
+        }
+        if let (true, Some(restored)) = (maximized, self.state.restored.get()) {
+            return WindowGeometry { maximized, ..restored }
+        }
+        let gl_window = self.display.gl_window();
+        let window = gl_window.window();
+        WindowGeometry {
+            position: window.outer_position().map(|p| p.into()).unwrap_or((0, 0)),
+            size: window.inner_size().into(),
+            maximized: self.state.maximized.get()
+        }
+    }
+
+    /// Video modes of the monitor the window is on.
+    pub fn video_modes(&self) -> Vec<VideoMode> {
+        self.display.gl_window().window().current_monitor().video_modes().collect()
+    }
+
+    /// Video mode of the current monitor closest to `size` in physical pixels, preferring the
+    /// highest bit depth and the refresh rate closest to `refresh_rate`, or the highest one.
+    pub fn video_mode(&self, size: (u32, u32), refresh_rate: Option<u16>) -> Option<VideoMode> {
+        let (width, height) = size;
+        self.video_modes().into_iter()
+            .min_by_key(|mode| {
+                let PhysicalSize { width: w, height: h } = mode.size();
+                let distance = (w as i64 - width as i64).abs() + (h as i64 - height as i64).abs();
+                let refresh = match refresh_rate {
+                    Some(rate) => (mode.refresh_rate() as i64 - rate as i64).abs(),
+                    None => -(mode.refresh_rate() as i64)
+                };
+                (distance, refresh, -(mode.bit_depth() as i64))
+            })
+    }
+
+    fn is_redraw_requested(&self) -> bool {
+        self.state.redraw_requested.get()
+    }
+
+    fn take_redraw_request(&self) -> bool {
+        self.state.redraw_requested.replace(false)
+    }
+}
+
 #[cfg(windows)]
-fn new_loop<T>() -> EventLoop<T> {
+pub(crate) fn new_loop<T>() -> EventLoop<T> {
     use winit::platform::windows::EventLoopExtWindows;
     EventLoop::new_dpi_unaware_any_thread()
 }
 
 
 #[cfg(not(windows))]
-fn new_loop<T>() -> EventLoop<T> {
+pub(crate) fn new_loop<T>() -> EventLoop<T> {
     use winit::platform::unix::EventLoopExtUnix;
     EventLoop::new_any_thread()
 }
 
+#[cfg(windows)]
+fn set_click_through(window: &winit::window::Window, click_through: bool) -> bool {
+    use winit::platform::windows::WindowExtWindows;
+    use winapi::shared::windef::HWND;
+    use winapi::um::winuser::{GetWindowLongW, SetWindowLongW, SetLayeredWindowAttributes, GWL_EXSTYLE,
+                              WS_EX_LAYERED, WS_EX_TRANSPARENT, LWA_ALPHA};
+
+    let hwnd = window.hwnd() as HWND;
+    unsafe {
+        let style = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+        // layered windows are hit tested by their style alone, fully opaque keeps the GL surface as it is
+        let style = if click_through {
+            style | WS_EX_LAYERED | WS_EX_TRANSPARENT
+        } else {
+            style & !WS_EX_TRANSPARENT
+        };
+        SetWindowLongW(hwnd, GWL_EXSTYLE, style as i32);
+        if click_through {
+            SetLayeredWindowAttributes(hwnd, 0, 255, LWA_ALPHA);
+        }
+    }
+    true
+}
+
+#[cfg(not(windows))]
+fn set_click_through(_window: &winit::window::Window, _click_through: bool) -> bool {
+    false
+}
+
 impl Window {
-    pub fn show<L, S, T>(size: S, title: T, icon: Option<DynamicImage>,
-                         decorated: bool, resizable: bool, top: bool, vsync: bool, listener: &mut L, tps: u32)
-        where L: WindowListener, S: Into<(u32, u32)>, T: Into<String> {
+    /// Reads the last presented frame, call it after `on_frame_draw` returned.
+    pub fn capture_frame(display: &Display) -> Result<RgbaImage> {
+        Ok(image_from_rows(display.read_front_buffer()?))
+    }
+
+    /// Runs the window until it is closed, returning the code given to `WindowHandle::exit`, zero otherwise.
+    /// Failing to create the context, compile the built-in shaders, load the listener's resources or
+    /// present a frame returns the error instead, so the window can be shown again with other settings.
+    pub fn show<L, T>(config: WindowConfig, listener: &mut L) -> std::result::Result<i32, WindowError>
+        where L: WindowListener<T>, T: Send + 'static {
 
-        let (window_w, window_h) = size.into();
+        let (window_w, window_h) = config.size;
+        let tick = Duration::from_secs_f64(1.0 / config.tps.max(1) as f64);
+        let reactive = config.present_mode == PresentMode::Reactive;
+        let transparent = config.transparent;
+        let frame_interval = config.max_fps.map(|fps| Duration::from_secs_f64(1.0 / fps.max(1) as f64));
 
-        let mut event_loop = new_loop();
+        let mut event_loop = new_loop::<LoopEvent<T>>();
         let mut wb = WindowBuilder::new()
-            .with_decorations(decorated)
-            .with_title(title)
-            .with_resizable(resizable) //Stupid winit warning about Xfce bug
-            .with_always_on_top(top)
+            .with_decorations(config.decorated)
+            .with_title(config.title)
+            .with_resizable(config.resizable) //Stupid winit warning about Xfce bug
+            .with_always_on_top(config.always_on_top)
+            .with_transparent(config.transparent)
             .with_visible(false)
             .with_inner_size(LogicalSize::new(window_w, window_h));
 
-        if cfg!(not(windows)) && !resizable {
+        if let Some((w, h)) = config.min_size {
+            wb = wb.with_min_inner_size(LogicalSize::new(w, h));
+        }
+        if let Some((w, h)) = config.max_size {
+            wb = wb.with_max_inner_size(LogicalSize::new(w, h));
+        }
+        if cfg!(not(windows)) && !config.resizable {
             wb = wb.with_min_inner_size(LogicalSize::new(window_w, window_h))
                 .with_max_inner_size(LogicalSize::new(window_w, window_h));
         }
 
-        if let Some(icon) = icon {
+        if let Some(icon) = config.icon {
             let (icon_w, icon_h) = icon.dimensions();
-            wb = wb.with_window_icon(Some(
-                Icon::from_rgba(icon.to_rgba().into_raw(), icon_w, icon_h)
-                    .expect("Bad icon")
-            ));
+            wb = wb.with_window_icon(Some(Icon::from_rgba(icon.to_rgba().into_raw(), icon_w, icon_h)?));
         }
         let cb = ContextBuilder::new()
             .with_gl(GlRequest::Specific(Api::OpenGl, (2, 1)))
             //.with_gl_robustness(Robustness::TryRobustNoResetNotification)
             .with_gl_profile(GlProfile::Compatibility)
             .with_depth_buffer(24)
-            //.with_stencil_buffer(8)
-            .with_pixel_format(24, 0)
-            .with_vsync(vsync)
-            .with_multisampling(0)
-            .with_srgb(false);
-
-        let display = Display::new(wb, cb, &event_loop)
-            .expect("Display creation failed");
+            .with_stencil_buffer(8)
+            .with_pixel_format(24, if config.transparent { 8 } else { 0 })
+            .with_vsync(config.vsync)
+            .with_multisampling(config.samples.unwrap_or_else(|| listener.multisampling()))
+            .with_srgb(config.srgb.unwrap_or(listener.color_space() == ColorSpace::Linear));
 
-        let monitor: MonitorHandle = display.gl_window().window().current_monitor();
-        let (monitor_w, monitor_h) = Into::<(f64, f64)>::into(monitor.size());
+        let monitors: Vec<MonitorInfo> = event_loop.available_monitors().map(MonitorInfo::new).collect();
+        let placement = match config.geometry_store.as_ref().and_then(|store| store.load()) {
+            Some(geometry) => Placement::Geometry(geometry),
+            None => config.placement.clone()
+        };
+        // saved on another monitor layout, it may be off screen now
+        let placement = match placement {
+            Placement::Geometry(geometry) => geometry.fit_to(&monitors)
+                .map(Placement::Geometry)
+                .unwrap_or(Placement::Centered),
+            placement => placement
+        };
+        let monitor = match &config.monitor {
+            MonitorChoice::Current => None,
+            MonitorChoice::Primary => Some(event_loop.primary_monitor()),
+            MonitorChoice::Index(index) => event_loop.available_monitors().nth(*index),
+            MonitorChoice::Named(name) => event_loop.available_monitors().find(|m| m.name().as_ref() == Some(name))
+        };
+        let display = Display::new(wb, cb, &event_loop)?;
+        let handle = WindowHandle::new(display.clone());
+        #[cfg(feature = "async")]
+        {
+            let proxy = Mutex::new(event_loop.create_proxy());
+            *handle.state.task_waker.borrow_mut() = Some(Arc::new(move || {
+                if let Ok(proxy) = proxy.lock() {
+                    // the window may be gone by the time the task finishes
+                    let _ = proxy.send_event(LoopEvent::TaskFinished);
+                }
+            }));
+        }
+        let monitor: MonitorHandle = monitor.unwrap_or_else(|| display.gl_window().window().current_monitor());
 
-        let shaders = Rc::new(RefCell::new(ShaderManager::new(&display)));
+        let shaders = Rc::new(RefCell::new(ShaderManager::try_new(&display)?));
         let fonts = Rc::new(RefCell::new(FontManager::new(&display)));
         let textures = Rc::new(RefCell::new(TextureManager::new(&display)));
+        let buffers = Rc::new(RefCell::new(GeometryBuffers::new()));
+        let gpu_timer = Rc::new(GpuTimer::new(&display));
+        fonts.borrow_mut().set_gpu_timer(Some(gpu_timer.clone()));
+        let mut post = PostProcessor::new();
+        let mut overlay = PerfOverlay::new(config.perf_overlay);
+        let mut loader = None;
+        let overlay_key = config.perf_overlay_key;
 
         {
             let gl_window = display.gl_window();
             let window: &winit::window::Window = gl_window.window();
-            listener.load_resources(&display, shaders.clone(), fonts.clone(), textures.clone());
-            window.set_visible(true);
-            window.set_outer_position(LogicalPosition::new(
-                monitor_w / 2.0 - window_w as f64 / 2.0,
-                monitor_h / 2.0 - window_h as f64 / 2.0
-            ));
+            listener.on_proxy(WindowProxy { proxy: event_loop.create_proxy() });
+            listener.load_resources(&handle, shaders.clone(), fonts.clone(), textures.clone())?;
+            let manifest = listener.resources();
+            if !manifest.is_empty() {
+                loader = Some(ResourceLoader::start(manifest));
+            }
+            if config.click_through {
+                // left off where unsupported, listeners check `is_click_through`
+                handle.set_click_through(true);
+            }
+            match &placement {
+                Placement::Geometry(geometry) => {
+                    let (x, y) = geometry.position;
+                    window.set_inner_size(PhysicalSize::new(geometry.size.0, geometry.size.1));
+                    window.set_visible(true);
+                    window.set_outer_position(PhysicalPosition::new(x, y));
+                    if geometry.maximized {
+                        handle.maximize();
+                    }
+                }
+                placement => {
+                    let (origin_x, origin_y): (i32, i32) = monitor.position().into();
+                    let (x, y) = match placement {
+                        Placement::Position(x, y) => {
+                            let factor = monitor.scale_factor();
+                            ((*x as f64 * factor) as i32, (*y as f64 * factor) as i32)
+                        }
+                        _ => {
+                            let (monitor_w, monitor_h): (u32, u32) = monitor.size().into();
+                            let (outer_w, outer_h): (u32, u32) = window.outer_size().into();
+                            ((monitor_w as i32 - outer_w as i32) / 2, (monitor_h as i32 - outer_h as i32) / 2)
+                        }
+                    };
+                    window.set_visible(true);
+                    // placed on the monitor first, so it is maximized there
+                    window.set_outer_position(PhysicalPosition::new(origin_x + x, origin_y + y));
+                    if *placement == Placement::Maximized {
+                        handle.maximize();
+                    }
+                }
+            }
         }
 
         let mut events = VecDeque::new();
+        // window or user events arrived that a reactive loop has not drawn a frame for
+        let mut pending = false;
+        let shown = Instant::now();
         let mut next_frame_time = Instant::now();
-        let mut last_frame_time = SystemTime::now();
+        let mut last_frame_time = Instant::now();
+        let mut average_frame_time: Option<f32> = None;
+        // time not yet simulated by ticks
+        let mut accumulator = Duration::from_secs(0);
         let mut mouse = (0f32, 0f32);
+        let mut primary_touch = None;
+        let mut gestures = GestureRecognizer::new();
+        #[cfg(feature = "gamepad")]
+        let mut gamepad = GamepadInput::new(listener.gamepad_mapping()).ok();
+        let mut failure = None;
+        let failed = &mut failure;
 
-        listener.on_created(&display);
+        listener.on_created(&handle);
 
-        event_loop.run_return(move |event: Event<()>, _, control_flow| {
-            if listener.is_closed(&display) {
+        let closing = handle.clone();
+        event_loop.run_return(move |event: Event<LoopEvent<T>>, _, control_flow| {
+            if let Event::LoopDestroyed = event {
+                listener.on_exit(&handle);
+                return;
+            }
+            if handle.is_close_requested() || listener.is_closed(&handle) {
                 *control_flow = ControlFlow::Exit;
                 return;
             }
+            // frames started by input in reactive mode are drawn right away instead of spinning until the interval ends
+            let timed = if let Event::NewEvents(StartCause::ResumeTimeReached { .. }) = &event { true } else { false };
             let new_frame = match event {
                 Event::NewEvents(cause) => {
                     match cause {
@@ -111,7 +1367,34 @@ impl Window {
                         _ => false
                     }
                 },
+                // delivered right away, the loop may have been woken up just for it
+                Event::UserEvent(LoopEvent::User(event)) => {
+                    listener.on_user_event(&handle, event);
+                    pending = true;
+                    false
+                }
+                #[cfg(feature = "async")]
+                Event::UserEvent(LoopEvent::TaskFinished) => {
+                    pending |= handle.poll_tasks();
+                    false
+                }
+                Event::MainEventsCleared => {
+                    #[cfg(feature = "tray")]
+                    for event in handle.poll_tray() {
+                        listener.on_tray_event(&handle, event);
+                        pending = true;
+                    }
+                    reactive && pending
+                }
                 other => {
+                    if let Event::WindowEvent { .. } = &other {
+                        pending = true;
+                    }
+                    if let Event::WindowEvent { event: WindowEvent::ScaleFactorChanged { scale_factor, .. }, .. } = &other {
+                        // cannot be queued with the other events as it borrows the new window size
+                        fonts.borrow_mut().set_scale_factor(*scale_factor as f32);
+                        listener.on_scale_factor_changed(&handle, *scale_factor);
+                    }
                     if let Some(event) = other.to_static() {
                         events.push_back(event);
                     }
@@ -119,72 +1402,352 @@ impl Window {
                 }
             };
             if new_frame {
-                let frame = display.draw();
+                pending = false;
+                if timed && frame_interval.is_some() {
+                    while Instant::now() < next_frame_time {
+                        std::thread::yield_now();
+                    }
+                }
+                let (w, h) = display.get_framebuffer_dimensions();
+                let factor = display.gl_window().window().scale_factor() as f32;
+                let dimensions = (w as f32 / factor, h as f32 / factor);
+                fonts.borrow_mut().set_scale_factor(factor);
+                textures.borrow_mut().set_scale_factor(factor);
+
+                let now = Instant::now();
+                let frame_time = now - last_frame_time;
+                accumulator += frame_time;
+                last_frame_time = now;
+                let average = match average_frame_time {
+                    Some(average) => average * 0.9 + frame_time.as_secs_f32() * 0.1,
+                    None => frame_time.as_secs_f32()
+                };
+                average_frame_time = Some(average);
+                let mut ticks = 0;
+                while accumulator >= tick && ticks < MAX_CATCH_UP_TICKS {
+                    listener.on_tick(&handle);
+                    accumulator -= tick;
+                    ticks += 1;
+                }
+                if accumulator >= tick {
+                    accumulator = Duration::from_secs_f64(accumulator.as_secs_f64() % tick.as_secs_f64());
+                }
+                // how far the frame is between the last tick and the next one
+                let partial_ticks = accumulator.as_secs_f32() / tick.as_secs_f32();
 
-                let (w, h) = frame.get_dimensions();
+                // released before notifying, the listener may load more textures
+                let loaded = textures.borrow_mut().poll_async();
+                for loaded in loaded {
+                    listener.on_texture_loaded(&handle, loaded);
+                }
+                if let Some(resources) = loader.as_mut() {
+                    if resources.poll(&shaders, &fonts, &textures) {
+                        listener.on_load_progress(&handle, resources.progress());
+                        handle.request_redraw();
+                    }
+                    if resources.is_finished() {
+                        let errors = resources.take_errors();
+                        loader = None;
+                        listener.on_resources_loaded(&handle, errors);
+                    }
+                }
+                let reloaded = shaders.borrow_mut().poll_changes();
+                for reloaded in reloaded {
+                    listener.on_shader_reloaded(&handle, reloaded);
+                }
 
-                let elapsed = SystemTime::now().duration_since(last_frame_time).expect("Error calculating frame time");
-                let partial_ticks = (elapsed.as_millis() as f64 / tps as f64) as f32;
+                listener.on_frame_update(&handle, (w as f32, h as f32), mouse, partial_ticks);
 
-                listener.on_frame_update(&display, (w as f32, h as f32), mouse, partial_ticks);
+                let cursor = handle.state.cursor_override.borrow().clone().or_else(|| listener.cursor(&handle, mouse));
+                if let Some(cursor) = cursor {
+                    handle.apply_cursor(cursor);
+                }
 
-                let mut canvas = Canvas::new(
-                    display.clone(), shaders.clone(), fonts.clone(), textures.clone(), frame
-                );
+                // nothing changed since the last frame, the swap chain keeps showing it
+                // both are evaluated so a pending request is consumed
+                let requested = handle.take_redraw_request();
+                if listener.needs_redraw(&handle) || requested {
+                    let mut canvas = Canvas::with_buffers(
+                        display.clone(), shaders.clone(), fonts.clone(), textures.clone(), buffers.clone(), display.draw()
+                    );
+                    canvas.set_color_space(listener.color_space());
+                    canvas.set_time(shown.elapsed().as_secs_f32());
+                    canvas.set_clipboard(handle.clipboard());
+                    canvas.set_gpu_timer(Some(gpu_timer.clone()));
+                    let cleared = if transparent { canvas.clear((0.0, 0.0, 0.0, 0.0), 1.0) } else { Ok(()) };
 
-                listener.on_frame_draw(&mut canvas, mouse, partial_ticks);
+                    let result = cleared.and_then(|_| listener.on_frame_draw(&mut canvas, mouse, partial_ticks));
 
-                let dimensions = canvas.dimensions();
+                    // flush even after a failed draw, nothing may stay queued once the frame is taken back
+                    let result = result.and(canvas.flush());
+                    let mut stats = canvas.stats();
+                    let effects = listener.post_effects(&handle);
 
-                canvas.into_inner().finish().expect("Frame finishing failed");
+                    let mut finish_time = Duration::from_secs(0);
+                    let result = canvas.into_inner()
+                        .and_then(|mut frame| {
+                            let processed = post.apply(&display, &mut frame, &effects, &shaders.borrow(), &textures.borrow());
+                            // drawn after the effects so they do not blur or tint it
+                            let cursor = match handle.cursor() {
+                                Cursor::Image { texture, size, hotspot } if handle.cursor_mode().is_visible() =>
+                                    Some((texture, size, hotspot)),
+                                _ => None
+                            };
+                            let (frame, overlaid) = if overlay.is_visible() || cursor.is_some() {
+                                let mut canvas = Canvas::with_buffers(
+                                    display.clone(), shaders.clone(), fonts.clone(), textures.clone(), buffers.clone(), frame
+                                );
+                                let drawn = if overlay.is_visible() { overlay.draw(&mut canvas) } else { Ok(()) };
+                                if let Some((texture, (w, h), (hx, hy))) = cursor {
+                                    let factor = canvas.scale_factor();
+                                    let (x, y) = (mouse.0 / factor - hx, mouse.1 / factor - hy);
+                                    canvas.queue_textured_rect(texture, [x, y, w, h], [0.0, 0.0, 1.0, 1.0], [1.0; 4]);
+                                }
+                                let drawn = drawn.and(canvas.flush());
+                                (canvas.into_inner()?, drawn)
+                            } else {
+                                (frame, Ok(()))
+                            };
+                            let finishing = Instant::now();
+                            // the frame has to be finished even if an effect failed
+                            let finished = frame.finish().map_err(Error::from);
+                            finish_time = finishing.elapsed();
+                            finished.and(processed).and(overlaid)
+                        })
+                        .and(result);
+                    stats.finish_time = finish_time;
+                    stats.cpu_time = now.elapsed().checked_sub(finish_time).unwrap_or_default();
+                    stats.frame_time = frame_time;
+                    stats.gpu_time = gpu_timer.end_frame();
+                    stats.fps = if average > 0.0 { 1.0 / average } else { 0.0 };
 
+                    match result {
+                        Ok(()) => {
+                            overlay.record(stats);
+                            listener.on_frame_stats(&handle, stats);
+                        }
+                        Err(Error::SwapBuffers(error)) => {
+                            *failed = Some(WindowError::SwapBuffers(error));
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                        Err(error) => listener.on_render_error(&handle, error)
+                    }
+                }
+
+                for gesture in gestures.update() {
+                    listener.on_gesture(&handle, dimensions, gesture);
+                }
+
+                #[cfg(feature = "gamepad")]
+                {
+                    if let Some(gamepad) = gamepad.as_mut() {
+                        for action in gamepad.poll() {
+                            listener.on_gamepad_action(&handle, dimensions, action);
+                        }
+                    }
+                }
+
+                // input is handled after drawing, so a reactive loop draws once more to show its effect
+                if reactive && events.iter().any(|event| if let Event::WindowEvent { .. } = event { true } else { false }) {
+                    handle.request_redraw();
+                }
                 while let Some(event) = events.pop_front() {
                     match event {
                         Event::WindowEvent { event, .. } => {
                             match event {
-                                WindowEvent::CloseRequested =>
-                                    listener.on_close_requested(&display, dimensions),
+                                WindowEvent::CloseRequested => {
+                                    listener.on_close_requested(&handle, dimensions);
+                                    if listener.on_closing(&handle) == CloseAction::Close {
+                                        handle.close();
+                                    }
+                                }
+                                WindowEvent::Resized(size) => {
+                                    let factor = display.gl_window().window().scale_factor();
+                                    let (w, h): (f64, f64) = size.to_logical::<f64>(factor).into();
+                                    listener.on_resized(&handle, (w as f32, h as f32));
+                                }
                                 WindowEvent::Focused(focused) =>
-                                    listener.on_focused(&display, dimensions, focused),
-                                WindowEvent::KeyboardInput { input, .. } =>
-                                    listener.on_keyboard_key(&display, dimensions, input),
+                                    listener.on_focused(&handle, dimensions, focused),
+                                WindowEvent::KeyboardInput { input, .. } => {
+                                    if input.state == ElementState::Pressed && input.virtual_keycode.is_some()
+                                        && input.virtual_keycode == overlay_key {
+                                        overlay.toggle();
+                                        handle.request_redraw();
+                                    }
+                                    listener.on_keyboard_key(&handle, dimensions, input);
+                                }
+                                WindowEvent::ModifiersChanged(modifiers) => {
+                                    handle.state.modifiers.set(modifiers);
+                                    listener.on_modifiers_changed(&handle, dimensions, modifiers);
+                                }
                                 WindowEvent::ReceivedCharacter(ch) =>
-                                    listener.on_keyboard_char(&display, dimensions, ch),
+                                    listener.on_keyboard_char(&handle, dimensions, ch),
                                 WindowEvent::MouseInput { state: e_state, button, .. } =>
-                                    listener.on_mouse_button(&display, dimensions, button, e_state, mouse),
-                                WindowEvent::MouseWheel { delta, .. } =>
-                                    listener.on_mouse_wheel(&display, dimensions, delta),
+                                    listener.on_mouse_button(&handle, dimensions, button, e_state, mouse),
+                                WindowEvent::MouseWheel { delta, .. } => {
+                                    listener.on_mouse_wheel(&handle, dimensions, delta);
+                                    if handle.modifiers().ctrl() {
+                                        let lines = match delta {
+                                            MouseScrollDelta::LineDelta(_, y) => y,
+                                            MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_ZOOM_LINE
+                                        };
+                                        if let Some(gesture) = gestures.on_zoom_wheel(lines, mouse) {
+                                            listener.on_gesture(&handle, dimensions, gesture);
+                                        }
+                                    }
+                                },
                                 WindowEvent::CursorMoved { position, .. } => {
                                     let (mouse_x, mouse_y): (f64, f64) = position.into();
                                     mouse = (mouse_x as f32, mouse_y as f32);
-                                    listener.on_mouse_move(&display, dimensions, mouse);
+                                    listener.on_mouse_move(&handle, dimensions, mouse);
+                                    // a drawn cursor has to follow the mouse
+                                    if let Cursor::Image { .. } = handle.cursor() {
+                                        handle.request_redraw();
+                                    }
+                                }
+                                WindowEvent::Touch(touch) => {
+                                    let (touch_x, touch_y): (f64, f64) = touch.location.into();
+                                    let pos = (touch_x as f32, touch_y as f32);
+                                    listener.on_touch(&handle, dimensions, touch);
+                                    if primary_touch.is_none() && touch.phase == TouchPhase::Started {
+                                        primary_touch = Some(touch.id);
+                                    }
+                                    if primary_touch == Some(touch.id) {
+                                        mouse = pos;
+                                        listener.on_mouse_move(&handle, dimensions, mouse);
+                                        match touch.phase {
+                                            TouchPhase::Started =>
+                                                listener.on_mouse_button(&handle, dimensions, MouseButton::Left, ElementState::Pressed, mouse),
+                                            TouchPhase::Ended | TouchPhase::Cancelled => {
+                                                listener.on_mouse_button(&handle, dimensions, MouseButton::Left, ElementState::Released, mouse);
+                                                primary_touch = None;
+                                            }
+                                            TouchPhase::Moved => ()
+                                        }
+                                    }
+                                    for gesture in gestures.on_touch(touch.id, touch.phase, pos) {
+                                        listener.on_gesture(&handle, dimensions, gesture);
+                                    }
                                 }
                                 _ => ()
                             }
                         },
+                        Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+                            // device events are left out of the redraw after input above
+                            if listener.on_raw_mouse_motion(&handle, delta) {
+                                handle.request_redraw();
+                            }
+                        }
                         _ => ()
                     }
                 }
 
-                last_frame_time = SystemTime::now();
-                next_frame_time = Instant::now() + Duration::from_secs_f32(1.0 / 60.0);
+                // paced from the previous target so frames keep their cadence, unless they fell behind
+                next_frame_time = match frame_interval {
+                    Some(interval) => (next_frame_time + interval).max(Instant::now()),
+                    None => Instant::now()
+                };
             }
-            *control_flow = ControlFlow::WaitUntil(next_frame_time);
+            // loading resources cannot wake the loop, so it keeps checking on them
+            *control_flow = if reactive && !handle.is_redraw_requested() {
+                if loader.is_some() {
+                    ControlFlow::WaitUntil(Instant::now() + POLL_INTERVAL)
+                } else {
+                    ControlFlow::Wait
+                }
+            } else {
+                ControlFlow::WaitUntil(next_frame_time.checked_sub(PACING_SPIN).unwrap_or(next_frame_time))
+            };
         });
+
+        if let Some(store) = &config.geometry_store {
+            // losing the geometry is not worth failing a clean shutdown for
+            let _ = store.save(closing.geometry());
+        }
+
+        match failure {
+            Some(error) => Err(error),
+            None => Ok(closing.exit_code())
+        }
     }
 }
 
-pub trait WindowListener {
-    fn is_closed(&self, display: &Display) -> bool;
-    fn load_resources(&self, display: &Display, shaders: Rc<RefCell<ShaderManager>>, fonts: Rc<RefCell<FontManager>>, textures: Rc<RefCell<TextureManager>>) {}
-    fn on_created(&mut self, display: &Display) {}
-    fn on_frame_update(&mut self, display: &Display, dimensions: (f32, f32), mouse: (f32, f32), partial_ticks: f32) {}
-    fn on_frame_draw(&self, canvas: &mut Canvas<Frame>, mouse_pos: (f32, f32), partial_ticks: f32);
-    fn on_close_requested(&mut self, display: &Display, dimensions: (f32, f32)) {}
-    fn on_focused(&mut self, display: &Display, dimensions: (f32, f32), focused: bool) {}
-    fn on_keyboard_char(&mut self, display: &Display, dimensions: (f32, f32), ch: char) {}
-    fn on_keyboard_key(&mut self, display: &Display, dimensions: (f32, f32), input: KeyboardInput) {}
-    fn on_mouse_button(&mut self, display: &Display, dimensions: (f32, f32), button: MouseButton, state: ElementState, pos: (f32, f32)) {}
-    fn on_mouse_wheel(&mut self, display: &Display, dimensions: (f32, f32), delta: MouseScrollDelta) {}
-    fn on_mouse_move(&mut self, display: &Display, dimensions: (f32, f32), pos: (f32, f32)) {}
+/// Receives the events of a window, `T` is the type of user events sent through its `WindowProxy`.
+pub trait WindowListener<T = ()> where T: 'static {
+    /// Polled before every event, `WindowHandle::close` and `on_closing` make overriding it unnecessary
+    fn is_closed(&self, window: &WindowHandle) -> bool { false }
+    /// An error stops `Window::show` before the window is shown
+    fn load_resources(&self, window: &WindowHandle, shaders: Rc<RefCell<ShaderManager>>, fonts: Rc<RefCell<FontManager>>, textures: Rc<RefCell<TextureManager>>)
+        -> std::result::Result<(), WindowError> {
+        Ok(())
+    }
+    /// Called once before `load_resources` with the proxy background threads send user events through
+    fn on_proxy(&mut self, proxy: WindowProxy<T>) {}
+    fn on_created(&mut self, window: &WindowHandle) {}
+    fn on_user_event(&mut self, window: &WindowHandle, event: T) {}
+    /// Advances the simulation by one tick, called exactly `WindowConfig::tps` times per second of
+    /// frames (catching up after slow frames) before `on_frame_update`. The `partial_ticks` of the
+    /// frame callbacks are the fraction of a tick elapsed since, for interpolating between ticks.
+    fn on_tick(&mut self, window: &WindowHandle) {}
+    fn on_frame_update(&mut self, window: &WindowHandle, dimensions: (f32, f32), mouse: (f32, f32), partial_ticks: f32) {}
+    /// Cursor for the mouse position, applied every frame, e.g. from `Widgets::get_cursor`. `None` keeps the shown one
+    fn cursor(&self, window: &WindowHandle, mouse: (f32, f32)) -> Option<Cursor> { None }
+    fn on_frame_draw(&self, canvas: &mut Canvas<Frame>, mouse_pos: (f32, f32), partial_ticks: f32) -> Result<()>;
+    /// Drawing the frame failed, by default the error is printed and the next frame is drawn as usual.
+    /// Failing to present it stops `Window::show` with `WindowError::SwapBuffers`
+    fn on_render_error(&mut self, window: &WindowHandle, error: Error) {
+        eprintln!("Rendering failed: {}", error);
+    }
+    /// Called after `on_frame_update`, returning `false` skips drawing the frame entirely
+    fn needs_redraw(&self, window: &WindowHandle) -> bool {
+        true
+    }
+    fn on_frame_stats(&mut self, window: &WindowHandle, stats: FrameStats) {}
+    /// Loaded in the background once `load_resources` returned, while frames are drawn already
+    fn resources(&self) -> ResourceManifest { ResourceManifest::new() }
+    /// More of the `resources` finished loading, to update a `LoadingScreen`
+    fn on_load_progress(&mut self, window: &WindowHandle, progress: &LoadProgress) {}
+    /// All of the `resources` are loaded, except the ones that failed
+    fn on_resources_loaded(&mut self, window: &WindowHandle, errors: Vec<ResourceError>) {}
+    /// A texture requested with `TextureManager::load_async` was uploaded or failed to load
+    fn on_texture_loaded(&mut self, window: &WindowHandle, loaded: TextureLoaded) {}
+    /// Source files of a program registered with `ShaderManager::register_files` changed and it was compiled again
+    fn on_shader_reloaded(&mut self, window: &WindowHandle, reloaded: ShaderReloaded) {}
+    fn on_close_requested(&mut self, window: &WindowHandle, dimensions: (f32, f32)) {}
+    /// The user asked to close the window, it closes unless this returns `CloseAction::Cancel`
+    fn on_closing(&mut self, window: &WindowHandle) -> CloseAction { CloseAction::Close }
+    /// The event loop stopped, for the last cleanup while the GL context is still alive
+    fn on_exit(&mut self, window: &WindowHandle) {}
+    /// New inner size of the window in logical pixels
+    fn on_resized(&mut self, window: &WindowHandle, dimensions: (f32, f32)) {}
+    /// The window moved to a monitor of another DPI or its scale changed, fonts are already rasterized for it
+    fn on_scale_factor_changed(&mut self, window: &WindowHandle, scale_factor: f64) {}
+    /// The window gained or lost keyboard focus, pass it on to `Widgets::on_window_focused`
+    fn on_focused(&mut self, window: &WindowHandle, dimensions: (f32, f32), focused: bool) {}
+    fn on_keyboard_char(&mut self, window: &WindowHandle, dimensions: (f32, f32), ch: char) {}
+    fn on_keyboard_key(&mut self, window: &WindowHandle, dimensions: (f32, f32), input: KeyboardInput) {}
+    /// Modifier keys were pressed or released, `WindowHandle::modifiers` returns the current ones
+    fn on_modifiers_changed(&mut self, window: &WindowHandle, dimensions: (f32, f32), modifiers: ModifiersState) {}
+    fn on_mouse_button(&mut self, window: &WindowHandle, dimensions: (f32, f32), button: MouseButton, state: ElementState, pos: (f32, f32)) {}
+    fn on_mouse_wheel(&mut self, window: &WindowHandle, dimensions: (f32, f32), delta: MouseScrollDelta) {}
+    fn on_mouse_move(&mut self, window: &WindowHandle, dimensions: (f32, f32), pos: (f32, f32)) {}
+    /// Unaccelerated mouse movement in device units, reported even when the cursor is captured or at
+    /// the edge of the screen, and also while the window is not focused. Returns whether it changed
+    /// what is drawn, the window then draws again as after `on_mouse_move`
+    fn on_raw_mouse_motion(&mut self, window: &WindowHandle, delta: (f64, f64)) -> bool { false }
+    fn on_touch(&mut self, window: &WindowHandle, dimensions: (f32, f32), touch: Touch) {}
+    fn on_gesture(&mut self, window: &WindowHandle, dimensions: (f32, f32), gesture: Gesture) {}
+    /// MSAA samples per pixel of the window surface, must be zero or a power of two
+    fn multisampling(&self) -> u16 { 0 }
+    /// Linear blending requests an sRGB framebuffer when the window is created
+    fn color_space(&self) -> ColorSpace { ColorSpace::Srgb }
+    /// Full screen passes applied in order to every drawn frame
+    fn post_effects(&self, window: &WindowHandle) -> Vec<PostEffect> { Vec::new() }
+    #[cfg(feature = "gamepad")]
+    fn gamepad_mapping(&self) -> GamepadMapping { Default::default() }
+    #[cfg(feature = "gamepad")]
+    fn on_gamepad_action(&mut self, window: &WindowHandle, dimensions: (f32, f32), action: GamepadAction) {}
+    #[cfg(feature = "tray")]
+    fn on_tray_event(&mut self, window: &WindowHandle, event: TrayEvent) {}
 }
\ No newline at end of file